@@ -0,0 +1,41 @@
+//! Scalar vs 4-wide ink blending over a 4-megapixel row run: `cargo bench --bench blend`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rickboard::{blend_pixel, blend_pixels4};
+
+const PIXELS: usize = 1 << 22;
+const ROUNDS: u32 = 10;
+
+/// Best of ROUNDS, so a busy machine doesn't skew the comparison
+fn best_of(mut run: impl FnMut()) -> Duration {
+    (0..ROUNDS).map(|_| {
+        let start = Instant::now();
+        run();
+        start.elapsed()
+    }).min().unwrap()
+}
+
+fn main() {
+    // Translucent ink, the case neither path can shortcut
+    let src: Vec<[u8; 4]> = (0..PIXELS).map(|i| [(i * 7) as u8, (i * 3) as u8, i as u8, 128]).collect();
+    let mut dst = vec![50u8; PIXELS * 4];
+    
+    let scalar = best_of(|| {
+        for (px, &ink) in dst.chunks_exact_mut(4).zip(&src) {
+            blend_pixel(px, ink);
+        }
+        black_box(&mut dst);
+    });
+    let wide = best_of(|| {
+        for (px, ink) in dst.chunks_exact_mut(16).zip(src.chunks_exact(4)) {
+            blend_pixels4(px, &[ink[0], ink[1], ink[2], ink[3]]);
+        }
+        black_box(&mut dst);
+    });
+    
+    let rate = |elapsed: Duration| PIXELS as f64 / elapsed.as_secs_f64() / 1e6;
+    println!("blend_pixel    {:>8.2?}  {:>7.0} Mpx/s", scalar, rate(scalar));
+    println!("blend_pixels4  {:>8.2?}  {:>7.0} Mpx/s  ({:.2}x)", wide, rate(wide), scalar.as_secs_f64() / wide.as_secs_f64());
+}
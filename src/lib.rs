@@ -0,0 +1,3557 @@
+//! RickBoard's board engine: the tiled layers, the board file and its sidecars, rendering,
+//! drawing primitives, undo and the stroke log. The window and input handling live in the
+//! `rickboard` binary on top of it.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use log::{error, info, warn};
+
+// File format: 9-byte header + pixel data
+// Header: [mode: u8, width: u32 (LE), height: u32 (LE)]
+// If the mode byte has HEADER_EXTENDED_FLAG set, a version byte follows. Version 1 files stored
+// the storage format itself there (0 or 1); from version 2 on the storage format comes next,
+// and from version 3 on the background color (RGBA) after it, then the paper block if
+// HEADER_PAPER_FLAG is set.
+// Plain 9-byte headers are version 0: raw pixels.
+pub const HEADER_SIZE: u64 = 9;
+const HEADER_EXTENDED_FLAG: u8 = 0x80;
+
+// Format version written by this build; older files are upgraded by Board::migrate
+const FORMAT_VERSION: u8 = 3;
+
+// Mode byte flag: the board wraps vertically too (toroidal)
+const HEADER_WRAP_VERTICAL_FLAG: u8 = 0x40;
+
+// Mode byte flag: a metadata block follows the storage format byte (see BoardMetadata)
+const HEADER_METADATA_FLAG: u8 = 0x20;
+
+// Mode byte flag: a paper block (see Paper) follows the background color, before any metadata
+const HEADER_PAPER_FLAG: u8 = 0x10;
+
+// Longest title or author kept in the header, in bytes
+const MAX_METADATA_LEN: usize = 256;
+
+// Largest width/height accepted from a header or the command line
+pub const MAX_BOARD_DIMENSION: u32 = 100000;
+
+// Shared sidecars used before drawings were stored per board; migrated on first load
+const LEGACY_DRAWING_FILE: &str = "drawing_layer.data";
+const LEGACY_BLEND_FILE: &str = "drawing_blend.data";
+use rayon::prelude::*;
+use image::{AnimationDecoder, GenericImageView};
+
+/// Represents a point on the board
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Board mode - the background preset (or a custom color); dark ones take chalk, light ones marker
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum BoardMode {
+    Blackboard,
+    Whiteboard,
+    Greenboard, // Classic green chalkboard
+    Corkboard,
+    Slate, // Dark grey
+    Custom([u8; 4]), // Any opaque background color
+}
+
+/// Presets the mode button cycles through, in order (a custom board goes back to the first)
+const BOARD_MODE_PRESETS: [BoardMode; 5] = [BoardMode::Blackboard, BoardMode::Whiteboard, BoardMode::Greenboard, BoardMode::Corkboard, BoardMode::Slate];
+
+impl BoardMode {
+    pub fn background_color(&self) -> [u8; 4] {
+        match self {
+            BoardMode::Blackboard => [15, 15, 15, 255],  // Dark grey
+            BoardMode::Whiteboard => [255, 255, 255, 255], // Pure white
+            BoardMode::Greenboard => [38, 70, 52, 255],
+            BoardMode::Corkboard => [196, 154, 108, 255],
+            BoardMode::Slate => [62, 66, 71, 255],
+            BoardMode::Custom(color) => *color,
+        }
+    }
+
+    /// Dark backgrounds draw like chalk with light UI, light ones like marker with dark UI
+    pub fn is_dark(&self) -> bool {
+        let [r, g, b, _] = self.background_color();
+        (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000 < 128
+    }
+
+    pub fn default_pen_color(&self) -> [u8; 4] {
+        if self.is_dark() {
+            [255, 255, 255, 255] // White chalk
+        } else {
+            [0, 0, 0, 255] // Black marker (inverts perfectly with white)
+        }
+    }
+    
+    /// Mode id in the header's mode byte (older builds only know 0 and 1)
+    fn header_id(&self) -> u8 {
+        match self {
+            BoardMode::Blackboard => 0,
+            BoardMode::Whiteboard => 1,
+            BoardMode::Greenboard => 2,
+            BoardMode::Corkboard => 3,
+            BoardMode::Slate => 4,
+            BoardMode::Custom(_) => 5,
+        }
+    }
+    
+    /// Mode for a header mode id; ids from a newer build fall back to the saved background color
+    fn from_header(id: u8, background: Option<[u8; 4]>) -> Option<Self> {
+        match id {
+            0 => Some(BoardMode::Blackboard),
+            1 => Some(BoardMode::Whiteboard),
+            2 => Some(BoardMode::Greenboard),
+            3 => Some(BoardMode::Corkboard),
+            4 => Some(BoardMode::Slate),
+            _ => background.map(BoardMode::Custom),
+        }
+    }
+    
+    /// Parse a preset name (config.toml and --mode) or a #RRGGBB custom background
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "blackboard" => Some(BoardMode::Blackboard),
+            "whiteboard" => Some(BoardMode::Whiteboard),
+            "greenboard" => Some(BoardMode::Greenboard),
+            "corkboard" => Some(BoardMode::Corkboard),
+            "slate" => Some(BoardMode::Slate),
+            // Backgrounds are always opaque; an alpha digit pair is ignored
+            _ if text.starts_with('#') => parse_hex_color(text).map(|[r, g, b, _]| BoardMode::Custom([r, g, b, 255])),
+            _ => None,
+        }
+    }
+    
+    /// Name shown on the mode button
+    pub fn label(&self) -> String {
+        match self {
+            BoardMode::Custom([r, g, b, _]) => format!("#{:02X}{:02X}{:02X}", r, g, b),
+            other => format!("{:?}", other),
+        }
+    }
+    
+    /// The preset after this one on the mode button
+    pub fn next(&self) -> Self {
+        let index = BOARD_MODE_PRESETS.iter().position(|mode| mode == self);
+        index.map_or(BOARD_MODE_PRESETS[0], |i| BOARD_MODE_PRESETS[(i + 1) % BOARD_MODE_PRESETS.len()])
+    }
+}
+
+impl TryFrom<String> for BoardMode {
+    type Error = String;
+    
+    fn try_from(text: String) -> Result<Self, String> {
+        BoardMode::parse(&text).ok_or_else(|| format!(
+            "unknown mode '{}' (blackboard, whiteboard, greenboard, corkboard, slate or a #RRGGBB color)", text))
+    }
+}
+
+/// Background "paper" ruling, drawn over the background color and under posters and ink
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaperStyle {
+    Plain,
+    Ruled, // Horizontal lines
+    Grid,  // Square grid
+    Dots,  // Dot grid
+}
+
+/// A board's paper: the style, the ruling pitch in board pixels and its color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Paper {
+    pub style: PaperStyle,
+    pub spacing: u16,
+    pub color: Option<[u8; 4]>, // None follows the mode's pen color (at PAPER_ALPHA), so mode switches keep it visible
+}
+
+/// Opacity of paper lines that follow the mode's pen color
+const PAPER_ALPHA: u8 = 48;
+
+/// Bytes in the header's paper block: style, spacing (u16 LE), color RGBA (alpha 0 = follow the mode)
+const PAPER_BLOCK_LEN: u64 = 7;
+
+impl Paper {
+    pub const PLAIN: Paper = Paper { style: PaperStyle::Plain, spacing: 32, color: None };
+    
+    /// Whether board row or column `coord` carries a line (or a dot); dots are a few pixels across
+    fn on_mark(&self, coord: usize) -> bool {
+        let size = if self.style == PaperStyle::Dots { (self.spacing / 12).max(1) } else { 1 };
+        coord % (self.spacing as usize) < size as usize
+    }
+    
+    /// Color the ruling is drawn in on a board in `mode`
+    fn color_in(&self, mode: BoardMode) -> [u8; 4] {
+        self.color.unwrap_or_else(|| {
+            let [r, g, b, _] = mode.default_pen_color();
+            [r, g, b, PAPER_ALPHA]
+        })
+    }
+    
+    /// Draw the ruling into one row of pixels showing board row `y`. `marked` lists the pixels
+    /// in the row whose board column is on a mark.
+    fn draw_row(&self, y: usize, row: &mut [u8], marked: &[usize], color: [u8; 4]) {
+        let row_marked = self.on_mark(y);
+        match (self.style, row_marked) {
+            (PaperStyle::Ruled | PaperStyle::Grid, true) => {
+                for pixel in row.chunks_exact_mut(4) {
+                    blend_pixel(pixel, color);
+                }
+            }
+            (PaperStyle::Grid, false) | (PaperStyle::Dots, true) => {
+                for &i in marked {
+                    blend_pixel(&mut row[i * 4..i * 4 + 4], color);
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    fn encode(&self) -> [u8; PAPER_BLOCK_LEN as usize] {
+        let mut block = [0u8; PAPER_BLOCK_LEN as usize];
+        block[0] = self.style as u8;
+        block[1..3].copy_from_slice(&self.spacing.to_le_bytes());
+        block[3..7].copy_from_slice(&self.color.unwrap_or([0, 0, 0, 0]));
+        block
+    }
+    
+    /// Read a paper block; a style from a newer build shows as plain rather than failing the load
+    fn decode(block: [u8; PAPER_BLOCK_LEN as usize]) -> Self {
+        let style = match block[0] {
+            1 => PaperStyle::Ruled,
+            2 => PaperStyle::Grid,
+            3 => PaperStyle::Dots,
+            _ => PaperStyle::Plain,
+        };
+        let spacing = u16::from_le_bytes([block[1], block[2]]).max(1);
+        let color = [block[3], block[4], block[5], block[6]];
+        Paper { style, spacing, color: (color[3] != 0).then_some(color) }
+    }
+}
+
+/// Represents the board configuration
+#[derive(Debug)]
+pub struct BoardConfig {
+    pub width: u32,
+    pub height: u32,
+    pixel_size: usize,
+    pub mode: BoardMode,
+    pub paper: Paper,
+    pub wrap_vertical: bool, // Toroidal board: y wraps like x instead of clipping at the edges
+}
+
+impl BoardConfig {
+    /// Map a board row into range: wrapped on toroidal boards, None outside a cylindrical one
+    #[inline(always)]
+    pub fn wrap_y(&self, y: i32) -> Option<usize> {
+        let height = self.height as i32;
+        if self.wrap_vertical {
+            Some(y.rem_euclid(height) as usize)
+        } else if (0..height).contains(&y) {
+            Some(y as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// How the board pixels are stored after the header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageFormat {
+    Raw, // width * height RGBA pixels (version 0, fixed size)
+    Rle, // Runs of identical pixels: [count: u32 (LE), r, g, b, a] repeated
+}
+
+/// How a drawing-layer pixel combines with what's under it. Pixels only ever store Normal or
+/// Multiply; the other modes are for the whole ink layer (Board::ink_blend).
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum BlendMode {
+    #[default]
+    Normal = 0,   // Src-over (ink)
+    Multiply = 1, // Tints/darkens the destination (highlighter)
+    Screen = 2,   // Lightens the destination (glow on a dark board)
+    Overlay = 3,  // Multiply on dark destinations, screen on light ones (more contrast)
+}
+
+impl BlendMode {
+    /// Layer composite modes in the order the ink blend key cycles them
+    pub const LAYER_MODES: [BlendMode; 4] = [BlendMode::Normal, BlendMode::Multiply, BlendMode::Screen, BlendMode::Overlay];
+    
+    /// Mode stored as a byte in the blend sidecar (unknown bytes read as normal)
+    fn from_byte(byte: u8) -> BlendMode {
+        Self::LAYER_MODES.get(byte as usize).copied().unwrap_or_default()
+    }
+    
+    /// Combine one straight-alpha pixel with `dst` (RGB only; dst alpha is left alone),
+    /// faded in by the pixel's alpha
+    #[inline(always)]
+    pub fn blend(self, dst: &mut [u8], src: [u8; 4]) {
+        let a = src[3] as u32;
+        match self {
+            // Plain src-over keeps blend_pixel's opaque and transparent shortcuts
+            BlendMode::Normal => blend_pixel(dst, src),
+            // Multiply: dst * lerp(1, src, alpha), so the underlying content shows through tinted
+            BlendMode::Multiply => {
+                for c in 0..3 {
+                    dst[c] = (dst[c] as u32 * (255 * (255 - a) + src[c] as u32 * a) / (255 * 255)) as u8;
+                }
+            }
+            BlendMode::Screen | BlendMode::Overlay => {
+                if a == 0 {
+                    return;
+                }
+                for c in 0..3 {
+                    let (s, d) = (src[c] as u32, dst[c] as u32);
+                    let mixed = if self == BlendMode::Screen || d >= 128 {
+                        let scale = if self == BlendMode::Screen { 1 } else { 2 };
+                        255 - (scale * (255 - s) * (255 - d) / 255).min(255)
+                    } else {
+                        2 * s * d / 255
+                    };
+                    dst[c] = ((mixed * a + d * (255 - a)) / 255) as u8;
+                }
+            }
+        }
+    }
+}
+
+/// A drawing layer tile as it was before an undo step first changed it
+struct UndoTile {
+    index: usize,
+    pixels: Option<Box<[u8]>>, // None = unallocated (all transparent)
+    blend_modes: Option<Box<[u8]>>, // None = all normal
+}
+
+/// One undo step: the drawing layer tiles it changed, copied before their first change
+pub struct UndoSnapshot {
+    tiles: Vec<UndoTile>,
+    saved: Vec<bool>, // Per drawing layer tile: already copied into `tiles`
+    stroke_count: usize, // Length of the stroke log when the snapshot was taken
+    has_drawings: bool,
+    cleared: Option<ClearedState>, // Set when the snapshot was taken by Clear
+}
+
+impl UndoSnapshot {
+    /// Memory held by the copies (counted against UNDO_MEMORY_BUDGET)
+    fn bytes(&self) -> u64 {
+        let tiles: usize = self.tiles.iter()
+            .map(|tile| tile.pixels.as_ref().map_or(0, |pixels| pixels.len()) + tile.blend_modes.as_ref().map_or(0, |modes| modes.len()))
+            .sum();
+        let background = self.cleared.as_ref().and_then(|cleared| cleared.background.as_ref()).map_or(0, TiledLayer::allocated_bytes);
+        (tiles + background) as u64
+    }
+}
+
+/// What a clear wipes besides the drawing layer tiles, kept so the clear can be undone
+/// (None for the part it left alone)
+struct ClearedState {
+    background: Option<TiledLayer>, // Usually all background color, so no tiles are allocated
+    strokes: Option<Vec<Stroke>>,
+}
+
+/// Edge length of a storage tile in pixels (256x256 RGBA = 256KB per tile)
+const TILE_SIZE: usize = 256;
+
+/// Layers up to this many pixels allocate every tile up front, like a plain buffer
+const DENSE_LAYER_MAX_PIXELS: usize = 4 * 1024 * 1024;
+
+/// RGBA pixel layer stored as TILE_SIZE x TILE_SIZE tiles allocated on first write.
+/// A missing tile reads as `fill` (background color, or transparent for ink), so a mostly
+/// empty 80000x1000 board costs almost nothing until it's drawn on. Small layers are dense.
+#[derive(Clone)]
+pub struct TiledLayer {
+    width: usize,
+    height: usize,
+    tiles_x: usize,  // Tiles per row of tiles
+    fill: [u8; 4],  // Color of every pixel in an unallocated tile
+    tiles: Vec<Option<Box<[u8]>>>,  // Row-major; edge tiles are full size, the overhang is unused
+    blend_modes: Vec<Option<Box<[u8]>>>,  // Per tile, one BlendMode byte per pixel; allocated on the first non-normal pixel
+    dirty: Vec<bool>,  // Tiles changed since the last save (see Board::sync)
+    edited: bool,  // Anything changed since the last take_edited() (minimap refresh)
+}
+
+impl TiledLayer {
+    pub fn new(width: u32, height: u32, fill: [u8; 4]) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        let tiles_x = width.div_ceil(TILE_SIZE);
+        let tile_count = tiles_x * height.div_ceil(TILE_SIZE);
+        let mut layer = TiledLayer {
+            width, height, tiles_x, fill,
+            tiles: vec![None; tile_count],
+            blend_modes: vec![None; tile_count],
+            dirty: vec![true; tile_count],
+            edited: true,
+        };
+        layer.reset(fill);
+        layer
+    }
+    
+    /// Small layers keep every tile allocated (the old plain-buffer behavior)
+    fn is_dense(&self) -> bool {
+        self.width * self.height <= DENSE_LAYER_MAX_PIXELS
+    }
+    
+    fn new_tile(&self) -> Box<[u8]> {
+        self.fill.repeat(TILE_SIZE * TILE_SIZE).into_boxed_slice()
+    }
+    
+    /// Make the whole layer read as `fill` with normal blending (drops tiles, or refills them
+    /// on dense layers)
+    fn reset(&mut self, fill: [u8; 4]) {
+        self.fill = fill;
+        self.mark_all_dirty();
+        self.blend_modes.iter_mut().for_each(|modes| *modes = None);
+        if self.is_dense() {
+            let tile = self.new_tile();
+            self.tiles.iter_mut().for_each(|t| *t = Some(tile.clone()));
+        } else {
+            self.tiles.iter_mut().for_each(|t| *t = None);
+        }
+    }
+    
+    /// Tile index and byte offset inside the tile for an in-bounds pixel
+    #[inline(always)]
+    fn locate(&self, x: usize, y: usize) -> (usize, usize) {
+        let tile = (y / TILE_SIZE) * self.tiles_x + x / TILE_SIZE;
+        let offset = ((y % TILE_SIZE) * TILE_SIZE + x % TILE_SIZE) * 4;
+        (tile, offset)
+    }
+    
+    /// Index of the tile holding an in-bounds pixel
+    #[inline(always)]
+    fn tile_index(&self, x: usize, y: usize) -> usize {
+        self.locate(x, y).0
+    }
+    
+    /// Pixel rectangle (x0, y0, x1, y1) a tile covers, clipped to the layer
+    fn tile_rect(&self, tile: usize) -> (usize, usize, usize, usize) {
+        let (x0, y0) = ((tile % self.tiles_x) * TILE_SIZE, (tile / self.tiles_x) * TILE_SIZE);
+        (x0, y0, (x0 + TILE_SIZE).min(self.width), (y0 + TILE_SIZE).min(self.height))
+    }
+    
+    /// Allocate a missing tile painted `color` (its in-bounds part) with normal blending
+    fn fill_tile(&mut self, tile: usize, color: [u8; 4]) {
+        let (x0, y0, x1, y1) = self.tile_rect(tile);
+        let mut data = self.new_tile();
+        let row = color.repeat(x1 - x0);
+        for r in 0..y1 - y0 {
+            data[r * TILE_SIZE * 4..r * TILE_SIZE * 4 + row.len()].copy_from_slice(&row);
+        }
+        self.tiles[tile] = Some(data);
+        self.blend_modes[tile] = None;
+        self.dirty[tile] = true;
+        self.edited = true;
+    }
+    
+    /// Put back a tile and its blend modes copied before they were edited (undo)
+    fn restore_tile(&mut self, tile: usize, data: Option<Box<[u8]>>, blend_modes: Option<Box<[u8]>>) {
+        self.tiles[tile] = data;
+        self.blend_modes[tile] = blend_modes;
+        self.dirty[tile] = true;
+        self.edited = true;
+    }
+    
+    /// Read a pixel (x < width, y < height)
+    #[inline(always)]
+    pub fn get(&self, x: usize, y: usize) -> [u8; 4] {
+        let (tile, offset) = self.locate(x, y);
+        match &self.tiles[tile] {
+            Some(data) => [data[offset], data[offset + 1], data[offset + 2], data[offset + 3]],
+            None => self.fill,
+        }
+    }
+    
+    /// Write a pixel (x < width, y < height); writing `fill` into a missing tile allocates nothing
+    #[inline(always)]
+    pub fn set(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let (tile, offset) = self.locate(x, y);
+        if self.tiles[tile].is_none() {
+            if color == self.fill {
+                return;
+            }
+            self.tiles[tile] = Some(self.new_tile());
+        }
+        if let Some(data) = &mut self.tiles[tile] {
+            data[offset..offset + 4].copy_from_slice(&color);
+        }
+        self.dirty[tile] = true;
+        self.edited = true;
+    }
+    
+    /// Blend mode of a pixel (x < width, y < height)
+    #[inline(always)]
+    pub fn blend_mode(&self, x: usize, y: usize) -> BlendMode {
+        let (tile, offset) = self.locate(x, y);
+        self.blend_modes[tile].as_ref().map_or(BlendMode::Normal, |modes| BlendMode::from_byte(modes[offset / 4]))
+    }
+    
+    /// Set the blend mode of a pixel (x < width, y < height); normal in a tile without any
+    /// other modes allocates nothing
+    pub fn set_blend_mode(&mut self, x: usize, y: usize, mode: BlendMode) {
+        let (tile, offset) = self.locate(x, y);
+        let modes = match &mut self.blend_modes[tile] {
+            Some(modes) => modes,
+            None if mode == BlendMode::Normal => return,
+            modes => modes.insert(vec![BlendMode::Normal as u8; TILE_SIZE * TILE_SIZE].into_boxed_slice()),
+        };
+        modes[offset / 4] = mode as u8;
+        self.dirty[tile] = true;
+        self.edited = true;
+    }
+    
+    /// True if any tile holds blend modes (they may all have gone back to normal since)
+    pub fn has_blend_modes(&self) -> bool {
+        self.blend_modes.iter().any(Option::is_some)
+    }
+    
+    /// Copy row `y` into `out` (width * 4 bytes)
+    fn read_row(&self, y: usize, out: &mut [u8]) {
+        self.read_span(y, 0, out);
+    }
+    
+    /// Copy `out.len() / 4` pixels of row `y` starting at tile-aligned `x0` into `out`
+    fn read_span(&self, y: usize, x0: usize, out: &mut [u8]) {
+        let tile_row = (y / TILE_SIZE) * self.tiles_x;
+        let row_offset = (y % TILE_SIZE) * TILE_SIZE * 4;
+        let x_end = x0 + out.len() / 4;
+        for tx in x0 / TILE_SIZE..x_end.div_ceil(TILE_SIZE) {
+            let tile_x0 = tx * TILE_SIZE;
+            let n = TILE_SIZE.min(x_end - tile_x0);
+            let dst = &mut out[(tile_x0 - x0) * 4..(tile_x0 - x0 + n) * 4];
+            match &self.tiles[tile_row + tx] {
+                Some(data) => dst.copy_from_slice(&data[row_offset..row_offset + n * 4]),
+                None => dst.chunks_exact_mut(4).for_each(|p| p.copy_from_slice(&self.fill)),
+            }
+        }
+    }
+    
+    /// Overwrite row `y` from `row` (width * 4 bytes); spans that are all `fill` stay unallocated
+    fn write_row(&mut self, y: usize, row: &[u8]) {
+        let tile_row = (y / TILE_SIZE) * self.tiles_x;
+        let row_offset = (y % TILE_SIZE) * TILE_SIZE * 4;
+        for tx in 0..self.tiles_x {
+            let x0 = tx * TILE_SIZE;
+            let n = TILE_SIZE.min(self.width - x0);
+            let src = &row[x0 * 4..(x0 + n) * 4];
+            if self.tiles[tile_row + tx].is_none() {
+                if src.chunks_exact(4).all(|p| p == self.fill) {
+                    continue;
+                }
+                self.tiles[tile_row + tx] = Some(self.new_tile());
+            }
+            if let Some(data) = &mut self.tiles[tile_row + tx] {
+                data[row_offset..row_offset + n * 4].copy_from_slice(src);
+            }
+            self.dirty[tile_row + tx] = true;
+            self.edited = true;
+        }
+    }
+    
+    /// Copy the blend mode bytes of `out.len()` pixels of row `y`, starting at tile-aligned `x0`
+    fn read_blend_span(&self, y: usize, x0: usize, out: &mut [u8]) {
+        let tile_row = (y / TILE_SIZE) * self.tiles_x;
+        let row_offset = (y % TILE_SIZE) * TILE_SIZE;
+        let x_end = x0 + out.len();
+        for tx in x0 / TILE_SIZE..x_end.div_ceil(TILE_SIZE) {
+            let tile_x0 = tx * TILE_SIZE;
+            let n = TILE_SIZE.min(x_end - tile_x0);
+            let dst = &mut out[tile_x0 - x0..tile_x0 - x0 + n];
+            match &self.blend_modes[tile_row + tx] {
+                Some(modes) => dst.copy_from_slice(&modes[row_offset..row_offset + n]),
+                None => dst.fill(BlendMode::Normal as u8),
+            }
+        }
+    }
+    
+    /// Stream the layer out as raw row-major RGBA
+    pub fn write_raw(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut row = vec![0u8; self.width * 4];
+        for y in 0..self.height {
+            self.read_row(y, &mut row);
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+    
+    /// Stream the blend modes out as one byte per pixel, row-major (the `.blend` sidecar)
+    pub fn write_blend_raw(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut row = vec![0u8; self.width];
+        for y in 0..self.height {
+            self.read_blend_span(y, 0, &mut row);
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+    
+    /// Fill the blend modes from one byte per pixel, row-major; all-normal tiles stay unallocated
+    fn read_blend_raw(&mut self, input: &mut dyn Read) -> io::Result<()> {
+        let mut row = vec![0u8; self.width];
+        for y in 0..self.height {
+            input.read_exact(&mut row)?;
+            for (x, &mode) in row.iter().enumerate() {
+                self.set_blend_mode(x, y, BlendMode::from_byte(mode));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Fill the layer from raw row-major RGBA
+    fn read_raw(&mut self, input: &mut dyn Read) -> io::Result<()> {
+        let mut row = vec![0u8; self.width * 4];
+        for y in 0..self.height {
+            input.read_exact(&mut row)?;
+            self.write_row(y, &row);
+        }
+        Ok(())
+    }
+    
+    /// Run `f(x0, y0, tile)` on every allocated tile in parallel; (x0, y0) is the tile's top-left pixel.
+    /// Edge tiles overhang the board, so callers must skip pixels outside width x height.
+    fn par_tiles_mut(&mut self, f: impl Fn(usize, usize, &mut [u8]) + Sync + Send) {
+        let tiles_x = self.tiles_x;
+        self.tiles.par_iter_mut().zip(self.dirty.par_iter_mut()).enumerate().for_each(|(i, (tile, dirty))| {
+            if let Some(data) = tile {
+                f((i % tiles_x) * TILE_SIZE, (i / tiles_x) * TILE_SIZE, data);
+                *dirty = true;
+            }
+        });
+        self.edited = true;
+    }
+    
+    fn mark_all_dirty(&mut self) {
+        self.dirty.iter_mut().for_each(|d| *d = true);
+        self.edited = true;
+    }
+    
+    /// True if the layer changed since the last call
+    pub fn take_edited(&mut self) -> bool {
+        std::mem::take(&mut self.edited)
+    }
+    
+    /// Everything matches what's on disk
+    fn mark_clean(&mut self) {
+        self.dirty.iter_mut().for_each(|d| *d = false);
+    }
+    
+    fn is_dirty(&self) -> bool {
+        self.dirty.contains(&true)
+    }
+    
+    /// Pixel spans (y, x0, x1) covered by dirty tiles, in file order. Horizontally adjacent
+    /// dirty tiles are merged so each row of a run is one contiguous write.
+    fn dirty_spans(&self) -> Vec<(usize, usize, usize)> {
+        let mut spans = Vec::new();
+        for (ty, row) in self.dirty.chunks(self.tiles_x).enumerate() {
+            let mut runs = Vec::new();
+            let mut tx = 0;
+            while tx < row.len() {
+                if !row[tx] {
+                    tx += 1;
+                    continue;
+                }
+                let start = tx;
+                while tx < row.len() && row[tx] {
+                    tx += 1;
+                }
+                runs.push((start * TILE_SIZE, (tx * TILE_SIZE).min(self.width)));
+            }
+            for y in ty * TILE_SIZE..((ty + 1) * TILE_SIZE).min(self.height) {
+                spans.extend(runs.iter().map(|&(x0, x1)| (y, x0, x1)));
+            }
+        }
+        spans
+    }
+    
+    /// Copy for a background save: only the dirty tiles (clean ones read as `fill` in the copy),
+    /// or every tile when the whole file is going to be rewritten. `whole_blend_modes` does the
+    /// same for the blend modes, whose sidecar can need rewriting on its own.
+    fn save_snapshot(&self, whole: bool, whole_blend_modes: bool) -> TiledLayer {
+        let copy = |tiles: &[Option<Box<[u8]>>], whole: bool| -> Vec<Option<Box<[u8]>>> {
+            tiles.iter().zip(&self.dirty)
+                .map(|(tile, &dirty)| if whole || dirty { tile.clone() } else { None })
+                .collect()
+        };
+        TiledLayer {
+            width: self.width,
+            height: self.height,
+            tiles_x: self.tiles_x,
+            fill: self.fill,
+            tiles: copy(&self.tiles, whole),
+            blend_modes: copy(&self.blend_modes, whole_blend_modes),
+            dirty: self.dirty.clone(),
+            edited: false,
+        }
+    }
+    
+    /// True if any pixel is not fully transparent
+    fn has_visible_pixels(&self) -> bool {
+        self.fill[3] != 0 || self.tiles.par_iter().flatten().any(|data| data.chunks_exact(4).any(|p| p[3] != 0))
+    }
+    
+    /// Bytes currently held by allocated tiles and their blend modes
+    pub fn allocated_bytes(&self) -> usize {
+        (self.tiles.iter().flatten().count() * 4 + self.blend_modes.iter().flatten().count()) * TILE_SIZE * TILE_SIZE
+    }
+}
+
+/// Main board structure with cylindrical topology
+pub struct Board {
+    pub config: BoardConfig,
+    data_file: File,
+    pub file_path: PathBuf,  // Board file on disk (replaced atomically on full writes)
+    storage_format: StorageFormat,  // Format used for the next write (detected from the header on load)
+    pub full_sync: bool,  // Rewrite whole files on every save instead of patching dirty tiles (--full-sync)
+    header_len: u64,  // Header size of the file on disk (9 to 11 bytes depending on version, more with metadata)
+    pub metadata: BoardMetadata,  // Title, author and creation time from the header
+    header_dirty: bool,  // Metadata changed; the next save writes the header even if no pixel did
+    pending_sync: Option<std::thread::JoinHandle<io::Result<Option<u64>>>>,  // Save running on a worker thread (see start_sync)
+    pub viewport: Viewport,
+    pub cache: TiledLayer,  // In-memory cache of entire board for fast rendering (background only)
+    pub drawing_layer: TiledLayer,  // Transparent drawing layer on top of posters (RGBA)
+    pub ink_blend: BlendMode,  // How normal ink composites over the background and posters (highlighter pixels always multiply)
+    pub strokes: Vec<Stroke>,  // Vector log of the board's strokes (SVG export and replay)
+    strokes_dirty: bool,  // Stroke log changed since the last save
+    pub bookmarks: [Option<Bookmark>; BOOKMARK_SLOTS],  // Saved views for keys 1-9, written to `<board>.bookmarks` as they change
+    journal: Option<File>,  // Open `<board>.journal` (append only), opened on the first entry
+    pub journal_len: u64,  // Bytes in the journal, including entries from before a crash
+    journal_covered: u64,  // Journal bytes the save in flight makes redundant (cut on success)
+    journal_paused: bool,  // The ink changed in a way the journal can't replay; resumes after a save
+    pub undo_stack: Vec<UndoSnapshot>,  // Up to max_undo_steps steps, each holding the tiles it changed
+    pub undo_pushes: usize,  // Snapshots ever taken, so RickBoard can interleave them with poster edits
+    max_undo_steps: usize,  // Older steps are also dropped once the copies pass UNDO_MEMORY_BUDGET
+    pub has_drawings: bool,  // Track if drawing layer has any non-transparent pixels
+    pub scratch_layer: TiledLayer,  // Throwaway marks on top of everything, never saved
+    pub scratch_active: bool,  // When true, draw_pixel targets the scratch layer
+    has_scratch: bool,  // Track if scratch layer has any non-transparent pixels
+    // Viewport render cache
+    viewport_cache: Vec<u8>,  // Cached rendered viewport
+    cached_viewport_width: u32,
+    cached_viewport_height: u32,
+    cached_viewport_pos: Point,
+    cached_viewport_zoom: f32,
+    pub viewport_dirty: bool,
+}
+
+/// Descriptive fields stored in the board header. On disk: created (u64 LE, Unix seconds,
+/// 0 = unknown), then title and author, each as a u16 LE byte length and UTF-8 text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BoardMetadata {
+    pub title: String,
+    pub author: String,
+    created: u64,
+}
+
+impl BoardMetadata {
+    fn is_empty(&self) -> bool {
+        self == &BoardMetadata::default()
+    }
+    
+    fn encode(&self) -> Vec<u8> {
+        let mut block = self.created.to_le_bytes().to_vec();
+        for text in [&self.title, &self.author] {
+            block.extend_from_slice(&(text.len() as u16).to_le_bytes());
+            block.extend_from_slice(text.as_bytes());
+        }
+        block
+    }
+    
+    /// Read a block written by `encode`. Returns it with its length in bytes.
+    fn read(input: &mut dyn Read) -> io::Result<(Self, u64)> {
+        let mut created = [0u8; 8];
+        input.read_exact(&mut created)?;
+        let mut len = 8u64;
+        let mut read_text = || -> io::Result<String> {
+            let mut text_len = [0u8; 2];
+            input.read_exact(&mut text_len)?;
+            let mut text = vec![0u8; u16::from_le_bytes(text_len) as usize];
+            input.read_exact(&mut text)?;
+            len += 2 + text.len() as u64;
+            Ok(String::from_utf8_lossy(&text).into_owned())
+        };
+        let title = read_text()?;
+        let author = read_text()?;
+        Ok((BoardMetadata { title, author, created: u64::from_le_bytes(created) }, len))
+    }
+}
+
+/// Cut `text` to at most MAX_METADATA_LEN bytes without splitting a character
+fn truncate_metadata(mut text: String) -> String {
+    if text.len() > MAX_METADATA_LEN {
+        let end = (0..=MAX_METADATA_LEN).rev().find(|&i| text.is_char_boundary(i)).unwrap_or(0);
+        text.truncate(end);
+    }
+    text
+}
+
+/// Human-readable byte count (KB below a megabyte, then MB, then GB)
+pub fn format_bytes(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    match bytes as f64 {
+        b if b < MB => format!("{} KB", bytes.div_ceil(1024)),
+        b if b < 1024.0 * MB => format!("{:.1} MB", b / MB),
+        b => format!("{:.2} GB", b / (1024.0 * MB)),
+    }
+}
+
+/// Format Unix seconds as a UTC calendar date (YYYY-MM-DD)
+fn format_date(unix_secs: u64) -> String {
+    // Days to civil date, after Howard Hinnant's days_from_civil inverse
+    let z = (unix_secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Camera/viewport for navigation
+pub struct Viewport {
+    pub position: Point,
+    pub zoom: f32,
+    pub pan_velocity: Point, // Momentum left over from a middle-drag flick, board pixels per second
+}
+
+/// Number of bookmark slots (keys 1-9)
+pub const BOOKMARK_SLOTS: usize = 9;
+
+/// A saved view: top-left board position and zoom (see `<board>.bookmarks`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub position: Point,
+    pub zoom: f32,
+}
+
+impl Viewport {
+    /// Screen-to-board mapping in 32.32 fixed point: screen pixel (sx, sy) shows board pixel
+    /// (((origin.0 + sx) * step) >> 32, ((origin.1 + sy) * step) >> 32). The origin is the view
+    /// position in whole screen pixels, so a view zoomed far in can sit part way into a board
+    /// pixel, and a pan moves the picture by whole screen pixels at any zoom.
+    fn fixed_mapping(&self) -> ((i64, i64), i64) {
+        Self::fixed_mapping_at(self.position, self.zoom)
+    }
+    
+    /// fixed_mapping for a given position and zoom (such as the cached view's)
+    fn fixed_mapping_at(position: Point, zoom: f32) -> ((i64, i64), i64) {
+        let zoom = zoom as f64;
+        let origin = ((position.x as f64 * zoom).floor() as i64, (position.y as f64 * zoom).floor() as i64);
+        (origin, ((1u64 << 32) as f64 / zoom) as i64)
+    }
+}
+
+impl Board {
+    /// Create a new board with specified dimensions
+    pub fn new(width: u32, height: u32, mode: BoardMode, paper: Paper, wrap_vertical: bool, file_path: &Path) -> io::Result<Self> {
+        let file_exists = file_path.exists();
+        
+        // Check if existing file has valid header
+        let mut has_valid_header = if file_exists {
+            if let Ok(metadata) = std::fs::metadata(file_path) {
+                metadata.len() > HEADER_SIZE
+            } else {
+                false
+            }
+        } else {
+            false
+        };
+        
+        let mut data_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(file_path)?;
+
+        let mut metadata = BoardMetadata::default();
+        let mut loaded_paper = paper; // A new board takes the configured paper
+        let (loaded_mode, loaded_width, loaded_height, storage_format, header_len, loaded_wrap, version) = if has_valid_header {
+            // Read header to get saved mode and dimensions
+            let mut header = [0u8; HEADER_SIZE as usize];
+            if data_file.read_exact(&mut header).is_ok() {
+                let saved_wrap = header[0] & HEADER_WRAP_VERTICAL_FLAG != 0;
+                let saved_mode_id = header[0] & !(HEADER_EXTENDED_FLAG | HEADER_WRAP_VERTICAL_FLAG | HEADER_METADATA_FLAG | HEADER_PAPER_FLAG);
+                let saved_width = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+                let saved_height = u32::from_le_bytes([header[5], header[6], header[7], header[8]]);
+                
+                // Extended header: the version byte, then (from version 2) the storage format
+                let mut saved_background = None;
+                let (saved_version, saved_format, mut saved_header_len) = if header[0] & HEADER_EXTENDED_FLAG != 0 {
+                    let mut version = [0u8; 1];
+                    data_file.read_exact(&mut version)?;
+                    let (saved_version, format, header_len) = match version[0] {
+                        // Version 1 had no version byte; this is its storage format
+                        0 | 1 => (1, version[0], HEADER_SIZE + 1),
+                        2 => {
+                            let mut format = [0u8; 1];
+                            data_file.read_exact(&mut format)?;
+                            (2, format[0], HEADER_SIZE + 2)
+                        }
+                        FORMAT_VERSION => {
+                            let mut format = [0u8; 1];
+                            data_file.read_exact(&mut format)?;
+                            let mut background = [0u8; 4];
+                            data_file.read_exact(&mut background)?;
+                            saved_background = Some(background);
+                            (FORMAT_VERSION, format[0], HEADER_SIZE + 6)
+                        }
+                        other => return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{} uses format version {}, this build reads up to {} (written by a newer version?)",
+                                    file_path.display(), other, FORMAT_VERSION),
+                        )),
+                    };
+                    let format = match format {
+                        0 => StorageFormat::Raw,
+                        1 => StorageFormat::Rle,
+                        other => return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{} uses unknown storage format {} (written by a newer version?)", file_path.display(), other),
+                        )),
+                    };
+                    (saved_version, format, header_len)
+                } else {
+                    (0, StorageFormat::Raw, HEADER_SIZE)
+                };
+                let saved_mode = BoardMode::from_header(saved_mode_id, saved_background).unwrap_or(mode);
+                
+                // Version 3 boards may carry a paper block; everything older is plain
+                let mut saved_paper = Paper::PLAIN;
+                if header[0] & HEADER_PAPER_FLAG != 0 && saved_version >= 3 {
+                    let mut block = [0u8; PAPER_BLOCK_LEN as usize];
+                    data_file.read_exact(&mut block)?;
+                    saved_paper = Paper::decode(block);
+                    saved_header_len += PAPER_BLOCK_LEN;
+                }
+                
+                // Then the optional title/author block
+                if header[0] & HEADER_METADATA_FLAG != 0 {
+                    let (saved_metadata, block_len) = BoardMetadata::read(&mut data_file)?;
+                    metadata = saved_metadata;
+                    saved_header_len += block_len;
+                }
+                
+                // Validate dimensions
+                if (1..=MAX_BOARD_DIMENSION).contains(&saved_width) && (1..=MAX_BOARD_DIMENSION).contains(&saved_height) {
+                    info!("Loading existing board: {}x{} ({} mode, {:?} storage{})", saved_width, saved_height, saved_mode.label(), saved_format,
+                             if saved_wrap { ", wraps vertically" } else { "" });
+                    if !metadata.title.is_empty() || !metadata.author.is_empty() {
+                        info!("  \"{}\"{}", metadata.title,
+                                 if metadata.author.is_empty() { String::new() } else { format!(" by {}", metadata.author) });
+                    }
+                    if metadata.created > 0 {
+                        info!("  created {}", format_date(metadata.created));
+                    }
+                    loaded_paper = saved_paper;
+                    (saved_mode, saved_width, saved_height, saved_format, saved_header_len, saved_wrap, saved_version)
+                } else {
+                    // Invalid dimensions, use defaults
+                    metadata = BoardMetadata::default();
+                    info!("Invalid saved dimensions, creating new board");
+                    has_valid_header = false;
+                    (mode, width, height, StorageFormat::Raw, HEADER_SIZE, wrap_vertical, FORMAT_VERSION)
+                }
+            } else {
+                // Can't read header, use defaults
+                info!("Cannot read header, creating new board");
+                has_valid_header = false;
+                (mode, width, height, StorageFormat::Raw, HEADER_SIZE, wrap_vertical, FORMAT_VERSION)
+            }
+        } else {
+            // No valid header, create new board
+            if file_exists {
+                info!("Old format detected, creating new board (old data will be overwritten)");
+            }
+            (mode, width, height, StorageFormat::Raw, HEADER_SIZE, wrap_vertical, FORMAT_VERSION)
+        };
+
+        let config = BoardConfig {
+            width: loaded_width,
+            height: loaded_height,
+            pixel_size: 4, // RGBA
+            mode: loaded_mode,
+            paper: loaded_paper,
+            wrap_vertical: loaded_wrap,
+        };
+
+        // Make sure the board fits on disk before allocating anything (new boards are written
+        // in full by clear(); an existing raw file that's short gets extended by set_len,
+        // RLE files have no fixed size)
+        let total_size = header_len + (loaded_width as u64) * (loaded_height as u64) * (config.pixel_size as u64);
+        let existing_len = data_file.metadata()?.len();
+        let needed = match (has_valid_header, storage_format) {
+            (false, _) => total_size,
+            (true, StorageFormat::Raw) => total_size.saturating_sub(existing_len),
+            (true, StorageFormat::Rle) => 0,
+        };
+        if let Err(e) = ensure_disk_space(file_path, needed) {
+            if !file_exists {
+                // Don't leave behind the empty file we just created
+                drop(data_file);
+                let _ = fs::remove_file(file_path);
+            }
+            return Err(e);
+        }
+        if has_valid_header && storage_format == StorageFormat::Raw && existing_len < total_size {
+            data_file.set_len(total_size)?;
+        }
+
+        if has_valid_header {
+            adopt_stem_sidecars(file_path, loaded_width as u64 * loaded_height as u64)?;
+        }
+        
+        // Tiled layers: background reads as the mode's color, ink starts fully transparent.
+        // Tiles are only allocated where something differs (see TiledLayer).
+        let cache = TiledLayer::new(loaded_width, loaded_height, loaded_mode.background_color());
+        let drawing_layer = TiledLayer::new(loaded_width, loaded_height, [0, 0, 0, 0]);
+        let scratch_layer = TiledLayer::new(loaded_width, loaded_height, [0, 0, 0, 0]);
+        
+        let mut board = Board {
+            config,
+            data_file,
+            file_path: file_path.to_path_buf(),
+            storage_format,
+            full_sync: false,
+            header_len,
+            metadata,
+            header_dirty: false,
+            pending_sync: None,
+            viewport: Viewport {
+                position: Point { x: 0.0, y: 0.0 },
+                zoom: 1.0,
+                pan_velocity: Point { x: 0.0, y: 0.0 },
+            },
+            cache,
+            drawing_layer,
+            strokes: Vec::new(),
+            strokes_dirty: false,
+            bookmarks: [None; BOOKMARK_SLOTS],
+            journal: None,
+            journal_len: fs::metadata(sidecar_path(file_path, "journal")).map_or(0, |m| m.len()),
+            journal_covered: 0,
+            journal_paused: false,
+            undo_stack: Vec::new(),
+            undo_pushes: 0,
+            max_undo_steps: DEFAULT_UNDO_STEPS,
+            has_drawings: false,  // Will be set to true when loading or drawing
+            ink_blend: BlendMode::Normal,
+            scratch_layer,
+            scratch_active: false,
+            has_scratch: false,
+            viewport_cache: Vec::new(),
+            cached_viewport_width: 0,
+            cached_viewport_height: 0,
+            cached_viewport_pos: Point { x: 0.0, y: 0.0 },
+            cached_viewport_zoom: 1.0,
+            viewport_dirty: true,
+        };
+
+        if has_valid_header {
+            // Load existing data from disk, then bring older layouts up to date
+            board.load_cache()?;
+            board.migrate(version);
+        } else {
+            // A new board must not pick up drawings left behind by an older board of the same name
+            for sidecar in [board.drawing_path(), board.blend_path(), board.strokes_path(), board.journal_path(), board.bookmarks_path(), board.session_path()] {
+                if sidecar.exists() {
+                    fs::remove_file(sidecar)?;
+                }
+            }
+            board.journal_len = 0;
+            board.metadata.created = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            
+            // Initialize new board with background color, then write header + pixels
+            board.clear();
+            info!("Initializing board (this may take a moment)...");
+            board.write_board_file(true)?;
+            info!("Board initialized");
+        }
+        
+        let tile_bytes = board.cache.allocated_bytes() + board.drawing_layer.allocated_bytes();
+        info!("Board tiles in memory: {} MB", tile_bytes / (1024 * 1024));
+
+        Ok(board)
+    }
+    
+    /// Upgrade a board loaded from an older format version. The new header is written
+    /// with the next save (a full rewrite, since the header grows).
+    fn migrate(&mut self, version: u8) {
+        if version >= FORMAT_VERSION {
+            return;
+        }
+        // Versions 0 and 1 predate the drawing layer split, so ink may be baked into the background
+        if version < 2 {
+            self.migrate_background_ink();
+        }
+        self.header_dirty = true;
+        info!("Upgrading board from format version {} to {} on the next save", version, FORMAT_VERSION);
+    }
+    
+    /// Encode header with mode, dimensions and format version
+    fn header_bytes(&self) -> Vec<u8> {
+        self.header_for(self.storage_format)
+    }
+    
+    /// Header for a board file written in `format`
+    fn header_for(&self, format: StorageFormat) -> Vec<u8> {
+        let mut header = vec![0u8; HEADER_SIZE as usize];
+        header[0] = self.config.mode.header_id();
+        if self.config.wrap_vertical {
+            header[0] |= HEADER_WRAP_VERTICAL_FLAG;
+        }
+        header[1..5].copy_from_slice(&self.config.width.to_le_bytes());
+        header[5..9].copy_from_slice(&self.config.height.to_le_bytes());
+        header[0] |= HEADER_EXTENDED_FLAG;
+        header.push(FORMAT_VERSION);
+        header.push(format as u8);
+        header.extend_from_slice(&self.config.mode.background_color());
+        if self.config.paper.style != PaperStyle::Plain {
+            header[0] |= HEADER_PAPER_FLAG;
+            header.extend_from_slice(&self.config.paper.encode());
+        }
+        if !self.metadata.is_empty() {
+            header[0] |= HEADER_METADATA_FLAG;
+            header.extend_from_slice(&self.metadata.encode());
+        }
+        header
+    }
+    
+    /// Replace the title and/or author (written with the next save)
+    pub fn set_metadata(&mut self, title: Option<String>, author: Option<String>) {
+        if let Some(title) = title {
+            self.metadata.title = truncate_metadata(title);
+        }
+        if let Some(author) = author {
+            self.metadata.author = truncate_metadata(author);
+        }
+        self.header_dirty = true;
+    }
+    
+    /// Choose the storage format for future writes (takes effect on the next save)
+    pub fn set_storage_format(&mut self, format: StorageFormat) {
+        if format != self.storage_format {
+            self.cache.mark_all_dirty(); // The next save has to rewrite the whole file
+        }
+        self.storage_format = format;
+    }
+    
+    /// Write header + cache to a temp file and atomically swap it in for the board file.
+    /// If anything fails (e.g. disk full) the previous board file is left intact.
+    fn write_board_file(&mut self, show_progress: bool) -> io::Result<()> {
+        let header = self.header_bytes();
+        write_board_to(&self.file_path, &header, &self.cache, self.storage_format, show_progress)?;
+        self.header_len = header.len() as u64;
+        self.cache.mark_clean();
+        
+        // The old handle still points at the replaced file
+        self.data_file = OpenOptions::new().read(true).write(true).open(&self.file_path)?;
+        Ok(())
+    }
+    
+    /// Load entire board from disk into memory cache
+    pub fn load_cache(&mut self) -> io::Result<()> {
+        let pixel_count = self.config.width as usize * self.config.height as usize;
+        self.data_file.seek(SeekFrom::Start(self.header_len))?;
+        match self.storage_format {
+            StorageFormat::Raw => self.cache.read_raw(&mut io::BufReader::new(&mut self.data_file))?,
+            StorageFormat::Rle => {
+                let mut encoded = Vec::new();
+                self.data_file.read_to_end(&mut encoded)?;
+                rle_decode(&encoded, &mut self.cache)?;
+            }
+        }
+        self.cache.mark_clean(); // Matches the file; later saves only write what changes
+        
+        // Boards from before per-board sidecars kept them in the working directory;
+        // the first board opened with a matching size adopts (renames) them
+        self.migrate_legacy_sidecar(Path::new(LEGACY_DRAWING_FILE), &self.drawing_path(), pixel_count as u64 * 4)?;
+        self.migrate_legacy_sidecar(Path::new(LEGACY_BLEND_FILE), &self.blend_path(), pixel_count as u64)?;
+        
+        // Load drawing layer if it exists
+        let drawing_path = self.drawing_path();
+        if drawing_path.exists() {
+            let drawing_file = File::open(&drawing_path)?;
+            let len = drawing_file.metadata()?.len();
+            if len == pixel_count as u64 * 4 {
+                self.drawing_layer.read_raw(&mut io::BufReader::new(drawing_file))?;
+                self.drawing_layer.mark_clean();
+                
+                // Check if there are any non-transparent pixels
+                self.has_drawings = self.drawing_layer.has_visible_pixels();
+            } else {
+                // Belongs to a different board size - never paint it onto this one
+                warn!(
+                    "ignoring {} ({} bytes, expected {} for a {}x{} board); it will be replaced on the next save",
+                    drawing_path.display(), len, pixel_count as u64 * 4, self.config.width, self.config.height
+                );
+            }
+        }
+        
+        // Load highlighter blend modes if any were saved
+        let blend_path = self.blend_path();
+        if blend_path.exists() {
+            let blend_file = File::open(&blend_path)?;
+            let len = blend_file.metadata()?.len();
+            if len == pixel_count as u64 {
+                self.drawing_layer.read_blend_raw(&mut io::BufReader::new(blend_file))?;
+                self.drawing_layer.mark_clean();
+            } else {
+                warn!(
+                    "ignoring {} ({} bytes, expected {}); highlighter strokes will show as normal ink",
+                    blend_path.display(), len, pixel_count
+                );
+            }
+        }
+        
+        // Stroke log; a damaged one only costs the replay and SVG history
+        let strokes_path = self.strokes_path();
+        if strokes_path.exists() {
+            match fs::read(&strokes_path).map_err(|e| e.to_string()).and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string())) {
+                Ok(strokes) => self.strokes = strokes,
+                Err(e) => warn!("ignoring {} ({}); it will be replaced on the next save", strokes_path.display(), e),
+            }
+        }
+        
+        // Bookmarks; a damaged file only costs the saved views
+        let bookmarks_path = self.bookmarks_path();
+        if bookmarks_path.exists() {
+            match fs::read(&bookmarks_path).map_err(|e| e.to_string()).and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string())) {
+                Ok(bookmarks) => self.bookmarks = bookmarks,
+                Err(e) => warn!("ignoring {} ({}); it will be replaced when a bookmark is set", bookmarks_path.display(), e),
+            }
+        }
+        Ok(())
+    }
+    
+    /// Older boards (and older mode toggles) baked ink into the background cache. Move any
+    /// pixel that isn't the background color into the drawing layer - composited under what's
+    /// already drawn there, so the picture doesn't change - and leave the cache plain background.
+    fn migrate_background_ink(&mut self) {
+        let bg = self.config.mode.background_color();
+        // A plain background allocates no tiles; skip the scan entirely then
+        if self.cache.fill == bg && !self.cache.is_dense() && self.cache.tiles.iter().all(Option::is_none) {
+            return;
+        }
+        
+        let width = self.config.width as usize;
+        let mut background = vec![0u8; width * 4];
+        let mut moved = 0usize;
+        for y in 0..self.config.height as usize {
+            self.cache.read_row(y, &mut background);
+            for (x, pixel) in background.chunks_exact(4).enumerate() {
+                if pixel == bg {
+                    continue;
+                }
+                let ink = self.drawing_layer.get(x, y);
+                let a = ink[3] as u32;
+                let multiply = self.drawing_layer.blend_mode(x, y) == BlendMode::Multiply;
+                let mut out = [0, 0, 0, 255];
+                for c in 0..3 {
+                    out[c] = if multiply {
+                        (pixel[c] as u32 * (255 * (255 - a) + ink[c] as u32 * a) / (255 * 255)) as u8
+                    } else {
+                        ((ink[c] as u32 * a + pixel[c] as u32 * (255 - a)) / 255) as u8
+                    };
+                }
+                self.drawing_layer.set(x, y, out);
+                self.drawing_layer.set_blend_mode(x, y, BlendMode::Normal);
+                moved += 1;
+            }
+        }
+        
+        if moved > 0 {
+            info!("Moved {} pixels of ink from the background into the drawing layer", moved);
+            self.cache.reset(bg);
+            self.has_drawings = true;
+        }
+    }
+    
+    /// Drawing layer sidecar for this board (`<board>.drawing`, next to the board file, e.g.
+    /// `notes.data.drawing`)
+    fn drawing_path(&self) -> PathBuf {
+        sidecar_path(&self.file_path, "drawing")
+    }
+    
+    /// Highlighter blend mode sidecar for this board (`<board>.blend`)
+    fn blend_path(&self) -> PathBuf {
+        sidecar_path(&self.file_path, "blend")
+    }
+    
+    /// Stroke log sidecar for this board (`<board>.strokes`)
+    fn strokes_path(&self) -> PathBuf {
+        sidecar_path(&self.file_path, "strokes")
+    }
+    
+    /// Crash-recovery journal for this board (`<board>.journal`, one JSON entry per line)
+    pub fn journal_path(&self) -> PathBuf {
+        sidecar_path(&self.file_path, "journal")
+    }
+    
+    /// Saved views sidecar for this board (`<board>.bookmarks`, JSON)
+    fn bookmarks_path(&self) -> PathBuf {
+        sidecar_path(&self.file_path, "bookmarks")
+    }
+    
+    /// Last view, brush and pen color sidecar for this board (`<board>.session`, JSON)
+    pub fn session_path(&self) -> PathBuf {
+        sidecar_path(&self.file_path, "session")
+    }
+    
+    /// Store the current view in a bookmark slot and write the bookmarks file right away
+    /// (it's tiny, and a view is worth keeping even if the board is never saved)
+    pub fn set_bookmark(&mut self, slot: usize) -> io::Result<()> {
+        self.bookmarks[slot] = Some(Bookmark { position: self.viewport.position, zoom: self.viewport.zoom });
+        let json = serde_json::to_vec_pretty(&self.bookmarks).map_err(io::Error::other)?;
+        write_file_atomically(&self.bookmarks_path(), json.len() as u64, false, |out| out.write_all(&json))
+    }
+    
+    /// Append a finished stroke to the log (saved with the next sync) and to the journal
+    pub fn log_stroke(&mut self, stroke: Stroke) {
+        self.append_journal(&JournalEntry::Stroke { index: self.strokes.len(), stroke: stroke.clone() });
+        self.strokes.push(stroke);
+        self.strokes_dirty = true;
+    }
+    
+    /// Add an entry to the journal. It's only written through to the OS (no fsync), which
+    /// is enough to survive the app crashing or being killed.
+    fn append_journal(&mut self, entry: &JournalEntry) {
+        if self.journal_paused {
+            return;
+        }
+        let mut line = match serde_json::to_vec(entry) {
+            Ok(line) => line,
+            Err(e) => return error!("Journal error: {}", e),
+        };
+        line.push(b'\n');
+        
+        if self.journal.is_none() {
+            match OpenOptions::new().create(true).append(true).open(self.journal_path()) {
+                Ok(file) => self.journal = Some(file),
+                Err(e) => return error!("Journal error: {}", e),
+            }
+        }
+        if let Some(file) = &mut self.journal {
+            match file.write_all(&line) {
+                Ok(()) => self.journal_len += line.len() as u64,
+                Err(e) => {
+                    // Reopened on the next entry; a torn line is skipped on recovery
+                    error!("Journal error: {}", e);
+                    self.journal = None;
+                }
+            }
+        }
+    }
+    
+    /// Drop the first `covered` bytes of the journal, which a finished save has made redundant.
+    /// Entries added while the save was running are kept; an empty journal is deleted.
+    pub fn trim_journal(&mut self, covered: u64) -> io::Result<()> {
+        if covered == 0 {
+            return Ok(());
+        }
+        let path = self.journal_path();
+        if covered >= self.journal_len {
+            self.journal = None;
+            self.journal_len = 0;
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        let mut rest = Vec::new();
+        let mut file = File::open(&path)?;
+        file.seek(SeekFrom::Start(covered))?;
+        file.read_to_end(&mut rest)?;
+        self.journal = None;
+        write_file_atomically(&path, rest.len() as u64, false, |out| out.write_all(&rest))?;
+        self.journal_len = rest.len() as u64;
+        Ok(())
+    }
+    
+    /// Forget the journal and stop journaling until the next save (after a change that can't
+    /// be replayed, like restoring a snapshot). A crash before that save loses the same
+    /// work as without a journal, but never replays strokes onto the wrong picture.
+    fn pause_journal(&mut self) {
+        self.journal_paused = true;
+        if let Err(e) = self.trim_journal(u64::MAX) {
+            error!("Journal error: {}", e);
+        }
+    }
+    
+    /// Bring back strokes from a journal left behind by a crash: replay its entries against
+    /// the stroke log loaded from disk and draw the strokes that never made it into a save
+    /// (along with mode switches and background clears, which are saved in the background).
+    /// Returns how many strokes were drawn. Undos that reach below the last save can't take
+    /// ink off the saved picture, so those strokes stay.
+    pub fn recover_journal(&mut self) -> io::Result<usize> {
+        let data = fs::read(self.journal_path())?;
+        let saved = self.strokes.len();
+        let mut log = self.strokes.clone();
+        let mut cleared_logs: Vec<Vec<Stroke>> = Vec::new(); // Logs set aside by Clear, for Undo
+        let mut kept = saved; // Length of the saved log prefix still intact
+        let mut background_clears = 0usize; // Background clears not undone again
+        let mut mode = None; // Last mode switched to
+        for line in data.split(|&b| b == b'\n').filter(|line| !line.is_empty()) {
+            // A line cut off by the crash ends the journal
+            let Ok(entry) = serde_json::from_slice::<JournalEntry>(line) else {
+                break;
+            };
+            match entry {
+                JournalEntry::Stroke { index, stroke } => {
+                    // Entries the last save already covered are in the loaded log
+                    if index < log.len() && cleared_logs.is_empty() && index < saved {
+                        continue;
+                    }
+                    log.truncate(index);
+                    log.push(stroke);
+                }
+                JournalEntry::Undo { strokes, background } => {
+                    if background {
+                        background_clears = background_clears.saturating_sub(1);
+                    }
+                    if strokes > log.len() {
+                        if let Some(previous) = cleared_logs.pop() {
+                            log = previous;
+                        }
+                    }
+                    log.truncate(strokes);
+                }
+                JournalEntry::Clear => cleared_logs.push(std::mem::take(&mut log)),
+                JournalEntry::ClearBackground => background_clears += 1,
+                JournalEntry::Mode { id, background } => mode = BoardMode::from_header(id, Some(background)),
+            }
+            if cleared_logs.is_empty() {
+                kept = kept.min(log.len());
+            }
+        }
+        
+        // Mode switches and background clears are only written by the next save; redo them
+        if let Some(mode) = mode.filter(|&mode| mode != self.config.mode) {
+            self.config.mode = mode;
+            self.header_dirty = true;
+            background_clears += 1; // The background takes the new mode's color
+        }
+        if background_clears > 0 {
+            self.cache.reset(self.config.mode.background_color());
+        }
+        
+        let redraw = if cleared_logs.is_empty() {
+            kept
+        } else {
+            // Cleared since the last save: start over from empty ink
+            self.drawing_layer.reset([0, 0, 0, 0]);
+            self.has_drawings = false;
+            0
+        };
+        for stroke in &log[redraw.min(log.len())..] {
+            self.draw_logged_stroke(stroke);
+        }
+        let drawn = log.len().saturating_sub(redraw);
+        self.strokes = log;
+        self.strokes_dirty = true;
+        self.viewport_dirty = true;
+        Ok(drawn)
+    }
+    
+    /// Move a legacy shared sidecar to this board's own path if it fits this board and the
+    /// board doesn't have its own yet
+    fn migrate_legacy_sidecar(&self, legacy: &Path, target: &Path, expected_len: u64) -> io::Result<()> {
+        if target.exists() || !legacy.exists() || fs::metadata(legacy)?.len() != expected_len {
+            return Ok(());
+        }
+        info!("Migrating {} to {}", legacy.display(), target.display());
+        fs::rename(legacy, target)
+    }
+
+    /// Draw a pixel at the given position (writes to drawing layer)
+    ///
+    /// After `wrap_y` and the horizontal `rem_euclid`, `0 <= x < width` and
+    /// `0 <= y < height`, which is what the tile lookup in `TiledLayer::set` expects.
+    #[inline(always)]
+    pub fn draw_pixel(&mut self, x: i32, y: i32, color: [u8; 4]) {
+        // Always wrap horizontally (cylindrical); vertically only on toroidal boards
+        let Some(y) = self.config.wrap_y(y) else {
+            return; // Don't draw outside vertical bounds
+        };
+        
+        let wrapped_x = x.rem_euclid(self.config.width as i32) as usize;
+        if !self.scratch_active {
+            self.record_undo_tile(wrapped_x, y);
+        }
+
+        // Scratch mode redirects strokes to the throwaway layer (same size)
+        let (layer, has_pixels) = if self.scratch_active {
+            (&mut self.scratch_layer, &mut self.has_scratch)
+        } else {
+            (&mut self.drawing_layer, &mut self.has_drawings)
+        };
+        layer.set(wrapped_x, y, color);
+        
+        // Mark that we have drawings (if not erasing)
+        if color[3] != 0 {
+            *has_pixels = true;
+        }
+        
+        // Plain writes make the pixel normal ink again (highlighter sets Multiply afterwards)
+        if !self.scratch_active {
+            self.drawing_layer.set_blend_mode(wrapped_x, y, BlendMode::Normal);
+        }
+    }
+    
+    /// Set the blend mode of a drawing layer pixel (x wraps; scratch marks are always normal)
+    pub fn set_blend_mode(&mut self, x: i32, y: i32, mode: BlendMode) {
+        let Some(y) = self.config.wrap_y(y).filter(|_| !self.scratch_active) else {
+            return;
+        };
+        let x = x.rem_euclid(self.config.width as i32) as usize;
+        if self.drawing_layer.blend_mode(x, y) == mode {
+            return;
+        }
+        self.record_undo_tile(x, y);
+        self.drawing_layer.set_blend_mode(x, y, mode); // Saved alongside the drawing layer's tiles
+    }
+    
+    /// Composite a finished translucent stroke onto the active layer (src-over, once per pixel).
+    /// `mask` maps pixel index (y * width + wrapped x) to the stroke's alpha at that pixel.
+    pub fn blend_stroke(&mut self, mask: &HashMap<usize, u8>, color: [u8; 4]) {
+        let width = self.config.width as usize;
+        if !self.scratch_active {
+            let height = self.drawing_layer.height;
+            for &index in mask.keys().filter(|&&index| index / width < height) {
+                self.record_undo_tile(index % width, index / width);
+            }
+        }
+        
+        let (layer, has_pixels) = if self.scratch_active {
+            (&mut self.scratch_layer, &mut self.has_scratch)
+        } else {
+            (&mut self.drawing_layer, &mut self.has_drawings)
+        };
+        
+        for (&index, &alpha) in mask {
+            let (x, y) = (index % width, index / width);
+            if y >= layer.height {
+                continue;
+            }
+            let dst = composite_over(layer.get(x, y), [color[0], color[1], color[2], alpha]);
+            if dst[3] == 0 {
+                continue;
+            }
+            layer.set(x, y, dst);
+            
+            if !self.scratch_active {
+                layer.set_blend_mode(x, y, BlendMode::Normal);
+            }
+        }
+        
+        if !mask.is_empty() {
+            *has_pixels = true;
+        }
+    }
+    
+    /// Rasterize a logged stroke onto the active layer the way the brush drew it (replay).
+    /// Texture grain isn't logged, so textured strokes come back smooth.
+    pub fn draw_logged_stroke(&mut self, stroke: &Stroke) {
+        if stroke.points.is_empty() {
+            return;
+        }
+        let radius = (stroke.brush_size / 2) as i32;
+        let color = if stroke.eraser { [0, 0, 0, 0] } else { stroke.color };
+        let masked = !stroke.eraser && !stroke.highlighter && color[3] < 255;
+        let board_width = self.config.width as i32;
+        let mut mask = HashMap::new();
+        
+        // Same stamping as draw_segment: one brush per pixel of distance between points
+        let last = stroke.points.len().saturating_sub(1);
+        for i in 0..=last {
+            let (from, to) = (stroke.points[i], stroke.points[(i + 1).min(last)]);
+            let (dx, dy) = (to.x - from.x, to.y - from.y);
+            let steps = (dx * dx + dy * dy).sqrt().ceil().max(1.0) as i32;
+            for step in 0..=steps {
+                let t = step as f32 / steps as f32;
+                let (cx, cy) = ((from.x + dx * t) as i32, (from.y + dy * t) as i32);
+                for oy in -radius..=radius {
+                    for ox in -radius..=radius {
+                        if !stroke.shape.contains(ox, oy, radius) {
+                            continue;
+                        }
+                        let (px, py) = (cx + ox, cy + oy);
+                        if masked {
+                            if let Some(row) = self.config.wrap_y(py) {
+                                mask.insert(row * board_width as usize + px.rem_euclid(board_width) as usize, color[3]);
+                            }
+                        } else {
+                            self.draw_pixel(px, py, color);
+                            if stroke.highlighter {
+                                self.set_blend_mode(px, py, BlendMode::Multiply);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.blend_stroke(&mask, color);
+    }
+    
+    /// Rasterize text into the active layer with the 5x7 font scaled up by `scale`.
+    /// Glyphs advance 6 * scale pixels; x wraps across the seam like any other stroke.
+    pub fn stamp_text(&mut self, board_x: i32, board_y: i32, text: &str, color: [u8; 4], scale: u32) {
+        let scale = scale.max(1) as i32;
+        for (i, ch) in text.chars().enumerate() {
+            let char_x = board_x + i as i32 * 6 * scale;
+            for (row, &bits) in glyph(ch).iter().enumerate() {
+                for col in 0..5 {
+                    if (bits >> (4 - col)) & 1 == 0 {
+                        continue;
+                    }
+                    let (x0, y0) = (char_x + col * scale, board_y + row as i32 * scale);
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            self.draw_pixel(x0 + sx, y0 + sy, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Export the whole board (background, optional posters, drawings) as one PNG starting at x=0.
+    /// Rows are composited EXPORT_BAND_ROWS at a time and streamed into the encoder, so only
+    /// one band is held in memory on top of the board itself.
+    pub fn export_full_png(&self, path: &Path, posters: &[PinnedPoster]) -> io::Result<()> {
+        let width = self.config.width as usize;
+        let height = self.config.height as usize;
+        
+        let file = File::create(path)?;
+        let mut encoder = png::Encoder::new(io::BufWriter::new(file), width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut stream = encoder.write_header()
+            .and_then(|writer| writer.into_stream_writer())
+            .map_err(io::Error::other)?;
+        
+        info!("Exporting {}x{} board to {}...", width, height, path.display());
+        let mut band = vec![0u8; EXPORT_BAND_ROWS * width * 4];
+        let mut drawing = vec![0u8; EXPORT_BAND_ROWS * width * 4];
+        let mut rgb = Vec::with_capacity(EXPORT_BAND_ROWS * width * 3);
+        let mut progress = ProgressLog::default();
+        
+        for band_y in (0..height).step_by(EXPORT_BAND_ROWS) {
+            let rows = EXPORT_BAND_ROWS.min(height - band_y);
+            let band = self.composite_band(&mut band, &mut drawing, band_y, rows, posters);
+            
+            rgb.clear();
+            rgb.extend(band.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]));
+            stream.write_all(&rgb)?;
+            
+            progress.update(((band_y + rows) * 100 / height) as u64);
+        }
+        
+        stream.finish().map_err(io::Error::other)?;
+        info!("Export complete");
+        Ok(())
+    }
+    
+    /// Composite full-width board rows band_y..band_y + rows (background, posters, drawings)
+    /// into `band`, using `drawing` as scratch space. Returns the filled part of `band`.
+    fn composite_band<'a>(&self, band: &'a mut [u8], drawing: &mut [u8], band_y: usize, rows: usize, posters: &[PinnedPoster]) -> &'a mut [u8] {
+        let width = self.config.width as usize;
+        let band_len = rows * width * 4;
+        for r in 0..rows {
+            let row = r * width * 4..(r + 1) * width * 4;
+            self.cache.read_row(band_y + r, &mut band[row.clone()]);
+            self.drawing_layer.read_row(band_y + r, &mut drawing[row]);
+        }
+        let band = &mut band[..band_len];
+        
+        // Paper ruling sits on the background, under posters and ink
+        let paper = self.config.paper;
+        if paper.style != PaperStyle::Plain {
+            let marked: Vec<usize> = (0..width).filter(|&x| paper.on_mark(x)).collect();
+            let color = paper.color_in(self.config.mode);
+            for (r, row) in band.chunks_exact_mut(width * 4).enumerate() {
+                paper.draw_row(band_y + r, row, &marked, color);
+            }
+        }
+        
+        for poster in posters {
+            composite_poster_band(band, width, band_y, rows, poster);
+        }
+        
+        // Drawings on top in the ink blend mode, honoring highlighter (multiply) pixels
+        let drawing = &drawing[..band_len];
+        for (i, (dst, src)) in band.chunks_exact_mut(4).zip(drawing.chunks_exact(4)).enumerate() {
+            if src[3] == 0 {
+                continue;
+            }
+            let multiply = self.drawing_layer.blend_mode(i % width, band_y + i / width) == BlendMode::Multiply;
+            let mode = if multiply { BlendMode::Multiply } else { self.ink_blend };
+            mode.blend(dst, [src[0], src[1], src[2], src[3]]);
+        }
+        band
+    }
+    
+    /// Composite what the viewport shows (without the UI) into a screen-sized RGBA image,
+    /// through the same band compositor as export_full_png. Only bands with visible rows are built.
+    pub fn composite_viewport(&self, screen_width: u32, screen_height: u32, posters: &[PinnedPoster]) -> Vec<u8> {
+        let width = self.config.width as usize;
+        let height = self.config.height as usize;
+        let ((origin_x, origin_y), step) = self.viewport.fixed_mapping();
+        
+        // Board row for each screen row (None off the edge of a non-wrapping board), as render() maps them
+        let board_rows: Vec<Option<usize>> = (0..screen_height as i64)
+            .map(|sy| self.config.wrap_y((((origin_y + sy) * step) >> 32) as i32))
+            .collect();
+        let board_columns: Vec<usize> = (0..screen_width as i64)
+            .map(|sx| ((((origin_x + sx) * step) >> 32) as i32).rem_euclid(width as i32) as usize)
+            .collect();
+        
+        let mut image = vec![0u8; (screen_width * screen_height * 4) as usize];
+        for pixel in image.chunks_exact_mut(4) {
+            pixel[3] = 255; // Off-board rows stay black, like on screen
+        }
+        
+        let mut band = vec![0u8; EXPORT_BAND_ROWS * width * 4];
+        let mut drawing = vec![0u8; EXPORT_BAND_ROWS * width * 4];
+        for band_y in (0..height).step_by(EXPORT_BAND_ROWS) {
+            let rows = EXPORT_BAND_ROWS.min(height - band_y);
+            let in_band = |row: &Option<usize>| matches!(row, Some(y) if (band_y..band_y + rows).contains(y));
+            if !board_rows.iter().any(in_band) {
+                continue;
+            }
+            let band = self.composite_band(&mut band, &mut drawing, band_y, rows, posters);
+            
+            for (sy, row) in board_rows.iter().enumerate() {
+                let Some(y) = row.filter(|_| in_band(row)) else {
+                    continue;
+                };
+                let src_row = (y - band_y) * width * 4;
+                let dst_row = sy * screen_width as usize * 4;
+                for (sx, &x) in board_columns.iter().enumerate() {
+                    let (src, dst) = (src_row + x * 4, dst_row + sx * 4);
+                    image[dst..dst + 3].copy_from_slice(&band[src..src + 3]);
+                }
+            }
+        }
+        image
+    }
+    
+    /// Turn scratch mode on/off
+    pub fn set_scratch_mode(&mut self, active: bool) {
+        self.scratch_active = active;
+    }
+    
+    /// Wipe all scratch marks (frees their tiles)
+    pub fn clear_scratch(&mut self) {
+        self.scratch_layer.reset([0, 0, 0, 0]);
+        self.has_scratch = false;
+    }
+    
+    /// Open a new undo step (keep max_undo_steps steps). Nothing is copied yet: each drawing
+    /// layer tile is copied into the step just before its first change (see record_undo_tile).
+    pub fn save_undo_state(&mut self) {
+        if self.max_undo_steps == 0 {
+            return;
+        }
+        
+        let snapshot = UndoSnapshot {
+            tiles: Vec::new(),
+            saved: vec![false; self.drawing_layer.tiles.len()],
+            stroke_count: self.strokes.len(),
+            has_drawings: self.has_drawings,
+            cleared: None,
+        };
+        self.undo_stack.push(snapshot);
+        self.undo_pushes += 1;
+        
+        // Keep only the most recent steps, and drop old ones while the copies are over budget
+        let mut total: u64 = self.undo_stack.iter().map(UndoSnapshot::bytes).sum();
+        while self.undo_stack.len() > self.max_undo_steps || (self.undo_stack.len() > 1 && total > UNDO_MEMORY_BUDGET) {
+            total -= self.undo_stack.remove(0).bytes();
+        }
+    }
+    
+    /// Copy the drawing layer tile holding (x, y), with its blend modes, into the open undo
+    /// step before the step first changes it (x < width, y < height)
+    #[inline(always)]
+    fn record_undo_tile(&mut self, x: usize, y: usize) {
+        let tile = self.drawing_layer.tile_index(x, y);
+        if self.undo_stack.last().is_some_and(|step| !step.saved[tile]) {
+            self.record_undo(tile);
+        }
+    }
+    
+    fn record_undo(&mut self, tile: usize) {
+        let Some(step) = self.undo_stack.last_mut() else {
+            return;
+        };
+        if std::mem::replace(&mut step.saved[tile], true) {
+            return;
+        }
+        step.tiles.push(UndoTile {
+            index: tile,
+            pixels: self.drawing_layer.tiles[tile].clone(),
+            blend_modes: self.drawing_layer.blend_modes[tile].clone(),
+        });
+    }
+    
+    /// Copy every tile a whole-layer change can touch into the open undo step (clear, whole-board quantize)
+    fn record_undo_all(&mut self) {
+        for tile in 0..self.drawing_layer.tiles.len() {
+            if self.drawing_layer.tiles[tile].is_some() || self.drawing_layer.blend_modes[tile].is_some() {
+                self.record_undo(tile);
+            }
+        }
+    }
+    
+    /// Put the tiles and blend modes an undo step changed back the way they were
+    fn restore_undo_tiles(&mut self, step: &mut UndoSnapshot) {
+        for tile in step.tiles.drain(..) {
+            self.drawing_layer.restore_tile(tile.index, tile.pixels, tile.blend_modes);
+        }
+    }
+    
+    /// Memory held by the undo steps' copies
+    pub fn undo_bytes(&self) -> u64 {
+        self.undo_stack.iter().map(UndoSnapshot::bytes).sum()
+    }
+    
+    /// Size of the board file and its sidecars on disk
+    pub fn disk_bytes(&self) -> u64 {
+        [self.file_path.clone(), self.drawing_path(), self.blend_path(), self.strokes_path(), self.journal_path(), self.bookmarks_path(), self.session_path()]
+            .iter()
+            .filter_map(|path| fs::metadata(path).map(|m| m.len()).ok())
+            .sum()
+    }
+    
+    /// Set the undo depth; memory is bounded separately by UNDO_MEMORY_BUDGET
+    pub fn set_max_undo_steps(&mut self, steps: usize) {
+        self.max_undo_steps = steps;
+        // Drop the oldest steps if the stack is now too deep
+        let excess = self.undo_stack.len().saturating_sub(steps);
+        self.undo_stack.drain(..excess);
+    }
+    
+    /// Undo last operation by putting back the tiles it changed
+    pub fn undo(&mut self) -> bool {
+        if let Some(mut previous_state) = self.undo_stack.pop() {
+            self.restore_undo_tiles(&mut previous_state);
+            self.has_drawings = previous_state.has_drawings;
+            let cleared = previous_state.cleared.unwrap_or(ClearedState { background: None, strokes: None });
+            let restored_background = cleared.background.is_some();
+            // Undoing a clear: bring back the background (rewritten on the next save) and the stroke log
+            if let Some(background) = cleared.background {
+                self.cache = background;
+                self.cache.mark_all_dirty();
+                self.viewport_dirty = true;
+            }
+            match cleared.strokes {
+                Some(strokes) => self.strokes = strokes,
+                None => self.strokes.truncate(previous_state.stroke_count),
+            }
+            self.strokes_dirty = true;
+            self.append_journal(&JournalEntry::Undo { strokes: self.strokes.len(), background: restored_background });
+            true
+        } else {
+            false
+        }
+    }
+    
+    /// Put back everything changed since the open undo step began, leaving the step open, so
+    /// a stroke can be redrawn differently as the same step. False if there is no open step.
+    pub fn rewind_undo_step(&mut self) -> bool {
+        let Some(mut step) = self.undo_stack.pop() else {
+            return false;
+        };
+        self.restore_undo_tiles(&mut step);
+        self.has_drawings = step.has_drawings;
+        // Tiles are copied again on their next change
+        step.saved.fill(false);
+        self.undo_stack.push(step);
+        true
+    }
+    
+    /// Export the stroke log as SVG polylines over a background rect the size of the board.
+    /// Only freehand and straight strokes are logged (not fills, text or anything drawn
+    /// before this session); eraser strokes are painted in the background color.
+    pub fn export_svg(&self, path: &Path) -> io::Result<()> {
+        let (width, height) = (self.config.width as f32, self.config.height as f32);
+        let background = self.config.mode.background_color();
+        let hex = |c: [u8; 4]| format!("#{:02x}{:02x}{:02x}", c[0], c[1], c[2]);
+        
+        let mut svg = io::BufWriter::new(File::create(path)?);
+        writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#, w = width, h = height)?;
+        writeln!(svg, r#"<rect width="100%" height="100%" fill="{}"/>"#, hex(background))?;
+        
+        for stroke in &self.strokes {
+            let Some(first) = stroke.points.first() else {
+                continue;
+            };
+            
+            // Points are unwrapped board coordinates; shift the stroke so it starts on the board,
+            // then repeat it one board over wherever it runs across the seam
+            let shift_x = -(first.x / width).floor() * width;
+            let (min_x, max_x) = stroke.points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.x + shift_x), hi.max(p.x + shift_x)));
+            let mut offsets_x = vec![shift_x];
+            if max_x >= width {
+                offsets_x.push(shift_x - width);
+            }
+            if min_x < 0.0 {
+                offsets_x.push(shift_x + width);
+            }
+            let mut offsets_y = vec![0.0];
+            if self.config.wrap_vertical {
+                let shift_y = -(first.y / height).floor() * height;
+                let (min_y, max_y) = stroke.points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.y + shift_y), hi.max(p.y + shift_y)));
+                offsets_y = vec![shift_y];
+                if max_y >= height {
+                    offsets_y.push(shift_y - height);
+                }
+                if min_y < 0.0 {
+                    offsets_y.push(shift_y + height);
+                }
+            }
+            
+            let (color, opacity) = if stroke.eraser {
+                (background, 1.0)
+            } else {
+                (stroke.color, stroke.color[3] as f32 / 255.0)
+            };
+            let (cap, join) = match stroke.shape {
+                BrushShape::Round => ("round", "round"),
+                BrushShape::Square | BrushShape::Chisel { .. } => ("square", "miter"),
+            };
+            let blend = if stroke.highlighter { r#" style="mix-blend-mode:multiply""# } else { "" };
+            
+            for &dx in &offsets_x {
+                for &dy in &offsets_y {
+                    if let BrushShape::Chisel { angle } = stroke.shape {
+                        writeln!(svg, "{}", chisel_svg(stroke, angle, (dx, dy), hex(color), opacity, blend))?;
+                        continue;
+                    }
+                    let points: Vec<String> = stroke.points.iter()
+                        .map(|p| format!("{:.1},{:.1}", p.x + dx, p.y + dy))
+                        .collect();
+                    writeln!(
+                        svg,
+                        r#"<polyline points="{}" fill="none" stroke="{}" stroke-opacity="{:.3}" stroke-width="{}" stroke-linecap="{}" stroke-linejoin="{}"{}/>"#,
+                        points.join(" "), hex(color), opacity, stroke.brush_size.max(1), cap, join, blend,
+                    )?;
+                }
+            }
+        }
+        
+        writeln!(svg, "</svg>")?;
+        svg.flush()?;
+        info!("Exported {} strokes to {}", self.strokes.len(), path.display());
+        Ok(())
+    }
+    
+    /// Copy out everything the next save has to write and mark the layers clean. Only dirty
+    /// tiles are patched, in place, into the raw board file and the sidecars; RLE boards,
+    /// missing or mis-sized files and --full-sync rewrite the whole file.
+    fn sync_job(&mut self) -> SyncJob {
+        let pixel_count = self.config.width as u64 * self.config.height as u64;
+        let file_len = |path: &Path| fs::metadata(path).map(|m| m.len()).ok();
+        
+        let header = self.header_bytes();
+        let rewrite_board = self.full_sync
+            || self.storage_format != StorageFormat::Raw
+            || self.header_len != header.len() as u64
+            || file_len(&self.file_path) != Some(self.header_len + pixel_count * 4);
+        let drawing_path = self.drawing_path();
+        let rewrite_drawing = self.full_sync || file_len(&drawing_path) != Some(pixel_count * 4);
+        let blend_path = self.blend_path();
+        let rewrite_blend = self.full_sync || file_len(&blend_path) != Some(pixel_count);
+        
+        let has_blend_modes = self.drawing_layer.has_blend_modes();
+        
+        let job = SyncJob {
+            board_path: self.file_path.clone(),
+            drawing_path,
+            blend_path,
+            header,
+            header_len: self.header_len,
+            width: self.config.width as usize,
+            pixel_count,
+            storage_format: self.storage_format,
+            rewrite_board,
+            write_header: std::mem::take(&mut self.header_dirty),
+            rewrite_drawing,
+            rewrite_blend,
+            cache: self.cache.save_snapshot(rewrite_board, false),
+            // Blend modes ride along with the drawing layer's dirty tiles
+            drawing_layer: self.drawing_layer.save_snapshot(rewrite_drawing, rewrite_blend),
+            has_blend_modes,
+            strokes_path: self.strokes_path(),
+            strokes: std::mem::take(&mut self.strokes_dirty).then(|| self.strokes.clone()),
+        };
+        self.cache.mark_clean();
+        self.drawing_layer.mark_clean();
+        self.journal_covered = self.journal_len;
+        job
+    }
+    
+    /// A save that writes the whole board to `board_path` and its sidecars instead of the live
+    /// files, for a named snapshot. The board file is RLE-encoded, since snapshots are mostly
+    /// plain background; nothing is marked clean.
+    pub fn snapshot_job(&self, board_path: &Path) -> SyncJob {
+        SyncJob {
+            board_path: board_path.to_path_buf(),
+            drawing_path: sidecar_path(board_path, "drawing"),
+            blend_path: sidecar_path(board_path, "blend"),
+            header: self.header_for(StorageFormat::Rle),
+            header_len: 0,
+            width: self.config.width as usize,
+            pixel_count: self.config.width as u64 * self.config.height as u64,
+            storage_format: StorageFormat::Rle,
+            rewrite_board: true,
+            write_header: false,
+            rewrite_drawing: true,
+            rewrite_blend: true,
+            cache: self.cache.save_snapshot(true, false),
+            drawing_layer: self.drawing_layer.save_snapshot(true, true),
+            has_blend_modes: self.drawing_layer.has_blend_modes(),
+            strokes_path: sidecar_path(board_path, "strokes"),
+            strokes: Some(self.strokes.clone()),
+        }
+    }
+    
+    /// Take over the contents of `other` (a snapshot opened as a board of the same size).
+    /// Everything is marked dirty, so the live files only change with the next save, and
+    /// the undo snapshots (which belong to the old contents) are dropped.
+    pub fn adopt(&mut self, other: Board) -> io::Result<()> {
+        self.finish_sync()?;
+        self.config.mode = other.config.mode;
+        self.config.paper = other.config.paper;
+        self.config.wrap_vertical = other.config.wrap_vertical;
+        self.cache = other.cache;
+        self.cache.mark_all_dirty();
+        self.drawing_layer = other.drawing_layer;
+        self.drawing_layer.mark_all_dirty();
+        self.strokes = other.strokes;
+        self.strokes_dirty = true;
+        self.has_drawings = other.has_drawings;
+        self.undo_stack.clear();
+        self.viewport_dirty = true;
+        self.pause_journal();
+        Ok(())
+    }
+    
+    /// Sync pending changes to disk and wait for the write (after any background save in flight)
+    pub fn sync(&mut self) -> io::Result<()> {
+        let pending = self.finish_sync();
+        let job = self.sync_job();
+        self.complete_sync(job.run())?;
+        pending
+    }
+    
+    /// Start writing pending changes on a worker thread so the UI keeps running. Returns false,
+    /// without doing anything, while the previous background save hasn't finished.
+    pub fn start_sync(&mut self) -> bool {
+        if self.pending_sync.is_some() {
+            return false;
+        }
+        let job = self.sync_job();
+        self.pending_sync = Some(std::thread::spawn(move || job.run()));
+        true
+    }
+    
+    /// Result of the background save once it has finished (None while it runs, or if there is none)
+    pub fn poll_sync(&mut self) -> Option<io::Result<()>> {
+        if !self.pending_sync.as_ref()?.is_finished() {
+            return None;
+        }
+        Some(self.finish_sync())
+    }
+    
+    /// Wait for the background save in flight, if any
+    pub fn finish_sync(&mut self) -> io::Result<()> {
+        match self.pending_sync.take() {
+            Some(handle) => {
+                let result = handle.join().unwrap_or_else(|_| Err(io::Error::other("save thread panicked")));
+                self.complete_sync(result)
+            }
+            None => Ok(()),
+        }
+    }
+    
+    /// Apply a finished SyncJob. A failed save re-marks everything so the next one retries it.
+    fn complete_sync(&mut self, result: io::Result<Option<u64>>) -> io::Result<()> {
+        let covered = std::mem::take(&mut self.journal_covered);
+        if result.is_ok() {
+            // Everything journaled before the save is on disk now
+            self.journal_paused = false;
+            if let Err(e) = self.trim_journal(covered) {
+                error!("Journal error: {}", e);
+            }
+        }
+        match result {
+            Ok(Some(header_len)) => {
+                self.header_len = header_len;
+                // The old handle still points at the replaced file
+                self.data_file = OpenOptions::new().read(true).write(true).open(&self.file_path)?;
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(e) => {
+                self.cache.mark_all_dirty();
+                self.drawing_layer.mark_all_dirty();
+                self.strokes_dirty = true;
+                self.header_dirty = true;
+                Err(e)
+            }
+        }
+    }
+    
+    /// Switch to the next background preset (see BOARD_MODE_PRESETS); written with the next save
+    pub fn toggle_mode(&mut self) {
+        let old_bg = self.config.mode.background_color();
+        
+        self.config.mode = self.config.mode.next();
+        
+        let new_bg = self.config.mode.background_color();
+        
+        // The cache holds nothing but background (ink lives in the drawing layer, see
+        // migrate_background_ink), so the toggle just swaps its color and is exactly reversible
+        if new_bg != old_bg {
+            self.cache.reset(new_bg);
+        }
+        let mode = self.config.mode;
+        self.append_journal(&JournalEntry::Mode { id: mode.header_id(), background: mode.background_color() });
+        self.viewport_dirty = true;
+    }
+    
+    /// Read a drawing layer pixel (x must already be wrapped, y in bounds)
+    #[inline(always)]
+    fn layer_pixel(&self, x: i32, y: i32) -> [u8; 4] {
+        self.drawing_layer.get(x as usize, y as usize)
+    }
+    
+    /// Write a horizontal run of `len` pixels starting at `x` (wrapping) on row `y`
+    fn fill_span(&mut self, x: i32, y: i32, len: i32, color: [u8; 4]) {
+        let width = self.config.width as i32;
+        for k in 0..len {
+            let wrapped_x = (x + k).rem_euclid(width) as usize;
+            self.record_undo_tile(wrapped_x, y as usize);
+            self.drawing_layer.set(wrapped_x, y as usize, color);
+            self.drawing_layer.set_blend_mode(wrapped_x, y as usize, BlendMode::Normal);
+        }
+    }
+    
+    /// Copy a rectangle (x, y, width, height) of the drawing layer; x wraps, and rows off the
+    /// edge of a non-wrapping board read transparent
+    pub fn copy_region(&self, rect: (i32, i32, u32, u32)) -> SelectionBuffer {
+        let (x, y, w, h) = rect;
+        let width = self.config.width as i32;
+        let mut pixels = vec![0u8; w as usize * h as usize * 4];
+        for (row, out) in pixels.chunks_exact_mut(w.max(1) as usize * 4).enumerate() {
+            let Some(board_y) = self.config.wrap_y(y + row as i32) else {
+                continue;
+            };
+            for (col, pixel) in out.chunks_exact_mut(4).enumerate() {
+                let board_x = (x + col as i32).rem_euclid(width) as usize;
+                pixel.copy_from_slice(&self.drawing_layer.get(board_x, board_y));
+            }
+        }
+        SelectionBuffer { width: w, height: h, pixels }
+    }
+    
+    /// Make a rectangle of the drawing layer transparent (recorded in the open undo step)
+    pub fn clear_region(&mut self, rect: (i32, i32, u32, u32)) {
+        let (x, y, w, h) = rect;
+        for row in 0..h as i32 {
+            if let Some(board_y) = self.config.wrap_y(y + row) {
+                self.fill_span(x, board_y as i32, w as i32, [0, 0, 0, 0]);
+            }
+        }
+    }
+    
+    /// Copy a rectangle out of the drawing layer and leave it transparent
+    pub fn lift_region(&mut self, rect: (i32, i32, u32, u32)) -> SelectionBuffer {
+        let buffer = self.copy_region(rect);
+        self.clear_region(rect);
+        buffer
+    }
+    
+    /// Alpha-blend lifted pixels onto the drawing layer with their top-left corner at (x, y),
+    /// wrapping like a stroke (recorded in the open undo step)
+    pub fn stamp_region(&mut self, buffer: &SelectionBuffer, x: i32, y: i32) {
+        let width = self.config.width as i32;
+        for (row, src_row) in buffer.pixels.chunks_exact(buffer.width.max(1) as usize * 4).enumerate() {
+            let Some(board_y) = self.config.wrap_y(y + row as i32) else {
+                continue;
+            };
+            for (col, src) in src_row.chunks_exact(4).enumerate() {
+                if src[3] == 0 {
+                    continue;
+                }
+                let board_x = (x + col as i32).rem_euclid(width) as usize;
+                self.record_undo_tile(board_x, board_y);
+                let pixel = composite_over(self.drawing_layer.get(board_x, board_y), [src[0], src[1], src[2], src[3]]);
+                self.drawing_layer.set(board_x, board_y, pixel);
+                self.drawing_layer.set_blend_mode(board_x, board_y, BlendMode::Normal);
+                self.has_drawings = true;
+            }
+        }
+    }
+    
+    /// Scanline flood fill of the contiguous drawing-layer region (exact RGBA match,
+    /// transparent included) containing board (x, y), wrapping horizontally.
+    /// Saves an undo state first. Returns the number of pixels filled, or None if more than
+    /// MAX_FILL_PIXELS had to be painted outside untouched tiles - in that case the layer is
+    /// left unchanged.
+    pub fn flood_fill(&mut self, x: i32, y: i32, color: [u8; 4]) -> Option<usize> {
+        let width = self.config.width as i32;
+        let Some(y) = self.config.wrap_y(y) else {
+            return Some(0);
+        };
+        
+        let (x, y) = (x.rem_euclid(width), y as i32);
+        let target = self.layer_pixel(x, y);
+        if target == color {
+            return Some(0);
+        }
+        
+        self.save_undo_state();
+        
+        let mut seeds = vec![(x, y)];
+        let mut spans: Vec<(i32, i32, i32)> = Vec::new(); // (start x, y, len) for rollback
+        let mut whole_tiles = Vec::new(); // Tiles allocated by the fill, for rollback
+        let mut filled = 0usize;
+        let mut painted = 0usize; // Pixels filled span by span, counted against MAX_FILL_PIXELS
+        
+        while let Some((sx, sy)) = seeds.pop() {
+            if self.layer_pixel(sx, sy) != target {
+                continue; // Already filled via another seed
+            }
+            
+            // An untouched tile of the target color is filled in one go, then flooding goes on
+            // from the pixels around it; a blank board is mostly such tiles
+            let tile = self.drawing_layer.tile_index(sx as usize, sy as usize);
+            if self.drawing_layer.tiles[tile].is_none() && self.drawing_layer.fill == target {
+                self.record_undo_tile(sx as usize, sy as usize);
+                self.drawing_layer.fill_tile(tile, color);
+                whole_tiles.push(tile);
+                let (x0, y0, x1, y1) = self.drawing_layer.tile_rect(tile);
+                filled += (x1 - x0) * (y1 - y0);
+                let (x0, y0, x1, y1) = (x0 as i32, y0 as i32, x1 as i32, y1 as i32);
+                let left = (x0 - 1).rem_euclid(width);
+                let right = x1.rem_euclid(width);
+                let sides = [
+                    (y0..y1).map(|y| (left, Some(y as usize))).collect::<Vec<_>>(),
+                    (y0..y1).map(|y| (right, Some(y as usize))).collect(),
+                    (x0..x1).map(|x| (x, self.config.wrap_y(y0 - 1))).collect(),
+                    (x0..x1).map(|x| (x, self.config.wrap_y(y1))).collect(),
+                ];
+                for side in sides {
+                    self.seed_runs(side.into_iter().filter_map(|(x, y)| Some((x, y? as i32))), target, &mut seeds);
+                }
+                continue;
+            }
+            
+            // Extend the run both ways; a row can wrap all the way around at most once
+            let mut left = sx;
+            let mut len = 1;
+            while len < width && self.layer_pixel((left - 1).rem_euclid(width), sy) == target {
+                left -= 1;
+                len += 1;
+            }
+            let mut right = sx;
+            while len < width && self.layer_pixel((right + 1).rem_euclid(width), sy) == target {
+                right += 1;
+                len += 1;
+            }
+            
+            self.fill_span(left, sy, len, color);
+            spans.push((left, sy, len));
+            filled += len as usize;
+            painted += len as usize;
+            
+            if painted > MAX_FILL_PIXELS {
+                // Runaway fill - put every span and tile back and drop the snapshot we just took
+                for &(span_x, span_y, span_len) in spans.iter().rev() {
+                    self.fill_span(span_x, span_y, span_len, target);
+                }
+                for &tile in &whole_tiles {
+                    self.drawing_layer.restore_tile(tile, None, None);
+                }
+                if let Some(mut snapshot) = self.undo_stack.pop() {
+                    self.restore_undo_tiles(&mut snapshot); // fill_span reset the blend modes to normal
+                    self.undo_pushes -= 1;
+                }
+                return None;
+            }
+            
+            // Seed one point per matching run in the rows above and below (wrapping on toroidal boards)
+            for ny in [sy - 1, sy + 1] {
+                let Some(ny) = self.config.wrap_y(ny) else {
+                    continue;
+                };
+                self.seed_runs((left..=right).map(|k| (k.rem_euclid(width), ny as i32)), target, &mut seeds);
+            }
+        }
+        
+        if color[3] != 0 {
+            self.has_drawings = true;
+        }
+        Some(filled)
+    }
+    
+    /// Push one seed per run of `target` pixels along a line of (wrapped, in-bounds) pixels
+    fn seed_runs(&self, line: impl Iterator<Item = (i32, i32)>, target: [u8; 4], seeds: &mut Vec<(i32, i32)>) {
+        let mut in_run = false;
+        for (x, y) in line {
+            let matches = self.layer_pixel(x, y) == target;
+            if matches && !in_run {
+                seeds.push((x, y));
+            }
+            in_run = matches;
+        }
+    }
+    
+    /// Snap every drawn pixel to its nearest palette color (squared RGB distance).
+    /// `region` is (x, y, w, h) in board coords with x wrapping; `None` covers the whole layer.
+    /// Partially transparent pixels become fully opaque or fully transparent so the result is crisp.
+    pub fn quantize_to_palette(&mut self, palette: &[[u8; 4]], region: Option<(i32, i32, u32, u32)>) {
+        if palette.is_empty() {
+            return;
+        }
+        
+        let (width, height) = (self.config.width as usize, self.config.height as usize);
+        let (rx, ry, rw, rh) = region.unwrap_or((0, 0, self.config.width, self.config.height));
+        let rw = rw.min(self.config.width) as i64;
+        let y_range = ry..ry.saturating_add(rh as i32);
+        
+        // Only tiles holding ink inside the region can change, so a selection only copies those
+        match region {
+            None => self.record_undo_all(),
+            Some(_) => {
+                for tile in 0..self.drawing_layer.tiles.len() {
+                    let (x0, y0, x1, y1) = self.drawing_layer.tile_rect(tile);
+                    let dx = (x0 as i64 - rx as i64).rem_euclid(width as i64);
+                    let in_x = dx < rw || dx + (x1 - x0) as i64 > width as i64; // Region may wrap into the tile
+                    let in_y = (y0 as i64) < y_range.end as i64 && y1 as i64 > y_range.start as i64;
+                    if in_x && in_y && self.drawing_layer.tiles[tile].is_some() {
+                        self.record_undo(tile);
+                    }
+                }
+            }
+        }
+        
+        // Parallel pass over the allocated tiles, like toggle_mode (missing tiles are transparent)
+        self.drawing_layer.par_tiles_mut(|x0, y0, tile| {
+            for (i, pixel) in tile.chunks_exact_mut(4).enumerate() {
+                let (x, y) = (x0 + i % TILE_SIZE, y0 + i / TILE_SIZE);
+                // Skip the overhang of edge tiles and anything outside the (wrapping) region
+                if x >= width || y >= height || !y_range.contains(&(y as i32))
+                    || (x as i64 - rx as i64).rem_euclid(width as i64) >= rw {
+                    continue;
+                }
+                if pixel[3] == 0 {
+                    continue;
+                }
+                if pixel[3] < 128 {
+                    pixel.copy_from_slice(&[0, 0, 0, 0]);
+                    continue;
+                }
+                
+                let nearest = palette.iter()
+                    .min_by_key(|c| {
+                        let dr = c[0] as i32 - pixel[0] as i32;
+                        let dg = c[1] as i32 - pixel[1] as i32;
+                        let db = c[2] as i32 - pixel[2] as i32;
+                        dr * dr + dg * dg + db * db
+                    })
+                    .unwrap_or(&palette[0]);
+                pixel.copy_from_slice(&[nearest[0], nearest[1], nearest[2], 255]);
+            }
+        });
+        
+        // Dropping faint pixels may have emptied the layer
+        self.has_drawings = self.drawing_layer.has_visible_pixels();
+    }
+    
+    /// Clear the board with background color and wipe the drawing layer, as one undo step
+    pub fn clear(&mut self) {
+        self.open_clear_step(true, true);
+        self.reset_drawing();
+        self.reset_background();
+    }
+    
+    /// Wipe the drawing layer and stroke log, keeping the background (one undo step)
+    pub fn clear_drawing(&mut self) {
+        self.open_clear_step(false, true);
+        self.reset_drawing();
+    }
+    
+    /// Fill the background with the board color, keeping the ink on top (one undo step)
+    pub fn clear_background(&mut self) {
+        self.open_clear_step(true, false);
+        self.reset_background();
+    }
+    
+    /// Keep everything a clear is about to wipe on the undo stack, so a stray C isn't fatal
+    fn open_clear_step(&mut self, background: bool, drawing: bool) {
+        self.save_undo_state();
+        if let Some(snapshot) = self.undo_stack.last_mut() {
+            snapshot.cleared = Some(ClearedState {
+                background: background.then(|| self.cache.clone()),
+                strokes: drawing.then(|| self.strokes.clone()),
+            });
+        }
+        if drawing {
+            self.record_undo_all();
+        }
+    }
+    
+    /// Make the drawing layer fully transparent and empty the stroke log
+    fn reset_drawing(&mut self) {
+        self.drawing_layer.reset([0, 0, 0, 0]);
+        self.strokes.clear();
+        self.strokes_dirty = true;
+        self.append_journal(&JournalEntry::Clear);
+        self.has_drawings = false;
+        self.viewport_dirty = true;
+    }
+    
+    /// Fill the background with the board color (frees every background tile; the whole
+    /// background is written with the next save)
+    fn reset_background(&mut self) {
+        self.cache.reset(self.config.mode.background_color());
+        self.append_journal(&JournalEntry::ClearBackground);
+        self.viewport_dirty = true;
+    }
+
+    /// Get the default pen color for the current board mode
+    pub fn default_pen_color(&self) -> [u8; 4] {
+        self.config.mode.default_pen_color()
+    }
+
+    /// Render the current viewport with optional cylindrical projection
+    /// Optimized with parallel processing for maximum CPU utilization
+    pub fn render(&mut self, frame: &mut [u8], screen_width: u32, screen_height: u32) -> io::Result<()> {
+        // Whatever moved the view, never render a frame that has lost the board
+        self.clamp_viewport(screen_height);
+        
+        // Check if we can reuse the cached viewport
+        let needs_rerender = self.viewport_dirty ||
+                            self.cached_viewport_width != screen_width ||
+                            self.cached_viewport_height != screen_height ||
+                            (self.viewport.position.x - self.cached_viewport_pos.x).abs() > 0.001 ||
+                            (self.viewport.position.y - self.cached_viewport_pos.y).abs() > 0.001 ||
+                            (self.viewport.zoom - self.cached_viewport_zoom).abs() > 0.001;
+        
+        if !needs_rerender && !self.viewport_cache.is_empty() {
+            // Use cached viewport
+            frame.copy_from_slice(&self.viewport_cache);
+            return Ok(());
+        }
+        
+        // Screen-to-board mapping for rendering (see Viewport::fixed_mapping)
+        let ((origin_x, origin_y), step) = self.viewport.fixed_mapping();
+        let zoom = self.viewport.zoom;
+        
+        // A pure pan just moves the picture by whole screen pixels (the mapping's origin is
+        // snapped to them): scroll the old buffer and only render the strips that came into view
+        let (w, h) = (screen_width as i32, screen_height as i32);
+        let same_view = !self.viewport_dirty && !self.viewport_cache.is_empty() &&
+                        self.cached_viewport_width == screen_width &&
+                        self.cached_viewport_height == screen_height &&
+                        zoom == self.cached_viewport_zoom;
+        let shift = if same_view {
+            let ((old_x, old_y), _) = Viewport::fixed_mapping_at(self.cached_viewport_pos, zoom);
+            let (dx, dy) = (origin_x - old_x, origin_y - old_y);
+            (dx.abs() < w as i64 && dy.abs() < h as i64).then_some((dx as i32, dy as i32))
+        } else {
+            None
+        };
+        
+        let buffer_size = (screen_width * screen_height * 4) as usize;
+        if self.viewport_cache.len() != buffer_size {
+            self.viewport_cache = vec![0u8; buffer_size];
+        }
+        
+        if let Some((dx, dy)) = shift {
+            // New pixel (x, y) is old pixel (x + dx, y + dy); walk rows so sources aren't overwritten first
+            let len = (w - dx.abs()) as usize * 4;
+            let rows: Box<dyn Iterator<Item = i32>> = if dy > 0 { Box::new(0..h - dy) } else { Box::new((-dy..h).rev()) };
+            for y in rows {
+                let src = (((y + dy) * w + dx.max(0)) * 4) as usize;
+                let dst = ((y * w + (-dx).max(0)) * 4) as usize;
+                self.viewport_cache.copy_within(src..src + len, dst);
+            }
+        }
+        
+        // Columns of a row that need rendering: all of them, or just the strip a pan exposed
+        let exposed = |screen_y: u32| -> std::ops::Range<u32> {
+            match shift {
+                None => 0..screen_width,
+                Some((dx, dy)) => {
+                    let new_row = if dy > 0 { screen_y as i32 >= h - dy } else { (screen_y as i32) < -dy };
+                    if new_row {
+                        0..screen_width
+                    } else if dx > 0 {
+                        (w - dx) as u32..screen_width
+                    } else {
+                        0..(-dx) as u32
+                    }
+                }
+            }
+        };
+        
+        let black = [0u8, 0u8, 0u8, 255u8]; // Black for out-of-bounds areas (never shown on toroidal boards)
+        let width = self.config.width as i32;
+        let cache = &self.cache;
+        let config = &self.config;
+        
+        // Parallel row rendering for maximum CPU utilization
+        self.viewport_cache.par_chunks_mut((screen_width * 4) as usize)
+            .enumerate()
+            .for_each(|(screen_y, row)| {
+                // Apply zoom: convert screen coords to board coords
+                let board_y = (((origin_y + screen_y as i64) * step) >> 32) as i32;
+                
+                if let Some(board_y) = config.wrap_y(board_y) {
+                    // Process pixels in this row
+                    for screen_x in exposed(screen_y as u32) {
+                        let board_x = (((origin_x + screen_x as i64) * step) >> 32) as i32;
+                        let wrapped_x = board_x.rem_euclid(width) as usize;
+                        let dst_offset = (screen_x * 4) as usize;
+                        row[dst_offset..dst_offset + 4].copy_from_slice(&cache.get(wrapped_x, board_y));
+                    }
+                } else {
+                    // Fill with black if out of vertical bounds
+                    for screen_x in exposed(screen_y as u32) {
+                        let dst_offset = (screen_x * 4) as usize;
+                        row[dst_offset..dst_offset + 4].copy_from_slice(&black);
+                    }
+                }
+            });
+        
+        // Update cache metadata
+        self.cached_viewport_width = screen_width;
+        self.cached_viewport_height = screen_height;
+        self.cached_viewport_pos = Point { x: self.viewport.position.x, y: self.viewport.position.y };
+        self.cached_viewport_zoom = self.viewport.zoom;
+        self.viewport_dirty = false;
+        
+        // Copy to output frame
+        frame.copy_from_slice(&self.viewport_cache);
+
+        Ok(())
+    }
+    
+    /// Keep the view on the board vertically: y wraps on a toroidal board and is otherwise held
+    /// within the board plus PAN_OVERSCROLL screen pixels. A board shorter than the window is
+    /// centered. Returns true if y had to be pulled back.
+    pub fn clamp_viewport(&mut self, screen_height: u32) -> bool {
+        let height = self.config.height as f32;
+        let viewport = &mut self.viewport;
+        let y = viewport.position.y;
+        if self.config.wrap_vertical {
+            viewport.position.y = y.rem_euclid(height);
+            return false;
+        }
+        
+        let visible_rows = screen_height as f32 / viewport.zoom;
+        viewport.position.y = if visible_rows >= height {
+            (height - visible_rows) / 2.0
+        } else {
+            let margin = PAN_OVERSCROLL / viewport.zoom;
+            y.clamp(-margin, height - visible_rows + margin)
+        };
+        viewport.position.y != y
+    }
+    
+    /// Change the zoom while keeping the board point under screen position (sx, sy) in place
+    pub fn zoom_at(&mut self, zoom: f32, sx: f64, sy: f64, screen_height: u32) {
+        let anchor_x = self.viewport.position.x + sx as f32 / self.viewport.zoom;
+        let anchor_y = self.viewport.position.y + sy as f32 / self.viewport.zoom;
+        self.viewport.zoom = zoom;
+        self.viewport.position.x = anchor_x - sx as f32 / self.viewport.zoom;
+        self.viewport.position.y = anchor_y - sy as f32 / self.viewport.zoom;
+        self.clamp_viewport(screen_height);
+    }
+    
+    /// Draw the paper ruling over the rendered background, mapped to the screen like render()
+    pub fn render_paper(&self, frame: &mut [u8], screen_width: u32, screen_height: u32) {
+        let paper = self.config.paper;
+        if paper.style == PaperStyle::Plain {
+            return;
+        }
+        
+        let width = self.config.width as i32;
+        let ((origin_x, origin_y), step) = self.viewport.fixed_mapping();
+        let marked: Vec<usize> = (0..screen_width as i64)
+            .filter(|sx| paper.on_mark(((((origin_x + sx) * step) >> 32) as i32).rem_euclid(width) as usize))
+            .map(|sx| sx as usize)
+            .collect();
+        let color = paper.color_in(self.config.mode);
+        for (sy, row) in frame.chunks_exact_mut(screen_width as usize * 4).take(screen_height as usize).enumerate() {
+            if let Some(y) = self.config.wrap_y((((origin_y + sy as i64) * step) >> 32) as i32) {
+                paper.draw_row(y, row, &marked, color);
+            }
+        }
+    }
+    
+    /// Render the drawing layer with alpha blending on top of the current frame
+    pub fn render_drawing_layer(&self, frame: &mut [u8], screen_width: u32, _screen_height: u32, opacity: u8) {
+        // Early exit if no drawings at all (or the layer is hidden)
+        if !self.has_drawings || opacity == 0 {
+            return;
+        }
+        
+        self.blend_layer(&self.drawing_layer, self.ink_blend, frame, screen_width, opacity);
+    }
+    
+    /// Render the scratch layer on top of everything drawn so far
+    pub fn render_scratch_layer(&self, frame: &mut [u8], screen_width: u32, opacity: u8) {
+        if !self.has_scratch || opacity == 0 {
+            return;
+        }
+        
+        self.blend_layer(&self.scratch_layer, BlendMode::Normal, frame, screen_width, opacity);
+    }
+    
+    /// Alpha blend a board-sized RGBA layer onto the frame through the current viewport, with
+    /// every pixel's alpha scaled by `opacity`. Pixels the layer marks as multiply (highlighter)
+    /// multiply; the rest composite in `layer_mode`.
+    fn blend_layer(&self, layer: &TiledLayer, layer_mode: BlendMode, frame: &mut [u8], screen_width: u32, opacity: u8) {
+        let width = self.config.width as i32;
+        let has_modes = layer.has_blend_modes();
+        
+        // Fixed-point screen-to-board mapping, the same one render() uses
+        let ((origin_x, origin_y), step) = self.viewport.fixed_mapping();
+        
+        // Parallel processing by rows
+        frame.par_chunks_mut((screen_width * 4) as usize)
+            .enumerate()
+            .for_each(|(screen_y, row)| {
+                let Some(board_y) = self.config.wrap_y((((origin_y + screen_y as i64) * step) >> 32) as i32) else {
+                    return;
+                };
+                
+                // Process pixels in this row, four at a time (see blend_pixels4)
+                let row_pixels = (screen_width as usize).min(row.len() / 4);
+                let mut screen_x = 0;
+                while screen_x < row_pixels {
+                    let n = (row_pixels - screen_x).min(4);
+                    let mut src = [[0u8; 4]; 4];
+                    let mut xs = [0usize; 4];
+                    // Only plain src-over takes the 4-wide path (and its copy/skip shortcuts)
+                    let mut per_pixel = layer_mode != BlendMode::Normal;
+                    for k in 0..n {
+                        let board_x = (((origin_x + (screen_x + k) as i64) * step) >> 32) as i32;
+                        xs[k] = board_x.rem_euclid(width) as usize;
+                        src[k] = layer.get(xs[k], board_y);
+                        if opacity < 255 {
+                            src[k][3] = (src[k][3] as u16 * opacity as u16 / 255) as u8;
+                        }
+                        per_pixel |= has_modes && layer.blend_mode(xs[k], board_y) == BlendMode::Multiply;
+                    }
+                    
+                    let dst = &mut row[screen_x * 4..(screen_x + n) * 4];
+                    if n == 4 && !per_pixel {
+                        blend_pixels4(dst, &src);
+                    } else {
+                        for k in 0..n {
+                            let (src, dst) = (src[k], &mut dst[k * 4..k * 4 + 4]);
+                            if src[3] == 0 {
+                                continue;
+                            }
+                            
+                            // Highlighter pixels always multiply; the rest follow the layer's mode
+                            let multiply = has_modes && layer.blend_mode(xs[k], board_y) == BlendMode::Multiply;
+                            let mode = if multiply { BlendMode::Multiply } else { layer_mode };
+                            mode.blend(dst, src);
+                        }
+                    }
+                    screen_x += 4;
+                }
+            });
+    }
+}
+
+/// Straight-alpha src-over of one layer pixel onto another: out_a = a + dst_a * (1 - a)
+#[inline(always)]
+fn composite_over(mut dst: [u8; 4], src: [u8; 4]) -> [u8; 4] {
+    let src_a = src[3] as u32;
+    let dst_a = dst[3] as u32 * (255 - src_a) / 255;
+    let out_a = src_a + dst_a;
+    if out_a == 0 {
+        return dst;
+    }
+    for c in 0..3 {
+        dst[c] = ((src[c] as u32 * src_a + dst[c] as u32 * dst_a) / out_a) as u8;
+    }
+    dst[3] = out_a as u8;
+    dst
+}
+
+/// Src-over blend one straight-alpha pixel onto `dst` (RGB only; dst alpha is left alone)
+#[inline(always)]
+pub fn blend_pixel(dst: &mut [u8], src: [u8; 4]) {
+    let alpha = src[3];
+    if alpha == 255 {
+        // Fully opaque - direct copy
+        dst[..3].copy_from_slice(&src[..3]);
+    } else if alpha != 0 {
+        // Partial transparency - integer blend
+        let inv_alpha = 255 - alpha;
+        for c in 0..3 {
+            dst[c] = ((src[c] as u16 * alpha as u16 + dst[c] as u16 * inv_alpha as u16) / 255) as u8;
+        }
+    }
+}
+
+/// Blend the first `n` gathered poster pixels onto the frame; anything a poster
+/// covers ends up opaque on screen
+pub fn blend_poster_pixels(dst: &mut [u8], src: &[[u8; 4]; 4], n: usize, opacity: u8) {
+    let mut faded = *src;
+    if opacity < 255 {
+        for px in &mut faded {
+            px[3] = (px[3] as u16 * opacity as u16 / 255) as u8;
+        }
+    }
+    let src = &faded;
+    if n == 4 {
+        blend_pixels4(dst, src);
+    } else {
+        for (k, px) in src.iter().enumerate().take(n) {
+            blend_pixel(&mut dst[k * 4..k * 4 + 4], *px);
+        }
+    }
+    for (k, px) in src.iter().enumerate().take(n) {
+        if px[3] != 0 {
+            dst[k * 4 + 3] = 255;
+        }
+    }
+}
+
+/// Spread an RGBA pixel into four 16-bit lanes of a u64 (SWAR: every channel in one multiply)
+#[inline(always)]
+fn widen_pixel(p: [u8; 4]) -> u64 {
+    p[0] as u64 | (p[1] as u64) << 16 | (p[2] as u64) << 32 | (p[3] as u64) << 48
+}
+
+/// Same as blend_pixel for four adjacent pixels (`dst` is 16 bytes). All-transparent and
+/// all-opaque groups take the skip/copy shortcuts; mixed alpha blends each pixel with two
+/// 64-bit multiplies instead of six per-channel ones. Gives the same bytes as blend_pixel.
+#[inline(always)]
+pub fn blend_pixels4(dst: &mut [u8], src: &[[u8; 4]; 4]) {
+    const LANE_ONES: u64 = 0x0001_0001_0001_0001;
+    const LANE_LOW_BYTES: u64 = 0x00FF_00FF_00FF_00FF;
+    
+    let alphas = [src[0][3], src[1][3], src[2][3], src[3][3]];
+    if alphas == [0; 4] {
+        return;
+    }
+    if alphas == [255; 4] {
+        for (k, px) in src.iter().enumerate() {
+            dst[k * 4..k * 4 + 3].copy_from_slice(&px[..3]);
+        }
+        return;
+    }
+    
+    for (k, px) in src.iter().enumerate() {
+        let d = &mut dst[k * 4..k * 4 + 4];
+        let a = px[3] as u64;
+        // s * a + d * (255 - a) stays below 65536 per lane, so lanes never carry into each other;
+        // x / 255 is then (x + 1 + (x >> 8)) >> 8, exact over that range
+        let x = widen_pixel(*px) * a + widen_pixel([d[0], d[1], d[2], d[3]]) * (255 - a);
+        let q = ((x + LANE_ONES + ((x >> 8) & LANE_LOW_BYTES)) >> 8) & LANE_LOW_BYTES;
+        d[0] = q as u8;
+        d[1] = (q >> 16) as u8;
+        d[2] = (q >> 32) as u8;
+    }
+}
+
+/// Alpha blend the part of a poster that overlaps rows band_y..band_y + rows into an RGBA band
+/// (nearest-neighbour scaling, wrapping horizontally like on screen)
+fn composite_poster_band(band: &mut [u8], width: usize, band_y: usize, rows: usize, poster: &PinnedPoster) {
+    if poster.rotation != 0.0 {
+        composite_rotated_poster_band(band, width, band_y, rows, poster);
+        return;
+    }
+    
+    let scaled_width = (poster.width as f32 * poster.scale_x) as i64;
+    let scaled_height = (poster.height as f32 * poster.scale_y) as i64;
+    let x0 = poster.position.x.floor() as i64;
+    let y0 = poster.position.y.floor() as i64;
+    
+    let first_row = y0.max(band_y as i64);
+    let last_row = (y0 + scaled_height).min((band_y + rows) as i64);
+    for y in first_row..last_row {
+        let poster_y = (((y - y0) as f32 / poster.scale_y) as u32).min(poster.height - 1);
+        let band_row = (y as usize - band_y) * width * 4;
+        
+        // A poster wider than the board only needs one lap
+        for lx in 0..scaled_width.min(width as i64) {
+            let poster_x = ((lx as f32 / poster.scale_x) as u32).min(poster.width - 1);
+            let src = ((poster_y * poster.width + poster_x) * 4) as usize;
+            let Some(pixel) = poster.image_data.get(src..src + 4) else {
+                continue;
+            };
+            let a = pixel[3] as u32;
+            if a == 0 {
+                continue;
+            }
+            
+            let dst = band_row + (x0 + lx).rem_euclid(width as i64) as usize * 4;
+            for c in 0..3 {
+                band[dst + c] = ((pixel[c] as u32 * a + band[dst + c] as u32 * (255 - a)) / 255) as u8;
+            }
+        }
+    }
+}
+
+/// composite_poster_band for a rotated poster: walk its rotated bounding box and sample
+/// the image through the inverse rotation, as render_rotated_poster_copy does on screen
+fn composite_rotated_poster_band(band: &mut [u8], width: usize, band_y: usize, rows: usize, poster: &PinnedPoster) {
+    let (half_x, half_y) = poster.rotated_half_extents();
+    let cx = poster.position.x + poster.width as f32 * poster.scale_x / 2.0;
+    let cy = poster.position.y + poster.height as f32 * poster.scale_y / 2.0;
+    let (sin, cos) = poster.rotation.sin_cos();
+    let half_w = poster.width as f32 / 2.0;
+    let half_h = poster.height as f32 / 2.0;
+    
+    let first_row = ((cy - half_y).floor() as i64).max(band_y as i64);
+    let last_row = ((cy + half_y).ceil() as i64).min((band_y + rows) as i64);
+    let first_col = (cx - half_x).floor() as i64;
+    let cols = ((cx + half_x).ceil() as i64 - first_col).min(width as i64);
+    for y in first_row..last_row {
+        let ry = y as f32 + 0.5 - cy;
+        let band_row = (y as usize - band_y) * width * 4;
+        
+        for x in first_col..first_col + cols {
+            let rx = x as f32 + 0.5 - cx;
+            let u = (cos * rx + sin * ry) / poster.scale_x + half_w;
+            let v = (-sin * rx + cos * ry) / poster.scale_y + half_h;
+            if u < 0.0 || v < 0.0 || u >= poster.width as f32 || v >= poster.height as f32 {
+                continue;
+            }
+            let src = ((v as u32 * poster.width + u as u32) * 4) as usize;
+            let Some(pixel) = poster.image_data.get(src..src + 4) else {
+                continue;
+            };
+            let a = pixel[3] as u32;
+            if a == 0 {
+                continue;
+            }
+            
+            let dst = band_row + x.rem_euclid(width as i64) as usize * 4;
+            for c in 0..3 {
+                band[dst + c] = ((pixel[c] as u32 * a + band[dst + c] as u32 * (255 - a)) / 255) as u8;
+            }
+        }
+    }
+}
+
+/// Run-length encode a layer (row-major) as [count: u32 (LE), r, g, b, a] records
+fn rle_encode(layer: &TiledLayer, out: &mut dyn Write) -> io::Result<()> {
+    let mut row = vec![0u8; layer.width * 4];
+    let mut run: Option<([u8; 4], u32)> = None;
+    for y in 0..layer.height {
+        layer.read_row(y, &mut row);
+        for p in row.chunks_exact(4) {
+            let pixel = [p[0], p[1], p[2], p[3]];
+            run = match run {
+                Some((current, count)) if current == pixel && count < u32::MAX => Some((current, count + 1)),
+                Some((current, count)) => {
+                    out.write_all(&count.to_le_bytes())?;
+                    out.write_all(&current)?;
+                    Some((pixel, 1))
+                }
+                None => Some((pixel, 1)),
+            };
+        }
+    }
+    if let Some((current, count)) = run {
+        out.write_all(&count.to_le_bytes())?;
+        out.write_all(&current)?;
+    }
+    Ok(())
+}
+
+/// Decode RLE records into `layer`, which must come out exactly full
+fn rle_decode(encoded: &[u8], layer: &mut TiledLayer) -> io::Result<()> {
+    let corrupt = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Corrupt RLE board data: {}", what));
+    if !encoded.len().is_multiple_of(8) {
+        return Err(corrupt("truncated run"));
+    }
+    
+    // Runs are expanded a row at a time so uniform rows never allocate tiles
+    let mut row = vec![0u8; layer.width * 4];
+    let (mut x, mut y) = (0usize, 0usize);
+    for record in encoded.chunks_exact(8) {
+        let mut remaining = u32::from_le_bytes([record[0], record[1], record[2], record[3]]) as usize;
+        while remaining > 0 {
+            if y == layer.height {
+                return Err(corrupt("more pixels than the board holds"));
+            }
+            let n = remaining.min(layer.width - x);
+            for pixel in row[x * 4..(x + n) * 4].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&record[4..8]);
+            }
+            x += n;
+            remaining -= n;
+            if x == layer.width {
+                layer.write_row(y, &row);
+                x = 0;
+                y += 1;
+            }
+        }
+    }
+    
+    if y != layer.height {
+        return Err(corrupt("fewer pixels than the board holds"));
+    }
+    Ok(())
+}
+
+/// Fail with a readable StorageFull error if the volume holding `path` has less than
+/// `needed` bytes free. If free space can't be queried we let the write proceed.
+fn ensure_disk_space(path: &Path, needed: u64) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    
+    match fs2::available_space(dir) {
+        Ok(available) if available < needed => Err(io::Error::new(
+            io::ErrorKind::StorageFull,
+            format!(
+                "Not enough disk space for {}: need {} MB, only {} MB free. Free up space and try again (existing data was not modified).",
+                path.display(),
+                needed / (1024 * 1024) + 1,
+                available / (1024 * 1024)
+            ),
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            warn!("could not check free disk space ({}), continuing", e);
+            Ok(())
+        }
+    }
+}
+
+/// Sidecar next to a board file: `suffix` appended to the whole file name, so boards that
+/// only differ in extension (`notes.data`, `notes.bak`) keep separate sidecars
+pub fn sidecar_path(board_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = board_path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Sidecars used to be named by replacing the board's extension (`notes.drawing`). Rename the
+/// ones that fit this board to its own sidecar names, unless an extensionless board of that
+/// name (which uses those names itself) exists. Ink and blend files must match the size.
+fn adopt_stem_sidecars(board_path: &Path, pixel_count: u64) -> io::Result<()> {
+    if board_path.extension().is_none() || board_path.with_extension("").is_file() {
+        return Ok(());
+    }
+    let sidecars = [
+        ("drawing", Some(pixel_count * 4)), ("blend", Some(pixel_count)), ("strokes", None),
+        ("journal", None), ("bookmarks", None), ("session", None),
+    ];
+    for (suffix, expected_len) in sidecars {
+        let (old, new) = (board_path.with_extension(suffix), sidecar_path(board_path, suffix));
+        if new.exists() || !old.is_file() || expected_len.is_some_and(|len| fs::metadata(&old).is_ok_and(|m| m.len() != len)) {
+            continue;
+        }
+        info!("Migrating {} to {}", old.display(), new.display());
+        fs::rename(&old, &new)?;
+    }
+    Ok(())
+}
+
+/// Atomically replace a board file with `header` followed by the layer in `format`
+fn write_board_to(path: &Path, header: &[u8], cache: &TiledLayer, format: StorageFormat, show_progress: bool) -> io::Result<()> {
+    // RLE output size isn't known up front; the raw size is a safe estimate for the space check
+    let raw_bytes = header.len() as u64 + cache.width as u64 * cache.height as u64 * 4;
+    write_file_atomically(path, raw_bytes, show_progress, |out| {
+        out.write_all(header)?;
+        match format {
+            StorageFormat::Raw => cache.write_raw(out),
+            StorageFormat::Rle => rle_encode(cache, out),
+        }
+    })
+}
+
+/// One save's worth of board data, copied out of the Board (see Board::sync_job) so the
+/// writing can happen on a worker thread while the UI keeps drawing
+pub struct SyncJob {
+    board_path: PathBuf,
+    drawing_path: PathBuf,
+    blend_path: PathBuf,
+    header: Vec<u8>,
+    header_len: u64,  // Header size of the board file currently on disk
+    width: usize,
+    pixel_count: u64,
+    storage_format: StorageFormat,
+    rewrite_board: bool,  // Replace whole files instead of patching the dirty tiles
+    write_header: bool,  // Patch the header in place even if no background tile changed
+    rewrite_drawing: bool,
+    rewrite_blend: bool,
+    cache: TiledLayer,  // Dirty tiles only, unless the file is rewritten
+    drawing_layer: TiledLayer,
+    has_blend_modes: bool,  // False removes the sidecar
+    strokes_path: PathBuf,
+    strokes: Option<Vec<Stroke>>,  // None when the log hasn't changed
+}
+
+impl SyncJob {
+    /// Write everything out. Returns the new header length if the board file was replaced.
+    pub fn run(self) -> io::Result<Option<u64>> {
+        let (width, pixel_count) = (self.width, self.pixel_count);
+        
+        // Board file
+        let mut replaced = None;
+        if self.rewrite_board {
+            write_board_to(&self.board_path, &self.header, &self.cache, self.storage_format, false)?;
+            replaced = Some(self.header.len() as u64);
+        } else if self.cache.is_dirty() || self.write_header {
+            let mut file = OpenOptions::new().write(true).open(&self.board_path)?;
+            file.write_all(&self.header)?; // Mode or metadata may have changed
+            let cache = &self.cache;
+            patch_spans(&mut file, self.header_len, width, 4, &cache.dirty_spans(), |y, x0, span| cache.read_span(y, x0, span))?;
+        }
+        
+        // Drawing layer next to the board file (blend modes follow the same dirty tiles)
+        let layer = &self.drawing_layer;
+        let spans = layer.dirty_spans();
+        if self.rewrite_drawing {
+            write_file_atomically(&self.drawing_path, pixel_count * 4, false, |out| layer.write_raw(out))?;
+        } else if !spans.is_empty() {
+            let mut file = OpenOptions::new().write(true).open(&self.drawing_path)?;
+            patch_spans(&mut file, 0, width, 4, &spans, |y, x0, span| layer.read_span(y, x0, span))?;
+        }
+        
+        // Save highlighter blend modes; drop a stale file once none are left
+        if !self.has_blend_modes {
+            if self.blend_path.exists() {
+                fs::remove_file(&self.blend_path)?;
+            }
+        } else if self.rewrite_blend {
+            write_file_atomically(&self.blend_path, pixel_count, false, |out| layer.write_blend_raw(out))?;
+        } else if !spans.is_empty() {
+            let mut file = OpenOptions::new().write(true).open(&self.blend_path)?;
+            patch_spans(&mut file, 0, width, 1, &spans, |y, x0, span| layer.read_blend_span(y, x0, span))?;
+        }
+        
+        // Stroke log, rewritten whole when it changed; an empty log leaves no file behind
+        match &self.strokes {
+            Some(strokes) if strokes.is_empty() && self.strokes_path.exists() => fs::remove_file(&self.strokes_path)?,
+            Some(strokes) if strokes.is_empty() => {}
+            Some(strokes) => {
+                let json = serde_json::to_vec(strokes).map_err(io::Error::other)?;
+                write_file_atomically(&self.strokes_path, json.len() as u64, false, |out| out.write_all(&json))?;
+            }
+            None => {}
+        }
+        Ok(replaced)
+    }
+}
+
+/// Write `parts` back to back into `<path>.tmp`, fsync, then rename over `path`.
+/// On failure the temp file is removed and `path` is untouched.
+pub fn write_file_atomically(
+    path: &Path,
+    expected_bytes: u64,
+    show_progress: bool,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    ensure_disk_space(path, expected_bytes)?;
+    
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+    
+    let write_tmp = || -> io::Result<()> {
+        let tmp = File::create(&tmp_path)?;
+        
+        // Large boards report progress as the bytes go out
+        let mut out = ProgressWriter {
+            inner: io::BufWriter::with_capacity(1024 * 256, tmp),
+            written: 0,
+            expected: expected_bytes.max(1),
+            progress: show_progress.then(ProgressLog::default),
+        };
+        write(&mut out)?;
+        out.inner.into_inner().map_err(|e| e.into_error())?.sync_all()
+    };
+    
+    if let Err(e) = write_tmp() {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    fs::rename(&tmp_path, path)
+}
+
+/// Logs "Progress: N%" each time a long write or export passes another PROGRESS_STEP_PERCENT
+#[derive(Default)]
+struct ProgressLog {
+    logged: u64,  // Last percentage logged
+}
+
+/// Progress is logged in steps of this many percent, so a log file doesn't fill up with it
+const PROGRESS_STEP_PERCENT: u64 = 10;
+
+impl ProgressLog {
+    fn update(&mut self, percent: u64) {
+        let step = percent.min(100) / PROGRESS_STEP_PERCENT * PROGRESS_STEP_PERCENT;
+        if step > self.logged {
+            self.logged = step;
+            info!("Progress: {}%", step);
+        }
+    }
+}
+
+/// Writer that logs progress against an expected total as bytes pass through
+struct ProgressWriter<W: Write> {
+    inner: W,
+    written: u64,
+    expected: u64,
+    progress: Option<ProgressLog>,  // None = quiet
+}
+
+impl<W: Write> Write for ProgressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        if let Some(progress) = &mut self.progress {
+            progress.update(self.written * 100 / self.expected);
+        }
+        Ok(n)
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Overwrite pixel spans (y, x0, x1) of a row-major layer file in place, starting at byte `base`.
+/// `fill(y, x0, buf)` supplies the span's bytes (`bytes_per_pixel` per pixel).
+fn patch_spans(
+    file: &mut File,
+    base: u64,
+    width: usize,
+    bytes_per_pixel: usize,
+    spans: &[(usize, usize, usize)],
+    mut fill: impl FnMut(usize, usize, &mut [u8]),
+) -> io::Result<()> {
+    let mut buf = Vec::new();
+    for &(y, x0, x1) in spans {
+        buf.resize((x1 - x0) * bytes_per_pixel, 0);
+        fill(y, x0, &mut buf);
+        file.seek(SeekFrom::Start(base + ((y * width + x0) * bytes_per_pixel) as u64))?;
+        file.write_all(&buf)?;
+    }
+    Ok(())
+}
+
+/// Parse a hex color ("#RGB", "RRGGBB" or "RRGGBBAA", leading '#' optional) into RGBA
+pub fn parse_hex_color(text: &str) -> Option<[u8; 4]> {
+    let hex = text.trim().trim_start_matches('#');
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    
+    match hex.len() {
+        3 => {
+            // Short form: each digit is doubled (F80 -> FF8800)
+            let nibble = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|n| n * 17);
+            Some([nibble(0)?, nibble(1)?, nibble(2)?, 255])
+        }
+        6 => Some([byte(0)?, byte(2)?, byte(4)?, 255]),
+        8 => Some([byte(0)?, byte(2)?, byte(4)?, byte(6)?]),
+        _ => None,
+    }
+}
+
+/// SVG for a chisel stroke: the parallelogram the nib sweeps along each segment, filled as one
+/// path in a translucent group so overlapping segments don't build up opacity
+fn chisel_svg(stroke: &Stroke, angle: f32, offset: (f32, f32), color: String, opacity: f32, blend: &str) -> String {
+    let radius = (stroke.brush_size / 2) as f32;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (nx, ny) = (cos * radius, -sin * radius);
+    let last = stroke.points.len() - 1;
+    let mut d = String::new();
+    // A single point still leaves the nib's mark
+    for i in 0..last.max(1) {
+        let (a, b) = (stroke.points[i], stroke.points[(i + 1).min(last)]);
+        let mut corners = [(a.x - nx, a.y - ny), (a.x + nx, a.y + ny), (b.x + nx, b.y + ny), (b.x - nx, b.y - ny)];
+        // Same winding for every segment, so the nonzero fill doesn't punch holes where they overlap
+        if (b.x - a.x) * ny - (b.y - a.y) * nx < 0.0 {
+            corners.reverse();
+        }
+        for (k, (x, y)) in corners.iter().enumerate() {
+            d.push_str(&format!("{}{:.1},{:.1} ", if k == 0 { "M" } else { "L" }, x + offset.0, y + offset.1));
+        }
+        d.push_str("Z ");
+    }
+    format!(
+        r#"<g opacity="{:.3}"{}><path d="{}" fill="{}" stroke="{}" stroke-width="{:.1}" stroke-linejoin="round"/></g>"#,
+        opacity, blend, d.trim_end(), color, color, 2.0 * BrushShape::nib_half_thickness(radius),
+    )
+}
+
+/// Drawing layer pixels lifted or copied out by the select tool (row-major RGBA)
+#[derive(Clone)]
+pub struct SelectionBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Brush footprint stamped along strokes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BrushShape {
+    Round,
+    Square,
+    Chisel { angle: f32 }, // Flat calligraphy nib, degrees counter-clockwise from horizontal
+}
+
+/// Chisel nib thickness as a fraction of its length (it's never thinner than ~1.5 pixels)
+const CHISEL_THICKNESS: f32 = 0.15;
+
+impl BrushShape {
+    /// Whether offset (dx, dy) from the brush center is inside a brush of this radius
+    pub fn contains(self, dx: i32, dy: i32, radius: i32) -> bool {
+        match self {
+            BrushShape::Round => dx * dx + dy * dy <= radius * radius,
+            BrushShape::Square => dx.abs() <= radius && dy.abs() <= radius,
+            BrushShape::Chisel { .. } => self.covers(dx as f32, dy as f32, radius as f32),
+        }
+    }
+    
+    /// Same test with a fractional radius, for stamps scaled to the screen
+    pub fn covers(self, dx: f32, dy: f32, radius: f32) -> bool {
+        match self {
+            BrushShape::Round => dx * dx + dy * dy <= radius * radius,
+            BrushShape::Square => dx.abs() <= radius && dy.abs() <= radius,
+            BrushShape::Chisel { angle } => {
+                // Stamped along a path, the nib sweeps a band as wide as its extent across the
+                // direction of motion: full length across the nib, only its thickness along it
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let along = dx * cos - dy * sin;
+                let across = dx * sin + dy * cos;
+                along.abs() <= radius + 0.5 && across.abs() <= Self::nib_half_thickness(radius)
+            }
+        }
+    }
+    
+    /// Half the thickness of a chisel nib with this radius (half its length)
+    pub fn nib_half_thickness(radius: f32) -> f32 {
+        (radius * CHISEL_THICKNESS).max(0.75)
+    }
+}
+
+/// One logged stroke for the SVG export and replay: the points the brush was stamped along,
+/// in board coordinates. The log is saved next to the board (`<board>.strokes`, JSON).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stroke {
+    pub time_ms: u64, // When the stroke started, milliseconds since the Unix epoch
+    pub color: [u8; 4], // Alpha already includes the brush opacity (or the highlighter alpha)
+    pub brush_size: u32,
+    pub shape: BrushShape,
+    pub eraser: bool,
+    pub highlighter: bool,
+    pub points: Vec<Point>,
+}
+
+/// One line of the crash-recovery journal (`<board>.journal`). It holds what happened to the
+/// stroke log, the mode and the background since the last save, so strokes drawn after it can
+/// be redrawn after a crash. Fills, text, stamps, quantize and poster edits aren't journaled.
+#[derive(Serialize, Deserialize)]
+enum JournalEntry {
+    Stroke { index: usize, stroke: Stroke }, // Logged as stroke number `index`
+    Undo {
+        strokes: usize, // The log went back to this many strokes
+        #[serde(default)]
+        background: bool, // The undo brought back a cleared background
+    },
+    Clear, // The drawing layer and log were wiped
+    ClearBackground, // The background was reset to the mode's color
+    Mode { id: u8, background: [u8; 4] }, // Switched to this mode (header id and background color)
+}
+
+/// Rows composited per step when exporting the full board (one band is ~20MB on the default board)
+const EXPORT_BAND_ROWS: usize = 64;
+
+/// Most pixels the fill tool will paint span by span before giving up (~64MB of pixels).
+/// Untouched tiles are filled whole and don't count, so filling a blank board works.
+pub const MAX_FILL_PIXELS: usize = 16_000_000;
+
+/// Default number of undo snapshots (max_undo_steps in config.toml, or --undo-steps)
+pub const DEFAULT_UNDO_STEPS: usize = 3;
+
+/// Upper bound on memory spent on undo copies; the oldest steps are dropped to stay under it
+const UNDO_MEMORY_BUDGET: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Pinned poster on board
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "PosterRecord")]
+pub struct PinnedPoster {
+    pub position: Point,
+    pub image_data: Vec<u8>,  // RGBA pixel data
+    pub width: u32,
+    pub height: u32,
+    pub name: String,
+    pub scale_x: f32,  // Horizontal scale factor for the poster (1.0 = original size)
+    pub scale_y: f32,  // Vertical scale factor
+    pub rotation: f32,  // Clockwise rotation about the poster's center, in radians
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<Vec<u8>>,  // Later frames of an animated GIF (image_data is the first)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    frame_delays_ms: Vec<u32>,  // How long each frame shows, the first included (empty for a still image)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub locked: bool,  // Can't be grabbed, scaled, rotated or deleted until unlocked
+}
+
+/// posters.json entry as read from disk; older files have a single uniform `scale`
+#[derive(Deserialize)]
+struct PosterRecord {
+    position: Point,
+    image_data: Vec<u8>,
+    width: u32,
+    height: u32,
+    name: String,
+    #[serde(default)]
+    scale: Option<f32>,
+    #[serde(default)]
+    scale_x: Option<f32>,
+    #[serde(default)]
+    scale_y: Option<f32>,
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default)]
+    frames: Vec<Vec<u8>>,
+    #[serde(default)]
+    frame_delays_ms: Vec<u32>,
+    #[serde(default)]
+    locked: bool,
+}
+
+impl From<PosterRecord> for PinnedPoster {
+    fn from(record: PosterRecord) -> Self {
+        let scale = record.scale.unwrap_or(1.0);
+        PinnedPoster {
+            position: record.position,
+            image_data: record.image_data,
+            width: record.width,
+            height: record.height,
+            name: record.name,
+            scale_x: record.scale_x.unwrap_or(scale),
+            scale_y: record.scale_y.unwrap_or(scale),
+            rotation: record.rotation,
+            frames: record.frames,
+            frame_delays_ms: record.frame_delays_ms,
+            locked: record.locked,
+        }
+    }
+}
+
+impl PinnedPoster {
+    /// A still poster at its natural size (placed by setting `position`)
+    pub fn new(image_data: Vec<u8>, width: u32, height: u32, name: String) -> Self {
+        PinnedPoster {
+            position: Point { x: 0.0, y: 0.0 },
+            image_data,
+            width,
+            height,
+            name,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            rotation: 0.0,
+            frames: Vec::new(),
+            frame_delays_ms: Vec::new(),
+            locked: false,
+        }
+    }
+    
+    /// Load an image file as a poster. Animated GIFs keep every frame with its delay;
+    /// single-frame GIFs and other formats are still images.
+    pub fn load(path: &Path, name: String) -> image::ImageResult<Self> {
+        let is_gif = path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("gif"));
+        if is_gif {
+            // The decoder composites each frame onto the full GIF canvas, so all frames share its size
+            let decoder = image::codecs::gif::GifDecoder::new(io::BufReader::new(File::open(path)?))?;
+            let mut images = Vec::new();
+            let mut frame_delays_ms = Vec::new();
+            let mut size = (0, 0);
+            for frame in decoder.into_frames() {
+                let frame = frame?;
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                let delay = numer / denom.max(1);
+                // Browsers play 0-10ms delays at 100ms, and plenty of GIFs rely on that
+                frame_delays_ms.push(if delay <= 10 { 100 } else { delay });
+                size = frame.buffer().dimensions();
+                images.push(frame.into_buffer().into_raw());
+            }
+            if !images.is_empty() {
+                let mut poster = Self::new(images.remove(0), size.0, size.1, name);
+                if !images.is_empty() {
+                    poster.frames = images;
+                    poster.frame_delays_ms = frame_delays_ms;
+                }
+                return Ok(poster);
+            }
+        }
+        let img = image::open(path)?;
+        let (width, height) = img.dimensions();
+        Ok(Self::new(img.to_rgba8().into_raw(), width, height, name))
+    }
+    
+    /// (frame index, milliseconds left on it) `elapsed` into the looping animation; None for a still poster
+    pub fn animation_position(&self, elapsed: std::time::Duration) -> Option<(usize, u64)> {
+        let total: u64 = self.frame_delays_ms.iter().map(|&delay| delay as u64).sum();
+        if self.frames.is_empty() || total == 0 {
+            return None;
+        }
+        let mut t = elapsed.as_millis() as u64 % total;
+        for (i, &delay) in self.frame_delays_ms.iter().enumerate() {
+            if t < delay as u64 {
+                return Some((i, delay as u64 - t));
+            }
+            t -= delay as u64;
+        }
+        None
+    }
+    
+    /// Pixels to show `elapsed` into the animation (image_data for a still poster)
+    pub fn frame_at(&self, elapsed: std::time::Duration) -> &[u8] {
+        match self.animation_position(elapsed) {
+            Some((i, _)) if i > 0 => self.frames.get(i - 1).unwrap_or(&self.image_data),
+            _ => &self.image_data,
+        }
+    }
+    
+    /// Half extents (board pixels) of the axis-aligned box around the rotated poster
+    pub fn rotated_half_extents(&self) -> (f32, f32) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let half_w = self.width as f32 * self.scale_x / 2.0;
+        let half_h = self.height as f32 * self.scale_y / 2.0;
+        (cos.abs() * half_w + sin.abs() * half_h, sin.abs() * half_w + cos.abs() * half_h)
+    }
+}
+
+/// 5x7 bitmap for a character: 7 rows, 5 bits each (MSB = leftmost column). Covers printable
+/// ASCII; lowercase descenders sit on the last row. Anything else is a box.
+pub fn glyph(ch: char) -> &'static [u8] {
+    match ch {
+        ' ' => &[0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000],
+        '!' => &[0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00000, 0b00100],
+        '"' => &[0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000],
+        '#' => &[0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010],
+        '$' => &[0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100],
+        '%' => &[0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011],
+        '&' => &[0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101],
+        '\'' => &[0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000],
+        '(' => &[0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => &[0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '*' => &[0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000],
+        '+' => &[0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        ',' => &[0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        '-' => &[0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '.' => &[0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        '/' => &[0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '0' => &[0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => &[0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => &[0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => &[0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => &[0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => &[0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => &[0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => &[0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => &[0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => &[0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        ':' => &[0b00000, 0b00100, 0b00000, 0b00000, 0b00000, 0b00100, 0b00000],
+        ';' => &[0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000],
+        '<' => &[0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010],
+        '=' => &[0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000],
+        '>' => &[0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000],
+        '?' => &[0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+        '@' => &[0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110],
+        'A' => &[0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => &[0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => &[0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => &[0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => &[0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => &[0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => &[0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01110],
+        'H' => &[0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => &[0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => &[0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => &[0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => &[0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => &[0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => &[0b10001, 0b11001, 0b10101, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => &[0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => &[0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => &[0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => &[0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => &[0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => &[0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => &[0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => &[0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => &[0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b11011, 0b10001],
+        'X' => &[0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => &[0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100],
+        'Z' => &[0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '[' => &[0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110],
+        '\\' => &[0b00000, 0b10000, 0b01000, 0b00100, 0b00010, 0b00001, 0b00000],
+        ']' => &[0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110],
+        '^' => &[0b00100, 0b01010, 0b10001, 0b00000, 0b00000, 0b00000, 0b00000],
+        '_' => &[0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111],
+        '`' => &[0b01000, 0b00100, 0b00010, 0b00000, 0b00000, 0b00000, 0b00000],
+        'a' => &[0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111],
+        'b' => &[0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110],
+        'c' => &[0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110],
+        'd' => &[0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111],
+        'e' => &[0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110],
+        'f' => &[0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000],
+        'g' => &[0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110],
+        'h' => &[0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001],
+        'i' => &[0b00100, 0b00000, 0b01100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'j' => &[0b00010, 0b00000, 0b00110, 0b00010, 0b00010, 0b10010, 0b01100],
+        'k' => &[0b10000, 0b10000, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010],
+        'l' => &[0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'm' => &[0b00000, 0b00000, 0b11010, 0b10101, 0b10101, 0b10001, 0b10001],
+        'n' => &[0b00000, 0b00000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001],
+        'o' => &[0b00000, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110],
+        'p' => &[0b00000, 0b00000, 0b11110, 0b10001, 0b11110, 0b10000, 0b10000],
+        'q' => &[0b00000, 0b00000, 0b01101, 0b10011, 0b01111, 0b00001, 0b00001],
+        'r' => &[0b00000, 0b00000, 0b10110, 0b11001, 0b10000, 0b10000, 0b10000],
+        's' => &[0b00000, 0b00000, 0b01110, 0b10000, 0b01110, 0b00001, 0b11110],
+        't' => &[0b01000, 0b01000, 0b11100, 0b01000, 0b01000, 0b01001, 0b00110],
+        'u' => &[0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101],
+        'v' => &[0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'w' => &[0b00000, 0b00000, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010],
+        'x' => &[0b00000, 0b00000, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001],
+        'y' => &[0b00000, 0b00000, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110],
+        'z' => &[0b00000, 0b00000, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111],
+        '{' => &[0b00010, 0b00100, 0b00100, 0b01000, 0b00100, 0b00100, 0b00010],
+        '|' => &[0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        '}' => &[0b01000, 0b00100, 0b00100, 0b00010, 0b00100, 0b00100, 0b01000],
+        '~' => &[0b00000, 0b00000, 0b01000, 0b10101, 0b00010, 0b00000, 0b00000],
+        _ => &[0b11111, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11111],
+    }
+}
+
+/// How far (in screen pixels) the view may be pulled past the top or bottom edge
+const PAN_OVERSCROLL: f32 = 48.0;
@@ -1,547 +1,492 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::{self, File, OpenOptions};
-use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
+use std::sync::{mpsc, Arc};
 use std::time::Instant;
 use serde::{Serialize, Deserialize};
-
-// File format: 9-byte header + pixel data
-// Header: [mode: u8, width: u32 (LE), height: u32 (LE)]
-const HEADER_SIZE: u64 = 9;
+use log::{debug, error, info, warn};
 use rayon::prelude::*;
 use winit::application::ApplicationHandler;
-use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, StartCause, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
-use winit::keyboard::{KeyCode, PhysicalKey, ModifiersState};
-use winit::window::{Window, WindowId};
+use winit::keyboard::{Key, KeyCode, NamedKey, PhysicalKey, ModifiersState};
+use winit::window::{CursorIcon, Window, WindowId};
 use pixels::{Pixels, SurfaceTexture};
 use image::GenericImageView;
+use rickboard::*;
 
-/// Represents a point on the board
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct Point {
-    pub x: f32,
-    pub y: f32,
-}
+/// Size of the bundled marker images, used for the swatches drawn in place of missing ones
+const MARKER_SWATCH_SIZE: (u32, u32) = (60, 256);
 
-/// Board mode - blackboard (dark) or whiteboard (light)
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum BoardMode {
-    Blackboard,
-    Whiteboard,
+/// Color marker data
+struct ColorMarker {
+    name: String,
+    color: [u8; 4],
+    open_image: Vec<u8>,   // RGBA data
+    closed_image: Vec<u8>, // RGBA data
+    width: u32,
+    height: u32,
 }
 
-impl BoardMode {
-    fn background_color(&self) -> [u8; 4] {
-        match self {
-            BoardMode::Blackboard => [15, 15, 15, 255],  // Dark grey
-            BoardMode::Whiteboard => [255, 255, 255, 255], // Pure white
+impl ColorMarker {
+    /// Plain stand-in for missing marker art: a body in the marker's color, capped when
+    /// closed and tapering to a tip when open
+    fn swatch(name: String, color: [u8; 4]) -> Self {
+        let (width, height) = MARKER_SWATCH_SIZE;
+        ColorMarker {
+            name,
+            color,
+            open_image: Self::swatch_image(color, true),
+            closed_image: Self::swatch_image(color, false),
+            width,
+            height,
         }
     }
-
-    fn default_pen_color(&self) -> [u8; 4] {
-        match self {
-            BoardMode::Blackboard => [255, 255, 255, 255], // White chalk
-            BoardMode::Whiteboard => [0, 0, 0, 255],    // Black marker (inverts perfectly with white)
+    
+    fn swatch_image(color: [u8; 4], open: bool) -> Vec<u8> {
+        let (width, height) = MARKER_SWATCH_SIZE;
+        let body = [color[0], color[1], color[2], 255];
+        let cap = [color[0] / 2 + 40, color[1] / 2 + 40, color[2] / 2 + 40, 255];
+        let outline = [60, 60, 60, 255];
+        let (tip_len, cap_len, half_body) = (48, 80, 22);
+        
+        let mut data = vec![0u8; (width * height * 4) as usize];
+        for y in 0..height {
+            let (half, fill) = if open && y < tip_len {
+                (6 + (half_body - 6) * y / tip_len, body)
+            } else if !open && y < cap_len {
+                (half_body + 2, cap)
+            } else {
+                (half_body, body)
+            };
+            let (x0, x1) = (width / 2 - half, width / 2 + half);
+            for x in x0..x1 {
+                let edge = x < x0 + 2 || x + 2 >= x1 || y < 2 || y + 2 >= height;
+                let offset = ((y * width + x) * 4) as usize;
+                data[offset..offset + 4].copy_from_slice(if edge { &outline } else { &fill });
+            }
         }
+        data
     }
 }
 
-/// Represents the board configuration
-#[derive(Debug)]
-struct BoardConfig {
-    width: u32,
-    height: u32,
-    pixel_size: usize,
-    mode: BoardMode,
+/// A layer the layers panel can hide or fade. View only: saves, exports and copies
+/// always use the real layers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layer {
+    Posters,
+    Ink,
+    Scratch,
 }
 
-/// Main board structure with cylindrical topology
-struct Board {
-    config: BoardConfig,
-    data_file: File,
-    pub viewport: Viewport,
-    cache: Vec<u8>,  // In-memory cache of entire board for fast rendering (background only)
-    drawing_layer: Vec<u8>,  // Transparent drawing layer on top of posters (RGBA)
-    undo_stack: Vec<Vec<u8>>,  // Store up to 3 previous drawing layer states
-    has_drawings: bool,  // Track if drawing layer has any non-transparent pixels
-    // Viewport render cache
-    viewport_cache: Vec<u8>,  // Cached rendered viewport
-    cached_viewport_width: u32,
-    cached_viewport_height: u32,
-    cached_viewport_pos: Point,
-    cached_viewport_zoom: f32,
-    viewport_dirty: bool,
+/// One row of the layers panel
+#[derive(Debug, Clone, Copy)]
+struct LayerView {
+    layer: Layer,
+    name: &'static str,
+    visible: bool,
+    opacity: u8,
 }
 
-/// Camera/viewport for navigation
-pub struct Viewport {
-    pub position: Point,
-    pub zoom: f32,
+/// Opacities stepped through by clicking a layer's percentage
+const LAYER_OPACITY_STEPS: [u8; 4] = [255, 191, 128, 64];
+
+/// Top of the first layers panel row in legend coordinates (the heading sits above it)
+const LAYERS_PANEL_Y: f64 = 220.0;
+
+/// What a left click on the board does
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tool {
+    Brush, // Freehand strokes (Shift for straight lines)
+    Fill,  // Flood-fill the clicked region with the current color
+    Highlighter, // Translucent strokes that multiply (tint) whatever is underneath
+    Text,  // Click to place a text cursor, type, Enter to stamp the label
+    Measure, // Drag to read off board distances; nothing is committed
+    Pen,   // Click (and drag out handles) to place Bezier anchors, Enter draws the curve
+    Select, // Drag a rectangle over the ink to move, delete, cut, copy or paste it
+    Stamp, // Strokes repeat the stamp image (config.toml stamp_image) along the path
 }
 
-impl Board {
-    /// Create a new board with specified dimensions
-    fn new(width: u32, height: u32, mode: BoardMode, file_path: &Path) -> io::Result<Self> {
-        let file_exists = file_path.exists();
-        
-        // Check if existing file has valid header
-        let has_valid_header = if file_exists {
-            if let Ok(metadata) = std::fs::metadata(file_path) {
-                metadata.len() > HEADER_SIZE
-            } else {
-                false
-            }
-        } else {
-            false
-        };
-        
-        let mut data_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(file_path)?;
-
-        let (loaded_mode, loaded_width, loaded_height) = if has_valid_header {
-            // Read header to get saved mode and dimensions
-            let mut header = [0u8; HEADER_SIZE as usize];
-            if let Ok(_) = data_file.read_exact(&mut header) {
-                let saved_mode = match header[0] {
-                    0 => BoardMode::Blackboard,
-                    1 => BoardMode::Whiteboard,
-                    _ => mode,
-                };
-                let saved_width = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
-                let saved_height = u32::from_le_bytes([header[5], header[6], header[7], header[8]]);
-                
-                // Validate dimensions
-                if saved_width > 0 && saved_height > 0 && saved_width <= 100000 && saved_height <= 100000 {
-                    println!("Loading existing board: {}x{} ({:?} mode)", saved_width, saved_height, saved_mode);
-                    (saved_mode, saved_width, saved_height)
-                } else {
-                    // Invalid dimensions, use defaults
-                    println!("Invalid saved dimensions, creating new board");
-                    (mode, width, height)
-                }
-            } else {
-                // Can't read header, use defaults
-                println!("Cannot read header, creating new board");
-                (mode, width, height)
-            }
-        } else {
-            // No valid header, create new board
-            if file_exists {
-                println!("Old format detected, creating new board (old data will be overwritten)");
-            }
-            (mode, width, height)
-        };
+/// Select tool state: a rectangle on the drawing layer, and the pixels floating over it
+/// once they've been lifted out to move (or pasted) until they're stamped back down
+struct Selection {
+    rect: (i32, i32, u32, u32), // Board x (0..width; the rectangle may run across the seam), y, width, height
+    floating: Option<SelectionBuffer>, // Drawn at the rectangle's corner; its undo step is already open
+    drag: Option<SelectionDrag>,
+}
 
-        let config = BoardConfig {
-            width: loaded_width,
-            height: loaded_height,
-            pixel_size: 4, // RGBA
-            mode: loaded_mode,
-        };
+/// What a left-drag with the select tool is doing
+#[derive(Clone, Copy)]
+enum SelectionDrag {
+    Marquee(Point), // Stretching a new rectangle from this corner
+    Move(Point), // Moving the floating pixels, grabbed at this offset from their corner
+}
 
-        // Pre-allocate disk space
-        let total_size = HEADER_SIZE + (loaded_width as u64) * (loaded_height as u64) * (config.pixel_size as u64);
-        data_file.set_len(total_size)?;
+/// One pen tool anchor. `handle` is the outgoing control point; the incoming one is its
+/// mirror image, so the curve passes through smoothly. Both are unwrapped relative to the
+/// previous anchor, so a curve across the seam runs the short way.
+#[derive(Debug, Clone, Copy)]
+struct PenAnchor {
+    point: Point,
+    handle: Point,
+}
 
-        // Allocate memory cache for entire board
-        let cache_size = (loaded_width as usize) * (loaded_height as usize) * 4;
-        let cache = vec![0u8; cache_size];
-        
-        // Allocate transparent drawing layer (all pixels start fully transparent)
-        let drawing_layer = vec![0u8; cache_size];
-        
-        let mut board = Board {
-            config,
-            data_file,
-            viewport: Viewport {
-                position: Point { x: 0.0, y: 0.0 },
-                zoom: 1.0,
-            },
-            cache,
-            drawing_layer,
-            undo_stack: Vec::new(),
-            has_drawings: false,  // Will be set to true when loading or drawing
-            viewport_cache: Vec::new(),
-            cached_viewport_width: 0,
-            cached_viewport_height: 0,
-            cached_viewport_pos: Point { x: 0.0, y: 0.0 },
-            cached_viewport_zoom: 1.0,
-            viewport_dirty: true,
-        };
-
-        if has_valid_header {
-            // Load existing data from disk
-            board.load_cache()?;
-        } else {
-            // Initialize new board with background color and write header
-            board.clear()?;
-            board.write_header()?;
-        }
+/// Largest distance a flattened curve piece may stray from its chord, in board pixels
+const PEN_FLATNESS: f32 = 0.25;
 
-        Ok(board)
-    }
-    
-    /// Write header with mode and dimensions
-    fn write_header(&mut self) -> io::Result<()> {
-        let mut header = [0u8; HEADER_SIZE as usize];
-        header[0] = match self.config.mode {
-            BoardMode::Blackboard => 0,
-            BoardMode::Whiteboard => 1,
-        };
-        header[1..5].copy_from_slice(&self.config.width.to_le_bytes());
-        header[5..9].copy_from_slice(&self.config.height.to_le_bytes());
-        
-        self.data_file.seek(SeekFrom::Start(0))?;
-        self.data_file.write_all(&header)?;
-        Ok(())
+/// Subdivision depth limit for flattening (2^12 pieces per segment at most)
+const PEN_MAX_DEPTH: u32 = 12;
+
+/// Append the points of cubic Bezier `p` (excluding its start) to `out`, splitting it in half
+/// until each piece is within `tolerance` of a straight line
+fn flatten_cubic(p: [Point; 4], tolerance: f32, depth: u32, out: &mut Vec<Point>) {
+    let (dx, dy) = (p[3].x - p[0].x, p[3].y - p[0].y);
+    let chord = (dx * dx + dy * dy).sqrt();
+    // Distance of both control points from the chord (or from the start, for a closed loop)
+    let deviation = |c: Point| if chord < 1e-3 {
+        ((c.x - p[0].x).powi(2) + (c.y - p[0].y).powi(2)).sqrt()
+    } else {
+        ((c.x - p[0].x) * dy - (c.y - p[0].y) * dx).abs() / chord
+    };
+    if depth >= PEN_MAX_DEPTH || deviation(p[1]).max(deviation(p[2])) <= tolerance {
+        out.push(p[3]);
+        return;
     }
     
-    /// Load entire board from disk into memory cache
-    fn load_cache(&mut self) -> io::Result<()> {
-        self.data_file.seek(SeekFrom::Start(HEADER_SIZE))?;
-        self.data_file.read_exact(&mut self.cache)?;
-        
-        // Load drawing layer if it exists
-        if Path::new("drawing_layer.data").exists() {
-            let drawing_data = std::fs::read("drawing_layer.data")?;
-            if drawing_data.len() == self.drawing_layer.len() {
-                self.drawing_layer.copy_from_slice(&drawing_data);
-                
-                // Check if there are any non-transparent pixels
-                self.has_drawings = self.drawing_layer.chunks(4).any(|pixel| pixel[3] != 0);
-            }
-        }
-        
-        Ok(())
+    // de Casteljau split at t = 0.5
+    let mid = |a: Point, b: Point| Point { x: (a.x + b.x) / 2.0, y: (a.y + b.y) / 2.0 };
+    let (ab, bc, cd) = (mid(p[0], p[1]), mid(p[1], p[2]), mid(p[2], p[3]));
+    let (abc, bcd) = (mid(ab, bc), mid(bc, cd));
+    let center = mid(abc, bcd);
+    flatten_cubic([p[0], ab, abc, center], tolerance, depth + 1, out);
+    flatten_cubic([center, bcd, cd, p[3]], tolerance, depth + 1, out);
+}
+
+/// The polyline through a chain of pen anchors, one cubic per pair of neighbours
+fn pen_curve_points(anchors: &[PenAnchor], tolerance: f32) -> Vec<Point> {
+    let mut points: Vec<Point> = anchors.first().map(|a| a.point).into_iter().collect();
+    for pair in anchors.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let incoming = Point { x: 2.0 * b.point.x - b.handle.x, y: 2.0 * b.point.y - b.handle.y };
+        flatten_cubic([a.point, a.handle, incoming, b.point], tolerance, 0, &mut points);
     }
+    points
+}
 
-    /// Draw a pixel at the given position (writes to drawing layer)
-    #[inline(always)]
-    fn draw_pixel(&mut self, x: i32, y: i32, color: [u8; 4]) {
-        // Only wrap horizontally (cylindrical), reject out-of-bounds vertical coords
-        if y < 0 || y >= self.config.height as i32 {
-            return; // Don't draw outside vertical bounds
-        }
-        
-        let wrapped_x = x.rem_euclid(self.config.width as i32) as u32;
-        let y = y as u32;
+/// Shape assist: strokes shorter than this (board pixels) are left alone
+const SHAPE_ASSIST_MIN_LENGTH: f32 = 24.0;
 
-        let offset = (((y as u64) * (self.config.width as u64) + (wrapped_x as u64)) 
-            * (self.config.pixel_size as u64)) as usize;
+/// Furthest a point may stray from the straight line, as a fraction of its length (never under 2px)
+const SHAPE_ASSIST_TOLERANCE: f32 = 0.04;
 
-        // Write to drawing layer using direct pointer write for maximum speed
-        unsafe {
-            let ptr = self.drawing_layer.as_mut_ptr().add(offset) as *mut u32;
-            *ptr = u32::from_ne_bytes(color);
-        }
-        
-        // Mark that we have drawings (if not erasing)
-        if color[3] != 0 {
-            self.has_drawings = true;
-        }
-    }
-    
-    /// Save current drawing layer state to undo stack (keep max 3 states)
-    fn save_undo_state(&mut self) {
-        let snapshot = self.drawing_layer.clone();
-        self.undo_stack.push(snapshot);
-        
-        // Keep only last 3 states
-        if self.undo_stack.len() > 3 {
-            self.undo_stack.remove(0);
-        }
-    }
-    
-    /// Undo last operation by restoring previous drawing layer state
-    fn undo(&mut self) -> bool {
-        if let Some(previous_state) = self.undo_stack.pop() {
-            self.drawing_layer = previous_state;
-            true
-        } else {
-            false
-        }
-    }
-    
-    /// Sync pending changes to disk (write entire cache and drawing layer)
-    fn sync(&mut self) -> io::Result<()> {
-        self.write_header()?;
-        self.data_file.seek(SeekFrom::Start(HEADER_SIZE))?;
-        self.data_file.write_all(&self.cache)?;
-        self.data_file.sync_data()?;
-        
-        // Save drawing layer
-        std::fs::write("drawing_layer.data", &self.drawing_layer)?;
-        
-        Ok(())
+/// Longest path, relative to the straight line, that still counts (rules out back-and-forth scribbles)
+const SHAPE_ASSIST_MAX_DETOUR: f32 = 1.15;
+
+/// Lines within this many degrees of horizontal or vertical snap onto the axis
+const SHAPE_ASSIST_AXIS_DEGREES: f32 = 6.0;
+
+/// The clean line a nearly straight freehand stroke stands for, if it is one: from the first
+/// point to the last, snapped onto the axis when it's close to horizontal or vertical
+fn straightened_line(points: &[Point]) -> Option<(Point, Point)> {
+    let (&from, &to) = (points.first()?, points.last()?);
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length = dx.hypot(dy);
+    if length < SHAPE_ASSIST_MIN_LENGTH {
+        return None;
     }
     
-    /// Toggle between Blackboard and Whiteboard modes
-    fn toggle_mode(&mut self) -> io::Result<()> {
-        let old_bg = self.config.mode.background_color();
-        
-        self.config.mode = match self.config.mode {
-            BoardMode::Blackboard => BoardMode::Whiteboard,
-            BoardMode::Whiteboard => BoardMode::Blackboard,
-        };
-        
-        let new_bg = self.config.mode.background_color();
-        
-        // Remap colors in parallel using rayon for better performance
-        self.cache.par_chunks_mut(4).for_each(|pixel| {
-            let r = pixel[0];
-            let g = pixel[1];
-            let b = pixel[2];
-            
-            // Check if this pixel is the old background color
-            if r == old_bg[0] && g == old_bg[1] && b == old_bg[2] {
-                // Replace with new background
-                pixel[0] = new_bg[0];
-                pixel[1] = new_bg[1];
-                pixel[2] = new_bg[2];
-            } else if r == 0 && g == 0 && b == 0 {
-                // Pure black -> white
-                pixel[0] = 255;
-                pixel[1] = 255;
-                pixel[2] = 255;
-            } else if r == 255 && g == 255 && b == 255 {
-                // Pure white -> black
-                pixel[0] = 0;
-                pixel[1] = 0;
-                pixel[2] = 0;
-            }
-            // All other colors remain unchanged
-        });
-        
-        self.sync()?;
-        Ok(())
+    let tolerance = (length * SHAPE_ASSIST_TOLERANCE).max(2.0);
+    let off_line = |p: &Point| ((p.x - from.x) * dy - (p.y - from.y) * dx).abs() / length;
+    let path: f32 = points.windows(2).map(|pair| (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y)).sum();
+    if points.iter().any(|p| off_line(p) > tolerance) || path > length * SHAPE_ASSIST_MAX_DETOUR {
+        return None;
     }
     
-    /// Clear the board with background color (optimized bulk write)
-    fn clear(&mut self) -> io::Result<()> {
-        let bg_color = self.config.mode.background_color();
-        
-        println!("Initializing board (this may take a moment)...");
-        
-        // Fill cache with background color
-        for i in (0..self.cache.len()).step_by(4) {
-            self.cache[i..i+4].copy_from_slice(&bg_color);
-        }
-        
-        // Clear drawing layer (fully transparent)
-        for i in 0..self.drawing_layer.len() {
-            self.drawing_layer[i] = 0;
-        }
-        
-        // Reset drawing flag
-        self.has_drawings = false;
-        
-        // Write cache to disk in chunks
-        let chunk_size = 1024 * 256; // 256KB chunks
-        let total_bytes = self.cache.len();
-        let num_chunks = (total_bytes + chunk_size - 1) / chunk_size;
-        
-        self.data_file.seek(SeekFrom::Start(0))?;
-        
-        for i in 0..num_chunks {
-            let start = i * chunk_size;
-            let end = (start + chunk_size).min(total_bytes);
-            self.data_file.write_all(&self.cache[start..end])?;
-            
-            let progress = ((i + 1) * 100 / num_chunks).min(100);
-            print!("\\rProgress: {}%", progress);
-            io::stdout().flush()?;
-        }
-        
-        println!(" - Complete!");
-        self.data_file.sync_all()?;
-        Ok(())
-    }
+    let angle = dy.abs().atan2(dx.abs()).to_degrees();
+    let to = if angle <= SHAPE_ASSIST_AXIS_DEGREES {
+        Point { x: to.x, y: from.y }
+    } else if angle >= 90.0 - SHAPE_ASSIST_AXIS_DEGREES {
+        Point { x: from.x, y: to.y }
+    } else {
+        to
+    };
+    Some((from, to))
+}
 
-    /// Get the default pen color for the current board mode
-    fn default_pen_color(&self) -> [u8; 4] {
-        self.config.mode.default_pen_color()
-    }
+/// Fixed alpha of highlighter strokes; pixels are overwritten, so overlaps never build up
+const HIGHLIGHTER_ALPHA: u8 = 96;
 
-    /// Render the current viewport with optional cylindrical projection
-    /// Optimized with parallel processing for maximum CPU utilization
-    fn render(&mut self, frame: &mut [u8], screen_width: u32, screen_height: u32) -> io::Result<()> {
-        // Check if we can reuse the cached viewport
-        let needs_rerender = self.viewport_dirty ||
-                            self.cached_viewport_width != screen_width ||
-                            self.cached_viewport_height != screen_height ||
-                            (self.viewport.position.x - self.cached_viewport_pos.x).abs() > 0.001 ||
-                            (self.viewport.position.y - self.cached_viewport_pos.y).abs() > 0.001 ||
-                            (self.viewport.zoom - self.cached_viewport_zoom).abs() > 0.001;
-        
-        if !needs_rerender && !self.viewport_cache.is_empty() {
-            // Use cached viewport
-            frame.copy_from_slice(&self.viewport_cache);
-            return Ok(());
-        }
-        
-        // Need to re-render
-        let buffer_size = (screen_width * screen_height * 4) as usize;
-        if self.viewport_cache.len() != buffer_size {
-            self.viewport_cache = vec![0u8; buffer_size];
-        }
-        
-        // Starting position for rendering
-        let start_x = self.viewport.position.x as i32;
-        let start_y = self.viewport.position.y as i32;
-        let zoom = self.viewport.zoom;
-        
-        let black = [0u8, 0u8, 0u8, 255u8]; // Black for out-of-bounds areas
-        let width = self.config.width as i32;
-        let height = self.config.height as i32;
-        let cache_ptr = &self.cache;
+/// Color of the brush outline at the cursor while the brush erases
+const ERASER_OUTLINE_COLOR: [u8; 4] = [255, 64, 64, 255];
+
+/// Longest pause between strokes a replay keeps, in seconds (idle time is squeezed out)
+const REPLAY_MAX_GAP_SECS: f32 = 1.0;
+
+/// Replay speed range (multiples of real time); +/- double or halve it during a replay
+const REPLAY_DEFAULT_SPEED: f32 = 4.0;
+const REPLAY_MIN_SPEED: f32 = 0.25;
+const REPLAY_MAX_SPEED: f32 = 256.0;
+
+/// Time-lapse playback of the stroke log: the real ink is set aside and the logged strokes
+/// are redrawn in order onto an empty drawing layer (see RickBoard::start_replay)
+struct Replay {
+    ink: TiledLayer, // The real drawing layer (with its blend modes), put back when the replay ends
+    undo_stack: Vec<UndoSnapshot>, // Set aside so the replayed strokes aren't recorded into it
+    has_drawings: bool,
+    scratch_active: bool,
+    schedule: Vec<f32>, // Replay time of each stroke in seconds (see replay_schedule)
+    next: usize, // Next stroke to draw
+    clock: f32, // Replay time reached so far
+    speed: f32,
+}
+
+/// Start time of each logged stroke on the replay clock: the real gaps between strokes,
+/// but none longer than REPLAY_MAX_GAP_SECS
+fn replay_schedule(strokes: &[Stroke]) -> Vec<f32> {
+    let mut clock = 0.0;
+    let mut previous = strokes.first().map_or(0, |stroke| stroke.time_ms);
+    strokes.iter().map(|stroke| {
+        let gap = stroke.time_ms.saturating_sub(previous) as f32 / 1000.0;
+        clock += gap.min(REPLAY_MAX_GAP_SECS);
+        previous = stroke.time_ms;
+        clock
+    }).collect()
+}
+
+/// Drawing tool state
+struct DrawingTool {
+    tool: Tool,
+    current_color: [u8; 4],
+    brush_size: u32,
+    brush_shape: BrushShape,
+    nib_angle: f32, // Chisel nib angle in degrees (config.toml nib_angle), used when B cycles to it
+    brush_opacity: u8, // 255 = opaque; lower values collect the stroke in stroke_mask until release
+    stroke_mask: HashMap<usize, u8>, // Pending translucent or textured stroke: pixel index -> alpha
+    is_drawing: bool,
+    is_eraser: bool, // True while the current stroke erases (right mouse, or eraser_mode)
+    eraser_mode: bool, // Persistent eraser toggled with E: the left button erases too
+    last_point: Option<Point>,
+    selected_marker_index: Option<usize>, // None when using a custom (hex) color
+    texture: StrokeTexture,
+    draw_straight: bool, // Shift-drag line mode: last_point is the anchor, nothing commits until release
+    line_end: Option<Point>, // Current end of the previewed straight line
+    smoothing: f32, // Stroke stabilization strength, 0.0 = off (raw cursor points)
+    recent_points: VecDeque<Point>, // Last SMOOTHING_WINDOW raw cursor points of the current stroke
+    current_stroke: Option<Stroke>, // Stroke being logged; moved to Board::strokes on release
+    recent_colors: VecDeque<[u8; 4]>, // Last RECENT_COLORS distinct colors picked, newest first
+    pen_anchors: Vec<PenAnchor>, // Pen tool curve being built; nothing is drawn until it's committed
+    pen_dragging: bool, // The button is down after dropping an anchor: the cursor pulls out its handle
+    stamp: Option<SelectionBuffer>, // Stamp tool image, already scaled (None when stamp_image isn't set or won't load)
+    stamp_travel: f32, // Distance the stroke has covered since the last stamp, in board pixels
+}
+
+/// Swatches kept in the recent-colors strip
+const RECENT_COLORS: usize = 8;
+
+/// Edge length and spacing of a recent-color swatch in screen pixels
+const RECENT_SWATCH_SIZE: u32 = 18;
+const RECENT_SWATCH_GAP: u32 = 4;
+
+/// Snapshot list layout: rows shown, row pitch and the offset of the first row in the panel
+const SNAPSHOT_LIST_ROWS: usize = 12;
+const SNAPSHOT_ROW_HEIGHT: u32 = 16;
+const SNAPSHOT_LIST_TOP: u32 = 24;
+
+/// Raw cursor points the smoother looks back over; bounds the added latency
+const SMOOTHING_WINDOW: usize = 8;
+
+/// Strengths cycled with the M key
+const SMOOTHING_LEVELS: [f32; 4] = [0.0, 0.5, 0.75, 0.9];
+
+/// Fixed 8-color palette used by the quantize filter when no markers are loaded
+const QUANTIZE_FALLBACK_PALETTE: [[u8; 4]; 8] = [
+    [0, 0, 0, 255],
+    [255, 255, 255, 255],
+    [255, 0, 0, 255],
+    [0, 255, 0, 255],
+    [0, 0, 255, 255],
+    [255, 255, 0, 255],
+    [255, 0, 255, 255],
+    [0, 255, 255, 255],
+];
+
+/// Side length of the tiled noise buffer used for stroke textures
+const TEXTURE_SIZE: usize = 64;
+
+/// Default seed for the stroke texture noise (override with RICKBOARD_TEXTURE_SEED)
+const DEFAULT_TEXTURE_SEED: u64 = 0x5EED_C4A1_4B0A_2D00;
+
+/// Optional grainy texture applied to stroke alpha (chalk on blackboard, marker on whiteboard)
+struct StrokeTexture {
+    enabled: bool,
+    seed: u64,
+    noise: Vec<u8>, // TEXTURE_SIZE x TEXTURE_SIZE tile, indexed by board coordinates
+}
+
+impl StrokeTexture {
+    /// Build the noise tile from a seed (same seed = same grain)
+    fn new(seed: u64) -> Self {
+        // xorshift64* - small, fast and deterministic
+        let mut state = seed.max(1);
+        let mut next = || {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+        };
+        let raw: Vec<u8> = (0..TEXTURE_SIZE * TEXTURE_SIZE).map(|_| next()).collect();
         
-        // Parallel row rendering for maximum CPU utilization
-        self.viewport_cache.par_chunks_mut((screen_width * 4) as usize)
-            .enumerate()
-            .for_each(|(screen_y, row)| {
-                // Apply zoom: convert screen coords to board coords
-                let board_y = start_y + ((screen_y as f32) / zoom) as i32;
-                
-                if board_y >= 0 && board_y < height {
-                    let row_start_offset = (board_y as usize) * (width as usize) * 4;
-                    
-                    // Process pixels in this row
-                    for screen_x in 0..screen_width {
-                        let board_x = start_x + ((screen_x as f32) / zoom) as i32;
-                        let wrapped_x = board_x.rem_euclid(width) as usize;
-                        let src_offset = row_start_offset + (wrapped_x * 4);
-                        let dst_offset = (screen_x * 4) as usize;
-                        row[dst_offset..dst_offset + 4].copy_from_slice(&cache_ptr[src_offset..src_offset + 4]);
-                    }
-                } else {
-                    // Fill with black if out of vertical bounds
-                    for screen_x in 0..screen_width {
-                        let dst_offset = (screen_x * 4) as usize;
-                        row[dst_offset..dst_offset + 4].copy_from_slice(&black);
+        // Soften the white noise with a 3x3 box blur (wrapping) so grains clump like chalk dust
+        let size = TEXTURE_SIZE as i32;
+        let mut noise = vec![0u8; raw.len()];
+        for y in 0..size {
+            for x in 0..size {
+                let mut sum = 0u32;
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let sx = (x + dx).rem_euclid(size) as usize;
+                        let sy = (y + dy).rem_euclid(size) as usize;
+                        sum += raw[sy * TEXTURE_SIZE + sx] as u32;
                     }
                 }
-            });
-        
-        // Update cache metadata
-        self.cached_viewport_width = screen_width;
-        self.cached_viewport_height = screen_height;
-        self.cached_viewport_pos = Point { x: self.viewport.position.x, y: self.viewport.position.y };
-        self.cached_viewport_zoom = self.viewport.zoom;
-        self.viewport_dirty = false;
+                noise[(y * size + x) as usize] = (sum / 9) as u8;
+            }
+        }
         
-        // Copy to output frame
-        frame.copy_from_slice(&self.viewport_cache);
-
-        Ok(())
+        StrokeTexture { enabled: false, seed, noise }
     }
     
-    /// Render the drawing layer with alpha blending on top of the current frame
-    fn render_drawing_layer(&self, frame: &mut [u8], screen_width: u32, _screen_height: u32) {
-        // Early exit if no drawings at all
-        if !self.has_drawings {
-            return;
-        }
-        
-        use rayon::prelude::*;
-        
-        let start_x = self.viewport.position.x as i32;
-        let start_y = self.viewport.position.y as i32;
-        let zoom = self.viewport.zoom;
-        let width = self.config.width as i32;
-        let height = self.config.height as i32;
-        
-        // Use fixed-point arithmetic for zoom (16.16 fixed point)
-        let zoom_inv_fixed = ((1.0 / zoom) * 65536.0) as i32;
+    /// Alpha to write for a stroke pixel at board (x, y)
+    #[inline(always)]
+    fn alpha_at(&self, x: i32, y: i32, base_alpha: u8, mode: BoardMode) -> u8 {
+        let tx = x.rem_euclid(TEXTURE_SIZE as i32) as usize;
+        let ty = y.rem_euclid(TEXTURE_SIZE as i32) as usize;
+        // Blurred noise clusters around 128; stretch it back out to the full range
+        let n = ((self.noise[ty * TEXTURE_SIZE + tx] as i32 - 128) * 3 + 128).clamp(0, 255) as u32;
         
-        // Parallel processing by rows
-        frame.par_chunks_mut((screen_width * 4) as usize)
-            .enumerate()
-            .for_each(|(screen_y, row)| {
-                let board_y = start_y + ((screen_y as i32 * zoom_inv_fixed) >> 16);
-                
-                if board_y < 0 || board_y >= height {
-                    return;
-                }
-                
-                let row_start_offset = (board_y as usize) * (width as usize) * 4;
-                
-                // Process pixels in this row
-                for screen_x in 0..screen_width {
-                    let board_x = start_x + ((screen_x as i32 * zoom_inv_fixed) >> 16);
-                    let wrapped_x = board_x.rem_euclid(width) as usize;
-                    let src_offset = row_start_offset + (wrapped_x * 4);
-                    let dst_offset = (screen_x * 4) as usize;
-                    
-                    if src_offset + 3 >= self.drawing_layer.len() || dst_offset + 3 >= row.len() {
-                        continue;
-                    }
-                    
-                    let alpha = self.drawing_layer[src_offset + 3];
-                    
-                    // Skip fully transparent pixels
-                    if alpha == 0 {
-                        continue;
-                    }
-                    
-                    // Use integer alpha blending
-                    if alpha == 255 {
-                        // Fully opaque - direct copy
-                        unsafe {
-                            std::ptr::copy_nonoverlapping(
-                                self.drawing_layer.as_ptr().add(src_offset),
-                                row.as_mut_ptr().add(dst_offset),
-                                3
-                            );
-                        }
-                    } else {
-                        // Partial transparency - integer blend
-                        let inv_alpha = 255 - alpha;
-                        row[dst_offset] = ((self.drawing_layer[src_offset] as u16 * alpha as u16 + row[dst_offset] as u16 * inv_alpha as u16) / 255) as u8;
-                        row[dst_offset + 1] = ((self.drawing_layer[src_offset + 1] as u16 * alpha as u16 + row[dst_offset + 1] as u16 * inv_alpha as u16) / 255) as u8;
-                        row[dst_offset + 2] = ((self.drawing_layer[src_offset + 2] as u16 * alpha as u16 + row[dst_offset + 2] as u16 * inv_alpha as u16) / 255) as u8;
-                    }
-                }
-            });
+        let factor = if mode.is_dark() {
+            // Chalk: heavy grain with occasional gaps where the chalk skips
+            if n < 40 { 0 } else { 90 + n * 165 / 255 }
+        } else {
+            // Marker: nearly solid with a faint streakiness
+            215 + n * 40 / 255
+        };
+        (base_alpha as u32 * factor / 255) as u8
     }
 }
 
-/// Color marker data
-struct ColorMarker {
-    color: [u8; 4],
-    open_image: Vec<u8>,   // RGBA data
-    closed_image: Vec<u8>, // RGBA data
+/// One step of the unified undo history (Ctrl+Z). Poster entries hold the state to go back to.
+enum UndoAction {
+    DrawStroke, // One Board undo snapshot: a stroke, line, fill, text label, quantize or clear
+    PosterAdd { index: usize },
+    PosterDelete { index: usize, poster: PinnedPoster },
+    PosterMove { index: usize, position: Point },
+    PosterScale { index: usize, scale_x: f32, scale_y: f32 },
+    PosterRotate { index: usize, rotation: f32 },
+    PosterRestack { from: usize, to: usize },
+}
+
+/// Undo history entries kept; drawing snapshots are limited separately by the undo depth
+const UNDO_HISTORY_LIMIT: usize = 200;
+
+/// Legend size in screen pixels (the panel's contents are laid out relative to its origin)
+const LEGEND_WIDTH: u32 = 290;
+const LEGEND_HEIGHT: u32 = 280;
+
+/// Cursor travel (screen pixels) before a press on the legend's top bar counts as a drag
+const LEGEND_DRAG_THRESHOLD: f64 = 4.0;
+
+/// Zoom slider track along the bottom of the legend (legend x offset and length in pixels)
+const ZOOM_SLIDER_LEFT: u32 = 100;
+const ZOOM_SLIDER_WIDTH: u32 = 180;
+
+/// Finest grid spacing in board pixels; doubled until lines are at least GRID_MIN_GAP apart on screen
+const GRID_SPACING: u32 = 50;
+const GRID_MIN_GAP: f32 = 16.0;
+
+/// Grid line opacity (0-255) over the board
+const GRID_ALPHA: u32 = 48;
+
+/// Seam marker opacity (0-255) and dash pattern in screen pixels (on, then off)
+const SEAM_ALPHA: u32 = 96;
+const SEAM_DASH: u32 = 6;
+
+/// An in-progress press on the legend's top bar
+#[derive(Clone, Copy)]
+struct LegendDrag {
+    start: (f64, f64), // Cursor position at the press
+    origin: Point, // Legend origin at the press
+    moved: bool, // Past LEGEND_DRAG_THRESHOLD, so releasing won't toggle collapse
+}
+
+/// Press on a scrollbar thumb: dragging it moves the view along that axis
+struct ScrollbarDrag {
+    vertical: bool,
+    grab: f32, // Cursor offset from the thumb's start along the track, in screen pixels
+}
+
+/// A poster image being copied and decoded on a worker thread (see RickBoard::poll_poster_loads)
+struct PosterLoad {
+    name: String,
+    drop_at: Option<(Point, (u32, u32))>, // A dropped file: board position and image size (drawn as a placeholder); None if picked from the menu
+    handle: std::thread::JoinHandle<image::ImageResult<PinnedPoster>>,
+}
+
+/// Downscaled preview of a poster file, shown next to its name in the poster picker
+struct PosterThumbnail {
     width: u32,
     height: u32,
+    pixels: Vec<u8>, // RGBA, at most PICKER_THUMB_SIZE on each side
 }
 
-/// Drawing tool state
-struct DrawingTool {
-    current_color: [u8; 4],
-    brush_size: u32,
-    is_drawing: bool,
-    is_eraser: bool, // True when using eraser (right mouse)
-    last_point: Option<Point>,
-    selected_marker_index: usize,
+/// An alignment guide shown while dragging a poster: a vertical line at x = `at` (horizontal at
+/// y = `at`) spanning `from..to` on the other axis, in board coordinates near the dragged poster
+struct PosterGuide {
+    vertical: bool,
+    at: f32,
+    from: f32,
+    to: f32,
+}
+
+/// Buttons at the right end of each poster picker row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PosterFileAction {
+    Reveal, // Show the file in the system file manager
+    Rename,
+    Delete,
 }
 
-/// Pinned poster on board
-#[derive(Clone, Serialize, Deserialize)]
-struct PinnedPoster {
+/// A poster picker row button: action, label and left edge relative to the panel
+const PICKER_ROW_BUTTONS: [(PosterFileAction, &str, u32); 3] = [
+    (PosterFileAction::Reveal, "Show", 280),
+    (PosterFileAction::Rename, "Rename", 314),
+    (PosterFileAction::Delete, "Del", 360),
+];
+
+/// A rename or delete from the poster picker waiting for input
+enum PosterFilePrompt {
+    Rename { index: usize, typed: String }, // New file name so far, starting from the old one
+    Delete(usize), // Waiting for Y or N
+}
+
+/// Where the board was left when the window closed: view, brush and pen (see `<board>.session`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Session {
     position: Point,
-    image_data: Vec<u8>,  // RGBA pixel data
-    width: u32,
-    height: u32,
-    name: String,
-    #[serde(default = "default_scale")]
-    scale: f32,  // Scale factor for the poster (1.0 = original size)
+    zoom: f32,
+    brush_size: u32,
+    eraser_mode: bool,
+    color: [u8; 4],
+    #[serde(default)]
+    ink_blend: BlendMode, // How the ink composites (Ctrl+B); older session files don't have it
 }
 
-fn default_scale() -> f32 {
-    1.0
+/// On-screen message for an action or error (see RickBoard::show_toast)
+struct Toast {
+    message: String,
+    error: bool, // Drawn in TOAST_ERROR_COLOR and kept up longer
+    until: Instant,
 }
 
 /// Main application state
@@ -552,93 +497,209 @@ struct RickBoard {
     posters: Vec<PinnedPoster>,
     show_poster_picker: bool,
     available_posters: Vec<(String, String)>, // (name, path)
-    placing_poster: Option<(Vec<u8>, u32, u32, String)>, // (image_data, width, height, name) while placing
+    poster_picker_scroll: usize, // First poster row shown in the picker
+    poster_thumbnails: HashMap<String, PosterThumbnail>, // Picker previews by path, decoded once the picker opens
+    thumbnail_loads: Option<mpsc::Receiver<(String, PosterThumbnail)>>, // Previews still decoding
+    poster_file_prompt: Option<PosterFilePrompt>, // Rename or delete from the picker in progress
+    placing_poster: Option<PinnedPoster>, // Picked from the poster menu, pinned by the next click
+    poster_loads: Vec<PosterLoad>, // Dropped or picked images still decoding
+    poster_clock: Instant, // Animated posters loop from here
     selected_poster_index: Option<usize>, // Index of currently selected poster for moving/scaling
     poster_drag_offset: Option<Point>, // Offset from poster position to cursor when dragging
+    hovered_poster_index: Option<usize>, // Poster under the cursor while Ctrl is held (grab preview)
+    show_poster_locks: bool, // Ctrl is held: locked posters show a padlock
+    poster_aspect_locked: bool, // Ctrl+Alt+Wheel stretches uniformly while set (L toggles)
+    show_grid: bool, // Board-space grid drawn over the ink (Ctrl+G toggles)
+    show_seam: bool, // Dashed line where the board wraps (config.toml seam_marker)
+    show_diagnostics: bool, // Memory and disk usage line above the minimap (F3 toggles)
     legend_collapsed: bool, // Whether the legend is collapsed
     legend_offset: f32, // Y offset for collapse animation (0.0 = fully visible, 200.0 = fully hidden)
+    legend_origin: Point, // Top-left corner of the legend panel on screen (kept on screen when drawn)
+    legend_drag: Option<LegendDrag>, // Press on the legend's top bar: a drag moves it, a click collapses it
+    show_scrollbars: bool, // Scrollbars along the bottom and right edges (config.toml scrollbars)
+    zoom_limits: (f32, f32), // Wheel, double-click and zoom slider range (config.toml min_zoom/max_zoom)
+    scrollbar_drag: Option<ScrollbarDrag>,
+    clipboard: Option<arboard::Clipboard>, // Opened on first use and kept, since some platforms drop copied data with it
+    toasts: VecDeque<Toast>, // Short messages stacked at the top, oldest first
+    layer_views: [LayerView; 3], // Layers panel rows, in compositing order
+    replay: Option<Replay>, // Time-lapse of the stroke log in progress (Ctrl+R)
+    minimap: Vec<u8>, // Downsampled board (RGBA), rebuilt by update_minimap when the board changes
+    minimap_size: (u32, u32), // Size the minimap was built at
+    hex_input: Option<String>, // Hex digits typed so far while the color field is focused
+    goto_input: Option<String>, // "x,y" typed so far while the go-to prompt (G) is open
+    snapshot_input: Option<String>, // Name typed so far while the save-snapshot prompt (Ctrl+S) is open
+    snapshot_list: Option<Vec<(String, std::time::SystemTime)>>, // Saved snapshots, newest first, while the list (Ctrl+O) is open
+    confirm_clear: bool, // Ask "Clear board? Y/N" before clearing (config.toml confirm_clear)
+    clear_prompt: Option<Action>, // The clear confirmation is showing (for this clear) and takes the next key press
+    error_dialog: Option<String>, // A failed save, shown until dismissed with Enter, Escape or a click
+    snapshot_selected: Option<usize>, // Row picked in the list; Enter restores it
+    measure: Option<(Point, Point)>, // Measure tool drag (anchor, cursor) in board coordinates
+    selection: Option<Selection>, // Select tool rectangle (and floating pixels)
+    selection_clipboard: Option<SelectionBuffer>, // Pixels copied or cut with the select tool
+    history: Vec<UndoAction>, // Unified undo history, newest last
+    drawing_undos_seen: usize, // Board::undo_pushes already entered into history
+    poster_drag_origin: Option<Point>, // Position of the dragged poster when the drag started
+    poster_guides: Vec<PosterGuide>, // Edges and centers the dragged poster lines up with
+    text_anchor: Option<Point>, // Board position of the label being typed (text tool)
+    pending_text: String, // Label typed so far, previewed until Enter
+    config: Config, // Settings from config.toml (poster directory etc.)
 }
 
 impl RickBoard {
-    fn load_marker_image(path: &str) -> io::Result<(Vec<u8>, u32, u32)> {
+    fn load_marker_image(path: &Path) -> io::Result<(Vec<u8>, u32, u32)> {
         let img = image::open(path)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(io::Error::other)?;
         let (width, height) = img.dimensions();
         let rgba = img.to_rgba8();
         Ok((rgba.into_raw(), width, height))
     }
     
-    fn new(width: u32, height: u32, mode: BoardMode, file_path: &Path) -> io::Result<Self> {
-        let board = Board::new(width, height, mode, file_path)?;
+    /// Load the stamp tool's image, scaled by `scale` (a warning and None if it can't be read)
+    fn load_stamp(path: &Path, scale: f32) -> Option<SelectionBuffer> {
+        let image = match image::open(path) {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => {
+                warn!("stamp image {} not loaded ({}); the stamp tool is unavailable", path.display(), e);
+                return None;
+            }
+        };
+        let width = ((image.width() as f32 * scale).round() as u32).max(1);
+        let height = ((image.height() as f32 * scale).round() as u32).max(1);
+        let image = if (width, height) == image.dimensions() {
+            image
+        } else {
+            image::imageops::resize(&image, width, height, image::imageops::FilterType::Triangle)
+        };
+        Some(SelectionBuffer { width, height, pixels: image.into_raw() })
+    }
+    
+    fn new(config: Config, file_path: &Path, texture_seed: u64) -> io::Result<Self> {
+        let board = Board::new(config.board_width, config.board_height, config.mode, config.paper(), config.wrap_vertical, file_path)?;
         let default_color = board.default_pen_color();
         
         // Load color markers
-        let marker_colors = vec![
-            ("black", [0, 0, 0, 255]),
-            ("white", [255, 255, 255, 255]),
-            ("red", [255, 0, 0, 255]),
-            ("blue", [30, 144, 255, 255]),      // Dodger blue
-            ("green", [0, 255, 0, 255]),
-            ("yellow", [255, 255, 0, 255]),
-            ("pink", [255, 0, 255, 255]),       // Magenta
-        ];
-        
         let mut markers = Vec::new();
-        for (name, color) in marker_colors {
-            let open_path = format!("assetts/{}_marker_open.png", name);
-            let closed_path = format!("assetts/{}_marker_closed.png", name);
-            
-            if let (Ok((open_data, w1, h1)), Ok((closed_data, _w2, _h2))) = 
-                (Self::load_marker_image(&open_path), Self::load_marker_image(&closed_path)) {
-                markers.push(ColorMarker {
+        let mut missing_markers = Vec::new();
+        for (name, color, open_path, closed_path) in config.palette_markers() {
+            match (Self::load_marker_image(&open_path), Self::load_marker_image(&closed_path)) {
+                (Ok((open_image, width, height)), Ok((closed_image, _, _))) => markers.push(ColorMarker {
+                    name,
                     color,
-                    open_image: open_data,
-                    closed_image: closed_data,
-                    width: w1,
-                    height: h1,
-                });
+                    open_image,
+                    closed_image,
+                    width,
+                    height,
+                }),
+                _ => {
+                    // Still usable, just without the artwork
+                    missing_markers.push(name.clone());
+                    markers.push(ColorMarker::swatch(name, color));
+                }
             }
         }
         
+        if !markers.is_empty() && missing_markers.len() == markers.len() {
+            warn!("no marker images found in {} (expected files like black_marker_open.png); drawing plain swatches.", config.assets_dir.display());
+            warn!("set assets_dir in {} or RICKBOARD_ASSETS to the assets folder", CONFIG_FILE);
+        } else if !missing_markers.is_empty() {
+            warn!("marker images missing in {} for: {} (drawn as plain swatches)", config.assets_dir.display(), missing_markers.join(", "));
+        }
+        
         // Find index of default color marker
         let selected_index = markers.iter()
-            .position(|m| m.color == default_color)
-            .unwrap_or(0);
-        
-        // Load available posters from posters/ directory
-        let mut available_posters = Vec::new();
-        if let Ok(entries) = std::fs::read_dir("posters") {
-            for entry in entries.flatten() {
-                if let Some(path_str) = entry.path().to_str() {
-                    if path_str.ends_with(".png") || path_str.ends_with(".jpg") || path_str.ends_with(".jpeg") {
-                        if let Some(name) = entry.file_name().to_str() {
-                            available_posters.push((name.to_string(), path_str.to_string()));
-                        }
-                    }
-                }
-            }
-        }
+            .position(|m| m.color == default_color);
+        
+        let available_posters = scan_posters(&config.posters_dir);
         
-        Ok(RickBoard {
+        let mut rickboard = RickBoard {
             board,
             drawing_tool: DrawingTool {
+                tool: Tool::Brush,
                 current_color: default_color,
-                brush_size: 2,
+                brush_size: config.brush_size,
+                brush_shape: BrushShape::Round,
+                nib_angle: config.nib_angle,
+                brush_opacity: 255,
+                stroke_mask: HashMap::new(),
                 is_drawing: false,
                 is_eraser: false,
+                eraser_mode: false,
                 last_point: None,
                 selected_marker_index: selected_index,
+                texture: StrokeTexture::new(texture_seed),
+                draw_straight: false,
+                line_end: None,
+                smoothing: 0.0,
+                recent_points: VecDeque::with_capacity(SMOOTHING_WINDOW),
+                current_stroke: None,
+                recent_colors: config.recent_colors.iter().filter_map(|hex| parse_hex_color(hex)).take(RECENT_COLORS).collect(),
+                pen_anchors: Vec::new(),
+                pen_dragging: false,
+                stamp: config.stamp_image.as_ref().and_then(|path| Self::load_stamp(&config.assets_dir.join(path), config.stamp_scale)),
+                stamp_travel: 0.0,
             },
             markers,
             posters: Vec::new(),
             show_poster_picker: false,
             available_posters,
+            poster_picker_scroll: 0,
+            poster_thumbnails: HashMap::new(),
+            thumbnail_loads: None,
+            poster_file_prompt: None,
             placing_poster: None,
+            poster_loads: Vec::new(),
+            poster_clock: Instant::now(),
             selected_poster_index: None,
             poster_drag_offset: None,
+            hovered_poster_index: None,
+            show_poster_locks: false,
+            poster_aspect_locked: true,
+            show_grid: false,
+            show_seam: config.seam_marker,
+            show_diagnostics: false,
             legend_collapsed: false,
             legend_offset: 0.0,
-        })
+            legend_origin: config.legend_position.map_or(Point { x: 0.0, y: 0.0 }, |[x, y]| Point { x, y }),
+            legend_drag: None,
+            show_scrollbars: config.scrollbars,
+            zoom_limits: (config.min_zoom, config.max_zoom),
+            scrollbar_drag: None,
+            clipboard: None,
+            toasts: VecDeque::new(),
+            replay: None,
+            layer_views: [Layer::Posters, Layer::Ink, Layer::Scratch].map(|layer| LayerView {
+                layer,
+                name: match layer {
+                    Layer::Posters => "Posters",
+                    Layer::Ink => "Ink",
+                    Layer::Scratch => "Scratch",
+                },
+                visible: true,
+                opacity: 255,
+            }),
+            minimap: Vec::new(),
+            minimap_size: (0, 0),
+            hex_input: None,
+            goto_input: None,
+            snapshot_input: None,
+            snapshot_list: None,
+            confirm_clear: config.confirm_clear,
+            clear_prompt: None,
+            error_dialog: None,
+            snapshot_selected: None,
+            measure: None,
+            selection: None,
+            selection_clipboard: None,
+            history: Vec::new(),
+            drawing_undos_seen: 0,
+            poster_drag_origin: None,
+            poster_guides: Vec::new(),
+            text_anchor: None,
+            pending_text: String::new(),
+            config,
+        };
+        rickboard.restore_session();
+        Ok(rickboard)
     }
     
     /// Initialize and load posters from file
@@ -648,451 +709,3087 @@ impl RickBoard {
     }
 
     fn start_drawing(&mut self, point: Point, is_eraser: bool) {
-        // Save undo state before starting new drawing operation
-        self.board.save_undo_state();
+        // Save undo state before starting new drawing operation (scratch marks are throwaway)
+        if !self.board.scratch_active {
+            self.board.save_undo_state();
+        }
         
         self.drawing_tool.is_drawing = true;
-        self.drawing_tool.is_eraser = is_eraser;
+        self.drawing_tool.is_eraser = is_eraser || self.drawing_tool.eraser_mode;
         self.drawing_tool.last_point = Some(point);
-        // Draw initial pixel with brush size
-        let _ = self.draw_brush(point);
-    }
-
-    fn continue_drawing(&mut self, point: Point) {
-        if self.drawing_tool.is_drawing {
-            // Draw line from last point to current point for solid strokes
-            if let Some(last_point) = self.drawing_tool.last_point {
-                // Calculate distance and interpolate to connect points
-                let dx = point.x - last_point.x;
-                let dy = point.y - last_point.y;
-                let distance = (dx * dx + dy * dy).sqrt();
-                let steps = distance.ceil().max(1.0) as i32;
-                
-                // Draw brushes along the line
-                for i in 0..=steps {
-                    let t = i as f32 / steps as f32;
-                    let interp_point = Point {
-                        x: last_point.x + dx * t,
-                        y: last_point.y + dy * t,
-                    };
-                    self.draw_brush(interp_point);
-                }
-            } else {
-                self.draw_brush(point);
-            }
-            self.drawing_tool.last_point = Some(point);
+        self.drawing_tool.recent_points.clear();
+        self.drawing_tool.recent_points.push_back(point);
+        self.drawing_tool.current_stroke = self.new_stroke(point);
+        self.drawing_tool.stamp_travel = 0.0;
+        // Draw initial pixel with brush size (or the first stamp)
+        if self.stamping() {
+            self.place_stamp(point);
+        } else {
+            self.draw_brush(point);
         }
     }
     
-    fn draw_brush(&mut self, center: Point) {
+    /// Whether strokes lay down the stamp image instead of the brush (the eraser and scratch
+    /// marks still use the brush)
+    fn stamping(&self) -> bool {
+        let tool = &self.drawing_tool;
+        tool.tool == Tool::Stamp && !tool.is_eraser && tool.stamp.is_some() && !self.board.scratch_active
+    }
+    
+    /// Distance between stamps along a stroke, in board pixels
+    fn stamp_spacing(&self) -> f32 {
+        let width = self.drawing_tool.stamp.as_ref().map_or(1, |stamp| stamp.width);
+        (width as f32 * self.config.stamp_spacing).max(1.0)
+    }
+    
+    /// Alpha-blend the stamp image onto the drawing layer, centered on `center`
+    fn place_stamp(&mut self, center: Point) {
+        if let Some(stamp) = &self.drawing_tool.stamp {
+            let (x, y) = (center.x as i32 - (stamp.width / 2) as i32, center.y as i32 - (stamp.height / 2) as i32);
+            self.board.stamp_region(stamp, x, y);
+        }
+    }
+    
+    /// Start logging a stroke at `point` with the current tool settings (scratch marks and stamps aren't logged)
+    fn new_stroke(&self, point: Point) -> Option<Stroke> {
+        if self.board.scratch_active || self.stamping() {
+            return None;
+        }
+        let tool = &self.drawing_tool;
+        let highlighter = tool.tool == Tool::Highlighter && !tool.is_eraser;
+        let mut color = tool.current_color;
+        color[3] = if highlighter {
+            HIGHLIGHTER_ALPHA
+        } else {
+            (color[3] as u32 * tool.brush_opacity as u32 / 255) as u8
+        };
+        let time_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        Some(Stroke {
+            time_ms,
+            color,
+            brush_size: tool.brush_size,
+            shape: tool.brush_shape,
+            eraser: tool.is_eraser,
+            highlighter,
+            points: vec![point],
+        })
+    }
+
+    /// Begin a Shift-drag straight line anchored at `point` (previewed until release)
+    fn start_line(&mut self, point: Point, is_eraser: bool) {
+        self.drawing_tool.is_drawing = true;
+        self.drawing_tool.is_eraser = is_eraser || self.drawing_tool.eraser_mode;
+        self.drawing_tool.draw_straight = true;
+        self.drawing_tool.last_point = Some(point);
+        self.drawing_tool.line_end = Some(point);
+        // A stamped line starts with a stamp on the anchor
+        self.drawing_tool.stamp_travel = self.stamp_spacing();
+    }
+
+    fn continue_drawing(&mut self, point: Point) {
+        if self.drawing_tool.is_drawing {
+            if self.drawing_tool.draw_straight {
+                // Line mode only moves the preview end point
+                self.drawing_tool.line_end = Some(point);
+                return;
+            }
+            
+            let point = self.smooth_point(point);
+            
+            // Draw line from last point to current point for solid strokes
+            if let Some(last_point) = self.drawing_tool.last_point {
+                self.draw_segment(last_point, point);
+            } else {
+                self.draw_brush(point);
+            }
+            self.drawing_tool.last_point = Some(point);
+            if let Some(stroke) = &mut self.drawing_tool.current_stroke {
+                stroke.points.push(point);
+            }
+        }
+    }
+    
+    /// Record a raw cursor point and return where the stroke should actually go.
+    /// With smoothing on, that's an exponential moving average over the recent points
+    /// (newest weighted most), so the stroke trails the cursor by at most SMOOTHING_WINDOW points.
+    fn smooth_point(&mut self, point: Point) -> Point {
+        let recent = &mut self.drawing_tool.recent_points;
+        if recent.len() == SMOOTHING_WINDOW {
+            recent.pop_front();
+        }
+        recent.push_back(point);
+        
+        let strength = self.drawing_tool.smoothing;
+        if strength <= 0.0 {
+            return point;
+        }
+        
+        let mut smoothed = recent[0];
+        for p in recent.iter().skip(1) {
+            smoothed.x += (p.x - smoothed.x) * (1.0 - strength);
+            smoothed.y += (p.y - smoothed.y) * (1.0 - strength);
+        }
+        smoothed
+    }
+    
+    /// Stamp the brush along a straight segment (interpolated so strokes are solid)
+    fn draw_segment(&mut self, from: Point, to: Point) {
+        // Calculate distance and interpolate to connect points
+        let dx = to.x - from.x;
+        let dy = to.y - from.y;
+        let distance = (dx * dx + dy * dy).sqrt();
+        
+        // The stamp tool places its image every stamp_spacing along the path instead,
+        // carrying the distance left over into the next segment
+        if self.stamping() {
+            let spacing = self.stamp_spacing();
+            let mut next = spacing - self.drawing_tool.stamp_travel;
+            while next <= distance {
+                let t = if distance > 0.0 { next / distance } else { 0.0 };
+                self.place_stamp(Point { x: from.x + dx * t, y: from.y + dy * t });
+                next += spacing;
+            }
+            self.drawing_tool.stamp_travel = distance - (next - spacing);
+            return;
+        }
+        let steps = distance.ceil().max(1.0) as i32;
+        
+        // Draw brushes along the line
+        for i in 0..=steps {
+            let t = i as f32 / steps as f32;
+            let interp_point = Point {
+                x: from.x + dx * t,
+                y: from.y + dy * t,
+            };
+            self.draw_brush(interp_point);
+        }
+    }
+    
+    fn draw_brush(&mut self, center: Point) {
         let radius = (self.drawing_tool.brush_size / 2) as i32;
         let cx = center.x as i32;
         let cy = center.y as i32;
         
-        // Use background color for eraser, current color for drawing
+        // Highlighter writes its fixed alpha straight into the layer and marks the pixel Multiply
+        let highlighter = self.drawing_tool.tool == Tool::Highlighter && !self.drawing_tool.is_eraser;
+        
+        // The eraser clears ink to fully transparent, whatever the brush opacity
         let color = if self.drawing_tool.is_eraser {
-            self.board.config.mode.background_color()
+            [0, 0, 0, 0]
+        } else if highlighter {
+            let c = self.drawing_tool.current_color;
+            [c[0], c[1], c[2], HIGHLIGHTER_ALPHA]
         } else {
             self.drawing_tool.current_color
         };
         
+        // Texture only applies to ink, never to the eraser or highlighter
+        let textured = self.drawing_tool.texture.enabled && !self.drawing_tool.is_eraser && !highlighter;
+        let mode = self.board.config.mode;
+        
+        // Translucent and textured ink go into the stroke mask (max alpha per pixel) so
+        // overlapping stamps within one stroke don't darken; it's blended over the ink already
+        // on the layer in stop_drawing, so grain gaps leave what's underneath alone
+        let stroke_alpha = (color[3] as u32 * self.drawing_tool.brush_opacity as u32 / 255) as u8;
+        let masked = !self.drawing_tool.is_eraser && !highlighter && (stroke_alpha < 255 || textured);
+        let board_width = self.board.config.width as i32;
+        let shape = self.drawing_tool.brush_shape;
+        
         // Direct pixel writes without allocation
         for dy in -radius..=radius {
-            let dy2 = dy * dy;
             for dx in -radius..=radius {
-                if dx * dx + dy2 <= radius * radius {
-                    self.board.draw_pixel(cx + dx, cy + dy, color);
+                if shape.contains(dx, dy, radius) {
+                    let (px, py) = (cx + dx, cy + dy);
+                    if masked {
+                        let Some(row) = self.board.config.wrap_y(py) else {
+                            continue;
+                        };
+                        // Texture is keyed to board coords so repeated stamps within a stroke agree
+                        let alpha = if textured {
+                            self.drawing_tool.texture.alpha_at(px, py, stroke_alpha, mode)
+                        } else {
+                            stroke_alpha
+                        };
+                        let index = row * board_width as usize + px.rem_euclid(board_width) as usize;
+                        let entry = self.drawing_tool.stroke_mask.entry(index).or_insert(0);
+                        *entry = (*entry).max(alpha);
+                    } else {
+                        self.board.draw_pixel(px, py, color);
+                        if highlighter {
+                            self.board.set_blend_mode(px, py, BlendMode::Multiply);
+                        }
+                    }
                 }
             }
         }
     }
 
     fn stop_drawing(&mut self) {
+        let freehand = self.drawing_tool.is_drawing && !self.drawing_tool.draw_straight && !self.stamping();
+        
+        // Commit a pending straight line as a single undo step
+        if self.drawing_tool.draw_straight {
+            if let (Some(anchor), Some(end)) = (self.drawing_tool.last_point, self.drawing_tool.line_end) {
+                if !self.board.scratch_active {
+                    self.board.save_undo_state();
+                }
+                self.draw_segment(anchor, end);
+                self.drawing_tool.current_stroke = self.new_stroke(anchor);
+                if let Some(stroke) = &mut self.drawing_tool.current_stroke {
+                    stroke.points.push(end);
+                }
+            }
+            self.drawing_tool.draw_straight = false;
+            self.drawing_tool.line_end = None;
+        } else if self.drawing_tool.smoothing > 0.0 {
+            // The smoothed stroke lags the cursor; finish it at the last real point
+            if let (Some(last), Some(&end)) = (self.drawing_tool.last_point, self.drawing_tool.recent_points.back()) {
+                if self.drawing_tool.is_drawing {
+                    self.draw_segment(last, end);
+                    if let Some(stroke) = &mut self.drawing_tool.current_stroke {
+                        stroke.points.push(end);
+                    }
+                }
+            }
+        }
+        self.drawing_tool.recent_points.clear();
+        
+        // Shape assist: a freehand stroke that's nearly straight is redrawn as a clean line
+        if freehand && self.config.shape_assist {
+            self.straighten_stroke();
+        }
+        
+        // Blend a translucent or textured stroke in one pass
+        if !self.drawing_tool.stroke_mask.is_empty() {
+            let mask = std::mem::take(&mut self.drawing_tool.stroke_mask);
+            self.board.blend_stroke(&mask, self.drawing_tool.current_color);
+        }
+        
+        if let Some(stroke) = self.drawing_tool.current_stroke.take() {
+            self.board.log_stroke(stroke);
+        }
         self.drawing_tool.is_drawing = false;
         self.drawing_tool.last_point = None;
         // Don't sync on every mouse release - too slow for large boards
         // Data is safely in cache and will sync on mode toggle or app close
     }
 
-    fn clear_board(&mut self) -> io::Result<()> {
-        self.board.clear()?;
-        self.board.sync()?;
-        Ok(())
+    /// Replace the stroke just drawn with its straightened line, within the same undo step.
+    /// Scratch marks (no logged points) and strokes that aren't nearly straight are kept as drawn.
+    fn straighten_stroke(&mut self) {
+        let Some((from, to)) = self.drawing_tool.current_stroke.as_ref().and_then(|stroke| straightened_line(&stroke.points)) else {
+            return;
+        };
+        if !self.board.rewind_undo_step() {
+            return;
+        }
+        self.drawing_tool.stroke_mask.clear();
+        self.draw_segment(from, to);
+        if let Some(stroke) = &mut self.drawing_tool.current_stroke {
+            stroke.points = vec![from, to];
+        }
+    }
+    
+    /// `point` moved by whole board widths (and heights, if the board wraps vertically) to
+    /// lie as close as possible to `near`
+    fn unwrap_near(&self, point: Point, near: Point) -> Point {
+        let (dx, dy) = self.measure_delta(near, point);
+        Point { x: near.x + dx, y: near.y + dy }
+    }
+    
+    /// Drop a pen anchor at `point`; dragging before release pulls out its handle
+    fn add_pen_anchor(&mut self, point: Point) {
+        let point = match self.drawing_tool.pen_anchors.last() {
+            Some(last) => self.unwrap_near(point, last.point),
+            None => point,
+        };
+        self.drawing_tool.pen_anchors.push(PenAnchor { point, handle: point });
+        self.drawing_tool.pen_dragging = true;
+    }
+    
+    /// Move the handle of the anchor just dropped to follow the cursor
+    fn drag_pen_handle(&mut self, point: Point) {
+        if let Some(&anchor) = self.drawing_tool.pen_anchors.last() {
+            let handle = self.unwrap_near(point, anchor.point);
+            if let Some(last) = self.drawing_tool.pen_anchors.last_mut() {
+                last.handle = handle;
+            }
+        }
     }
     
-    /// Toggle between Blackboard and Whiteboard modes
-    fn toggle_mode(&mut self) -> io::Result<()> {
-        // If currently using white pen (index 1), switch to black (index 0)
-        // If currently using black pen (index 0), switch to white (index 1)
-        if self.drawing_tool.selected_marker_index == 1 {
-            self.drawing_tool.selected_marker_index = 0;
-            self.drawing_tool.current_color = self.markers[0].color; // Black
-        } else if self.drawing_tool.selected_marker_index == 0 {
-            self.drawing_tool.selected_marker_index = 1;
-            self.drawing_tool.current_color = self.markers[1].color; // White
+    /// Draw the pen curve with the current brush as one undo step (and one logged stroke).
+    /// Returns true if anything was drawn; fewer than two anchors are just discarded.
+    fn commit_pen_curve(&mut self) -> bool {
+        let anchors = std::mem::take(&mut self.drawing_tool.pen_anchors);
+        self.drawing_tool.pen_dragging = false;
+        if anchors.len() < 2 {
+            return false;
         }
         
-        self.board.toggle_mode()?;
-        Ok(())
+        let points = pen_curve_points(&anchors, PEN_FLATNESS);
+        if !self.board.scratch_active {
+            self.board.save_undo_state();
+        }
+        self.drawing_tool.is_eraser = self.drawing_tool.eraser_mode;
+        self.drawing_tool.current_stroke = self.new_stroke(points[0]);
+        self.draw_brush(points[0]);
+        for pair in points.windows(2) {
+            self.draw_segment(pair[0], pair[1]);
+        }
+        if let Some(stroke) = &mut self.drawing_tool.current_stroke {
+            stroke.points = points;
+        }
+        // Blends a translucent curve and logs the stroke, same as a freehand release
+        self.stop_drawing();
+        true
     }
     
-    /// Find poster at given board coordinates (returns index, checks from top to bottom)
-    fn find_poster_at(&self, board_x: f32, board_y: f32) -> Option<usize> {
-        // Check posters in reverse order (top to bottom)
-        for (i, poster) in self.posters.iter().enumerate().rev() {
-            let poster_width = poster.width as f32 * poster.scale;
-            let poster_height = poster.height as f32 * poster.scale;
-            
-            if board_x >= poster.position.x && board_x < poster.position.x + poster_width &&
-               board_y >= poster.position.y && board_y < poster.position.y + poster_height {
-                return Some(i);
+    /// Rectangle spanned by two board points: x goes the short way across the seam, y is
+    /// clamped to a non-wrapping board. Corners snap down to whole pixels, so a click is empty.
+    fn selection_rect(&self, a: Point, b: Point) -> (i32, i32, u32, u32) {
+        let config = &self.board.config;
+        let (dx, dy) = self.measure_delta(a, b);
+        let (x0, x1) = if dx < 0.0 { (a.x + dx, a.x) } else { (a.x, a.x + dx) };
+        let (y0, y1) = if dy < 0.0 { (a.y + dy, a.y) } else { (a.y, a.y + dy) };
+        let (y0, y1) = if config.wrap_vertical {
+            (y0, y1)
+        } else {
+            (y0.clamp(0.0, config.height as f32), y1.clamp(0.0, config.height as f32))
+        };
+        let x = (x0.floor() as i32).rem_euclid(config.width as i32);
+        let y = if config.wrap_vertical { (y0.floor() as i32).rem_euclid(config.height as i32) } else { y0.floor() as i32 };
+        (x, y, (x1.floor() - x0.floor()) as u32, (y1.floor() - y0.floor()) as u32)
+    }
+    
+    /// Whether a board point falls inside the selection rectangle (seam-aware)
+    fn point_in_selection(&self, point: Point) -> bool {
+        let Some(selection) = &self.selection else {
+            return false;
+        };
+        let (x, y, w, h) = selection.rect;
+        let corner = Point { x: x as f32, y: y as f32 };
+        let (dx, dy) = self.measure_delta(corner, point);
+        // measure_delta picks the nearer way round; a rectangle over half the board wide can
+        // need the other one
+        let dx = if dx < 0.0 { dx + self.board.config.width as f32 } else { dx };
+        let dy = if dy < 0.0 && self.board.config.wrap_vertical { dy + self.board.config.height as f32 } else { dy };
+        dx >= 0.0 && dy >= 0.0 && dx < w as f32 && dy < h as f32
+    }
+    
+    /// Left press with the select tool: grab the selection to move it (lifting the pixels out
+    /// on the first grab), or put down what's floating and start a new rectangle.
+    /// Returns whether floating pixels were stamped onto the board.
+    fn press_selection(&mut self, point: Point) -> bool {
+        if self.point_in_selection(point) {
+            let rect = self.selection.as_ref().map(|s| s.rect).unwrap_or_default();
+            let corner = Point { x: rect.0 as f32, y: rect.1 as f32 };
+            let (dx, dy) = self.measure_delta(corner, point);
+            if self.selection.as_ref().is_some_and(|s| s.floating.is_none()) {
+                self.board.save_undo_state();
+                let buffer = self.board.lift_region(rect);
+                if let Some(selection) = &mut self.selection {
+                    selection.floating = Some(buffer);
+                }
             }
+            if let Some(selection) = &mut self.selection {
+                selection.drag = Some(SelectionDrag::Move(Point { x: dx, y: dy }));
+            }
+            return false;
         }
-        None
+        
+        let stamped = self.commit_selection();
+        self.selection = Some(Selection {
+            rect: self.selection_rect(point, point),
+            floating: None,
+            drag: Some(SelectionDrag::Marquee(point)),
+        });
+        stamped
     }
     
-    /// Toggle legend collapse state
-    fn toggle_legend(&mut self) {
-        self.legend_collapsed = !self.legend_collapsed;
+    /// Follow the cursor while stretching a rectangle or moving the floating pixels
+    fn drag_selection(&mut self, point: Point) {
+        let Some(drag) = self.selection.as_ref().and_then(|s| s.drag) else {
+            return;
+        };
+        let rect = match drag {
+            SelectionDrag::Marquee(anchor) => self.selection_rect(anchor, point),
+            SelectionDrag::Move(offset) => {
+                let (_, _, w, h) = self.selection.as_ref().map(|s| s.rect).unwrap_or_default();
+                let x = ((point.x - offset.x).floor() as i32).rem_euclid(self.board.config.width as i32);
+                let y = (point.y - offset.y).floor() as i32;
+                let y = if self.board.config.wrap_vertical { y.rem_euclid(self.board.config.height as i32) } else { y };
+                (x, y, w, h)
+            }
+        };
+        if let Some(selection) = &mut self.selection {
+            selection.rect = rect;
+        }
     }
     
-    /// Update legend animation (smooth slide in/out)
-    fn update_legend_animation(&mut self) {
-        let target_offset = if self.legend_collapsed { 270.0 } else { 0.0 };
-        let speed = 15.0; // pixels per frame
-        
-        if (self.legend_offset - target_offset).abs() > 0.5 {
-            if self.legend_offset < target_offset {
-                self.legend_offset = (self.legend_offset + speed).min(target_offset);
-            } else {
-                self.legend_offset = (self.legend_offset - speed).max(target_offset);
+    /// Left release with the select tool; a click without a drag leaves nothing selected
+    fn end_selection_drag(&mut self) {
+        if let Some(selection) = &mut self.selection {
+            selection.drag = None;
+            let (_, _, w, h) = selection.rect;
+            if selection.floating.is_none() && (w == 0 || h == 0) {
+                self.selection = None;
             }
-        } else {
-            self.legend_offset = target_offset;
         }
     }
     
-    /// Save posters to JSON file
-    fn save_posters(&self) -> io::Result<()> {
-        let json = serde_json::to_string_pretty(&self.posters)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        std::fs::write("posters.json", json)?;
-        Ok(())
+    /// Stamp floating pixels down where they are (into the undo step opened when they were
+    /// lifted or pasted). The rectangle stays selected. Returns whether anything was stamped.
+    fn commit_selection(&mut self) -> bool {
+        let Some(selection) = &mut self.selection else {
+            return false;
+        };
+        let Some(buffer) = selection.floating.take() else {
+            return false;
+        };
+        let (x, y, _, _) = selection.rect;
+        self.board.stamp_region(&buffer, x, y);
+        true
     }
     
-    /// Load posters from JSON file
-    fn load_posters(&mut self) -> io::Result<()> {
-        if Path::new("posters.json").exists() {
-            let json = std::fs::read_to_string("posters.json")?;
-            self.posters = serde_json::from_str(&json)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    /// Put down anything floating and drop the selection. Returns whether anything was stamped.
+    fn deselect(&mut self) -> bool {
+        let stamped = self.commit_selection();
+        self.selection = None;
+        stamped
+    }
+    
+    /// Delete the selected ink (floating pixels are simply dropped; their lift is already undoable)
+    fn delete_selection(&mut self) {
+        let Some(selection) = self.selection.take() else {
+            return;
+        };
+        if selection.floating.is_none() {
+            self.board.save_undo_state();
+            self.board.clear_region(selection.rect);
         }
-        Ok(())
     }
     
-    /// Handle dropped file - copy to posters folder and add as poster at drop location
-    fn handle_dropped_file(&mut self, path: &PathBuf, screen_x: f64, screen_y: f64) -> io::Result<()> {
-        // Check if file is an image
-        let extension = path.extension()
-            .and_then(|e| e.to_str())
-            .map(|e| e.to_lowercase());
-        
-        let is_image = match extension.as_deref() {
-            Some("png") | Some("jpg") | Some("jpeg") | Some("bmp") | Some("gif") => true,
-            _ => false,
+    /// Copy the selected ink (or the floating pixels) to the select tool's clipboard
+    fn copy_selection(&mut self) {
+        let Some(selection) = &self.selection else {
+            return;
+        };
+        let buffer = match &selection.floating {
+            Some(buffer) => buffer.clone(),
+            None => self.board.copy_region(selection.rect),
+        };
+        self.show_toast(format!("Copied {}x{} selection", buffer.width, buffer.height));
+        self.selection_clipboard = Some(buffer);
+    }
+    
+    /// Copy then delete the selected ink
+    fn cut_selection(&mut self) {
+        self.copy_selection();
+        self.delete_selection();
+    }
+    
+    /// Float a copy of the clipboard with its top-left corner at a board point, ready to move
+    /// (stamped down by the next click outside it, a tool change or Enter)
+    fn paste_selection(&mut self, point: Point) {
+        let Some(buffer) = self.selection_clipboard.clone() else {
+            return;
+        };
+        self.commit_selection();
+        self.board.save_undo_state();
+        let x = (point.x.floor() as i32).rem_euclid(self.board.config.width as i32);
+        let y = point.y.floor() as i32;
+        let y = if self.board.config.wrap_vertical { y.rem_euclid(self.board.config.height as i32) } else { y };
+        self.selection = Some(Selection {
+            rect: (x, y, buffer.width, buffer.height),
+            floating: Some(buffer),
+            drag: None,
+        });
+    }
+    
+    /// Handle a key press while something is selected: Enter or Escape puts it down and deselects,
+    /// Delete or Backspace deletes it. Returns None for keys the selection doesn't use,
+    /// otherwise whether the drawing layer changed.
+    fn handle_selection_key(&mut self, event: &KeyEvent) -> Option<bool> {
+        match &event.logical_key {
+            Key::Named(NamedKey::Enter | NamedKey::Escape) => Some(self.deselect()),
+            Key::Named(NamedKey::Delete | NamedKey::Backspace) => {
+                self.delete_selection();
+                Some(true)
+            }
+            _ => None,
+        }
+    }
+    
+    /// Handle a key press while a pen curve is being built: Enter commits it, Escape discards it,
+    /// Backspace takes back the last anchor. Returns None for keys the pen doesn't use,
+    /// otherwise whether the curve was committed.
+    fn handle_pen_key(&mut self, event: &KeyEvent) -> Option<bool> {
+        match &event.logical_key {
+            Key::Named(NamedKey::Enter) => Some(self.commit_pen_curve()),
+            Key::Named(NamedKey::Escape) => {
+                self.drawing_tool.pen_anchors.clear();
+                self.drawing_tool.pen_dragging = false;
+                Some(false)
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.drawing_tool.pen_anchors.pop();
+                self.drawing_tool.pen_dragging = false;
+                Some(false)
+            }
+            _ => None,
+        }
+    }
+    
+    /// Set the ink aside and start redrawing the stroke log from an empty drawing layer.
+    /// Returns false if there's nothing to replay.
+    fn start_replay(&mut self) -> bool {
+        if self.replay.is_some() || self.board.strokes.is_empty() {
+            return false;
+        }
+        let board = &mut self.board;
+        let empty = TiledLayer::new(board.config.width, board.config.height, [0, 0, 0, 0]);
+        self.replay = Some(Replay {
+            ink: std::mem::replace(&mut board.drawing_layer, empty),
+            undo_stack: std::mem::take(&mut board.undo_stack),
+            has_drawings: std::mem::replace(&mut board.has_drawings, false),
+            scratch_active: std::mem::replace(&mut board.scratch_active, false),
+            schedule: replay_schedule(&board.strokes),
+            next: 0,
+            clock: 0.0,
+            speed: REPLAY_DEFAULT_SPEED,
+        });
+        true
+    }
+    
+    /// Draw the next stroke of the replay. Returns false once every stroke has been drawn.
+    fn replay_next_stroke(&mut self) -> bool {
+        let Some(replay) = &mut self.replay else {
+            return false;
+        };
+        let Some(stroke) = self.board.strokes.get(replay.next).cloned() else {
+            return false;
+        };
+        replay.next += 1;
+        self.board.draw_logged_stroke(&stroke);
+        true
+    }
+    
+    /// Move the replay clock on by `dt` real seconds, drawing every stroke that came due;
+    /// the real ink comes back after the last one
+    fn advance_replay(&mut self, dt: f32) {
+        let Some(replay) = &mut self.replay else {
+            return;
         };
+        replay.clock += dt * replay.speed;
+        while self.replay.as_ref().is_some_and(|r| r.schedule.get(r.next).is_some_and(|&t| t <= r.clock)) {
+            self.replay_next_stroke();
+        }
+        if self.replay.as_ref().is_some_and(|r| r.next >= r.schedule.len()) {
+            self.stop_replay();
+            info!("Replay finished");
+        }
+    }
+    
+    /// End a replay and put the real ink back
+    fn stop_replay(&mut self) {
+        if let Some(replay) = self.replay.take() {
+            self.board.drawing_layer = replay.ink;
+            self.board.undo_stack = replay.undo_stack;
+            self.board.has_drawings = replay.has_drawings;
+            self.board.scratch_active = replay.scratch_active;
+        }
+    }
+    
+    /// Clear everything (Clear), just the ink (ClearDrawing) or just the background
+    /// (ClearBackground); the caller saves
+    fn clear_board(&mut self, action: Action) {
+        match action {
+            Action::ClearDrawing => self.board.clear_drawing(),
+            Action::ClearBackground => self.board.clear_background(),
+            _ => self.board.clear(),
+        }
+    }
+    
+    /// Bucket-fill the region under a board point with the current color
+    fn fill_at(&mut self, point: Point) {
+        let color = self.drawing_tool.current_color;
+        match self.board.flood_fill(point.x.floor() as i32, point.y.floor() as i32, color) {
+            Some(filled) => info!("Filled {} pixels", filled),
+            None => info!("Fill region too large (over {} pixels), nothing changed", MAX_FILL_PIXELS),
+        }
+    }
+    
+    /// Posterize the drawing layer (or just the selected rectangle) to the marker palette (one undo step)
+    fn quantize_drawing(&mut self) {
+        let mut palette: Vec<[u8; 4]> = self.markers.iter().map(|m| m.color).collect();
+        if palette.is_empty() {
+            // Markers failed to load - fall back to the corners of the RGB cube
+            palette = QUANTIZE_FALLBACK_PALETTE.to_vec();
+        }
         
-        if !is_image {
-            eprintln!("Dropped file is not a supported image format");
-            return Ok(());
+        // Floating pixels go down first so they're part of what gets quantized
+        self.commit_selection();
+        let region = self.selection.as_ref().map(|s| s.rect);
+        self.board.save_undo_state();
+        self.board.quantize_to_palette(&palette, region);
+    }
+    
+    /// Index of the marker with this name (a custom palette may not have it)
+    fn marker_named(&self, name: &str) -> Option<usize> {
+        self.markers.iter().position(|m| m.name == name)
+    }
+    
+    /// Switch the board to the next background preset
+    fn toggle_mode(&mut self) {
+        // Going between dark and light, a white pen becomes black and a black one white, so it stays visible
+        let flips = self.board.config.mode.is_dark() != self.board.config.mode.next().is_dark();
+        let selected = self.drawing_tool.selected_marker_index.and_then(|i| self.markers.get(i));
+        let swap_to = match selected.map(|m| m.name.as_str()) {
+            Some("white") if flips => self.marker_named("black"),
+            Some("black") if flips => self.marker_named("white"),
+            _ => None,
+        };
+        if let Some(marker) = swap_to.map(|i| (i, self.markers[i].color)) {
+            self.drawing_tool.selected_marker_index = Some(marker.0);
+            self.drawing_tool.current_color = marker.1;
         }
         
-        // Create posters directory if it doesn't exist
-        fs::create_dir_all("posters")?;
+        self.board.toggle_mode();
+    }
+    
+    /// Offset of a board point from a poster's top-left corner, if the point is inside the poster.
+    /// Wrap-aware: a poster crossing the seam is hit on both sides of x=0.
+    fn poster_local_point(&self, poster: &PinnedPoster, board_x: f32, board_y: f32) -> Option<Point> {
+        let board_width = self.board.config.width as f32;
+        let board_height = self.board.config.height as f32;
+        let half_w = poster.width as f32 * poster.scale_x / 2.0;
+        let half_h = poster.height as f32 * poster.scale_y / 2.0;
         
-        // Get filename and create destination path
-        let filename = path.file_name()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid file path"))?;
-        let dest_path = PathBuf::from("posters").join(filename);
+        // Offset from the poster's center, taking the shortest way around the seam(s)
+        let center_x = board_x - (poster.position.x + half_w);
+        let center_y = board_y - (poster.position.y + half_h);
+        let rel_x = (center_x + board_width / 2.0).rem_euclid(board_width) - board_width / 2.0;
+        let rel_y = if self.board.config.wrap_vertical {
+            (center_y + board_height / 2.0).rem_euclid(board_height) - board_height / 2.0
+        } else {
+            center_y
+        };
         
-        // Copy file to posters folder
-        fs::copy(path, &dest_path)?;
-        println!("Copied {} to posters folder", filename.to_string_lossy());
+        // Undo the rotation to test against the poster's own rectangle
+        let (sin, cos) = poster.rotation.sin_cos();
+        let u = cos * rel_x + sin * rel_y;
+        let v = -sin * rel_x + cos * rel_y;
         
-        // Load the image and add as poster at drop location
-        if let Ok(img) = image::open(&dest_path) {
-            let (width, height) = img.dimensions();
-            let rgba = img.to_rgba8();
-            let image_data = rgba.into_raw();
-            
-            // Convert screen coordinates to board coordinates
-            let board_x = self.board.viewport.position.x + (screen_x as f32 / self.board.viewport.zoom);
-            let board_y = self.board.viewport.position.y + (screen_y as f32 / self.board.viewport.zoom);
-            
-            let poster = PinnedPoster {
-                position: Point { x: board_x, y: board_y },
-                image_data,
-                width,
-                height,
-                name: filename.to_string_lossy().to_string(),
-                scale: 1.0,
-            };
-            
-            self.posters.push(poster);
-            self.save_posters()?;
-            
-            println!("Added poster '{}' at ({}, {})", filename.to_string_lossy(), board_x, board_y);
+        if u >= -half_w && u < half_w && v >= -half_h && v < half_h {
+            Some(Point { x: rel_x + half_w, y: rel_y + half_h })
         } else {
-            eprintln!("Failed to load image: {}", filename.to_string_lossy());
+            None
         }
-        
-        Ok(())
     }
     
-    /// Handle click on UI elements, returns true if click was on UI
-    fn handle_ui_click(&mut self, x: f64, y: f64, render_height: u32, render_width: u32) -> io::Result<(bool, bool)> {
-        // Returns (clicked_on_ui, mode_was_toggled)
-        
-        // Apply legend offset to y-coordinate for click detection
-        let y_offset = -(self.legend_offset as f64);
-        let adjusted_y = y - y_offset;
-        
-        // Check for click on legend collapse/expand area (top bar: x:10-290)
-        // When collapsed, check the actual visible screen position
-        // When expanded, check the adjusted position
-        let is_top_bar_click = if self.legend_collapsed {
-            // When collapsed, the visible hint bar is near y:0-20
-            x >= 10.0 && x <= 290.0 && y >= 0.0 && y <= 30.0
-        } else {
-            // When expanded, use adjusted coordinates
-            x >= 10.0 && x <= 290.0 && adjusted_y >= 0.0 && adjusted_y <= 20.0
+    /// Find the poster to grab, scale, rotate or delete at given board coordinates (returns
+    /// index, checks from top to bottom). Locked posters are passed over.
+    fn find_poster_at(&self, board_x: f32, board_y: f32) -> Option<usize> {
+        self.posters.iter().enumerate().rev()
+            .find(|(_, poster)| !poster.locked && self.poster_local_point(poster, board_x, board_y).is_some())
+            .map(|(i, _)| i)
+    }
+    
+    /// Topmost poster at given board coordinates, locked or not
+    fn find_any_poster_at(&self, board_x: f32, board_y: f32) -> Option<usize> {
+        // Check posters in reverse order (top to bottom)
+        self.posters.iter().enumerate().rev()
+            .find(|(_, poster)| self.poster_local_point(poster, board_x, board_y).is_some())
+            .map(|(i, _)| i)
+    }
+    
+    /// Say so when Ctrl+Click or Ctrl+Right Click lands on a locked poster and nothing else
+    fn locked_poster_hint(&mut self, board_x: f32, board_y: f32) {
+        if let Some(index) = self.find_any_poster_at(board_x, board_y).filter(|&i| self.posters[i].locked) {
+            let name = self.posters[index].name.clone();
+            self.show_toast(format!("Poster '{}' is locked", name));
+        }
+    }
+    
+    /// Lock or unlock a poster. Locking the poster being dragged drops it where it is.
+    fn toggle_poster_lock(&mut self, index: usize) -> io::Result<()> {
+        let locked = !self.posters[index].locked;
+        if locked && self.selected_poster_index == Some(index) {
+            if let Some(origin) = self.poster_drag_origin.take() {
+                let position = self.posters[index].position;
+                if position.x != origin.x || position.y != origin.y {
+                    self.record(UndoAction::PosterMove { index, position: origin });
+                }
+            }
+            self.selected_poster_index = None;
+            self.poster_drag_offset = None;
+        }
+        if locked && self.hovered_poster_index == Some(index) {
+            self.hovered_poster_index = None;
+        }
+        self.posters[index].locked = locked;
+        let name = &self.posters[index].name;
+        self.show_toast(format!("{} poster '{}'", if locked { "Locked" } else { "Unlocked" }, name));
+        self.save_posters()
+    }
+    
+    /// Move a poster to the top of the stack (drawn last, picked first), returns its new index
+    fn bring_to_front(&mut self, index: usize) -> usize {
+        let poster = self.posters.remove(index);
+        self.posters.push(poster);
+        self.restack_poster(index, self.posters.len() - 1)
+    }
+    
+    /// Move a poster to the bottom of the stack (drawn first, right above the board)
+    fn send_to_back(&mut self, index: usize) -> usize {
+        let poster = self.posters.remove(index);
+        self.posters.insert(0, poster);
+        self.restack_poster(index, 0)
+    }
+    
+    /// Keep the selected/hovered indices pointing at the same posters after a move from `from` to `to`
+    fn restack_poster(&mut self, from: usize, to: usize) -> usize {
+        let remap = |i: usize| {
+            if i == from {
+                to
+            } else if from < to && i > from && i <= to {
+                i - 1
+            } else if to < from && i >= to && i < from {
+                i + 1
+            } else {
+                i
+            }
         };
-        
-        if is_top_bar_click {
-            self.toggle_legend();
-            return Ok((true, false));
+        self.selected_poster_index = self.selected_poster_index.map(remap);
+        self.hovered_poster_index = self.hovered_poster_index.map(remap);
+        to
+    }
+    
+    /// Add an entry to the undo history. Drawing snapshots taken since the last call are entered
+    /// first so everything stays in order. Repeated wheel steps on one poster merge into one entry.
+    fn record(&mut self, action: UndoAction) {
+        self.record_drawing_undos();
+        let merges = match (self.history.last(), &action) {
+            (Some(UndoAction::PosterScale { index: a, .. }), UndoAction::PosterScale { index: b, .. }) => a == b,
+            (Some(UndoAction::PosterRotate { index: a, .. }), UndoAction::PosterRotate { index: b, .. }) => a == b,
+            _ => false,
+        };
+        if !merges {
+            self.history.push(action);
+        }
+        if self.history.len() > UNDO_HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+    }
+    
+    /// Enter drawing-layer snapshots the board took since we last looked
+    fn record_drawing_undos(&mut self) {
+        while self.drawing_undos_seen < self.board.undo_pushes {
+            self.drawing_undos_seen += 1;
+            self.history.push(UndoAction::DrawStroke);
+        }
+        let excess = self.history.len().saturating_sub(UNDO_HISTORY_LIMIT);
+        self.history.drain(..excess);
+    }
+    
+    /// Revert the most recent drawing or poster edit. Drawing entries whose snapshot was
+    /// already dropped (beyond the undo depth) are skipped. Returns false if nothing was undone.
+    fn undo(&mut self) -> bool {
+        self.record_drawing_undos();
+        while let Some(action) = self.history.pop() {
+            if let UndoAction::DrawStroke = action {
+                if self.board.undo() {
+                    // The rectangle (or floating pixels) may not match the restored ink
+                    self.selection = None;
+                    return true;
+                }
+                continue;
+            }
+            
+            // Poster indices shift below; drop any selection rather than point at the wrong one
+            self.selected_poster_index = None;
+            self.poster_drag_offset = None;
+            self.hovered_poster_index = None;
+            match action {
+                UndoAction::PosterAdd { index } if index < self.posters.len() => {
+                    self.posters.remove(index);
+                }
+                UndoAction::PosterDelete { index, poster } => {
+                    self.posters.insert(index.min(self.posters.len()), poster);
+                }
+                UndoAction::PosterMove { index, position } if index < self.posters.len() => {
+                    self.posters[index].position = position;
+                }
+                UndoAction::PosterScale { index, scale_x, scale_y } if index < self.posters.len() => {
+                    self.posters[index].scale_x = scale_x;
+                    self.posters[index].scale_y = scale_y;
+                }
+                UndoAction::PosterRotate { index, rotation } if index < self.posters.len() => {
+                    self.posters[index].rotation = rotation;
+                }
+                UndoAction::PosterRestack { from, to } if to < self.posters.len() => {
+                    let poster = self.posters.remove(to);
+                    self.posters.insert(from.min(self.posters.len()), poster);
+                }
+                _ => continue, // Out of range - history and posters disagree, nothing sensible to do
+            }
+            return true;
+        }
+        false
+    }
+    
+    /// Poster the stacking keys act on: the one being dragged, else the one under the cursor
+    fn stacking_target(&self, board_x: f32, board_y: f32) -> Option<usize> {
+        self.selected_poster_index.or_else(|| self.find_poster_at(board_x, board_y))
+    }
+    
+    /// Pin a copy of a poster on top and select it. Mid-drag the copy takes over the drag and
+    /// the original stays where it is; otherwise the copy lands slightly down and right.
+    fn duplicate_poster(&mut self, index: usize) -> io::Result<()> {
+        let mut copy = self.posters[index].clone();
+        if let Some(origin) = self.poster_drag_origin.take() {
+            let position = self.posters[index].position;
+            if position.x != origin.x || position.y != origin.y {
+                self.record(UndoAction::PosterMove { index, position: origin });
+            }
+            self.poster_drag_origin = Some(copy.position);
+        } else {
+            let offset = POSTER_DUPLICATE_OFFSET / self.board.viewport.zoom;
+            copy.position.x = (copy.position.x + offset).rem_euclid(self.board.config.width as f32);
+            copy.position.y += offset;
+        }
+        self.show_toast(format!("Duplicated poster '{}'", copy.name));
+        self.posters.push(copy);
+        self.selected_poster_index = Some(self.posters.len() - 1);
+        self.record(UndoAction::PosterAdd { index: self.posters.len() - 1 });
+        self.save_posters()
+    }
+    
+    /// Box around a poster as seen on the board: center and half extents (rotation included)
+    fn poster_box(poster: &PinnedPoster) -> (f32, f32, f32, f32) {
+        let (half_x, half_y) = poster.rotated_half_extents();
+        let center_x = poster.position.x + poster.width as f32 * poster.scale_x / 2.0;
+        let center_y = poster.position.y + poster.height as f32 * poster.scale_y / 2.0;
+        (center_x, center_y, half_x, half_y)
+    }
+    
+    /// Smart guides for the dragged poster: if one of its edges or its center comes within
+    /// POSTER_GUIDE_SNAP screen pixels of another poster's, move it to line up exactly (when
+    /// `snap` is set) and keep guides for every line that now matches
+    fn snap_poster_to_guides(&mut self, index: usize, snap: bool) {
+        self.poster_guides.clear();
+        if !snap {
+            return;
+        }
+        let (board_width, board_height) = (self.board.config.width as f32, self.board.config.height as f32);
+        let wrap_vertical = self.board.config.wrap_vertical;
+        let threshold = POSTER_GUIDE_SNAP / self.board.viewport.zoom;
+        let (center_x, center_y, half_x, half_y) = Self::poster_box(&self.posters[index]);
+        
+        // Other posters' boxes, taking the copy nearest the dragged poster across the seam(s)
+        let nearest = |d: f32, period: f32| (d + period / 2.0).rem_euclid(period) - period / 2.0;
+        let others: Vec<(f32, f32, f32, f32)> = self.posters.iter().enumerate()
+            .filter(|&(i, _)| i != index)
+            .map(|(_, poster)| {
+                let (x, y, hx, hy) = Self::poster_box(poster);
+                let y = if wrap_vertical { center_y + nearest(y - center_y, board_height) } else { y };
+                (center_x + nearest(x - center_x, board_width), y, hx, hy)
+            })
+            .collect();
+        
+        // Smallest move on each axis that lines up an edge or center with another poster's
+        let lines = |center: f32, half: f32| [center - half, center, center + half];
+        let closest = |mine: [f32; 3], theirs: &mut dyn Iterator<Item = [f32; 3]>| {
+            theirs.flat_map(|their| mine.into_iter().flat_map(move |a| their.into_iter().map(move |b| b - a)))
+                .filter(|d| d.abs() <= threshold)
+                .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .unwrap_or(0.0)
+        };
+        let shift_x = closest(lines(center_x, half_x), &mut others.iter().map(|o| lines(o.0, o.2)));
+        let shift_y = closest(lines(center_y, half_y), &mut others.iter().map(|o| lines(o.1, o.3)));
+        let poster = &mut self.posters[index];
+        poster.position.x = (poster.position.x + shift_x).rem_euclid(board_width);
+        poster.position.y += shift_y;
+        let (center_x, center_y) = (center_x + shift_x, center_y + shift_y);
+        
+        // A guide per matching line, long enough to reach both posters
+        for &(x, y, hx, hy) in &others {
+            for (vertical, mine, theirs) in [(true, lines(center_x, half_x), lines(x, hx)), (false, lines(center_y, half_y), lines(y, hy))] {
+                for at in theirs.into_iter().filter(|b| mine.iter().any(|a| (a - b).abs() < 0.01)) {
+                    let (from, to) = if vertical {
+                        ((center_y - half_y).min(y - hy), (center_y + half_y).max(y + hy))
+                    } else {
+                        ((center_x - half_x).min(x - hx), (center_x + half_x).max(x + hx))
+                    };
+                    match self.poster_guides.iter_mut().find(|guide| guide.vertical == vertical && (guide.at - at).abs() < 0.01) {
+                        Some(guide) => {
+                            guide.from = guide.from.min(from);
+                            guide.to = guide.to.max(to);
+                        }
+                        None => self.poster_guides.push(PosterGuide { vertical, at, from, to }),
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Update the Ctrl-hover poster highlight, returns true if it changed
+    fn update_hovered_poster(&mut self, board_x: f32, board_y: f32, ctrl_held: bool) -> bool {
+        let hovered = if ctrl_held { self.find_poster_at(board_x, board_y) } else { None };
+        let locks_shown = ctrl_held && self.posters.iter().any(|poster| poster.locked);
+        let changed = hovered != self.hovered_poster_index || locks_shown != self.show_poster_locks;
+        self.hovered_poster_index = hovered;
+        self.show_poster_locks = locks_shown;
+        changed
+    }
+    
+    /// Handle a key press while the hex color field is focused
+    fn handle_hex_key(&mut self, event: &KeyEvent) {
+        let Some(typed) = self.hex_input.as_mut() else {
+            return;
+        };
+        
+        match &event.logical_key {
+            Key::Named(NamedKey::Enter) => {
+                if let Some(color) = parse_hex_color(typed) {
+                    // A custom color isn't any marker
+                    info!("Color set to #{}", typed);
+                    self.hex_input = None;
+                    self.pick_color(color, None);
+                } else {
+                    let message = format!("Invalid hex color '{}' (use RGB, RRGGBB or RRGGBBAA)", typed);
+                    self.show_error(message);
+                }
+            }
+            Key::Named(NamedKey::Escape) => self.hex_input = None,
+            Key::Named(NamedKey::Backspace) => {
+                typed.pop();
+            }
+            _ => {
+                if let Some(text) = &event.text {
+                    for c in text.chars().filter(|c| c.is_ascii_hexdigit()) {
+                        if typed.len() < 8 {
+                            typed.push(c.to_ascii_uppercase());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Move the view's top-left corner to a board coordinate: x wraps, y is clamped
+    /// to the board (or wraps too on a toroidal board)
+    fn go_to(&mut self, x: i64, y: i64) {
+        let config = &self.board.config;
+        let x = x.rem_euclid(config.width as i64);
+        let y = if config.wrap_vertical {
+            y.rem_euclid(config.height as i64)
+        } else {
+            y.clamp(0, config.height as i64 - 1)
+        };
+        self.board.viewport.position = Point { x: x as f32, y: y as f32 };
+        self.board.viewport.pan_velocity = Point { x: 0.0, y: 0.0 };
+        self.board.viewport_dirty = true;
+        info!("Jumped to {},{}", x, y);
+    }
+    
+    /// Back to the board origin at 1:1 zoom
+    fn reset_view(&mut self) {
+        self.board.viewport.position = Point { x: 0.0, y: 0.0 };
+        self.board.viewport.zoom = 1.0;
+        self.board.viewport.pan_velocity = Point { x: 0.0, y: 0.0 };
+        self.board.viewport_dirty = true;
+    }
+    
+    /// Handle a key press while the go-to prompt is open ("x,y" or just "x" to keep the row)
+    fn handle_goto_key(&mut self, event: &KeyEvent) {
+        let Some(typed) = self.goto_input.as_mut() else {
+            return;
+        };
+        
+        match &event.logical_key {
+            Key::Named(NamedKey::Enter) => {
+                let mut parts = typed.split(',').map(|part| part.trim().parse::<i64>());
+                let current_y = self.board.viewport.position.y as i64;
+                match (parts.next(), parts.next().unwrap_or(Ok(current_y)), parts.next()) {
+                    (Some(Ok(x)), Ok(y), None) => {
+                        self.goto_input = None;
+                        self.go_to(x, y);
+                    }
+                    _ => {
+                        let message = format!("Invalid coordinate '{}' (use x,y)", typed);
+                        self.show_error(message);
+                    }
+                }
+            }
+            Key::Named(NamedKey::Escape) => self.goto_input = None,
+            Key::Named(NamedKey::Backspace) => {
+                typed.pop();
+            }
+            _ => {
+                if let Some(text) = &event.text {
+                    for c in text.chars().filter(|c| c.is_ascii_digit() || *c == '-' || *c == ',') {
+                        if typed.len() < 24 {
+                            typed.push(c);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Folder holding the named snapshots, next to the board file
+    fn snapshots_dir(&self) -> PathBuf {
+        self.board.file_path.parent().unwrap_or(Path::new("")).join("snapshots")
+    }
+    
+    /// Write the board, its ink and stroke log and the posters to `snapshots/<name>/`,
+    /// replacing an older snapshot of that name. The live board files aren't touched.
+    fn save_snapshot(&mut self, name: &str) -> io::Result<()> {
+        let dir = self.snapshots_dir().join(name);
+        fs::create_dir_all(&dir)?;
+        self.board.snapshot_job(&dir.join("board.data")).run()?;
+        let json = serde_json::to_vec(&self.posters).map_err(io::Error::other)?;
+        write_file_atomically(&dir.join("posters.json"), json.len() as u64, false, |out| out.write_all(&json))
+    }
+    
+    /// Replace the board contents and posters with a snapshot. The live files keep the
+    /// old state until the next save; undo history starts over.
+    fn load_snapshot(&mut self, name: &str) -> io::Result<()> {
+        let dir = self.snapshots_dir().join(name);
+        let board_path = dir.join("board.data");
+        // Board::new would start a fresh board over a missing or headerless file
+        if fs::metadata(&board_path)?.len() <= HEADER_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a board file", board_path.display())));
+        }
+        let posters_path = dir.join("posters.json");
+        let posters: Vec<PinnedPoster> = if posters_path.exists() {
+            serde_json::from_slice(&fs::read(&posters_path)?).map_err(io::Error::other)?
+        } else {
+            Vec::new()
+        };
+        
+        let config = &self.board.config;
+        let snapshot = Board::new(config.width, config.height, config.mode, config.paper, config.wrap_vertical, &board_path)?;
+        if (snapshot.config.width, snapshot.config.height) != (config.width, config.height) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "snapshot '{}' is {}x{}, this board is {}x{}", name,
+                snapshot.config.width, snapshot.config.height, config.width, config.height
+            )));
+        }
+        self.board.adopt(snapshot)?;
+        
+        self.posters = posters;
+        self.selected_poster_index = None;
+        self.poster_drag_offset = None;
+        self.hovered_poster_index = None;
+        self.history.clear();
+        self.drawing_undos_seen = self.board.undo_pushes;
+        Ok(())
+    }
+    
+    /// Saved snapshots with their modification times, newest first
+    fn list_snapshots(&self) -> Vec<(String, std::time::SystemTime)> {
+        let Ok(entries) = fs::read_dir(self.snapshots_dir()) else {
+            return Vec::new();
+        };
+        let mut snapshots: Vec<(String, std::time::SystemTime)> = entries.flatten()
+            .filter_map(|entry| {
+                let modified = fs::metadata(entry.path().join("board.data")).and_then(|m| m.modified()).ok()?;
+                Some((entry.file_name().to_str()?.to_string(), modified))
+            })
+            .collect();
+        snapshots.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+        snapshots
+    }
+    
+    /// Handle a key press while the save-snapshot prompt is open. An empty name picks the
+    /// next free `snapshot-N`.
+    fn handle_snapshot_input_key(&mut self, event: &KeyEvent) {
+        let Some(typed) = self.snapshot_input.as_mut() else {
+            return;
+        };
+        
+        match &event.logical_key {
+            Key::Named(NamedKey::Enter) => {
+                let mut name = std::mem::take(typed);
+                self.snapshot_input = None;
+                if name.is_empty() {
+                    let taken = self.list_snapshots();
+                    let n = (1..).find(|n| !taken.iter().any(|(existing, _)| *existing == format!("snapshot-{}", n))).unwrap_or(1);
+                    name = format!("snapshot-{}", n);
+                }
+                match self.save_snapshot(&name) {
+                    Ok(()) => {
+                        info!("Saved snapshot '{}' to {}", name, self.snapshots_dir().join(&name).display());
+                        self.show_toast(format!("Snapshot '{}' saved", name));
+                    }
+                    Err(e) => self.show_error(format!("Snapshot error: {}", e)),
+                }
+            }
+            Key::Named(NamedKey::Escape) => self.snapshot_input = None,
+            Key::Named(NamedKey::Backspace) => {
+                typed.pop();
+            }
+            _ => {
+                // Names become folder names: keep to characters that are safe everywhere
+                if let Some(text) = &event.text {
+                    for c in text.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_') {
+                        if typed.len() < 32 {
+                            typed.push(c);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Handle a key press while the snapshot list is open: arrows pick a row, Enter restores it,
+    /// Escape drops the pick (or closes the list). Returns true if a snapshot was restored.
+    fn handle_snapshot_list_key(&mut self, event: &KeyEvent) -> bool {
+        let Some(list) = &self.snapshot_list else {
+            return false;
+        };
+        
+        match &event.logical_key {
+            Key::Named(NamedKey::ArrowDown) if !list.is_empty() => {
+                self.snapshot_selected = Some(self.snapshot_selected.map_or(0, |i| (i + 1).min(list.len() - 1)));
+            }
+            Key::Named(NamedKey::ArrowUp) if !list.is_empty() => {
+                self.snapshot_selected = Some(self.snapshot_selected.map_or(0, |i| i.saturating_sub(1)));
+            }
+            Key::Named(NamedKey::Enter) => {
+                let Some(name) = self.snapshot_selected.and_then(|i| list.get(i)).map(|(name, _)| name.clone()) else {
+                    return false;
+                };
+                self.snapshot_list = None;
+                self.snapshot_selected = None;
+                match self.load_snapshot(&name) {
+                    Ok(()) => {
+                        info!("Restored snapshot '{}' (the board file is updated on the next save)", name);
+                        self.show_toast(format!("Restored '{}'", name));
+                        return true;
+                    }
+                    Err(e) => self.show_error(format!("Restore error: {}", e)),
+                }
+            }
+            Key::Named(NamedKey::Escape) => {
+                if self.snapshot_selected.is_some() {
+                    self.snapshot_selected = None;
+                } else {
+                    self.snapshot_list = None;
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+    
+    /// Text tool glyph scale (font pixels per board pixel), follows the brush size
+    fn text_scale(&self) -> u32 {
+        self.drawing_tool.brush_size.clamp(1, 32)
+    }
+    
+    /// Place the text cursor at a board point (stamps any label still being typed first)
+    fn start_text(&mut self, point: Point) {
+        self.commit_text();
+        self.text_anchor = Some(point);
+    }
+    
+    /// Stamp the pending label onto the board as one undo step. Returns true if anything was drawn.
+    fn commit_text(&mut self) -> bool {
+        let Some(anchor) = self.text_anchor.take() else {
+            return false;
+        };
+        let text = std::mem::take(&mut self.pending_text);
+        if text.is_empty() {
+            return false;
+        }
+        
+        if !self.board.scratch_active {
+            self.board.save_undo_state();
+        }
+        let scale = self.text_scale();
+        self.board.stamp_text(anchor.x.floor() as i32, anchor.y.floor() as i32, &text, self.drawing_tool.current_color, scale);
+        true
+    }
+    
+    /// Handle a key press while a label is being typed. Returns true if the label was committed.
+    fn handle_text_key(&mut self, event: &KeyEvent) -> bool {
+        match &event.logical_key {
+            Key::Named(NamedKey::Enter) => return self.commit_text(),
+            Key::Named(NamedKey::Escape) => {
+                self.text_anchor = None;
+                self.pending_text.clear();
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.pending_text.pop();
+            }
+            _ => {
+                if let Some(text) = &event.text {
+                    self.pending_text.extend(text.chars().filter(|c| !c.is_control()));
+                }
+            }
+        }
+        false
+    }
+    
+    /// Live preview of the label being typed, with a caret after the last glyph
+    fn render_text_preview(&self, frame: &mut [u8], width: u32, height: u32) {
+        let Some(anchor) = self.text_anchor else {
+            return;
+        };
+        
+        let zoom = self.board.viewport.zoom;
+        let board_width = self.board.config.width as f32;
+        let view = self.board.viewport.position;
+        let scale = self.text_scale() as f32;
+        let color = self.drawing_tool.current_color;
+        
+        // Fill one font pixel (scale x scale board pixels) at font offset (fx, fy) from the anchor
+        let mut fill = |fx: f32, fy: f32| {
+            let bx = (anchor.x.floor() + fx * scale - view.x).rem_euclid(board_width);
+            let sx0 = (bx * zoom) as i32;
+            let sy0 = ((anchor.y.floor() + fy * scale - view.y) * zoom) as i32;
+            let size = (scale * zoom).ceil().max(1.0) as i32;
+            for sy in sy0.max(0)..(sy0 + size).min(height as i32) {
+                for sx in sx0.max(0)..(sx0 + size).min(width as i32) {
+                    let offset = ((sy as u32 * width + sx as u32) * 4) as usize;
+                    frame[offset..offset + 4].copy_from_slice(&color);
+                }
+            }
+        };
+        
+        let mut glyph_count = 0;
+        for (i, ch) in self.pending_text.chars().enumerate() {
+            for (row, &bits) in glyph(ch).iter().enumerate() {
+                for col in 0..5 {
+                    if (bits >> (4 - col)) & 1 == 1 {
+                        fill((i * 6 + col) as f32, row as f32);
+                    }
+                }
+            }
+            glyph_count = i + 1;
+        }
+        
+        // Caret: a 1-font-pixel bar where the next glyph goes
+        for row in 0..7 {
+            fill((glyph_count * 6) as f32, row as f32);
+        }
+    }
+    
+    /// Toggle legend collapse state
+    fn toggle_legend(&mut self) {
+        self.legend_collapsed = !self.legend_collapsed;
+    }
+    
+    /// Legend origin for a window of this size, pulled back on screen if the window shrank
+    fn legend_origin(&self, width: u32, height: u32) -> (u32, i32) {
+        let max_x = width.saturating_sub(LEGEND_WIDTH) as f32;
+        let max_y = height.saturating_sub(LEGEND_HEIGHT) as f32;
+        (self.legend_origin.x.clamp(0.0, max_x) as u32, self.legend_origin.y.clamp(0.0, max_y) as i32)
+    }
+    
+    /// Screen point in legend coordinates (what handle_ui_click hit-tests against),
+    /// following the collapse animation
+    fn legend_point(&self, x: f64, y: f64, width: u32, height: u32) -> (f64, f64) {
+        let (origin_x, origin_y) = self.legend_origin(width, height);
+        (x - origin_x as f64, y - origin_y as f64 + self.legend_offset as f64)
+    }
+    
+    /// Whether a screen point is on the brush size, opacity or zoom slider
+    fn on_legend_slider(&self, x: f64, y: f64, width: u32, height: u32) -> bool {
+        let (lx, ly) = self.legend_point(x, y, width, height);
+        !self.legend_collapsed && (20.0..=280.0).contains(&lx) && ((150.0..=165.0).contains(&ly) || Self::on_zoom_slider(lx, ly))
+    }
+    
+    /// Whether a legend-coordinate point is on the zoom slider (x:100-280, y:262-276)
+    fn on_zoom_slider(lx: f64, ly: f64) -> bool {
+        (ZOOM_SLIDER_LEFT as f64..=(ZOOM_SLIDER_LEFT + ZOOM_SLIDER_WIDTH) as f64).contains(&lx) && (262.0..=276.0).contains(&ly)
+    }
+    
+    /// Slider position (0.0-1.0) of a zoom level; logarithmic, so every doubling gets the same travel
+    fn zoom_slider_fraction(&self, zoom: f32) -> f32 {
+        let (min, max) = self.zoom_limits;
+        if max <= min {
+            return 0.0;
+        }
+        ((zoom / min).ln() / (max / min).ln()).clamp(0.0, 1.0)
+    }
+    
+    /// Zoom to the level at a slider position, keeping the board point at the window center in place
+    fn set_zoom_from_slider(&mut self, fraction: f32, width: u32, height: u32) {
+        let (min, max) = self.zoom_limits;
+        let zoom = (min * (max / min).powf(fraction.clamp(0.0, 1.0))).clamp(min, max);
+        self.board.zoom_at(zoom, width as f64 / 2.0, height as f64 / 2.0, height);
+        self.board.viewport.pan_velocity = Point { x: 0.0, y: 0.0 };
+    }
+    
+    /// Move the legend while its top bar is dragged, returns true while a press is active
+    fn drag_legend(&mut self, x: f64, y: f64, width: u32, height: u32) -> bool {
+        let Some(drag) = self.legend_drag.as_mut() else {
+            return false;
+        };
+        let (dx, dy) = (x - drag.start.0, y - drag.start.1);
+        drag.moved |= dx.abs() > LEGEND_DRAG_THRESHOLD || dy.abs() > LEGEND_DRAG_THRESHOLD;
+        if drag.moved {
+            let max_x = width.saturating_sub(LEGEND_WIDTH) as f32;
+            let max_y = height.saturating_sub(LEGEND_HEIGHT) as f32;
+            self.legend_origin = Point {
+                x: (drag.origin.x + dx as f32).clamp(0.0, max_x),
+                y: (drag.origin.y + dy as f32).clamp(0.0, max_y),
+            };
+        }
+        true
+    }
+    
+    /// Scrollbar tracks as (x, y, length): the horizontal one just above the status bar, the
+    /// vertical one down the right edge, stopping short of the minimap
+    fn scrollbar_tracks(&self, width: u32, height: u32) -> ((u32, u32, u32), (u32, u32, u32)) {
+        let (_, minimap_y, _, _) = Self::minimap_rect(width, height, &self.board.config);
+        let horizontal = (0, height.saturating_sub(STATUS_BAR_HEIGHT + SCROLLBAR_SIZE), width.saturating_sub(SCROLLBAR_SIZE));
+        let vertical = (width.saturating_sub(SCROLLBAR_SIZE), 0, minimap_y.saturating_sub(4));
+        (horizontal, vertical)
+    }
+    
+    /// Thumb (start, length) along a track of `track` pixels: the length is the visible share
+    /// of the board, the start where the view is. x wraps, and so does y on a toroidal board.
+    fn scrollbar_thumb(&self, vertical: bool, track: u32, width: u32, height: u32) -> (f32, f32) {
+        let zoom = self.board.viewport.zoom;
+        let track = track as f32;
+        let (period, visible, position, wraps) = if vertical {
+            (self.board.config.height as f32, height as f32 / zoom, self.board.viewport.position.y, self.board.config.wrap_vertical)
+        } else {
+            (self.board.config.width as f32, width as f32 / zoom, self.board.viewport.position.x, true)
+        };
+        let length = (visible / period * track).clamp(SCROLLBAR_MIN_THUMB.min(track), track);
+        let start = if wraps {
+            position.rem_euclid(period) / period * track
+        } else {
+            (position / period * track).clamp(0.0, track - length)
+        };
+        (start, length)
+    }
+    
+    /// Press on a scrollbar: grab the thumb, or center it on the press and grab it there.
+    /// Returns true if the press was on a scrollbar.
+    fn press_scrollbar(&mut self, x: f64, y: f64, width: u32, height: u32) -> bool {
+        if !self.show_scrollbars {
+            return false;
+        }
+        let ((hx, hy, h_len), (vx, vy, v_len)) = self.scrollbar_tracks(width, height);
+        let (vertical, along, track) = if (hx as f64..(hx + h_len) as f64).contains(&x) && (hy as f64..(hy + SCROLLBAR_SIZE) as f64).contains(&y) {
+            (false, (x - hx as f64) as f32, h_len)
+        } else if (vx as f64..(vx + SCROLLBAR_SIZE) as f64).contains(&x) && (vy as f64..(vy + v_len) as f64).contains(&y) {
+            (true, (y - vy as f64) as f32, v_len)
+        } else {
+            return false;
+        };
+        let (start, length) = self.scrollbar_thumb(vertical, track, width, height);
+        // The horizontal thumb can wrap past the end of its track
+        let offset = if vertical { along - start } else { (along - start).rem_euclid(track as f32) };
+        let grab = if (0.0..length).contains(&offset) { offset } else { length / 2.0 };
+        self.scrollbar_drag = Some(ScrollbarDrag { vertical, grab });
+        self.drag_scrollbar(x, y, width, height);
+        true
+    }
+    
+    /// Move the view while a scrollbar thumb is dragged, returns true while a press is active
+    fn drag_scrollbar(&mut self, x: f64, y: f64, width: u32, height: u32) -> bool {
+        let Some(drag) = &self.scrollbar_drag else {
+            return false;
+        };
+        let ((hx, _, h_len), (_, vy, v_len)) = self.scrollbar_tracks(width, height);
+        let viewport = &mut self.board.viewport;
+        if drag.vertical {
+            let start = (y - vy as f64) as f32 - drag.grab;
+            viewport.position.y = start / v_len.max(1) as f32 * self.board.config.height as f32;
+        } else {
+            let start = (x - hx as f64) as f32 - drag.grab;
+            let board_width = self.board.config.width as f32;
+            viewport.position.x = (start / h_len.max(1) as f32 * board_width).rem_euclid(board_width);
+        }
+        viewport.pan_velocity = Point { x: 0.0, y: 0.0 };
+        true
+    }
+    
+    fn end_scrollbar_drag(&mut self) {
+        self.scrollbar_drag = None;
+    }
+    
+    /// Draw both scrollbars: a faint track with the thumb on top (the horizontal thumb wraps)
+    fn render_scrollbars(&self, frame: &mut [u8], width: u32, height: u32, text_color: [u8; 4], bg_color: [u8; 4]) {
+        if !self.show_scrollbars {
+            return;
+        }
+        let thumb_color = [text_color[0], text_color[1], text_color[2], if self.scrollbar_drag.is_some() { 200 } else { 120 }];
+        let ((hx, hy, h_len), (vx, vy, v_len)) = self.scrollbar_tracks(width, height);
+        for (vertical, (x0, y0, track)) in [(false, (hx, hy, h_len)), (true, (vx, vy, v_len))] {
+            let (start, length) = self.scrollbar_thumb(vertical, track, width, height);
+            for along in 0..track {
+                let offset_in_thumb = if vertical { along as f32 - start } else { (along as f32 - start).rem_euclid(track as f32) };
+                let color = if (0.0..length).contains(&offset_in_thumb) { thumb_color } else { bg_color };
+                for across in 0..SCROLLBAR_SIZE {
+                    let (x, y) = if vertical { (x0 + across, y0 + along) } else { (x0 + along, y0 + across) };
+                    if x < width && y < height {
+                        let offset = ((y * width + x) * 4) as usize;
+                        blend_pixel(&mut frame[offset..offset + 4], color);
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Finish a press on the legend's top bar: a click toggles collapse, a drag saves the new
+    /// position to config.toml
+    fn end_legend_drag(&mut self) {
+        let Some(drag) = self.legend_drag.take() else {
+            return;
+        };
+        if !drag.moved {
+            self.toggle_legend();
+        } else if let Err(e) = save_legend_position(Path::new(CONFIG_FILE), self.legend_origin) {
+            error!("Could not save legend position: {}", e);
+        }
+    }
+    
+    /// Update legend animation (smooth slide in/out)
+    /// Returns true while the panel is still sliding (so another frame is needed)
+    fn update_legend_animation(&mut self) -> bool {
+        let target_offset = if self.legend_collapsed { 270.0 } else { 0.0 };
+        let speed = 15.0; // pixels per frame
+        
+        if (self.legend_offset - target_offset).abs() > 0.5 {
+            if self.legend_offset < target_offset {
+                self.legend_offset = (self.legend_offset + speed).min(target_offset);
+            } else {
+                self.legend_offset = (self.legend_offset - speed).max(target_offset);
+            }
+        } else {
+            self.legend_offset = target_offset;
+        }
+        self.legend_offset != target_offset
+    }
+    
+    /// Remember the view, brush size, eraser, pen color and ink blend mode so the next start lands back here
+    fn save_session(&self) -> io::Result<()> {
+        let session = Session {
+            position: self.board.viewport.position,
+            zoom: self.board.viewport.zoom,
+            brush_size: self.drawing_tool.brush_size,
+            eraser_mode: self.drawing_tool.eraser_mode,
+            color: self.drawing_tool.current_color,
+            ink_blend: self.board.ink_blend,
+        };
+        let json = serde_json::to_vec_pretty(&session).map_err(io::Error::other)?;
+        write_file_atomically(&self.board.session_path(), json.len() as u64, false, |out| out.write_all(&json))
+    }
+    
+    /// Pick up where the board was left (see save_session); a damaged or out-of-range
+    /// session file is ignored and the usual defaults apply
+    fn restore_session(&mut self) {
+        let path = self.board.session_path();
+        if !path.exists() {
+            return;
+        }
+        let session: Session = match fs::read(&path).map_err(|e| e.to_string()).and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string())) {
+            Ok(session) => session,
+            Err(e) => {
+                warn!("ignoring {} ({}); it will be replaced on exit", path.display(), e);
+                return;
+            }
+        };
+        
+        let (width, height) = (self.board.config.width as f32, self.board.config.height as f32);
+        let Point { x, y } = session.position;
+        if ZOOM_RANGE.contains(&session.zoom) && (0.0..width).contains(&x) && (-height..height).contains(&y) {
+            self.board.viewport.position = session.position;
+            self.board.viewport.zoom = session.zoom;
+        }
+        self.drawing_tool.brush_size = session.brush_size.clamp(1, 100);
+        self.drawing_tool.eraser_mode = session.eraser_mode;
+        self.drawing_tool.current_color = session.color;
+        self.board.ink_blend = session.ink_blend;
+        self.drawing_tool.selected_marker_index = self.markers.iter().position(|m| m.color == session.color);
+    }
+    
+    /// Save posters to JSON file
+    fn save_posters(&self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.posters)
+            .map_err(io::Error::other)?;
+        std::fs::write("posters.json", json)?;
+        Ok(())
+    }
+    
+    /// Load posters from JSON file
+    fn load_posters(&mut self) -> io::Result<()> {
+        if Path::new("posters.json").exists() {
+            let json = std::fs::read_to_string("posters.json")?;
+            self.posters = serde_json::from_str(&json)
+                .map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+    
+    /// Handle dropped file - copy to posters folder and add as poster at drop location
+    fn handle_dropped_file(&mut self, path: &PathBuf, screen_x: f64, screen_y: f64) -> io::Result<()> {
+        // Check if file is an image
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        
+        let is_image = matches!(extension.as_deref(), Some("png") | Some("jpg") | Some("jpeg") | Some("bmp") | Some("gif"));
+        
+        if !is_image {
+            self.show_error("Dropped file is not a supported image format".to_string());
+            return Ok(());
+        }
+        
+        // Create posters directory if it doesn't exist
+        fs::create_dir_all(&self.config.posters_dir)?;
+        
+        // Get filename and create destination path
+        let filename = path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid file path"))?;
+        let dest_path = self.config.posters_dir.join(filename);
+        let name = filename.to_string_lossy().to_string();
+        
+        // The header alone gives the size for the placeholder; copying and decoding happen
+        // on a worker thread so a big photo doesn't stall drawing
+        let size = match image::image_dimensions(path) {
+            Ok(size) => size,
+            Err(e) => {
+                self.show_error(format!("Failed to load image {}: {}", name, e));
+                return Ok(());
+            }
+        };
+        let position = Point {
+            x: self.board.viewport.position.x + screen_x as f32 / self.board.viewport.zoom,
+            y: self.board.viewport.position.y + screen_y as f32 / self.board.viewport.zoom,
+        };
+        let (source, poster_name) = (path.clone(), name.clone());
+        let handle = std::thread::spawn(move || {
+            fs::copy(&source, &dest_path)?;
+            PinnedPoster::load(&dest_path, poster_name)
+        });
+        info!("Loading {} ({}x{})...", name, size.0, size.1);
+        self.poster_loads.push(PosterLoad { name, drop_at: Some((position, size)), handle });
+        Ok(())
+    }
+    
+    /// Decode a poster from the menu on a worker thread; it becomes placing_poster when done
+    fn load_poster_for_placing(&mut self, name: String, path: PathBuf) {
+        let poster_name = name.clone();
+        let handle = std::thread::spawn(move || PinnedPoster::load(&path, poster_name));
+        self.show_toast(format!("Loading {}...", name));
+        self.poster_loads.push(PosterLoad { name, drop_at: None, handle });
+    }
+    
+    /// Pick up posters whose decoding has finished: dropped files are pinned where they were
+    /// dropped, menu picks wait for a click. Returns true if a poster was pinned.
+    fn poll_poster_loads(&mut self) -> bool {
+        let mut pinned = false;
+        let mut i = 0;
+        while i < self.poster_loads.len() {
+            if !self.poster_loads[i].handle.is_finished() {
+                i += 1;
+                continue;
+            }
+            let load = self.poster_loads.remove(i);
+            match load.handle.join() {
+                Ok(Ok(poster)) => match load.drop_at {
+                    Some((position, _)) => {
+                        info!("Copied {} to posters folder", load.name);
+                        self.refresh_available_posters();
+                        if let Err(e) = self.pin_poster(poster, position) {
+                            self.show_error(format!("Error saving posters: {}", e));
+                        }
+                        pinned = true;
+                    }
+                    None => {
+                        self.show_toast(format!("Click to place {}", load.name));
+                        self.placing_poster = Some(poster);
+                    }
+                },
+                Ok(Err(e)) => self.show_error(format!("Failed to load image {}: {}", load.name, e)),
+                Err(_) => self.show_error(format!("Failed to load image {}: the loader crashed", load.name)),
+            }
+        }
+        pinned
+    }
+    
+    /// Open or close the poster picker. Opening starts decoding the previews it doesn't have
+    /// yet on a worker thread; they show up as they arrive (see poll_thumbnails).
+    fn toggle_poster_picker(&mut self) {
+        self.show_poster_picker = !self.show_poster_picker;
+        self.poster_file_prompt = None;
+        if self.show_poster_picker && self.thumbnail_loads.is_none() {
+            self.load_thumbnails();
+        }
+    }
+    
+    /// Decode previews for the listed posters that have none, replacing any worker still running
+    fn load_thumbnails(&mut self) {
+        let paths: Vec<String> = self.available_posters.iter()
+            .map(|(_, path)| path.clone())
+            .filter(|path| !self.poster_thumbnails.contains_key(path))
+            .collect();
+        if paths.is_empty() {
+            self.thumbnail_loads = None;
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for path in paths {
+                // Unreadable files just go without a preview; picking one reports the error
+                let Ok(image) = image::open(&path) else {
+                    continue;
+                };
+                let preview = image.thumbnail(PICKER_THUMB_SIZE, PICKER_THUMB_SIZE).to_rgba8();
+                let thumbnail = PosterThumbnail { width: preview.width(), height: preview.height(), pixels: preview.into_raw() };
+                if sender.send((path, thumbnail)).is_err() {
+                    return;
+                }
+            }
+        });
+        self.thumbnail_loads = Some(receiver);
+    }
+    
+    /// Re-read the posters folder after its files changed, keeping the scroll in range
+    fn refresh_available_posters(&mut self) {
+        self.available_posters = scan_posters(&self.config.posters_dir);
+        self.scroll_poster_picker(0);
+        if self.show_poster_picker {
+            self.load_thumbnails();
+        }
+    }
+    
+    /// Run a picker row button on the poster file at `index` in available_posters
+    fn poster_file_action(&mut self, index: usize, action: PosterFileAction) {
+        let Some((name, path)) = self.available_posters.get(index).cloned() else {
+            return;
+        };
+        match action {
+            PosterFileAction::Reveal => {
+                if let Err(e) = reveal_in_file_manager(Path::new(&path)) {
+                    self.show_error(format!("Could not open the file manager: {}", e));
+                }
+            }
+            PosterFileAction::Rename => {
+                // Start from the old name without its extension, which is kept
+                let stem = Path::new(&name).file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+                self.poster_file_prompt = Some(PosterFilePrompt::Rename { index, typed: stem });
+            }
+            PosterFileAction::Delete => self.poster_file_prompt = Some(PosterFilePrompt::Delete(index)),
+        }
+    }
+    
+    /// Handle a key press while a picker rename or delete is waiting: the rename takes a file
+    /// name and Enter, the delete takes Y (anything else cancels)
+    fn handle_poster_file_key(&mut self, event: &KeyEvent) {
+        match self.poster_file_prompt.take() {
+            Some(PosterFilePrompt::Delete(index)) => {
+                if matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("y")) {
+                    self.delete_poster_file(index);
+                } else {
+                    self.show_toast("Delete cancelled".to_string());
+                }
+            }
+            Some(PosterFilePrompt::Rename { index, mut typed }) => match &event.logical_key {
+                Key::Named(NamedKey::Enter) => self.rename_poster_file(index, &typed),
+                Key::Named(NamedKey::Escape) => {}
+                Key::Named(NamedKey::Backspace) => {
+                    typed.pop();
+                    self.poster_file_prompt = Some(PosterFilePrompt::Rename { index, typed });
+                }
+                _ => {
+                    // Keep to characters that are safe in file names everywhere
+                    if let Some(text) = &event.text {
+                        for c in text.chars().filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' ')) {
+                            if typed.len() < 64 {
+                                typed.push(c);
+                            }
+                        }
+                    }
+                    self.poster_file_prompt = Some(PosterFilePrompt::Rename { index, typed });
+                }
+            },
+            None => {}
+        }
+    }
+    
+    /// Give a poster file a new name (its extension is kept). Pinned posters carry their own
+    /// copy of the image, so they aren't affected.
+    fn rename_poster_file(&mut self, index: usize, new_stem: &str) {
+        let Some((name, path)) = self.available_posters.get(index).cloned() else {
+            return;
+        };
+        let new_stem = new_stem.trim();
+        if new_stem.is_empty() || new_stem.starts_with('.') {
+            self.show_error("Enter a file name to rename the poster to".to_string());
+            return;
+        }
+        let old_path = PathBuf::from(&path);
+        let extension = old_path.extension().map_or(String::new(), |ext| format!(".{}", ext.to_string_lossy()));
+        let new_name = format!("{}{}", new_stem, extension);
+        let new_path = old_path.with_file_name(&new_name);
+        if new_name == name {
+            return;
+        }
+        if new_path.exists() {
+            self.show_error(format!("{} already exists", new_name));
+            return;
+        }
+        match fs::rename(&old_path, &new_path) {
+            Ok(()) => {
+                if let Some(thumbnail) = self.poster_thumbnails.remove(&path) {
+                    self.poster_thumbnails.insert(new_path.to_string_lossy().to_string(), thumbnail);
+                }
+                self.show_toast(format!("Renamed {} to {}", name, new_name));
+            }
+            Err(e) => self.show_error(format!("Could not rename {}: {}", name, e)),
+        }
+        self.refresh_available_posters();
+    }
+    
+    /// Remove a poster file from the posters folder. Pinned copies of it stay on the board.
+    fn delete_poster_file(&mut self, index: usize) {
+        let Some((name, path)) = self.available_posters.get(index).cloned() else {
+            return;
+        };
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                self.poster_thumbnails.remove(&path);
+                self.show_toast(format!("Deleted {}", name));
+            }
+            Err(e) => self.show_error(format!("Could not delete {}: {}", name, e)),
+        }
+        self.refresh_available_posters();
+    }
+    
+    /// Take in the picker previews decoded so far. Returns true if any arrived.
+    fn poll_thumbnails(&mut self) -> bool {
+        let Some(receiver) = &self.thumbnail_loads else {
+            return false;
+        };
+        let mut arrived = false;
+        loop {
+            match receiver.try_recv() {
+                Ok((path, thumbnail)) => {
+                    self.poster_thumbnails.insert(path, thumbnail);
+                    arrived = true;
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.thumbnail_loads = None;
+                    break;
+                }
+            }
+        }
+        arrived
+    }
+    
+    /// Scroll the poster picker by whole rows, keeping the last page full
+    fn scroll_poster_picker(&mut self, rows: isize) {
+        let max_scroll = self.available_posters.len().saturating_sub(PICKER_VISIBLE_ROWS);
+        self.poster_picker_scroll = self.poster_picker_scroll.saturating_add_signed(rows).min(max_scroll);
+    }
+    
+    /// Ctrl+V: pin the image on the system clipboard as a poster at the cursor
+    fn handle_paste(&mut self, screen_x: f64, screen_y: f64) -> io::Result<()> {
+        let image = match self.clipboard().and_then(|clipboard| clipboard.get_image()) {
+            Ok(image) => image,
+            Err(arboard::Error::ContentNotAvailable) => {
+                self.show_toast("No image on the clipboard".to_string());
+                return Ok(());
+            }
+            Err(e) => {
+                self.show_error(format!("Failed to read clipboard: {}", e));
+                return Ok(());
+            }
+        };
+        
+        // arboard hands out tightly packed RGBA rows
+        let (width, height) = (image.width as u32, image.height as u32);
+        if width == 0 || height == 0 || image.bytes.len() != image.width * image.height * 4 {
+            error!("Clipboard image has an unexpected size ({}x{}, {} bytes)", width, height, image.bytes.len());
+            return Ok(());
+        }
+        
+        self.add_poster_at(PinnedPoster::new(image.bytes.into_owned(), width, height, "clipboard".to_string()), screen_x, screen_y)
+    }
+    
+    /// The system clipboard, opened on first use
+    fn clipboard(&mut self) -> Result<&mut arboard::Clipboard, arboard::Error> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(arboard::Clipboard::new()?);
+        }
+        Ok(self.clipboard.as_mut().unwrap())
+    }
+    
+    /// Copy to the clipboard as an image: the poster under the screen point if there is one,
+    /// otherwise the composited viewport (board, posters and ink, without the UI)
+    fn copy_to_clipboard(&mut self, screen_x: f64, screen_y: f64, render_width: u32, render_height: u32) {
+        let board_x = self.board.viewport.position.x + screen_x as f32 / self.board.viewport.zoom;
+        let board_y = self.board.viewport.position.y + screen_y as f32 / self.board.viewport.zoom;
+        
+        let (width, height, bytes, what) = if let Some(poster) = self.find_any_poster_at(board_x, board_y).map(|i| &self.posters[i]) {
+            (poster.width, poster.height, poster.image_data.clone(), format!("poster '{}'", poster.name))
+        } else {
+            let image = self.board.composite_viewport(render_width, render_height, &self.posters);
+            (render_width, render_height, image, "view".to_string())
+        };
+        
+        let image = arboard::ImageData {
+            width: width as usize,
+            height: height as usize,
+            bytes: bytes.into(),
+        };
+        match self.clipboard().and_then(|clipboard| clipboard.set_image(image)) {
+            Ok(()) => {
+                info!("Copied {} to the clipboard ({}x{})", what, width, height);
+                self.show_toast(format!("Copied {}", what));
+            }
+            Err(e) => self.show_error(format!("Failed to copy to the clipboard: {}", e)),
+        }
+    }
+    
+    /// Show a short confirmation message at the top of the window
+    fn show_toast(&mut self, message: String) {
+        self.push_toast(message, false, TOAST_MILLIS);
+    }
+    
+    /// Report an error on the console and on screen, where it stays up a little longer
+    fn show_error(&mut self, message: String) {
+        error!("{}", message);
+        self.push_toast(message, true, TOAST_ERROR_MILLIS);
+    }
+    
+    /// Report a failed save in a dialog that stays up until dismissed; unlike a toast it
+    /// can't go unnoticed, since the work isn't safe on disk
+    fn show_save_error(&mut self, message: String) {
+        error!("{}", message);
+        self.error_dialog = Some(message);
+    }
+    
+    fn push_toast(&mut self, message: String, error: bool, millis: u64) {
+        // Repeating the newest message just keeps it up
+        self.toasts.retain(|toast| toast.message != message);
+        if self.toasts.len() == TOAST_LIMIT {
+            self.toasts.pop_front();
+        }
+        let until = Instant::now() + std::time::Duration::from_millis(millis);
+        self.toasts.push_back(Toast { message, error, until });
+    }
+    
+    /// Drop toasts that have faded out; true while one is still fading (needs more frames)
+    fn expire_toasts(&mut self) -> bool {
+        let now = Instant::now();
+        self.toasts.retain(|toast| toast.until > now);
+        let fade = std::time::Duration::from_millis(TOAST_FADE_MILLIS);
+        self.toasts.iter().any(|toast| toast.until <= now + fade)
+    }
+    
+    /// When the next toast starts fading out
+    fn next_toast_fade(&self) -> Option<Instant> {
+        let fade = std::time::Duration::from_millis(TOAST_FADE_MILLIS);
+        self.toasts.iter().map(|toast| toast.until - fade).min()
+    }
+    
+    /// Pin a poster on top with its corner at the given screen point, and save posters.json
+    fn add_poster_at(&mut self, poster: PinnedPoster, screen_x: f64, screen_y: f64) -> io::Result<()> {
+        // Convert screen coordinates to board coordinates
+        let board_x = self.board.viewport.position.x + (screen_x as f32 / self.board.viewport.zoom);
+        let board_y = self.board.viewport.position.y + (screen_y as f32 / self.board.viewport.zoom);
+        self.pin_poster(poster, Point { x: board_x, y: board_y })
+    }
+    
+    /// Pin a poster on top with its corner at a board position, and save posters.json
+    fn pin_poster(&mut self, mut poster: PinnedPoster, position: Point) -> io::Result<()> {
+        self.show_toast(format!("Added poster '{}'", poster.name));
+        poster.position = position;
+        self.posters.push(poster);
+        self.record(UndoAction::PosterAdd { index: self.posters.len() - 1 });
+        self.save_posters()
+    }
+    
+    /// Handle click on UI elements, returns true if click was on UI
+    fn handle_ui_click(&mut self, x: f64, y: f64, render_height: u32, render_width: u32) -> io::Result<(bool, bool)> {
+        // Returns (clicked_on_ui, mode_was_toggled)
+        
+        // Any click takes focus away from the hex color field (clicking the field re-focuses it)
+        self.hex_input = None;
+        
+        // A click anywhere dismisses the save error
+        if self.error_dialog.take().is_some() {
+            return Ok((true, false));
+        }
+        
+        // The clear confirmation is modal too: a click anywhere answers no
+        if self.clear_prompt.take().is_some() {
+            self.show_toast("Clear cancelled".to_string());
+            return Ok((true, false));
+        }
+        
+        // ...and so are the picker's rename and delete prompts
+        if self.poster_file_prompt.take().is_some() {
+            return Ok((true, false));
+        }
+        
+        // The snapshot list is modal: a click picks a row, anywhere outside closes it
+        if let Some(list) = &self.snapshot_list {
+            let (panel_x, panel_y, panel_width, panel_height) = Self::snapshot_panel_rect(render_width, list.len());
+            let inside = x >= panel_x as f64 && x < (panel_x + panel_width) as f64 && y >= panel_y as f64 && y < (panel_y + panel_height) as f64;
+            if !inside {
+                self.snapshot_list = None;
+                self.snapshot_selected = None;
+            } else if y >= (panel_y + SNAPSHOT_LIST_TOP) as f64 {
+                let row = ((y - (panel_y + SNAPSHOT_LIST_TOP) as f64) / SNAPSHOT_ROW_HEIGHT as f64) as usize;
+                if row < list.len().min(SNAPSHOT_LIST_ROWS) {
+                    self.snapshot_selected = Some(row);
+                }
+            }
+            return Ok((true, false));
+        }
+        
+        // Scrollbars: grab the thumb (or jump to the press) and drag
+        if self.press_scrollbar(x, y, render_width, render_height) {
+            return Ok((true, false));
+        }
+        
+        // Minimap (bottom-right, always visible): jump there, centering the view on the click
+        let (mx, my, mw, mh) = Self::minimap_rect(render_width, render_height, &self.board.config);
+        if x >= mx as f64 && x < (mx + mw) as f64 && y >= my as f64 && y < (my + mh) as f64 {
+            let zoom = self.board.viewport.zoom;
+            let board_x = (x - mx as f64) / mw as f64 * self.board.config.width as f64;
+            let board_y = (y - my as f64) / mh as f64 * self.board.config.height as f64;
+            self.board.viewport.position.x = (board_x as f32 - render_width as f32 / zoom / 2.0).rem_euclid(self.board.config.width as f32);
+            self.board.viewport.position.y = board_y as f32 - render_height as f32 / zoom / 2.0;
+            self.board.viewport.pan_velocity = Point { x: 0.0, y: 0.0 };
+            return Ok((true, false));
+        }
+        
+        // Legend controls are hit-tested relative to the panel origin, following the collapse animation
+        let (origin_x, origin_y) = self.legend_origin(render_width, render_height);
+        let (lx, adjusted_y) = self.legend_point(x, y, render_width, render_height);
+        
+        // Check for press on legend top bar (x:10-290): a click collapses/expands, a drag moves the panel
+        // When collapsed, check the actual visible screen position
+        // When expanded, check the adjusted position
+        let is_top_bar_click = if self.legend_collapsed {
+            // When collapsed, the visible hint bar is near the top of the panel origin
+            (10.0..=290.0).contains(&lx) && (0.0..=30.0).contains(&(y - origin_y as f64))
+        } else {
+            // When expanded, use adjusted coordinates
+            (10.0..=290.0).contains(&lx) && (0.0..=20.0).contains(&adjusted_y)
+        };
+        
+        if is_top_bar_click {
+            self.legend_drag = Some(LegendDrag {
+                start: (x, y),
+                origin: Point { x: origin_x as f32, y: origin_y as f32 },
+                moved: false,
+            });
+            return Ok((true, false));
+        }
+        
+        // Only check other UI elements if legend is not fully collapsed
+        if self.legend_offset >= 269.0 {
+            return Ok((false, false));
+        }
+        
+        // Check if poster picker is open and handle clicks on it
+        if self.show_poster_picker {
+            let panel_width = 400u32;
+            let panel_height = 300u32;
+            let panel_x = (render_width / 2).saturating_sub(panel_width / 2);
+            let panel_y = (render_height / 2).saturating_sub(panel_height / 2);
+            
+            // Check if click is within the poster picker panel
+            if x >= panel_x as f64 && x <= (panel_x + panel_width) as f64 &&
+               y >= panel_y as f64 && y <= (panel_y + panel_height) as f64 {
+                // Check which poster row was clicked, counting the rows scrolled past
+                let relative_y = y - panel_y as f64 - PICKER_LIST_TOP as f64;
+                if relative_y >= 0.0 && relative_y < (PICKER_VISIBLE_ROWS as u32 * PICKER_ROW_HEIGHT) as f64 {
+                    let poster_index = self.poster_picker_scroll + (relative_y as u32 / PICKER_ROW_HEIGHT) as usize;
+                    let relative_x = x - panel_x as f64;
+                    let button = PICKER_ROW_BUTTONS.iter()
+                        .find(|(_, label, left)| relative_x >= *left as f64 && relative_x < (*left + label.len() as u32 * 6 + 6) as f64);
+                    if let Some(&(action, _, _)) = button {
+                        self.poster_file_action(poster_index, action);
+                    } else if poster_index < self.available_posters.len() {
+                        // Load the selected poster (it's placed by the first click after it decodes)
+                        if let Some((name, path)) = self.available_posters.get(poster_index).cloned() {
+                            self.load_poster_for_placing(name, PathBuf::from(path));
+                            self.show_poster_picker = false;
+                        }
+                    }
+                }
+                return Ok((true, false));
+            }
+        }
+        
+        // Buttons, field and sliders below are in legend coordinates (lx, adjusted_y)
+        
+        // Check if click is on the layers panel: the box toggles a layer, the percentage steps its opacity
+        for (row, view) in self.layer_views.iter_mut().enumerate() {
+            let row_y = LAYERS_PANEL_Y + row as f64 * 15.0;
+            if !(row_y - 3.0..row_y + 12.0).contains(&adjusted_y) {
+                continue;
+            }
+            if (158.0..=172.0).contains(&lx) {
+                view.visible = !view.visible;
+                info!("{} layer {}", view.name, if view.visible { "shown" } else { "hidden" });
+                return Ok((true, false));
+            }
+            if (240.0..=285.0).contains(&lx) {
+                let step = LAYER_OPACITY_STEPS.iter().position(|&o| o == view.opacity).map_or(0, |i| (i + 1) % LAYER_OPACITY_STEPS.len());
+                view.opacity = LAYER_OPACITY_STEPS[step];
+                return Ok((true, false));
+            }
+        }
+        
+        // Check if click is on mode toggle button (x:20-135, y:170-190) with offset
+        if (20.0..=135.0).contains(&lx) && (170.0..=190.0).contains(&adjusted_y) {
+            self.toggle_mode();
+            return Ok((true, true));
+        }
+        
+        // Check if click is on Posters button (x:145-210, y:170-190) with offset
+        if (145.0..=210.0).contains(&lx) && (170.0..=190.0).contains(&adjusted_y) {
+            self.toggle_poster_picker();
+            return Ok((true, false));
+        }
+        
+        // Check if click is on hex color field (x:215-285, y:170-190) with offset
+        if (215.0..=285.0).contains(&lx) && (170.0..=190.0).contains(&adjusted_y) {
+            self.hex_input = Some(String::new());
+            return Ok((true, false));
+        }
+        
+        // Check if click is on slider (x:20-160, y:150-165) with offset
+        if (20.0..=160.0).contains(&lx) && (150.0..=165.0).contains(&adjusted_y) {
+            // Calculate brush size from x position
+            let slider_x = (lx - 20.0).clamp(0.0, 140.0);
+            self.drawing_tool.brush_size = ((slider_x / 140.0) * 100.0).round() as u32;
+            self.drawing_tool.brush_size = self.drawing_tool.brush_size.clamp(1, 100);
+            return Ok((true, false));
+        }
+        
+        // Check if click is on opacity slider (x:170-280, y:150-165) with offset
+        if (170.0..=280.0).contains(&lx) && (150.0..=165.0).contains(&adjusted_y) {
+            let slider_x = (lx - 170.0).clamp(0.0, 110.0);
+            self.drawing_tool.brush_opacity = ((slider_x / 110.0) * 255.0).round().clamp(1.0, 255.0) as u8;
+            return Ok((true, false));
+        }
+        
+        // Check if click is on the zoom slider (x:100-280, y:262-276) with offset
+        if Self::on_zoom_slider(lx, adjusted_y) {
+            let slider_x = (lx - ZOOM_SLIDER_LEFT as f64).clamp(0.0, ZOOM_SLIDER_WIDTH as f64);
+            self.set_zoom_from_slider((slider_x / ZOOM_SLIDER_WIDTH as f64) as f32, render_width, render_height);
+            return Ok((true, false));
+        }
+        
+        // Check if click is on a recent-color swatch (right of the markers)
+        for (i, &color) in self.drawing_tool.recent_colors.iter().enumerate() {
+            let (sx, sy) = self.recent_swatch_origin(i, render_height);
+            let size = RECENT_SWATCH_SIZE as f64;
+            if (sx as f64..sx as f64 + size).contains(&x) && (sy as f64..sy as f64 + size).contains(&y) {
+                let marker = self.markers.iter().position(|m| m.color == color);
+                self.pick_color(color, marker);
+                return Ok((true, false));
+            }
+        }
+        
+        // Check if click is on color markers (bottom-left corner)
+        let marker_spacing = 5.0;
+        let bottom_margin = -10.0;
+        let scale = 0.5; // 50% scale
+        
+        for (i, marker) in self.markers.iter().enumerate() {
+            // Skip the marker that would draw in the background color
+            let hidden = match self.board.config.mode {
+                BoardMode::Blackboard => Some("black"),
+                BoardMode::Whiteboard => Some("white"),
+                _ => None,
+            };
+            if hidden == Some(marker.name.as_str()) {
+                continue;
+            }
+            
+            let scaled_width = marker.width as f64 * scale;
+            let scaled_height = marker.height as f64 * scale;
+            
+            let x_pos = marker_spacing + (i as f64) * (scaled_width + marker_spacing);
+            let y_pos = render_height as f64 - scaled_height - bottom_margin;
+            
+            if x >= x_pos && x <= x_pos + scaled_width && 
+               y >= y_pos && y <= y_pos + scaled_height {
+                // Marker clicked - update selected marker and current color
+                self.pick_color(marker.color, Some(i));
+                return Ok((true, false));
+            }
+        }
+        
+        Ok((false, false))
+    }
+    
+    /// Switch the pen to `color` (`marker` is its marker, None for a custom color) and move it
+    /// to the front of the recent colors, which are saved to config.toml
+    fn pick_color(&mut self, color: [u8; 4], marker: Option<usize>) {
+        self.drawing_tool.current_color = color;
+        self.drawing_tool.selected_marker_index = marker;
+        
+        let recent = &mut self.drawing_tool.recent_colors;
+        if recent.front() == Some(&color) {
+            return;
+        }
+        recent.retain(|&c| c != color);
+        recent.push_front(color);
+        recent.truncate(RECENT_COLORS);
+        
+        let hex: Vec<String> = recent.iter()
+            .map(|c| format!("\"#{:02X}{:02X}{:02X}{:02X}\"", c[0], c[1], c[2], c[3]))
+            .collect();
+        if let Err(e) = save_config_value(Path::new(CONFIG_FILE), "recent_colors", &format!("[{}]", hex.join(", "))) {
+            error!("Could not save recent colors: {}", e);
+        }
+    }
+    
+    /// Top-left corner of recent-color swatch `i`: one row to the right of the markers,
+    /// just above the status bar
+    fn recent_swatch_origin(&self, i: usize, height: u32) -> (u32, u32) {
+        let marker_spacing = 5;
+        let marker_width = self.markers.first().map_or(0, |m| m.width / 2); // Markers are drawn at 50%
+        let row_x = marker_spacing + self.markers.len() as u32 * (marker_width + marker_spacing) + RECENT_SWATCH_GAP;
+        let y = height.saturating_sub(STATUS_BAR_HEIGHT + RECENT_SWATCH_GAP + RECENT_SWATCH_SIZE);
+        (row_x + i as u32 * (RECENT_SWATCH_SIZE + RECENT_SWATCH_GAP), y)
+    }
+    
+    /// Compose the board as the viewport sees it - background, posters, then ink - into a
+    /// plain RGBA buffer of width x height. Used for the window and for --render-to.
+    fn render_scene(&mut self, frame: &mut [u8], width: u32, height: u32) -> io::Result<()> {
+        self.board.render(frame, width, height)?;
+        self.board.render_paper(frame, width, height);
+        if self.layer_opacity(Layer::Posters) > 0 {
+            self.render_posters(frame, width, height);
+        }
+        self.board.render_drawing_layer(frame, width, height, self.layer_opacity(Layer::Ink));
+        Ok(())
+    }
+    
+    /// Opacity a layer is composited with (0 when it's hidden in the layers panel)
+    fn layer_opacity(&self, layer: Layer) -> u8 {
+        self.layer_views.iter()
+            .find(|view| view.layer == layer)
+            .map_or(255, |view| if view.visible { view.opacity } else { 0 })
+    }
+    
+    /// Pixels of a poster's current frame (animated posters advance with poster_clock)
+    fn poster_pixels<'a>(&self, poster: &'a PinnedPoster) -> &'a [u8] {
+        poster.frame_at(self.poster_clock.elapsed())
+    }
+    
+    /// When the next animated poster frame is due (None if no poster is animated or posters are hidden)
+    fn next_poster_frame(&self) -> Option<Instant> {
+        if self.layer_opacity(Layer::Posters) == 0 {
+            return None;
+        }
+        let elapsed = self.poster_clock.elapsed();
+        self.posters.iter()
+            .filter_map(|poster| poster.animation_position(elapsed))
+            .map(|(_, left)| left)
+            .min()
+            .map(|left| Instant::now() + std::time::Duration::from_millis(left))
+    }
+    
+    /// Render pinned posters as overlay on top of board
+    fn render_posters(&self, frame: &mut [u8], width: u32, height: u32) {
+        let zoom = self.board.viewport.zoom;
+        let board_width = self.board.config.width as f32;
+        let board_height = self.board.config.height as f32;
+        
+        // Dropped images still decoding show as an outline of their size
+        for (position, (w, h)) in self.poster_loads.iter().filter_map(|load| load.drop_at) {
+            let dx = (position.x - self.board.viewport.position.x).rem_euclid(board_width);
+            let rect = (
+                (dx * zoom) as i32,
+                ((position.y - self.board.viewport.position.y) * zoom) as i32,
+                (w as f32 * zoom) as i32,
+                (h as f32 * zoom) as i32,
+            );
+            Self::draw_rect_outline(frame, width, height, rect, [160, 160, 160, 255], 1);
+        }
+        
+        for (i, poster) in self.posters.iter().enumerate() {
+            // Apply cylindrical wrapping: offset of the poster from the viewport, wrapped into [0, width)
+            let dx = (poster.position.x - self.board.viewport.position.x).rem_euclid(board_width);
+            
+            // A rotated poster pokes out of its unrotated rectangle by this much (board pixels)
+            let (half_x, half_y) = poster.rotated_half_extents();
+            let margin_x = half_x - poster.width as f32 * poster.scale_x / 2.0;
+            let margin_y = half_y - poster.height as f32 * poster.scale_y / 2.0;
+            
+            // Toroidal boards repeat posters vertically the same way (one wrap above, then down the screen)
+            let offset_y = poster.position.y - self.board.viewport.position.y;
+            let mut copies_y = Vec::new();
+            if self.board.config.wrap_vertical {
+                let mut copy_y = offset_y.rem_euclid(board_height) - board_height;
+                while (copy_y - margin_y) * zoom < height as f32 {
+                    copies_y.push(copy_y);
+                    copy_y += board_height;
+                }
+            } else {
+                copies_y.push(offset_y);
+            }
+            
+            // Selected (dragging) poster gets a bold outline, Ctrl-hovered one a thin outline
+            let outline = if self.selected_poster_index == Some(i) {
+                Some(([255u8, 200u8, 0u8, 255u8], 2))
+            } else if self.hovered_poster_index == Some(i) {
+                Some(([120u8, 200u8, 255u8, 255u8], 1))
+            } else {
+                None
+            };
+            
+            // A poster crossing the left edge of the screen lives at dx - board_width,
+            // so start one wrap to the left and draw every copy that reaches the screen
+            for screen_y in copies_y.into_iter().map(|copy_y| (copy_y * zoom) as i32) {
+                let mut copy_x = dx - board_width;
+                while (copy_x - margin_x) * zoom < width as f32 {
+                    let screen_x = (copy_x * zoom) as i32;
+                    let scaled_width = (poster.width as f32 * poster.scale_x * zoom) as i32;
+                    let scaled_height = (poster.height as f32 * poster.scale_y * zoom) as i32;
+                    if poster.rotation == 0.0 {
+                        self.render_poster_copy(frame, width, height, poster, screen_x, screen_y);
+                        if let Some((color, thickness)) = outline {
+                            Self::draw_rect_outline(frame, width, height, (screen_x, screen_y, scaled_width, scaled_height), color, thickness);
+                        }
+                        if poster.locked && self.show_poster_locks {
+                            Self::draw_lock_badge(frame, width, height, screen_x + 3, screen_y + 3);
+                        }
+                    } else {
+                        let center = (screen_x as f32 + scaled_width as f32 / 2.0, screen_y as f32 + scaled_height as f32 / 2.0);
+                        self.render_rotated_poster_copy(frame, width, height, poster, center);
+                        if let Some(outline) = outline {
+                            Self::draw_rotated_outline(frame, width, height, center, (scaled_width as f32, scaled_height as f32), poster.rotation, outline);
+                        }
+                        if poster.locked && self.show_poster_locks {
+                            // Top-left of the box around the rotated poster
+                            let (x, y) = (center.0 - half_x * zoom, center.1 - half_y * zoom);
+                            Self::draw_lock_badge(frame, width, height, x as i32 + 3, y as i32 + 3);
+                        }
+                    }
+                    copy_x += board_width;
+                }
+            }
+        }
+        
+        // Alignment guides for the poster being dragged, repeated across the seam like posters
+        if self.selected_poster_index.is_some() {
+            let (view_x, view_y) = (self.board.viewport.position.x, self.board.viewport.position.y);
+            let mut put = |x: i32, y: i32| {
+                if x >= 0 && y >= 0 && x < width as i32 && y < height as i32 {
+                    let offset = ((y as u32 * width + x as u32) * 4) as usize;
+                    frame[offset..offset + 4].copy_from_slice(&POSTER_GUIDE_COLOR);
+                }
+            };
+            for guide in &self.poster_guides {
+                let start_x = if guide.vertical { guide.at } else { guide.from };
+                let mut copy_x = (start_x - view_x).rem_euclid(board_width) - board_width;
+                while copy_x * zoom < width as f32 {
+                    if guide.vertical {
+                        let x = (copy_x * zoom) as i32;
+                        for y in (((guide.from - view_y) * zoom) as i32).max(0)..=(((guide.to - view_y) * zoom) as i32).min(height as i32) {
+                            put(x, y);
+                        }
+                    } else {
+                        let y = ((guide.at - view_y) * zoom) as i32;
+                        for x in ((copy_x * zoom) as i32).max(0)..=(((copy_x + guide.to - guide.from) * zoom) as i32).min(width as i32) {
+                            put(x, y);
+                        }
+                    }
+                    copy_x += board_width;
+                }
+            }
+        }
+    }
+    
+    /// Draw one on-screen copy of a poster with its top-left corner at (screen_x, screen_y)
+    fn render_poster_copy(&self, frame: &mut [u8], width: u32, height: u32, poster: &PinnedPoster, screen_x: i32, screen_y: i32) {
+        let zoom = self.board.viewport.zoom;
+        let opacity = self.layer_opacity(Layer::Posters);
+        
+        // Calculate scaled poster dimensions (applying both poster scale and viewport zoom)
+        let scaled_width = (poster.width as f32 * poster.scale_x * zoom) as i32;
+        let scaled_height = (poster.height as f32 * poster.scale_y * zoom) as i32;
+        
+        // Early exit: skip if poster is completely off-screen
+        if screen_x + scaled_width < 0 || screen_x >= width as i32 ||
+           screen_y + scaled_height < 0 || screen_y >= height as i32 {
+            return;
+        }
+        
+        // Calculate visible bounds to avoid iterating off-screen pixels
+        let start_sx = 0.max(-screen_x);
+        let start_sy = 0.max(-screen_y);
+        let end_sx = scaled_width.min(width as i32 - screen_x);
+        let end_sy = scaled_height.min(height as i32 - screen_y);
+        
+        // Use fixed-point arithmetic for faster scaling (32.32 fixed point, so the step stays
+        // precise when a poster is magnified a lot and large posters can't overflow)
+        let scale_x_inv = ((1u64 << 32) as f64 / (poster.scale_x * zoom) as f64) as i64;
+        let scale_y_inv = ((1u64 << 32) as f64 / (poster.scale_y * zoom) as f64) as i64;
+        let image = self.poster_pixels(poster);
+        
+        // Render poster pixels with scaling (only visible portion)
+        for sy in start_sy..end_sy {
+            let screen_py = screen_y + sy;
+            let poster_py = ((sy as i64 * scale_y_inv) >> 32) as u32;
+            
+            if poster_py >= poster.height {
+                continue;
+            }
+            
+            let poster_row_base = (poster_py * poster.width * 4) as usize;
+            let screen_row_base = (screen_py * width as i32) as usize * 4;
+            
+            // Four screen pixels at a time (see blend_pixels4); pixels past the poster stay transparent
+            let mut sx = start_sx;
+            while sx < end_sx {
+                let screen_offset = screen_row_base + ((screen_x + sx) * 4) as usize;
+                let n = ((end_sx - sx) as usize).min(4).min(frame.len().saturating_sub(screen_offset) / 4);
+                if n == 0 {
+                    break;
+                }
+                
+                let mut src = [[0u8; 4]; 4];
+                for (k, px) in src.iter_mut().enumerate().take(n) {
+                    let poster_px = (((sx + k as i32) as i64 * scale_x_inv) >> 32) as u32;
+                    let poster_offset = poster_row_base + (poster_px * 4) as usize;
+                    if poster_px < poster.width && poster_offset + 3 < image.len() {
+                        px.copy_from_slice(&image[poster_offset..poster_offset + 4]);
+                    }
+                }
+                
+                blend_poster_pixels(&mut frame[screen_offset..screen_offset + n * 4], &src, n, opacity);
+                sx += 4;
+            }
+        }
+    }
+    
+    /// Draw one on-screen copy of a rotated poster centered at `center` (screen pixels),
+    /// sampling the image through the inverse rotation
+    fn render_rotated_poster_copy(&self, frame: &mut [u8], width: u32, height: u32, poster: &PinnedPoster, center: (f32, f32)) {
+        let opacity = self.layer_opacity(Layer::Posters);
+        let scale_x = poster.scale_x * self.board.viewport.zoom;
+        let scale_y = poster.scale_y * self.board.viewport.zoom;
+        let (half_x, half_y) = poster.rotated_half_extents();
+        let (cx, cy) = center;
+        
+        // Only walk the rotated poster's bounding box, clipped to the screen
+        let zoom = self.board.viewport.zoom;
+        let start_sx = ((cx - half_x * zoom).floor() as i32).max(0);
+        let end_sx = ((cx + half_x * zoom).ceil() as i32).min(width as i32);
+        let start_sy = ((cy - half_y * zoom).floor() as i32).max(0);
+        let end_sy = ((cy + half_y * zoom).ceil() as i32).min(height as i32);
+        if start_sx >= end_sx || start_sy >= end_sy {
+            return;
+        }
+        
+        let (sin, cos) = poster.rotation.sin_cos();
+        let half_w = poster.width as f32 / 2.0;
+        let half_h = poster.height as f32 / 2.0;
+        let image = self.poster_pixels(poster);
+        
+        for sy in start_sy..end_sy {
+            let ry = sy as f32 + 0.5 - cy;
+            let screen_row_base = (sy as u32 * width) as usize * 4;
+            
+            let mut sx = start_sx;
+            while sx < end_sx {
+                let n = ((end_sx - sx) as usize).min(4);
+                let mut src = [[0u8; 4]; 4];
+                for (k, px) in src.iter_mut().enumerate().take(n) {
+                    // Rotate the screen offset back into the poster's frame, then unscale
+                    let rx = (sx + k as i32) as f32 + 0.5 - cx;
+                    let u = (cos * rx + sin * ry) / scale_x + half_w;
+                    let v = (-sin * rx + cos * ry) / scale_y + half_h;
+                    if u < 0.0 || v < 0.0 || u >= poster.width as f32 || v >= poster.height as f32 {
+                        continue;
+                    }
+                    let poster_offset = ((v as u32 * poster.width + u as u32) * 4) as usize;
+                    if poster_offset + 3 < image.len() {
+                        px.copy_from_slice(&image[poster_offset..poster_offset + 4]);
+                    }
+                }
+                
+                let screen_offset = screen_row_base + (sx * 4) as usize;
+                blend_poster_pixels(&mut frame[screen_offset..screen_offset + n * 4], &src, n, opacity);
+                sx += 4;
+            }
+        }
+    }
+    
+    /// Outline a rotated poster (center and size in screen pixels), clipped to the frame
+    fn draw_rotated_outline(frame: &mut [u8], width: u32, height: u32, center: (f32, f32), size: (f32, f32), rotation: f32, outline: ([u8; 4], i32)) {
+        let (color, thickness) = outline;
+        let (sin, cos) = rotation.sin_cos();
+        let corner = |u: f32, v: f32| (center.0 + cos * u - sin * v, center.1 + sin * u + cos * v);
+        let (half_w, half_h) = (size.0 / 2.0 + 1.0, size.1 / 2.0 + 1.0);
+        let corners = [corner(-half_w, -half_h), corner(half_w, -half_h), corner(half_w, half_h), corner(-half_w, half_h)];
+        
+        for i in 0..4 {
+            let (x0, y0) = corners[i];
+            let (x1, y1) = corners[(i + 1) % 4];
+            let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as i32;
+            for step in 0..=steps {
+                let t = step as f32 / steps as f32;
+                let (x, y) = ((x0 + (x1 - x0) * t) as i32, (y0 + (y1 - y0) * t) as i32);
+                for py in y..y + thickness {
+                    for px in x..x + thickness {
+                        if px >= 0 && py >= 0 && px < width as i32 && py < height as i32 {
+                            let offset = ((py as u32 * width + px as u32) * 4) as usize;
+                            frame[offset..offset + 4].copy_from_slice(&color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Draw the uncommitted Shift-drag line in screen space at its final brush size
+    fn render_line_preview(&self, frame: &mut [u8], width: u32, height: u32) {
+        if !self.drawing_tool.draw_straight {
+            return;
+        }
+        let (Some(anchor), Some(end)) = (self.drawing_tool.last_point, self.drawing_tool.line_end) else {
+            return;
+        };
+        
+        let zoom = self.board.viewport.zoom;
+        let to_screen = |p: Point| (
+            (p.x - self.board.viewport.position.x) * zoom,
+            (p.y - self.board.viewport.position.y) * zoom,
+        );
+        let (x0, y0) = to_screen(anchor);
+        let (x1, y1) = to_screen(end);
+        
+        let color = if self.drawing_tool.is_eraser {
+            self.board.config.mode.background_color()
+        } else {
+            self.drawing_tool.current_color
+        };
+        let radius = ((self.drawing_tool.brush_size / 2) as f32 * zoom).max(0.5);
+        
+        // Stamp circles along the line; half-radius spacing keeps it solid without overdraw
+        let length = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt();
+        let steps = (length / (radius * 0.5).max(1.0)).ceil().max(1.0) as i32;
+        for i in 0..=steps {
+            let t = i as f32 / steps as f32;
+            Self::fill_stamp(frame, width, height, (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t), radius, self.drawing_tool.brush_shape, color);
+        }
+    }
+    
+    /// Draw the pen curve being built at its final brush size, continued to the cursor while no
+    /// handle is being dragged, with the anchors and their handles marked
+    fn render_pen_preview(&self, frame: &mut [u8], width: u32, height: u32, cursor: (f64, f64)) {
+        let tool = &self.drawing_tool;
+        let Some(first) = tool.pen_anchors.first() else {
+            return;
+        };
+        if tool.tool != Tool::Pen {
+            return;
+        }
+        
+        // Anchors are unwrapped along the chain, so place the first relative to the view and
+        // keep the rest at their offsets from it (as render_measure does)
+        let zoom = self.board.viewport.zoom;
+        let view = self.board.viewport.position;
+        let (ox, oy) = self.measure_delta(view, first.point);
+        let to_screen = |p: Point| Point {
+            x: (ox + p.x - first.point.x) * zoom,
+            y: (oy + p.y - first.point.y) * zoom,
+        };
+        
+        let mut anchors: Vec<PenAnchor> = tool.pen_anchors.iter()
+            .map(|a| PenAnchor { point: to_screen(a.point), handle: to_screen(a.handle) })
+            .collect();
+        if !tool.pen_dragging {
+            let last = tool.pen_anchors[tool.pen_anchors.len() - 1].point;
+            let cursor = Point { x: view.x + cursor.0 as f32 / zoom, y: view.y + cursor.1 as f32 / zoom };
+            let next = to_screen(self.unwrap_near(cursor, last));
+            anchors.push(PenAnchor { point: next, handle: next });
+        }
+        
+        let color = if tool.eraser_mode {
+            self.board.config.mode.background_color()
+        } else {
+            tool.current_color
+        };
+        let radius = ((tool.brush_size / 2) as f32 * zoom).max(0.5);
+        let spacing = (radius * 0.5).max(1.0);
+        let points = pen_curve_points(&anchors, 0.5);
+        Self::fill_stamp(frame, width, height, (points[0].x, points[0].y), radius, tool.brush_shape, color);
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let steps = (((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt() / spacing).ceil().max(1.0) as i32;
+            for i in 1..=steps {
+                let t = i as f32 / steps as f32;
+                Self::fill_stamp(frame, width, height, (a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t), radius, tool.brush_shape, color);
+            }
+        }
+        
+        // Handle lines through each anchor with dots at both ends, and a square on the anchor
+        let ui = self.board.config.mode.default_pen_color();
+        for anchor in &anchors[..tool.pen_anchors.len()] {
+            let (p, h) = (anchor.point, anchor.handle);
+            let mirror = (2.0 * p.x - h.x, 2.0 * p.y - h.y);
+            if (h.x - p.x).abs() + (h.y - p.y).abs() >= 1.0 {
+                let steps = (mirror.0 - h.x).abs().max((mirror.1 - h.y).abs()).ceil() as i32;
+                for i in 0..=steps {
+                    let t = i as f32 / steps as f32;
+                    let (px, py) = ((h.x + (mirror.0 - h.x) * t) as i32, (h.y + (mirror.1 - h.y) * t) as i32);
+                    if px >= 0 && py >= 0 && px < width as i32 && py < height as i32 {
+                        let offset = ((py as u32 * width + px as u32) * 4) as usize;
+                        frame[offset..offset + 4].copy_from_slice(&ui);
+                    }
+                }
+                for end in [(h.x, h.y), mirror] {
+                    Self::fill_stamp(frame, width, height, end, 2.0, BrushShape::Round, ui);
+                }
+            }
+            Self::fill_stamp(frame, width, height, (p.x, p.y), 3.0, BrushShape::Square, ui);
+        }
+    }
+    
+    /// Shortest board-space offset from `from` to `to`, going across the seam when that's closer
+    fn measure_delta(&self, from: Point, to: Point) -> (f32, f32) {
+        let shortest = |d: f32, size: u32| {
+            let size = size as f32;
+            (d + size / 2.0).rem_euclid(size) - size / 2.0
+        };
+        let dx = shortest(to.x - from.x, self.board.config.width);
+        let dy = if self.board.config.wrap_vertical {
+            shortest(to.y - from.y, self.board.config.height)
+        } else {
+            to.y - from.y
+        };
+        (dx, dy)
+    }
+    
+    /// Draw the measure tool's line and its length in board pixels next to the cursor end
+    fn render_measure(&self, frame: &mut [u8], width: u32, height: u32) {
+        let Some((anchor, end)) = self.measure else {
+            return;
+        };
+        
+        let zoom = self.board.viewport.zoom;
+        let view = self.board.viewport.position;
+        // Place the cursor end relative to the view, then walk back along the shortest offset
+        // so a measurement across the seam is drawn as one short line
+        let (ex, ey) = self.measure_delta(view, end);
+        let (dx, dy) = self.measure_delta(anchor, end);
+        let (x1, y1) = (ex * zoom, ey * zoom);
+        let (x0, y0) = (x1 - dx * zoom, y1 - dy * zoom);
+        
+        let color = self.board.config.mode.default_pen_color();
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as i32;
+        for i in 0..=steps {
+            let t = i as f32 / steps as f32;
+            let (px, py) = ((x0 + (x1 - x0) * t) as i32, (y0 + (y1 - y0) * t) as i32);
+            if px >= 0 && py >= 0 && px < width as i32 && py < height as i32 {
+                let offset = ((py as u32 * width + px as u32) * 4) as usize;
+                frame[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+        // Small squares mark both ends
+        for (cx, cy) in [(x0, y0), (x1, y1)] {
+            Self::fill_stamp(frame, width, height, (cx, cy), 2.0, BrushShape::Square, color);
+        }
+        
+        let label = format!("{:.1} px ({:.0}, {:.0})", (dx * dx + dy * dy).sqrt(), dx, dy);
+        let label_width = label.len() as u32 * 6;
+        let label_x = ((x1 + 12.0).max(0.0) as u32).min(width.saturating_sub(label_width + 4));
+        let label_y = ((y1 + 12.0).max(0.0) as u32).min(height.saturating_sub(11));
+        // Backing box in the board color keeps the label readable over ink
+        let bg = self.board.config.mode.background_color();
+        for y in label_y.saturating_sub(2)..(label_y + 9).min(height) {
+            for x in label_x.saturating_sub(2)..(label_x + label_width + 1).min(width) {
+                let offset = ((y * width + x) * 4) as usize;
+                frame[offset..offset + 4].copy_from_slice(&bg);
+            }
+        }
+        self.draw_simple_text(frame, width, label_x, label_y, &label, color);
+    }
+    
+    /// Draw the floating pixels (if any) and the select tool's rectangle, with a second copy
+    /// when it runs across the seam
+    fn render_selection(&self, frame: &mut [u8], width: u32, height: u32) {
+        let Some(selection) = &self.selection else {
+            return;
+        };
+        
+        let zoom = self.board.viewport.zoom;
+        let (x, y, w, h) = selection.rect;
+        let (dx, dy) = self.measure_delta(self.board.viewport.position, Point { x: x as f32, y: y as f32 });
+        let board_width = self.board.config.width as f32;
+        let color = self.board.config.mode.default_pen_color();
+        for sx in [dx, dx - board_width, dx + board_width] {
+            let (left, top) = (sx * zoom, dy * zoom);
+            let (right, bottom) = (left + w as f32 * zoom, top + h as f32 * zoom);
+            if right < 0.0 || bottom < 0.0 || left >= width as f32 || top >= height as f32 {
+                continue;
+            }
+            
+            if let Some(buffer) = &selection.floating {
+                // Nearest-neighbour over the visible part only, so a big paste zoomed in stays cheap
+                let (px0, px1) = (left.max(0.0) as u32, right.min(width as f32).ceil() as u32);
+                let (py0, py1) = (top.max(0.0) as u32, bottom.min(height as f32).ceil() as u32);
+                for py in py0..py1 {
+                    let by = ((py as f32 - top) / zoom) as u32;
+                    if by >= buffer.height {
+                        continue;
+                    }
+                    for px in px0..px1 {
+                        let bx = ((px as f32 - left) / zoom) as u32;
+                        if bx >= buffer.width {
+                            continue;
+                        }
+                        let src = ((by * buffer.width + bx) * 4) as usize;
+                        let pixel = [buffer.pixels[src], buffer.pixels[src + 1], buffer.pixels[src + 2], buffer.pixels[src + 3]];
+                        if pixel[3] != 0 {
+                            let offset = ((py * width + px) * 4) as usize;
+                            blend_pixel(&mut frame[offset..offset + 4], pixel);
+                        }
+                    }
+                }
+            }
+            
+            let rect = (left.round() as i32, top.round() as i32, (w as f32 * zoom).round() as i32, (h as f32 * zoom).round() as i32);
+            Self::draw_rect_outline(frame, width, height, rect, color, 1);
+        }
+    }
+    
+    /// Draw faint board-space gridlines over the visible area, wrapping at the seam
+    fn render_grid(&self, frame: &mut [u8], width: u32, height: u32) {
+        if !self.show_grid {
+            return;
+        }
+        
+        let zoom = self.board.viewport.zoom;
+        let view = self.board.viewport.position;
+        let config = &self.board.config;
+        
+        // Coarsen the spacing when zoomed out so the lines don't turn into a solid wash
+        let mut spacing = GRID_SPACING;
+        while (spacing as f32) * zoom < GRID_MIN_GAP && spacing < config.width.max(config.height) {
+            spacing *= 2;
+        }
+        
+        let columns = Self::grid_lines(view.x, width, zoom, config.width, true, spacing);
+        let rows = Self::grid_lines(view.y, height, zoom, config.height, config.wrap_vertical, spacing);
+        
+        // Off the top/bottom edge of a non-wrapping board there is nothing to grid
+        let (row_start, row_end) = if config.wrap_vertical {
+            (0, height)
+        } else {
+            (
+                ((-view.y * zoom).max(0.0) as u32).min(height),
+                (((config.height as f32 - view.y) * zoom).max(0.0) as u32).min(height),
+            )
+        };
+        
+        let color = config.mode.default_pen_color();
+        let mut blend = |x: u32, y: u32| {
+            let offset = ((y * width + x) * 4) as usize;
+            for c in 0..3 {
+                frame[offset + c] = ((color[c] as u32 * GRID_ALPHA + frame[offset + c] as u32 * (255 - GRID_ALPHA)) / 255) as u8;
+            }
+        };
+        for &sx in &columns {
+            for sy in row_start..row_end {
+                blend(sx, sy);
+            }
+        }
+        for &sy in &rows {
+            for sx in 0..width {
+                // Skip the crossings so intersections aren't drawn twice as dark
+                if columns.binary_search(&sx).is_err() {
+                    blend(sx, sy);
+                }
+            }
+        }
+    }
+    
+    /// Draw a faint dashed line at x=0 (and y=0 on a vertically wrapping board) so the wrap seam is visible
+    fn render_seam(&self, frame: &mut [u8], width: u32, height: u32) {
+        if !self.show_seam {
+            return;
+        }
+        
+        let zoom = self.board.viewport.zoom;
+        let view = self.board.viewport.position;
+        let config = &self.board.config;
+        
+        // One line per lap of the board that's on screen
+        let columns = Self::grid_lines(view.x, width, zoom, config.width, true, config.width);
+        let rows = if config.wrap_vertical {
+            Self::grid_lines(view.y, height, zoom, config.height, true, config.height)
+        } else {
+            Vec::new()
+        };
+        let (row_start, row_end) = if config.wrap_vertical {
+            (0, height)
+        } else {
+            (
+                ((-view.y * zoom).max(0.0) as u32).min(height),
+                (((config.height as f32 - view.y) * zoom).max(0.0) as u32).min(height),
+            )
+        };
+        
+        let color = config.mode.default_pen_color();
+        let mut blend = |x: u32, y: u32| {
+            let offset = ((y * width + x) * 4) as usize;
+            for c in 0..3 {
+                frame[offset + c] = ((color[c] as u32 * SEAM_ALPHA + frame[offset + c] as u32 * (255 - SEAM_ALPHA)) / 255) as u8;
+            }
+        };
+        // Dashed so it reads as a marker rather than a gridline or ink
+        for &sx in &columns {
+            for sy in (row_start..row_end).filter(|sy| (sy / SEAM_DASH).is_multiple_of(2)) {
+                blend(sx, sy);
+            }
+        }
+        for &sy in &rows {
+            for sx in (0..width).filter(|sx| (sx / SEAM_DASH).is_multiple_of(2)) {
+                blend(sx, sy);
+            }
+        }
+    }
+    
+    /// Screen positions (ascending) of gridlines along one axis; `start` is the viewport edge in board pixels
+    fn grid_lines(start: f32, screen_len: u32, zoom: f32, board_len: u32, wrap: bool, spacing: u32) -> Vec<u32> {
+        let end = start + screen_len as f32 / zoom;
+        let laps = if wrap {
+            (start / board_len as f32).floor() as i64..=(end / board_len as f32).floor() as i64
+        } else {
+            0..=0
+        };
+        
+        let mut lines = Vec::new();
+        for lap in laps {
+            // Each lap restarts at the seam, so the grid stays anchored to board coordinates
+            for offset in (0..=board_len).step_by(spacing as usize) {
+                if wrap && offset == board_len {
+                    continue; // Same line as the next lap's 0
+                }
+                let board = (lap * board_len as i64 + offset as i64) as f32;
+                if board >= start && board < end {
+                    let screen = ((board - start) * zoom) as u32;
+                    if screen < screen_len && lines.last() != Some(&screen) {
+                        lines.push(screen);
+                    }
+                }
+            }
+        }
+        lines
+    }
+    
+    /// Draw the pending translucent or textured stroke (not yet blended into the layer) at its final alpha
+    fn render_stroke_mask(&self, frame: &mut [u8], width: u32, height: u32) {
+        if self.drawing_tool.stroke_mask.is_empty() {
+            return;
         }
         
-        // Only check other UI elements if legend is not fully collapsed
-        if self.legend_offset >= 269.0 {
-            return Ok((false, false));
-        }
+        let color = self.drawing_tool.current_color;
+        let zoom = self.board.viewport.zoom;
+        let board_width = self.board.config.width as usize;
+        let view = self.board.viewport.position;
         
-        // Check if poster picker is open and handle clicks on it
-        if self.show_poster_picker {
-            let panel_width = 400u32;
-            let panel_height = 300u32;
-            let panel_x = (render_width / 2).saturating_sub(panel_width / 2);
-            let panel_y = (render_height / 2).saturating_sub(panel_height / 2);
+        for (&index, &alpha) in &self.drawing_tool.stroke_mask {
+            let (bx, by) = ((index % board_width) as f32, (index / board_width) as f32);
+            // Wrap relative to the viewport so strokes across the seam show up
+            let sx0 = ((bx - view.x).rem_euclid(board_width as f32) * zoom) as i32;
+            let dy = if self.board.config.wrap_vertical {
+                (by - view.y).rem_euclid(self.board.config.height as f32)
+            } else {
+                by - view.y
+            };
+            let sy0 = (dy * zoom) as i32;
+            let size = zoom.ceil().max(1.0) as i32;
             
-            // Check if click is within the poster picker panel
-            if x >= panel_x as f64 && x <= (panel_x + panel_width) as f64 &&
-               y >= panel_y as f64 && y <= (panel_y + panel_height) as f64 {
-                // Check which poster was clicked (each poster is 20 pixels tall, starting at y_offset 40)
-                let relative_y = (y - panel_y as f64 - 40.0) as i32;
-                if relative_y >= 0 {
-                    let poster_index = (relative_y / 20) as usize;
-                    if poster_index < self.available_posters.len() {
-                        // Load the selected poster
-                        if let Some((_name, path)) = self.available_posters.get(poster_index) {
-                            if let Ok(img) = image::open(path) {
-                                let (width, height) = img.dimensions();
-                                let rgba = img.to_rgba8();
-                                let image_data = rgba.into_raw();
-                                let name = self.available_posters[poster_index].0.clone();
-                                self.placing_poster = Some((image_data, width, height, name));
-                                self.show_poster_picker = false;
-                            }
-                        }
+            let a = alpha as u32;
+            for sy in sy0.max(0)..(sy0 + size).min(height as i32) {
+                for sx in sx0.max(0)..(sx0 + size).min(width as i32) {
+                    let offset = ((sy as u32 * width + sx as u32) * 4) as usize;
+                    for c in 0..3 {
+                        frame[offset + c] = ((color[c] as u32 * a + frame[offset + c] as u32 * (255 - a)) / 255) as u8;
                     }
                 }
-                return Ok((true, false));
             }
         }
-        
-        // Check if click is on mode toggle button (x:20-135, y:170-190) with offset
-        if x >= 20.0 && x <= 135.0 && adjusted_y >= 170.0 && adjusted_y <= 190.0 {
-            self.toggle_mode()?;
-            return Ok((true, true));
+    }
+    
+    /// Whether the current tool stamps the brush, so the cursor shows its outline
+    fn shows_brush_outline(&self) -> bool {
+        matches!(self.drawing_tool.tool, Tool::Brush | Tool::Highlighter | Tool::Pen | Tool::Stamp) && self.placing_poster.is_none()
+    }
+    
+    /// Outline of the brush tip under the cursor: the size and shape a click would stamp
+    /// (snapped to board pixels like draw_brush), in the pen color or red while erasing
+    fn render_brush_outline(&self, frame: &mut [u8], width: u32, height: u32, cursor: (f64, f64)) {
+        if !self.shows_brush_outline() {
+            return;
         }
         
-        // Check if click is on Posters button (x:145-210, y:170-190) with offset
-        if x >= 145.0 && x <= 210.0 && adjusted_y >= 170.0 && adjusted_y <= 190.0 {
-            self.show_poster_picker = !self.show_poster_picker;
-            return Ok((true, false));
-        }
+        let tool = &self.drawing_tool;
+        let zoom = self.board.viewport.zoom;
+        let view = self.board.viewport.position;
+        let cx = (view.x + cursor.0 as f32 / zoom).floor();
+        let cy = (view.y + cursor.1 as f32 / zoom).floor();
+        let radius = (tool.brush_size / 2) as f32;
+        let erasing = if tool.is_drawing { tool.is_eraser } else { tool.eraser_mode };
+        let color = if erasing {
+            ERASER_OUTLINE_COLOR
+        } else {
+            let c = tool.current_color;
+            [c[0], c[1], c[2], 255]
+        };
         
-        // Check if click is on slider (x:20-160, y:150-165) with offset
-        if x >= 20.0 && x <= 160.0 && adjusted_y >= 150.0 && adjusted_y <= 165.0 {
-            // Calculate brush size from x position
-            let slider_x = (x - 20.0).max(0.0).min(140.0);
-            self.drawing_tool.brush_size = ((slider_x / 140.0) * 100.0).round() as u32;
-            self.drawing_tool.brush_size = self.drawing_tool.brush_size.max(1).min(100);
-            return Ok((true, false));
+        // The stamp tool shows the box its image will cover
+        if let Some(stamp) = tool.stamp.as_ref().filter(|_| tool.tool == Tool::Stamp && !erasing) {
+            let left = (cx - (stamp.width / 2) as f32 - view.x) * zoom;
+            let top = (cy - (stamp.height / 2) as f32 - view.y) * zoom;
+            let rect = (left.round() as i32, top.round() as i32, (stamp.width as f32 * zoom).round() as i32, (stamp.height as f32 * zoom).round() as i32);
+            Self::draw_rect_outline(frame, width, height, rect, color, 1);
+            return;
         }
         
-        // Check if click is on color markers (bottom-left corner)
-        let marker_spacing = 5.0;
-        let bottom_margin = -10.0;
-        let scale = 0.5; // 50% scale
-        
-        for (i, marker) in self.markers.iter().enumerate() {
-            // Skip black marker in blackboard mode (index 0)
-            if self.board.config.mode == BoardMode::Blackboard && i == 0 {
-                continue;
+        // The stamp covers board pixels cx - r ..= cx + r
+        let left = (cx - radius - view.x) * zoom;
+        let top = (cy - radius - view.y) * zoom;
+        let size = (2.0 * radius + 1.0) * zoom;
+        match tool.brush_shape {
+            BrushShape::Square => {
+                let rect = (left.round() as i32, top.round() as i32, size.round() as i32, size.round() as i32);
+                Self::draw_rect_outline(frame, width, height, rect, color, 1);
             }
-            // Skip white marker in whiteboard mode (index 1)
-            if self.board.config.mode == BoardMode::Whiteboard && i == 1 {
-                continue;
+            BrushShape::Round => {
+                let center = (left + size / 2.0, top + size / 2.0);
+                Self::draw_circle_outline(frame, width, height, center, (size / 2.0).max(2.0), color);
             }
-            
-            let scaled_width = marker.width as f64 * scale;
-            let scaled_height = marker.height as f64 * scale;
-            
-            let x_pos = marker_spacing + (i as f64) * (scaled_width + marker_spacing);
-            let y_pos = render_height as f64 - scaled_height - bottom_margin;
-            
-            if x >= x_pos && x <= x_pos + scaled_width && 
-               y >= y_pos && y <= y_pos + scaled_height {
-                // Marker clicked - update selected marker and current color
-                self.drawing_tool.selected_marker_index = i;
-                self.drawing_tool.current_color = marker.color;
-                return Ok((true, false));
+            BrushShape::Chisel { angle } => {
+                // The nib as a thin rotated box (screen y points down, so the angle flips)
+                let center = (left + size / 2.0, top + size / 2.0);
+                let thickness = 2.0 * BrushShape::nib_half_thickness(radius) * zoom;
+                Self::draw_rotated_outline(frame, width, height, center, (size, thickness), -angle.to_radians(), (color, 1));
             }
         }
-        
-        Ok((false, false))
     }
     
-    /// Render pinned posters as overlay on top of board
-    fn render_posters(&self, frame: &mut [u8], width: u32, height: u32) {
-        let zoom = self.board.viewport.zoom;
-        let board_width = self.board.config.width as f32;
-        
-        for poster in &self.posters {
-            // Apply cylindrical wrapping: calculate wrapped x position
-            let wrapped_x = poster.position.x;
-            let viewport_x = self.board.viewport.position.x;
-            
-            // Calculate the difference and wrap it
-            let mut dx = wrapped_x - viewport_x;
-            while dx < 0.0 {
-                dx += board_width;
-            }
-            while dx >= board_width {
-                dx -= board_width;
+    /// Draw a 1px circle outline in screen space, clipped to the frame
+    fn draw_circle_outline(frame: &mut [u8], width: u32, height: u32, center: (f32, f32), radius: f32, color: [u8; 4]) {
+        let steps = (radius * std::f32::consts::TAU).ceil().max(8.0) as u32;
+        for i in 0..steps {
+            let (sin, cos) = (i as f32 / steps as f32 * std::f32::consts::TAU).sin_cos();
+            let (px, py) = ((center.0 + cos * radius) as i32, (center.1 + sin * radius) as i32);
+            if px >= 0 && py >= 0 && px < width as i32 && py < height as i32 {
+                let offset = ((py as u32 * width + px as u32) * 4) as usize;
+                frame[offset..offset + 4].copy_from_slice(&color);
             }
-            
-            // Calculate screen position with cylindrical wrapping
-            let screen_x = (dx * zoom) as i32;
-            let screen_y = ((poster.position.y - self.board.viewport.position.y) * zoom) as i32;
-            
-            // Calculate scaled poster dimensions (applying both poster scale and viewport zoom)
-            let scaled_width = (poster.width as f32 * poster.scale * zoom) as i32;
-            let scaled_height = (poster.height as f32 * poster.scale * zoom) as i32;
-            
-            // Early exit: skip if poster is completely off-screen
-            if screen_x + scaled_width < 0 || screen_x >= width as i32 ||
-               screen_y + scaled_height < 0 || screen_y >= height as i32 {
+        }
+    }
+    
+    /// Fill a brush-shaped stamp in screen space, clipped to the frame
+    fn fill_stamp(frame: &mut [u8], width: u32, height: u32, center: (f32, f32), radius: f32, shape: BrushShape, color: [u8; 4]) {
+        let (cx, cy) = center;
+        let r = radius.ceil() as i32;
+        let (icx, icy) = (cx as i32, cy as i32);
+        for dy in -r..=r {
+            let py = icy + dy;
+            if py < 0 || py >= height as i32 {
                 continue;
             }
-            
-            // Calculate visible bounds to avoid iterating off-screen pixels
-            let start_sx = 0.max(-screen_x);
-            let start_sy = 0.max(-screen_y);
-            let end_sx = scaled_width.min(width as i32 - screen_x);
-            let end_sy = scaled_height.min(height as i32 - screen_y);
-            
-            // Use fixed-point arithmetic for faster scaling (16.16 fixed point)
-            let scale_factor_inv = ((1.0 / (poster.scale * zoom)) * 65536.0) as i32;
-            
-            // Render poster pixels with scaling (only visible portion)
-            for sy in start_sy..end_sy {
-                let screen_py = screen_y + sy;
-                let poster_py = ((sy * scale_factor_inv) >> 16) as u32;
-                
-                if poster_py >= poster.height {
+            for dx in -r..=r {
+                let px = icx + dx;
+                let outside = match shape {
+                    BrushShape::Round => (dx * dx + dy * dy) as f32 > radius * radius,
+                    BrushShape::Square => false, // The -r..=r loop bounds are the square
+                    BrushShape::Chisel { .. } => !shape.covers(dx as f32, dy as f32, radius),
+                };
+                if px < 0 || px >= width as i32 || outside {
                     continue;
                 }
-                
-                let poster_row_base = (poster_py * poster.width * 4) as usize;
-                let screen_row_base = (screen_py * width as i32) as usize * 4;
-                
-                for sx in start_sx..end_sx {
-                    let poster_px = ((sx * scale_factor_inv) >> 16) as u32;
-                    
-                    if poster_px >= poster.width {
-                        continue;
-                    }
-                    
-                    let poster_offset = poster_row_base + (poster_px * 4) as usize;
-                    
-                    // Skip if out of bounds or fully transparent
-                    if poster_offset + 3 >= poster.image_data.len() {
-                        continue;
-                    }
-                    
-                    let alpha = poster.image_data[poster_offset + 3];
-                    if alpha == 0 {
-                        continue;
-                    }
-                    
-                    let screen_offset = screen_row_base + ((screen_x + sx) * 4) as usize;
-                    if screen_offset + 3 >= frame.len() {
-                        continue;
-                    }
-                    
-                    // Alpha blend the poster with the background
-                    if alpha == 255 {
-                        // Fully opaque - direct copy (most common case)
-                        unsafe {
-                            std::ptr::copy_nonoverlapping(
-                                poster.image_data.as_ptr().add(poster_offset),
-                                frame.as_mut_ptr().add(screen_offset),
-                                3
-                            );
+                let offset = ((py as u32 * width + px as u32) * 4) as usize;
+                frame[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+    }
+    
+    /// Draw a rectangle outline (x, y, w, h in screen pixels), clipped to the frame
+    /// Small padlock on a dark square with its top-left corner at (x, y), marking a locked poster
+    fn draw_lock_badge(frame: &mut [u8], width: u32, height: u32, x: i32, y: i32) {
+        const PADLOCK: [&str; 8] = ["..###..", ".#...#.", ".#...#.", "#######", "#######", "###.###", "###.###", "#######"];
+        for by in 0..12 {
+            for bx in 0..11 {
+                let (px, py) = (x + bx, y + by);
+                if px < 0 || py < 0 || px >= width as i32 || py >= height as i32 {
+                    continue;
+                }
+                let offset = ((py as u32 * width + px as u32) * 4) as usize;
+                let lit = PADLOCK.get((by - 2) as usize).is_some_and(|row| row.as_bytes().get((bx - 2) as usize) == Some(&b'#'));
+                let color = if lit { [255, 200, 0, 255] } else { [0, 0, 0, 160] };
+                blend_pixel(&mut frame[offset..offset + 4], color);
+            }
+        }
+    }
+    
+    fn draw_rect_outline(frame: &mut [u8], width: u32, height: u32, rect: (i32, i32, i32, i32), color: [u8; 4], thickness: i32) {
+        let (rx, ry, rw, rh) = rect;
+        let mut put = |x: i32, y: i32| {
+            if x >= 0 && y >= 0 && x < width as i32 && y < height as i32 {
+                let offset = ((y as u32 * width + x as u32) * 4) as usize;
+                frame[offset..offset + 4].copy_from_slice(&color);
+            }
+        };
+        
+        for t in 0..thickness {
+            // Clamp the horizontal span to the screen so huge zoomed posters stay cheap
+            for x in (rx - t).max(0)..(rx + rw + t).min(width as i32) {
+                put(x, ry - 1 - t);
+                put(x, ry + rh + t);
+            }
+            for y in (ry - 1 - t).max(0)..(ry + rh + 1 + t).min(height as i32) {
+                put(rx - 1 - t, y);
+                put(rx + rw + t, y);
+            }
+        }
+    }
+    
+    /// Screen rectangle (x, y, w, h) of the minimap: bottom-right, board aspect within height limits
+    fn minimap_rect(width: u32, height: u32, config: &BoardConfig) -> (u32, u32, u32, u32) {
+        let w = MINIMAP_WIDTH.min(width.saturating_sub(20)).max(1);
+        let h = ((w as u64 * config.height as u64 / config.width as u64) as u32)
+            .clamp(MINIMAP_MIN_HEIGHT, MINIMAP_MAX_HEIGHT)
+            .min(height.saturating_sub(20 + STATUS_BAR_HEIGHT).max(1));
+        (width.saturating_sub(w + 10), height.saturating_sub(h + 10 + STATUS_BAR_HEIGHT), w, h)
+    }
+    
+    /// Rebuild the cached minimap if the board changed or the window was resized.
+    /// Each minimap pixel samples a MINIMAP_SAMPLES^2 grid of its board area so thin strokes show up.
+    fn update_minimap(&mut self, width: u32, height: u32) {
+        let (_, _, w, h) = Self::minimap_rect(width, height, &self.board.config);
+        // Both flags must be taken, so no short-circuit
+        let edited = self.board.cache.take_edited() | self.board.drawing_layer.take_edited();
+        if !edited && self.minimap_size == (w, h) {
+            return;
+        }
+        
+        let board = &self.board;
+        let (board_w, board_h) = (board.config.width as usize, board.config.height as usize);
+        let mut minimap = vec![0u8; (w * h * 4) as usize];
+        minimap.par_chunks_mut((w * 4) as usize).enumerate().for_each(|(my, row)| {
+            for mx in 0..w as usize {
+                let mut color = None;
+                'samples: for sy in 0..MINIMAP_SAMPLES {
+                    let by = ((my * MINIMAP_SAMPLES + sy) * board_h / (h as usize * MINIMAP_SAMPLES)).min(board_h - 1);
+                    for sx in 0..MINIMAP_SAMPLES {
+                        let bx = ((mx * MINIMAP_SAMPLES + sx) * board_w / (w as usize * MINIMAP_SAMPLES)).min(board_w - 1);
+                        let ink = board.drawing_layer.get(bx, by);
+                        if ink[3] != 0 {
+                            let mut pixel = board.cache.get(bx, by);
+                            blend_pixel(&mut pixel, ink);
+                            color = Some(pixel);
+                            break 'samples;
                         }
-                        frame[screen_offset + 3] = 255;
-                    } else {
-                        // Partial transparency - blend (using integer math)
-                        let inv_alpha = 255 - alpha;
-                        
-                        frame[screen_offset] = ((poster.image_data[poster_offset] as u16 * alpha as u16 + frame[screen_offset] as u16 * inv_alpha as u16) / 255) as u8;
-                        frame[screen_offset + 1] = ((poster.image_data[poster_offset + 1] as u16 * alpha as u16 + frame[screen_offset + 1] as u16 * inv_alpha as u16) / 255) as u8;
-                        frame[screen_offset + 2] = ((poster.image_data[poster_offset + 2] as u16 * alpha as u16 + frame[screen_offset + 2] as u16 * inv_alpha as u16) / 255) as u8;
-                        frame[screen_offset + 3] = 255;
                     }
                 }
+                let color = color.unwrap_or_else(|| board.cache.get(
+                    (mx * board_w + board_w / 2) / w as usize,
+                    ((my * board_h + board_h / 2) / h as usize).min(board_h - 1),
+                ));
+                row[mx * 4..mx * 4 + 3].copy_from_slice(&color[..3]);
+                row[mx * 4 + 3] = 255;
+            }
+        });
+        
+        self.minimap = minimap;
+        self.minimap_size = (w, h);
+    }
+    
+    /// Draw the cached minimap with a frame and the current viewport marked on it
+    fn render_minimap(&self, frame: &mut [u8], width: u32, height: u32) {
+        let (x0, y0, w, h) = Self::minimap_rect(width, height, &self.board.config);
+        if self.minimap_size != (w, h) {
+            return; // Not built for this window size yet
+        }
+        
+        for my in 0..h {
+            let src = (my * w * 4) as usize;
+            let dst = (((y0 + my) * width + x0) * 4) as usize;
+            frame[dst..dst + (w * 4) as usize].copy_from_slice(&self.minimap[src..src + (w * 4) as usize]);
+        }
+        let border = [128u8, 128u8, 128u8, 255u8];
+        Self::draw_rect_outline(frame, width, height, (x0 as i32 - 1, y0 as i32 - 1, w as i32 + 2, h as i32 + 2), border, 1);
+        
+        // Viewport rectangle; x wraps around the strip like the board does
+        let zoom = self.board.viewport.zoom;
+        let (board_w, board_h) = (self.board.config.width as f32, self.board.config.height as f32);
+        let view = self.board.viewport.position;
+        let rect_x = (view.x.rem_euclid(board_w) / board_w * w as f32) as i32;
+        let rect_w = ((width as f32 / zoom / board_w * w as f32) as i32).max(2).min(w as i32);
+        let top = ((view.y / board_h * h as f32) as i32).clamp(0, h as i32 - 1);
+        let bottom = (((view.y + height as f32 / zoom) / board_h * h as f32) as i32).clamp(top + 1, h as i32) - 1;
+        let highlight = [255u8, 200u8, 0u8, 255u8];
+        for c in 0..rect_w {
+            let mx = (rect_x + c).rem_euclid(w as i32) as u32;
+            let edge_column = c == 0 || c == rect_w - 1;
+            for my in top..=bottom {
+                if edge_column || my == top || my == bottom {
+                    let offset = (((y0 + my as u32) * width + x0 + mx) * 4) as usize;
+                    frame[offset..offset + 4].copy_from_slice(&highlight);
+                }
             }
         }
     }
     
     /// Render UI overlay (legend and brush controls)
-    fn render_ui_overlay(&self, frame: &mut [u8], width: u32, height: u32, fps: f32) {
-        let text_color = match self.board.config.mode {
-            BoardMode::Blackboard => [255u8, 255u8, 255u8, 255u8], // White text
-            BoardMode::Whiteboard => [0u8, 0u8, 0u8, 255u8], // Black text
+    fn render_ui_overlay(&self, frame: &mut [u8], width: u32, height: u32, fps: f32, cursor: (f64, f64)) {
+        let text_color = if self.board.config.mode.is_dark() {
+            [255u8, 255u8, 255u8, 255u8] // White text
+        } else {
+            [0u8, 0u8, 0u8, 255u8] // Black text
         };
         
         // Different transparency for different modes
-        let bg_color = match self.board.config.mode {
-            BoardMode::Blackboard => [0u8, 0u8, 0u8, 128u8], // 50% transparent black
-            BoardMode::Whiteboard => [255u8, 255u8, 255u8, 153u8], // 60% transparent white
+        let bg_color = if self.board.config.mode.is_dark() {
+            [0u8, 0u8, 0u8, 128u8] // 50% transparent black
+        } else {
+            [255u8, 255u8, 255u8, 153u8] // 60% transparent white
         };
         
-        // Apply collapse animation offset
-        let y_offset = -(self.legend_offset as i32);
+        self.render_status_bar(frame, width, height, cursor, text_color, bg_color);
+        self.render_minimap(frame, width, height);
+        self.render_scrollbars(frame, width, height, text_color, bg_color);
+        self.render_diagnostics(frame, width, height, text_color, bg_color);
         
-        // Draw background panel (top-left, from y:0 to y:280, 290 pixels wide)
+        // Panel origin (top-left by default, draggable), plus the collapse animation offset.
+        // Everything below is laid out relative to the origin and clipped above it.
+        let (origin_x, origin_y) = self.legend_origin(width, height);
+        let y_offset = origin_y - self.legend_offset as i32;
+        
+        // Draw background panel (from y:0 to y:280, 290 pixels wide)
         let bg_alpha = bg_color[3];
         let inv_bg_alpha = 255 - bg_alpha;
         
         for y in 0..280 {
             let screen_y = y + y_offset;
-            if screen_y < 0 || screen_y >= height as i32 { continue; }
+            if screen_y < origin_y || screen_y >= height as i32 { continue; }
             let row_offset = (screen_y as u32 * width * 4) as usize;
             
-            for x in 10..290 {
+            for x in origin_x + 10..origin_x + 290 {
                 let offset = row_offset + (x * 4) as usize;
                 if offset + 3 < frame.len() {
                     // Alpha blend with existing content using integer math
@@ -1107,14 +3804,15 @@ impl RickBoard {
         // Helper to draw text with y-offset
         let draw_text = |f: &mut [u8], w: u32, x: u32, y: u32, text: &str, color: [u8; 4]| {
             let screen_y = y as i32 + y_offset;
-            if screen_y >= 0 && screen_y < height as i32 {
-                self.draw_simple_text(f, w, x, screen_y as u32, text, color);
+            if screen_y >= origin_y && screen_y < height as i32 {
+                self.draw_simple_text(f, w, origin_x + x, screen_y as u32, text, color);
             }
         };
         
         // Render text legend (simplified - just draw simple characters)
         draw_text(frame, width, 20, 20, "CONTROLS:", text_color);
-        draw_text(frame, width, 20, 35, "Left Click: Draw", text_color);
+        let left_click = if self.drawing_tool.eraser_mode { "Left Click: Erase" } else { "Left Click: Draw" };
+        draw_text(frame, width, 20, 35, left_click, text_color);
         draw_text(frame, width, 20, 48, "Right Click: Erase", text_color);
         draw_text(frame, width, 20, 61, "WASD: Pan", text_color);
         draw_text(frame, width, 20, 74, "Mouse Wheel: Zoom", text_color);
@@ -1131,10 +3829,10 @@ impl RickBoard {
         draw_text(frame, width, 20, 139, &format!("Brush: {}", self.drawing_tool.brush_size), text_color);
         
         // Draw slider bar (140 pixels wide) with offset
-        for x in 20..160 {
+        for x in origin_x + 20..origin_x + 160 {
             for dy in 0..3 {
                 let screen_y = 155 + dy + y_offset;
-                if screen_y >= 0 && screen_y < height as i32 {
+                if screen_y >= origin_y && screen_y < height as i32 {
                     let offset = ((screen_y as u32 * width + x) * 4) as usize;
                     if offset + 3 < frame.len() {
                         frame[offset..offset + 4].copy_from_slice(&text_color);
@@ -1144,12 +3842,40 @@ impl RickBoard {
         }
         
         // Draw slider position indicator with offset
-        let slider_pos = 20 + ((self.drawing_tool.brush_size.min(100) * 140) / 100) as u32;
+        let slider_pos = origin_x + 20 + ((self.drawing_tool.brush_size.min(100) * 140) / 100);
         for dy in -5..=5 {
             for dx in -2..=2 {
                 let py = 156 + dy + y_offset;
                 let px = slider_pos as i32 + dx;
-                if px >= 0 && py >= 0 && py < height as i32 {
+                if px >= 0 && py >= origin_y && py < height as i32 {
+                    let offset = ((py as u32 * width + px as u32) * 4) as usize;
+                    if offset + 3 < frame.len() {
+                        frame[offset..offset + 4].copy_from_slice(&[255, 100, 100, 255]);
+                    }
+                }
+            }
+        }
+        
+        // Draw opacity slider (right of the brush slider, 110 pixels wide)
+        let opacity_percent = (self.drawing_tool.brush_opacity as u32 * 100 + 127) / 255;
+        draw_text(frame, width, 170, 139, &format!("Opacity: {}", opacity_percent), text_color);
+        for x in origin_x + 170..origin_x + 280 {
+            for dy in 0..3 {
+                let screen_y = 155 + dy + y_offset;
+                if screen_y >= origin_y && screen_y < height as i32 {
+                    let offset = ((screen_y as u32 * width + x) * 4) as usize;
+                    if offset + 3 < frame.len() {
+                        frame[offset..offset + 4].copy_from_slice(&text_color);
+                    }
+                }
+            }
+        }
+        let opacity_pos = origin_x + 170 + (self.drawing_tool.brush_opacity as u32 * 110) / 255;
+        for dy in -5..=5 {
+            for dx in -2..=2 {
+                let py = 156 + dy + y_offset;
+                let px = opacity_pos as i32 + dx;
+                if px >= 0 && py >= origin_y && py < height as i32 {
                     let offset = ((py as u32 * width + px as u32) * 4) as usize;
                     if offset + 3 < frame.len() {
                         frame[offset..offset + 4].copy_from_slice(&[255, 100, 100, 255]);
@@ -1158,16 +3884,16 @@ impl RickBoard {
             }
         }
         
-        // Draw brush preview circle with offset
-        let preview_x = 210;
+        // Draw brush preview (matches the brush shape) with offset
+        let preview_x = origin_x as i32 + 210;
         let preview_y = 86;
         let radius = (self.drawing_tool.brush_size / 2).min(50) as i32;
         for dy in -radius..=radius {
             for dx in -radius..=radius {
-                if dx * dx + dy * dy <= radius * radius {
+                if self.drawing_tool.brush_shape.contains(dx, dy, radius) {
                     let px = preview_x + dx;
                     let py = preview_y + dy + y_offset;
-                    if px >= 0 && py >= 0 && py < height as i32 {
+                    if px >= 0 && py >= origin_y && py < height as i32 {
                         let offset = ((py as u32 * width + px as u32) * 4) as usize;
                         if offset + 3 < frame.len() {
                             frame[offset..offset + 4].copy_from_slice(&text_color);
@@ -1178,17 +3904,14 @@ impl RickBoard {
         }
         
         // Draw mode toggle button
-        let button_text = match self.board.config.mode {
-            BoardMode::Blackboard => "Mode: Blackboard",
-            BoardMode::Whiteboard => "Mode: Whiteboard",
-        };
-        draw_text(frame, width, 30, 175, button_text, text_color);
+        let button_text = format!("Mode: {}", self.board.config.mode.label());
+        draw_text(frame, width, 30, 175, &button_text, text_color);
         
         // Draw button border (clickable area: x:20-135, y:170-190) with offset
-        for x in 20..135 {
+        for x in origin_x + 20..origin_x + 135 {
             for y in [170, 189].iter() {
-                let screen_y = *y as i32 + y_offset;
-                if screen_y >= 0 && screen_y < height as i32 {
+                let screen_y = *y + y_offset;
+                if screen_y >= origin_y && screen_y < height as i32 {
                     let offset = ((screen_y as u32 * width + x) * 4) as usize;
                     if offset + 3 < frame.len() {
                         frame[offset..offset + 4].copy_from_slice(&text_color);
@@ -1197,10 +3920,10 @@ impl RickBoard {
             }
         }
         for y in 170..190 {
-            let screen_y = y as i32 + y_offset;
-            if screen_y >= 0 && screen_y < height as i32 {
+            let screen_y = y + y_offset;
+            if screen_y >= origin_y && screen_y < height as i32 {
                 for x in [20, 134].iter() {
-                    let offset = ((screen_y as u32 * width + *x) * 4) as usize;
+                    let offset = ((screen_y as u32 * width + origin_x + *x) * 4) as usize;
                     if offset + 3 < frame.len() {
                         frame[offset..offset + 4].copy_from_slice(&text_color);
                     }
@@ -1212,10 +3935,10 @@ impl RickBoard {
         draw_text(frame, width, 150, 175, "Posters", text_color);
         
         // Draw button border (clickable area: x:145-210, y:170-190) with offset
-        for x in 145..210 {
+        for x in origin_x + 145..origin_x + 210 {
             for y in [170, 189].iter() {
-                let screen_y = *y as i32 + y_offset;
-                if screen_y >= 0 && screen_y < height as i32 {
+                let screen_y = *y + y_offset;
+                if screen_y >= origin_y && screen_y < height as i32 {
                     let offset = ((screen_y as u32 * width + x) * 4) as usize;
                     if offset + 3 < frame.len() {
                         frame[offset..offset + 4].copy_from_slice(&text_color);
@@ -1224,10 +3947,10 @@ impl RickBoard {
             }
         }
         for y in 170..190 {
-            let screen_y = y as i32 + y_offset;
-            if screen_y >= 0 && screen_y < height as i32 {
+            let screen_y = y + y_offset;
+            if screen_y >= origin_y && screen_y < height as i32 {
                 for x in [145, 209].iter() {
-                    let offset = ((screen_y as u32 * width + *x) * 4) as usize;
+                    let offset = ((screen_y as u32 * width + origin_x + *x) * 4) as usize;
                     if offset + 3 < frame.len() {
                         frame[offset..offset + 4].copy_from_slice(&text_color);
                     }
@@ -1235,35 +3958,295 @@ impl RickBoard {
             }
         }
         
+        // Draw hex color field (next to Posters button): typed digits while editing, else current color
+        let hex_text = match &self.hex_input {
+            Some(typed) => format!("{:-<6}", typed),
+            None => {
+                let c = self.drawing_tool.current_color;
+                format!("{:02X}{:02X}{:02X}", c[0], c[1], c[2])
+            }
+        };
+        draw_text(frame, width, 232, 175, &hex_text, text_color);
+        
+        // Border shows the current color, or the text color while typing (clickable area: x:215-285, y:170-190)
+        let field_color = if self.hex_input.is_some() { text_color } else { self.drawing_tool.current_color };
+        for x in origin_x + 215..origin_x + 285 {
+            for y in [170, 189].iter() {
+                let screen_y = *y + y_offset;
+                if screen_y >= origin_y && screen_y < height as i32 {
+                    let offset = ((screen_y as u32 * width + x) * 4) as usize;
+                    if offset + 3 < frame.len() {
+                        frame[offset..offset + 4].copy_from_slice(&field_color);
+                    }
+                }
+            }
+        }
+        for y in 170..190 {
+            let screen_y = y + y_offset;
+            if screen_y >= origin_y && screen_y < height as i32 {
+                for x in [215, 284].iter() {
+                    let offset = ((screen_y as u32 * width + origin_x + *x) * 4) as usize;
+                    if offset + 3 < frame.len() {
+                        frame[offset..offset + 4].copy_from_slice(&field_color);
+                    }
+                }
+            }
+        }
+        
         // Draw poster controls help text
         draw_text(frame, width, 20, 205, "Poster Controls:", text_color);
         draw_text(frame, width, 20, 220, "Ctrl+Click: Move", text_color);
         draw_text(frame, width, 20, 235, "Ctrl+Wheel: Scale", text_color);
         draw_text(frame, width, 20, 250, "Ctrl+RClick: Delete", text_color);
         
+        // Draw layers panel (right of the poster help): visibility box, name, opacity
+        draw_text(frame, width, 160, 205, "Layers:", text_color);
+        for (row, view) in self.layer_views.iter().enumerate() {
+            let row_y = LAYERS_PANEL_Y as u32 + row as u32 * 15;
+            let check = if view.visible { "[x]" } else { "[ ]" };
+            draw_text(frame, width, 160, row_y, check, text_color);
+            draw_text(frame, width, 182, row_y, view.name, text_color);
+            let percent = (view.opacity as u32 * 100 + 127) / 255;
+            draw_text(frame, width, 245, row_y, &format!("{}%", percent), text_color);
+        }
+        
+        // Draw zoom slider along the bottom of the panel, log scale between the zoom limits
+        let zoom_percent = (self.board.viewport.zoom * 100.0).round() as u32;
+        draw_text(frame, width, 20, 265, &format!("Zoom: {}%", zoom_percent), text_color);
+        for x in origin_x + ZOOM_SLIDER_LEFT..origin_x + ZOOM_SLIDER_LEFT + ZOOM_SLIDER_WIDTH {
+            for dy in 0..3 {
+                let screen_y = 268 + dy + y_offset;
+                if screen_y >= origin_y && screen_y < height as i32 {
+                    let offset = ((screen_y as u32 * width + x) * 4) as usize;
+                    if offset + 3 < frame.len() {
+                        frame[offset..offset + 4].copy_from_slice(&text_color);
+                    }
+                }
+            }
+        }
+        let zoom_pos = origin_x + ZOOM_SLIDER_LEFT + (self.zoom_slider_fraction(self.board.viewport.zoom) * ZOOM_SLIDER_WIDTH as f32).round() as u32;
+        for dy in -5..=5 {
+            for dx in -2..=2 {
+                let py = 269 + dy + y_offset;
+                let px = zoom_pos as i32 + dx;
+                if px >= 0 && py >= origin_y && py < height as i32 {
+                    let offset = ((py as u32 * width + px as u32) * 4) as usize;
+                    if offset + 3 < frame.len() {
+                        frame[offset..offset + 4].copy_from_slice(&[255, 100, 100, 255]);
+                    }
+                }
+            }
+        }
+        
         // Draw collapse/expand hint at top
         let hint_text = if self.legend_collapsed { "Click to show" } else { "Click to hide" };
         draw_text(frame, width, 100, 5, hint_text, text_color);
         
-        // Render color markers at bottom-left corner
-        self.render_markers(frame, width, height);
+        // Render color markers at bottom-left corner, recent colors next to them
+        self.render_markers(frame, width, height);
+        self.render_recent_colors(frame, width, height, text_color);
+        
+        // Render poster picker if active
+        if self.show_poster_picker {
+            self.render_poster_picker(frame, width, height);
+        }
+        
+        self.render_goto_prompt(frame, width, height, text_color, bg_color);
+        self.render_snapshot_prompt(frame, width, height, text_color, bg_color);
+        self.render_snapshot_list(frame, width, height, text_color, bg_color);
+        self.render_clear_prompt(frame, width, height, text_color, bg_color);
+        self.render_poster_file_prompt(frame, width, height, text_color, bg_color);
+        self.render_toasts(frame, width, height, text_color, bg_color);
+        self.render_error_dialog(frame, width, height, text_color, bg_color);
+    }
+    
+    /// Save error dialog (centered, red frame) with the message wrapped to the panel
+    fn render_error_dialog(&self, frame: &mut [u8], width: u32, height: u32, text_color: [u8; 4], bg_color: [u8; 4]) {
+        let Some(message) = &self.error_dialog else {
+            return;
+        };
+        
+        let panel_width = 480.min(width.saturating_sub(40)).max(120);
+        let lines = wrap_text(message, ((panel_width - 16) / 6) as usize);
+        let panel_height = 40 + lines.len() as u32 * 10 + 12;
+        let panel_x = (width / 2).saturating_sub(panel_width / 2);
+        let panel_y = (height / 2).saturating_sub(panel_height / 2);
+        // Opaque, so the message reads over any drawing
+        for y in panel_y..(panel_y + panel_height).min(height) {
+            for x in panel_x..(panel_x + panel_width).min(width) {
+                let offset = ((y * width + x) * 4) as usize;
+                frame[offset..offset + 3].copy_from_slice(&bg_color[..3]);
+            }
+        }
+        Self::draw_rect_outline(frame, width, height, (panel_x as i32, panel_y as i32, panel_width as i32, panel_height as i32), TOAST_ERROR_COLOR, 2);
+        self.draw_simple_text(frame, width, panel_x + 8, panel_y + 8, "Save failed - your latest changes are not on disk", TOAST_ERROR_COLOR);
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_simple_text(frame, width, panel_x + 8, panel_y + 24 + i as u32 * 10, line, text_color);
+        }
+        self.draw_simple_text(frame, width, panel_x + 8, panel_y + panel_height - 16, "Enter, Escape or click to dismiss", text_color);
+    }
+    
+    /// Go-to prompt (top-center, under the save progress bar) while G input is active
+    fn render_goto_prompt(&self, frame: &mut [u8], width: u32, height: u32, text_color: [u8; 4], bg_color: [u8; 4]) {
+        let Some(typed) = &self.goto_input else {
+            return;
+        };
+        
+        let (panel_width, panel_height) = (240u32, 24u32);
+        let panel_x = (width / 2).saturating_sub(panel_width / 2);
+        let panel_y = 40u32;
+        let alpha = bg_color[3] as u16;
+        for y in panel_y..(panel_y + panel_height).min(height) {
+            for x in panel_x..(panel_x + panel_width).min(width) {
+                let offset = ((y * width + x) * 4) as usize;
+                for c in 0..3 {
+                    frame[offset + c] = ((bg_color[c] as u16 * alpha + frame[offset + c] as u16 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+        Self::draw_rect_outline(frame, width, height, (panel_x as i32, panel_y as i32, panel_width as i32, panel_height as i32), text_color, 1);
+        self.draw_simple_text(frame, width, panel_x + 8, panel_y + 8, &format!("Go to x,y: {}_", typed), text_color);
+    }
+    
+    /// Save-snapshot prompt (same place as the go-to prompt) while Ctrl+S input is active
+    fn render_snapshot_prompt(&self, frame: &mut [u8], width: u32, height: u32, text_color: [u8; 4], bg_color: [u8; 4]) {
+        let Some(typed) = &self.snapshot_input else {
+            return;
+        };
+        
+        let (panel_width, panel_height) = (320u32, 24u32);
+        let panel_x = (width / 2).saturating_sub(panel_width / 2);
+        let panel_y = 40u32;
+        let alpha = bg_color[3] as u16;
+        for y in panel_y..(panel_y + panel_height).min(height) {
+            for x in panel_x..(panel_x + panel_width).min(width) {
+                let offset = ((y * width + x) * 4) as usize;
+                for c in 0..3 {
+                    frame[offset + c] = ((bg_color[c] as u16 * alpha + frame[offset + c] as u16 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+        Self::draw_rect_outline(frame, width, height, (panel_x as i32, panel_y as i32, panel_width as i32, panel_height as i32), text_color, 1);
+        self.draw_simple_text(frame, width, panel_x + 8, panel_y + 8, &format!("Snapshot name: {}_", typed), text_color);
+    }
+    
+    /// Clear confirmation (same place as the go-to prompt) while it waits for Y or N
+    fn render_clear_prompt(&self, frame: &mut [u8], width: u32, height: u32, text_color: [u8; 4], bg_color: [u8; 4]) {
+        let question = match self.clear_prompt {
+            Some(Action::ClearDrawing) => "Clear drawing? Y/N",
+            Some(Action::ClearBackground) => "Clear background? Y/N",
+            Some(_) => "Clear board? Y/N",
+            None => return,
+        };
+        
+        let (panel_width, panel_height) = (240u32, 24u32);
+        let panel_x = (width / 2).saturating_sub(panel_width / 2);
+        let panel_y = 40u32;
+        let alpha = bg_color[3] as u16;
+        for y in panel_y..(panel_y + panel_height).min(height) {
+            for x in panel_x..(panel_x + panel_width).min(width) {
+                let offset = ((y * width + x) * 4) as usize;
+                for c in 0..3 {
+                    frame[offset + c] = ((bg_color[c] as u16 * alpha + frame[offset + c] as u16 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+        Self::draw_rect_outline(frame, width, height, (panel_x as i32, panel_y as i32, panel_width as i32, panel_height as i32), text_color, 1);
+        self.draw_simple_text(frame, width, panel_x + 8, panel_y + 8, question, text_color);
+    }
+    
+    /// Poster rename or delete prompt (same place as the go-to prompt)
+    fn render_poster_file_prompt(&self, frame: &mut [u8], width: u32, height: u32, text_color: [u8; 4], bg_color: [u8; 4]) {
+        let name = |index: usize| self.available_posters.get(index).map_or("", |(name, _)| name.as_str());
+        let text = match &self.poster_file_prompt {
+            Some(PosterFilePrompt::Rename { index, typed }) => format!("Rename {} to: {}_", name(*index), typed),
+            Some(PosterFilePrompt::Delete(index)) => format!("Delete {}? Y/N", name(*index)),
+            None => return,
+        };
+        
+        let panel_width = (text.chars().count() as u32 * 6 + 16).clamp(240, width.saturating_sub(20).max(240));
+        let panel_height = 24u32;
+        let panel_x = (width / 2).saturating_sub(panel_width / 2);
+        let panel_y = 40u32;
+        let alpha = bg_color[3] as u16;
+        for y in panel_y..(panel_y + panel_height).min(height) {
+            for x in panel_x..(panel_x + panel_width).min(width) {
+                let offset = ((y * width + x) * 4) as usize;
+                for c in 0..3 {
+                    frame[offset + c] = ((bg_color[c] as u16 * alpha + frame[offset + c] as u16 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+        Self::draw_rect_outline(frame, width, height, (panel_x as i32, panel_y as i32, panel_width as i32, panel_height as i32), text_color, 1);
+        self.draw_simple_text(frame, width, panel_x + 8, panel_y + 8, &text, text_color);
+    }
+    
+    /// Screen rectangle (x, y, width, height) of the snapshot list with `count` entries
+    fn snapshot_panel_rect(width: u32, count: usize) -> (u32, u32, u32, u32) {
+        let rows = count.clamp(1, SNAPSHOT_LIST_ROWS) as u32;
+        let panel_width = 320u32;
+        let panel_height = SNAPSHOT_LIST_TOP + rows * SNAPSHOT_ROW_HEIGHT + 22;
+        ((width / 2).saturating_sub(panel_width / 2), 40, panel_width, panel_height)
+    }
+    
+    /// Snapshot list (Ctrl+O): names with their age, the picked row highlighted, and what
+    /// Enter will do at the bottom
+    fn render_snapshot_list(&self, frame: &mut [u8], width: u32, height: u32, text_color: [u8; 4], bg_color: [u8; 4]) {
+        let Some(list) = &self.snapshot_list else {
+            return;
+        };
+        
+        let (panel_x, panel_y, panel_width, panel_height) = Self::snapshot_panel_rect(width, list.len());
+        let alpha = bg_color[3] as u16;
+        for y in panel_y..(panel_y + panel_height).min(height) {
+            for x in panel_x..(panel_x + panel_width).min(width) {
+                let offset = ((y * width + x) * 4) as usize;
+                for c in 0..3 {
+                    frame[offset + c] = ((bg_color[c] as u16 * alpha + frame[offset + c] as u16 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+        Self::draw_rect_outline(frame, width, height, (panel_x as i32, panel_y as i32, panel_width as i32, panel_height as i32), text_color, 1);
+        self.draw_simple_text(frame, width, panel_x + 8, panel_y + 8, "Snapshots (click one to restore)", text_color);
         
-        // Render poster picker if active
-        if self.show_poster_picker {
-            self.render_poster_picker(frame, width, height);
+        if list.is_empty() {
+            self.draw_simple_text(frame, width, panel_x + 16, panel_y + SNAPSHOT_LIST_TOP + 4, "None yet - Ctrl+S saves one", text_color);
+        }
+        for (i, (name, modified)) in list.iter().take(SNAPSHOT_LIST_ROWS).enumerate() {
+            let row_y = panel_y + SNAPSHOT_LIST_TOP + i as u32 * SNAPSHOT_ROW_HEIGHT;
+            if self.snapshot_selected == Some(i) {
+                Self::draw_rect_outline(frame, width, height, (panel_x as i32 + 6, row_y as i32, panel_width as i32 - 12, SNAPSHOT_ROW_HEIGHT as i32), text_color, 1);
+            }
+            let age = modified.elapsed().map_or(0, |elapsed| elapsed.as_secs());
+            let age = match age {
+                0..=59 => "just now".to_string(),
+                60..=3599 => format!("{} min ago", age / 60),
+                3600..=86399 => format!("{} h ago", age / 3600),
+                _ => format!("{} d ago", age / 86400),
+            };
+            self.draw_simple_text(frame, width, panel_x + 16, row_y + 4, name, text_color);
+            self.draw_simple_text(frame, width, panel_x + panel_width - 16 - age.len() as u32 * 6, row_y + 4, &age, text_color);
         }
+        
+        let footer = match self.snapshot_selected.and_then(|i| list.get(i)) {
+            Some((name, _)) => format!("Enter: replace the board with '{}'", name),
+            None => "Esc: close".to_string(),
+        };
+        self.draw_simple_text(frame, width, panel_x + 8, panel_y + panel_height - 14, &footer, text_color);
     }
     
     /// Render poster picker overlay
     fn render_poster_picker(&self, frame: &mut [u8], width: u32, height: u32) {
-        let text_color = match self.board.config.mode {
-            BoardMode::Blackboard => [255u8, 255u8, 255u8, 255u8],
-            BoardMode::Whiteboard => [0u8, 0u8, 0u8, 255u8],
+        let text_color = if self.board.config.mode.is_dark() {
+            [255u8, 255u8, 255u8, 255u8]
+        } else {
+            [0u8, 0u8, 0u8, 255u8]
         };
         
-        let bg_color = match self.board.config.mode {
-            BoardMode::Blackboard => [0u8, 0u8, 0u8, 200u8],
-            BoardMode::Whiteboard => [255u8, 255u8, 255u8, 200u8],
+        let bg_color = if self.board.config.mode.is_dark() {
+            [0u8, 0u8, 0u8, 200u8]
+        } else {
+            [255u8, 255u8, 255u8, 200u8]
         };
         
         // Draw semi-transparent overlay panel (center of screen)
@@ -1308,55 +4291,97 @@ impl RickBoard {
         // Draw title
         self.draw_simple_text(frame, width, panel_x + 10, panel_y + 10, "Select a Poster:", text_color);
         
-        // List available posters
-        let mut y_offset = 40;
-        for (i, (name, _path)) in self.available_posters.iter().enumerate() {
-            let display_text = format!("{}. {}", i + 1, name);
-            self.draw_simple_text(frame, width, panel_x + 20, panel_y + y_offset, &display_text, text_color);
-            y_offset += 20;
+        // List the rows scrolled into view: preview, then number and name (cut to fit)
+        let max_chars = ((PICKER_ROW_BUTTONS[0].2 - PICKER_THUMB_SIZE - 24) / 6) as usize;
+        let visible = self.available_posters.iter().enumerate().skip(self.poster_picker_scroll).take(PICKER_VISIBLE_ROWS);
+        for (row, (i, (name, path))) in visible.enumerate() {
+            let row_y = panel_y + PICKER_LIST_TOP + row as u32 * PICKER_ROW_HEIGHT;
+            if let Some(thumbnail) = self.poster_thumbnails.get(path) {
+                // Centered in its square, blended so transparent posters show the panel
+                let thumb_x = panel_x + 12 + (PICKER_THUMB_SIZE - thumbnail.width) / 2;
+                let thumb_y = row_y + (PICKER_THUMB_SIZE - thumbnail.height) / 2;
+                for (ty, line) in thumbnail.pixels.chunks_exact(thumbnail.width as usize * 4).enumerate() {
+                    for (tx, px) in line.chunks_exact(4).enumerate() {
+                        let offset = (((thumb_y + ty as u32) * width + thumb_x + tx as u32) * 4) as usize;
+                        if offset + 3 < frame.len() {
+                            blend_pixel(&mut frame[offset..offset + 4], [px[0], px[1], px[2], px[3]]);
+                        }
+                    }
+                }
+            }
+            let mut label = format!("{}. {}", i + 1, name);
+            if label.chars().count() > max_chars {
+                label = label.chars().take(max_chars - 3).collect::<String>() + "...";
+            }
+            let text_y = row_y + (PICKER_THUMB_SIZE - 7) / 2;
+            self.draw_simple_text(frame, width, panel_x + PICKER_THUMB_SIZE + 20, text_y, &label, text_color);
+            for (_, button, left) in PICKER_ROW_BUTTONS {
+                let rect = ((panel_x + left) as i32, text_y as i32 - 4, button.len() as i32 * 6 + 6, 15);
+                Self::draw_rect_outline(frame, width, height, rect, text_color, 1);
+                self.draw_simple_text(frame, width, panel_x + left + 3, text_y, button, text_color);
+            }
+        }
+        
+        // Scrollbar along the right edge when the list doesn't fit
+        let count = self.available_posters.len();
+        if count > PICKER_VISIBLE_ROWS {
+            let track_height = PICKER_VISIBLE_ROWS as u32 * PICKER_ROW_HEIGHT;
+            let bar_height = (track_height * PICKER_VISIBLE_ROWS as u32 / count as u32).max(8);
+            let bar_y = (track_height - bar_height) * self.poster_picker_scroll as u32 / (count - PICKER_VISIBLE_ROWS) as u32;
+            for y in panel_y + PICKER_LIST_TOP + bar_y..panel_y + PICKER_LIST_TOP + bar_y + bar_height {
+                for x in panel_x + panel_width - 10..panel_x + panel_width - 6 {
+                    let offset = ((y * width + x) * 4) as usize;
+                    if offset + 3 < frame.len() {
+                        frame[offset..offset + 4].copy_from_slice(&text_color);
+                    }
+                }
+            }
         }
         
-        self.draw_simple_text(frame, width, panel_x + 10, panel_y + panel_height - 25, "Click poster name to select", text_color);
+        let footer = if count > PICKER_VISIBLE_ROWS { "Click a poster to select, wheel scrolls" } else { "Click a poster to select" };
+        self.draw_simple_text(frame, width, panel_x + 10, panel_y + panel_height - 25, footer, text_color);
     }
     
     /// Render save progress bar at top center
     fn render_save_progress(&self, frame: &mut [u8], width: u32, time_until_save: f32, is_saving: bool) {
-        let bar_width = 200u32;
+        let bar_width = SAVE_BAR_WIDTH;
         let bar_height = 6u32;
         let bar_x = (width / 2) - (bar_width / 2);
         let bar_y = 10u32;
         
-        let text_color = match self.board.config.mode {
-            BoardMode::Blackboard => [220, 220, 220, 255],
-            BoardMode::Whiteboard => [40, 40, 40, 255],
-        };
+        let text_color = if self.board.config.mode.is_dark() { [220, 220, 220, 255] } else { [40, 40, 40, 255] };
         
-        let bg_color = match self.board.config.mode {
-            BoardMode::Blackboard => [0u8, 0u8, 0u8, 128u8], // 50% transparent black
-            BoardMode::Whiteboard => [255u8, 255u8, 255u8, 153u8], // 60% transparent white
+        let bg_color = if self.board.config.mode.is_dark() {
+            [0u8, 0u8, 0u8, 128u8] // 50% transparent black
+        } else {
+            [255u8, 255u8, 255u8, 153u8] // 60% transparent white
         };
         
-        // Draw progress bar background (empty)
-        for y in bar_y..bar_y + bar_height {
-            for x in bar_x..bar_x + bar_width {
-                let offset = ((y * width + x) * 4) as usize;
-                if offset + 3 < frame.len() {
-                    frame[offset] = text_color[0] / 3;
-                    frame[offset + 1] = text_color[1] / 3;
-                    frame[offset + 2] = text_color[2] / 3;
-                    frame[offset + 3] = 255;
+        // No countdown to show with auto-save off (auto_save_secs = 0), only manual saves
+        let interval = self.config.auto_save_secs as f32;
+        if interval > 0.0 {
+            // Draw progress bar background (empty)
+            for y in bar_y..bar_y + bar_height {
+                for x in bar_x..bar_x + bar_width {
+                    let offset = ((y * width + x) * 4) as usize;
+                    if offset + 3 < frame.len() {
+                        frame[offset] = text_color[0] / 3;
+                        frame[offset + 1] = text_color[1] / 3;
+                        frame[offset + 2] = text_color[2] / 3;
+                        frame[offset + 3] = 255;
+                    }
                 }
             }
-        }
-        
-        // Draw progress bar fill (elapsed time)
-        let progress = (60.0 - time_until_save) / 60.0; // 60 seconds = 1 minute
-        let fill_width = (bar_width as f32 * progress) as u32;
-        for y in bar_y..bar_y + bar_height {
-            for x in bar_x..bar_x + fill_width {
-                let offset = ((y * width + x) * 4) as usize;
-                if offset + 3 < frame.len() {
-                    frame[offset..offset + 4].copy_from_slice(&text_color);
+            
+            // Draw progress bar fill (elapsed share of the interval)
+            let progress = (interval - time_until_save) / interval;
+            let fill_width = (bar_width as f32 * progress) as u32;
+            for y in bar_y..bar_y + bar_height {
+                for x in bar_x..bar_x + fill_width {
+                    let offset = ((y * width + x) * 4) as usize;
+                    if offset + 3 < frame.len() {
+                        frame[offset..offset + 4].copy_from_slice(&text_color);
+                    }
                 }
             }
         }
@@ -1397,7 +4422,7 @@ impl RickBoard {
         let scale = 0.5; // 50% scale
         
         for (i, marker) in self.markers.iter().enumerate() {
-            let is_selected = i == self.drawing_tool.selected_marker_index;
+            let is_selected = self.drawing_tool.selected_marker_index == Some(i);
             let image_data = if is_selected { &marker.open_image } else { &marker.closed_image };
             
             let scaled_width = (marker.width as f32 * scale) as u32;
@@ -1435,6 +4460,162 @@ impl RickBoard {
         }
     }
     
+    /// Draw the recent-colors strip; the swatch matching the pen color gets an outline
+    fn render_recent_colors(&self, frame: &mut [u8], width: u32, height: u32, outline_color: [u8; 4]) {
+        for (i, &color) in self.drawing_tool.recent_colors.iter().enumerate() {
+            let (sx, sy) = self.recent_swatch_origin(i, height);
+            // Translucent colors are shown over the board like the ink they make
+            let alpha = color[3] as u16;
+            for y in sy..(sy + RECENT_SWATCH_SIZE).min(height) {
+                for x in sx..(sx + RECENT_SWATCH_SIZE).min(width) {
+                    let offset = ((y * width + x) * 4) as usize;
+                    for c in 0..3 {
+                        frame[offset + c] = ((color[c] as u16 * alpha + frame[offset + c] as u16 * (255 - alpha)) / 255) as u8;
+                    }
+                }
+            }
+            
+            let thickness = if color == self.drawing_tool.current_color { 2 } else { 1 };
+            let rect = (sx as i32, sy as i32, RECENT_SWATCH_SIZE as i32, RECENT_SWATCH_SIZE as i32);
+            Self::draw_rect_outline(frame, width, height, rect, outline_color, thickness);
+        }
+    }
+    
+    /// Draw the toasts centered below the save progress bar, oldest on top, each fading out
+    /// over its last TOAST_FADE_MILLIS
+    fn render_toasts(&self, frame: &mut [u8], width: u32, height: u32, text_color: [u8; 4], bg_color: [u8; 4]) {
+        let now = Instant::now();
+        let mut panel_y = 40u32;
+        for toast in &self.toasts {
+            let left = toast.until.saturating_duration_since(now).as_millis() as u64;
+            if left == 0 {
+                continue;
+            }
+            let fade = (left * 255 / TOAST_FADE_MILLIS).min(255) as u16;
+            
+            let (panel_width, panel_height) = (toast.message.len() as u32 * 6 + 16, 15u32);
+            let panel_x = (width / 2).saturating_sub(panel_width / 2);
+            let alpha = bg_color[3] as u16 * fade / 255;
+            for y in panel_y..(panel_y + panel_height).min(height) {
+                for x in panel_x..(panel_x + panel_width).min(width) {
+                    let offset = ((y * width + x) * 4) as usize;
+                    for c in 0..3 {
+                        frame[offset + c] = ((bg_color[c] as u16 * alpha + frame[offset + c] as u16 * (255 - alpha)) / 255) as u8;
+                    }
+                }
+            }
+            // Text fades towards the panel color (the glyphs are drawn opaque)
+            let color = if toast.error { TOAST_ERROR_COLOR } else { text_color };
+            let mut faded = color;
+            for c in 0..3 {
+                faded[c] = ((color[c] as u16 * fade + bg_color[c] as u16 * (255 - fade)) / 255) as u8;
+            }
+            self.draw_simple_text(frame, width, panel_x + 8, panel_y + 4, &toast.message, faded);
+            panel_y += panel_height + 3;
+        }
+    }
+    
+    /// Diagnostics line: approximate memory held by the tiles, undo copies and posters, what
+    /// a fully drawn layer would take on this board, and the board's files on disk
+    fn diagnostics_text(&self) -> String {
+        let board = &self.board;
+        let layers = (board.cache.allocated_bytes() + board.drawing_layer.allocated_bytes()
+            + board.scratch_layer.allocated_bytes()) as u64;
+        let undo = board.undo_bytes();
+        let posters: usize = self.posters.iter()
+            .map(|poster| poster.image_data.len() + poster.frames.iter().map(Vec::len).sum::<usize>())
+            .sum();
+        let full_layer = board.config.width as u64 * board.config.height as u64 * 4;
+        format!(
+            "RAM ~{} (tiles {}, undo {} in {} steps, posters {})  full layer {}  disk {}",
+            format_bytes(layers + undo + posters as u64),
+            format_bytes(layers),
+            format_bytes(undo),
+            board.undo_stack.len(),
+            format_bytes(posters as u64),
+            format_bytes(full_layer),
+            format_bytes(board.disk_bytes()),
+        )
+    }
+    
+    /// Draw the diagnostics line (when shown) right-aligned just above the minimap
+    fn render_diagnostics(&self, frame: &mut [u8], width: u32, height: u32, text_color: [u8; 4], bg_color: [u8; 4]) {
+        if !self.show_diagnostics {
+            return;
+        }
+        let text = self.diagnostics_text();
+        let visible: String = text.chars().take((width.saturating_sub(20) / 6) as usize).collect();
+        let (panel_width, panel_height) = (visible.len() as u32 * 6 + 8, 13u32);
+        let (_, minimap_y, _, _) = Self::minimap_rect(width, height, &self.board.config);
+        let Some(panel_y) = minimap_y.checked_sub(panel_height + 4) else {
+            return;
+        };
+        let panel_x = width.saturating_sub(panel_width + 10);
+        
+        let alpha = bg_color[3] as u16;
+        for y in panel_y..panel_y + panel_height {
+            for x in panel_x..(panel_x + panel_width).min(width) {
+                let offset = ((y * width + x) * 4) as usize;
+                for c in 0..3 {
+                    frame[offset + c] = ((bg_color[c] as u16 * alpha + frame[offset + c] as u16 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+        self.draw_simple_text(frame, width, panel_x + 4, panel_y + 3, &visible, text_color);
+    }
+    
+    /// Draw the bottom status bar: board coordinate under the cursor, zoom and board size
+    fn render_status_bar(&self, frame: &mut [u8], width: u32, height: u32, cursor: (f64, f64), text_color: [u8; 4], bg_color: [u8; 4]) {
+        if height < STATUS_BAR_HEIGHT {
+            return;
+        }
+        
+        let bar_y = height - STATUS_BAR_HEIGHT;
+        let alpha = bg_color[3] as u16;
+        for y in bar_y..height {
+            for x in 0..width {
+                let offset = ((y * width + x) * 4) as usize;
+                for c in 0..3 {
+                    frame[offset + c] = ((bg_color[c] as u16 * alpha + frame[offset + c] as u16 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+        
+        // Same conversion as CursorMoved; x wraps so the readout stays within the board
+        let config = &self.board.config;
+        let viewport = &self.board.viewport;
+        let board_x = (viewport.position.x + cursor.0 as f32 / viewport.zoom).rem_euclid(config.width as f32);
+        let mut board_y = viewport.position.y + cursor.1 as f32 / viewport.zoom;
+        if config.wrap_vertical {
+            board_y = board_y.rem_euclid(config.height as f32);
+        }
+        let mut status = format!(
+            "x {}  y {}    zoom {:.0}%    board {} x {}",
+            board_x.floor() as i64,
+            board_y.floor() as i64,
+            viewport.zoom * 100.0,
+            config.width,
+            config.height,
+        );
+        // Filled bookmark slots, e.g. "marks 1 3 7"
+        let marks: Vec<String> = (1..=BOOKMARK_SLOTS).filter(|slot| self.board.bookmarks[slot - 1].is_some()).map(|slot| slot.to_string()).collect();
+        if !marks.is_empty() {
+            status.push_str(&format!("    marks {}", marks.join(" ")));
+        }
+        let metadata = &self.board.metadata;
+        if !metadata.title.is_empty() {
+            status.push_str(&format!("    {}", metadata.title));
+        }
+        if !metadata.author.is_empty() {
+            status.push_str(&format!(" by {}", metadata.author));
+        }
+        // Right-aligned under the minimap (the color markers cover the bar's left end); cut at
+        // the window edge rather than letting it wrap onto the next row
+        let visible: String = status.chars().take((width.saturating_sub(8) / 6) as usize).collect();
+        let text_x = width.saturating_sub(visible.len() as u32 * 6 + 6);
+        self.draw_simple_text(frame, width, text_x, bar_y + 4, &visible, text_color);
+    }
+    
     /// Draw simple text (basic bitmap font)
     fn draw_simple_text(&self, frame: &mut [u8], width: u32, x: u32, y: u32, text: &str, color: [u8; 4]) {
         for (i, ch) in text.chars().enumerate() {
@@ -1445,47 +4626,7 @@ impl RickBoard {
     
     /// Draw a single character (very simple 5x7 bitmap)
     fn draw_char(&self, frame: &mut [u8], width: u32, x: u32, y: u32, ch: char, color: [u8; 4]) {
-        // Simple pixel patterns for basic characters
-        let pattern: &[u8] = match ch {
-            'A' | 'a' => &[0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
-            'B' | 'b' => &[0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
-            'C' | 'c' => &[0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
-            'D' | 'd' => &[0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
-            'E' | 'e' => &[0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
-            'F' | 'f' => &[0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
-            'G' | 'g' => &[0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01110],
-            'H' | 'h' => &[0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
-            'I' | 'i' => &[0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
-            'K' | 'k' => &[0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
-            'L' | 'l' => &[0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
-            'M' | 'm' => &[0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
-            'N' | 'n' => &[0b10001, 0b11001, 0b10101, 0b10101, 0b10011, 0b10001, 0b10001],
-            'O' | 'o' => &[0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
-            'P' | 'p' => &[0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
-            'R' | 'r' => &[0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
-            'S' | 's' => &[0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
-            'T' | 't' => &[0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
-            'U' | 'u' => &[0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
-            'W' | 'w' => &[0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b11011, 0b10001],
-            'X' | 'x' => &[0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
-            'Y' | 'y' => &[0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100],
-            'Z' | 'z' => &[0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
-            '0' => &[0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
-            '1' => &[0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
-            '2' => &[0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
-            '3' => &[0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
-            '4' => &[0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
-            '5' => &[0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
-            '6' => &[0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
-            '7' => &[0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
-            '8' => &[0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
-            '9' => &[0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
-            ':' => &[0b00000, 0b00100, 0b00000, 0b00000, 0b00000, 0b00100, 0b00000],
-            '+' => &[0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
-            '-' | '/' => &[0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
-            ' ' => &[0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000],
-            _ => &[0b11111, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11111],
-        };
+        let pattern = glyph(ch);
         
         for (row, &bits) in pattern.iter().enumerate() {
             for col in 0..5 {
@@ -1502,38 +4643,488 @@ impl RickBoard {
     }
 }
 
+/// The images in the posters folder as (file name, path), sorted by name
+fn scan_posters(dir: &Path) -> Vec<(String, String)> {
+    let mut posters = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if let Some(path_str) = entry.path().to_str() {
+                if path_str.ends_with(".png") || path_str.ends_with(".jpg") || path_str.ends_with(".jpeg") {
+                    if let Some(name) = entry.file_name().to_str() {
+                        posters.push((name.to_string(), path_str.to_string()));
+                    }
+                }
+            }
+        }
+    }
+    posters.sort();
+    posters
+}
+
+/// Open the system file manager on a file (selected where the platform supports it)
+fn reveal_in_file_manager(path: &Path) -> io::Result<()> {
+    let path = path.canonicalize()?;
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = std::process::Command::new("open");
+        command.arg("-R").arg(&path);
+        command
+    } else {
+        // No common way to select a file; open its folder instead
+        let mut command = std::process::Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(&path));
+        command
+    };
+    command.spawn().map(|_| ())
+}
+
+/// Break text into lines of at most `max_chars`, at spaces where possible
+fn wrap_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word;
+        if !line.is_empty() && line.len() + 1 + word.len() > max_chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        // A word longer than a line is cut
+        while word.len() > max_chars {
+            let cut = word.char_indices().nth(max_chars).map_or(word.len(), |(i, _)| i);
+            lines.push(word[..cut].to_string());
+            word = &word[cut..];
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
 struct App {
-    window: Option<Rc<Window>>,
+    /// Shared with the surface inside `pixels`, which holds its own clone of the Arc, so the
+    /// window can't be destroyed while anything still renders to it
+    window: Option<Arc<Window>>,
     pixels: Option<Pixels<'static>>,
     rickboard: RickBoard,
     mouse_down: bool,
     right_mouse_down: bool, // Track right mouse button for eraser
     cursor_pos: (f64, f64), // Track cursor position for zoom
+    cursor_in_window: bool, // The brush outline is only drawn while the cursor is over the window
     render_width: u32,
     render_height: u32,
     frame_count: u32,
     last_fps_update: Instant,
     fps: f32,
     last_save: Instant,
+    auto_save_interval: f32, // Seconds between auto-saves (config.toml auto_save_secs), 0 = off
+    key_bindings: KeyBindings, // Defaults plus config.toml [keys] overrides
     is_saving: bool,
     has_unsaved_changes: bool,
     modifiers: ModifiersState,
     save_message_until: Option<Instant>, // Show saving message until this time
+    held_pan_keys: HashMap<KeyCode, Action>, // Pan keys (WASD by default) currently held down
+    pan_speed: f32, // Keyboard pan speed in screen pixels per second (config.toml pan_step)
+    pan_hold_secs: f32, // How long pan keys have been held without a break (acceleration)
+    last_frame: Instant, // Start of the previous frame (for time-based panning)
+    frame_interval: Option<std::time::Duration>, // Shortest time between frames (config.toml max_fps), None = uncapped
+    next_frame_at: Option<Instant>, // A redraw came too early for the cap and is due again at this time
+    animating: bool, // The previous frame asked for the next one right away (pans, glides, zooms, legend slide, replay, save in flight)
+    focused: bool,
+    occluded: bool, // Fully covered by other windows (where the platform reports it)
+    minimized: bool, // Resized to 0x0
+    middle_pan: Option<(f64, f64)>, // Last cursor position while middle-dragging the view
+    space_pan: bool, // The hand tool key (Space) is held: left-drag pans instead of drawing
+    hand_drag: bool, // The current middle_pan drag was started with the left button under the hand tool
+    last_pan_move: Instant, // Time of the last middle-drag movement (velocity sampling)
+    last_left_press: Option<(Instant, (f64, f64))>, // Time and place of the previous canvas click (double-click detection)
+    zoom_target: Option<(f32, (f64, f64))>, // Double-click zoom in flight: target zoom and the screen point it centers on
+    bookmark_jump: Option<(Bookmark, Bookmark, f32)>, // Jump to a bookmark in flight: from, to and seconds so far
+}
+
+/// How long a toast message stays up, and an error toast
+const TOAST_MILLIS: u64 = 1500;
+const TOAST_ERROR_MILLIS: u64 = 4000;
+
+/// Toasts fade out over their last this many milliseconds
+const TOAST_FADE_MILLIS: u64 = 300;
+
+/// Most toasts on screen at once; a new one pushes out the oldest
+const TOAST_LIMIT: usize = 4;
+
+/// Text color of error toasts, readable on light and dark boards
+const TOAST_ERROR_COLOR: [u8; 4] = [220, 48, 48, 255];
+
+/// How often to check on posters decoding in the background
+const POSTER_LOAD_POLL_MILLIS: u64 = 50;
+
+/// Poster picker rows: a preview square plus a gap, starting below the title
+const PICKER_THUMB_SIZE: u32 = 32;
+const PICKER_ROW_HEIGHT: u32 = 36;
+const PICKER_LIST_TOP: u32 = 34;
+
+/// Poster rows that fit in the picker above its footer; the rest are reached by scrolling
+const PICKER_VISIBLE_ROWS: usize = 6;
+
+/// Width of the auto-save countdown bar in screen pixels
+const SAVE_BAR_WIDTH: u32 = 200;
+
+/// Height of the status bar along the bottom edge (the minimap sits above it)
+const STATUS_BAR_HEIGHT: u32 = 14;
+
+/// Scrollbar thickness, and the shortest thumb (so a huge board still has something to grab)
+const SCROLLBAR_SIZE: u32 = 6;
+const SCROLLBAR_MIN_THUMB: f32 = 16.0;
+
+/// Minimap size in screen pixels (height follows the board's aspect within these limits)
+const MINIMAP_WIDTH: u32 = 320;
+const MINIMAP_MIN_HEIGHT: u32 = 24;
+const MINIMAP_MAX_HEIGHT: u32 = 160;
+
+/// Samples per minimap pixel along each axis
+const MINIMAP_SAMPLES: usize = 4;
+
+/// Dragged posters snap to another poster's edge or center within this many screen pixels
+const POSTER_GUIDE_SNAP: f32 = 6.0;
+
+/// Alignment guide color while dragging a poster
+const POSTER_GUIDE_COLOR: [u8; 4] = [255, 0, 200, 255];
+
+/// How far a duplicated poster lands from the original, in screen pixels down and right
+const POSTER_DUPLICATE_OFFSET: f32 = 20.0;
+
+/// Poster rotation per Ctrl+Shift+Wheel notch (5 degrees)
+const POSTER_ROTATION_STEP: f32 = std::f32::consts::PI / 36.0;
+
+/// config.toml pan_step is the keyboard pan distance per 60th of a second
+const PAN_STEPS_PER_SEC: f32 = 60.0;
+
+/// A pan key held this many seconds starts to speed up...
+const PAN_ACCEL_DELAY: f32 = 0.4;
+
+/// ...reaching full speed this many seconds later...
+const PAN_ACCEL_RAMP: f32 = 1.2;
+
+/// ...at this multiple of the configured speed
+const PAN_ACCEL_MAX: f32 = 4.0;
+
+/// Holding Shift multiplies the keyboard pan speed by this much
+const PAN_FAST_FACTOR: f32 = 4.0;
+
+/// Momentum decay rate after a pan flick (per second, exponential)
+const PAN_FRICTION: f32 = 4.0;
+
+/// Momentum stops once the glide is slower than this many screen pixels per second
+const PAN_MIN_SPEED: f32 = 20.0;
+
+/// A middle-drag released after resting this long doesn't glide
+const PAN_FLICK_TIMEOUT: f32 = 0.08;
+
+/// Zoom range the renderer supports; config.toml min_zoom/max_zoom and --zoom stay inside it
+const ZOOM_RANGE: std::ops::RangeInclusive<f32> = 0.01..=16.0;
+
+/// Two clicks count as a double-click within this many milliseconds and screen pixels
+const DOUBLE_CLICK_MILLIS: u128 = 400;
+const DOUBLE_CLICK_DISTANCE: f64 = 4.0;
+
+/// Zoom factor of one double-click
+const DOUBLE_CLICK_ZOOM: f32 = 2.0;
+
+/// How quickly an animated zoom closes in on its target (per second, exponential)
+const ZOOM_ANIMATION_RATE: f32 = 14.0;
+
+/// How long the glide to a bookmark takes, in seconds
+const BOOKMARK_JUMP_SECS: f32 = 0.35;
+
+impl App {
+    /// Start dragging the view with the cursor (middle button, or left with the hand tool)
+    fn start_drag_pan(&mut self) {
+        self.bookmark_jump = None;
+        self.middle_pan = Some(self.cursor_pos);
+        self.last_pan_move = Instant::now();
+        self.rickboard.board.viewport.pan_velocity = Point { x: 0.0, y: 0.0 };
+        self.update_cursor();
+    }
+    
+    /// Let go of a drag pan; a flick keeps the view gliding
+    fn end_drag_pan(&mut self) {
+        self.middle_pan = None;
+        if self.last_pan_move.elapsed().as_secs_f32() > PAN_FLICK_TIMEOUT {
+            self.rickboard.board.viewport.pan_velocity = Point { x: 0.0, y: 0.0 };
+        }
+        self.update_cursor();
+    }
+    
+    /// Show a grabbing hand while dragging the view, an open hand while the hand tool is held
+    fn update_cursor(&self) {
+        let icon = if self.middle_pan.is_some() {
+            CursorIcon::Grabbing
+        } else if self.space_pan {
+            CursorIcon::Grab
+        } else {
+            CursorIcon::Default
+        };
+        if let Some(window) = &self.window {
+            window.set_cursor(icon);
+        }
+    }
+    
+    /// Note a left press on the canvas; returns true if it completes a double-click
+    fn register_left_press(&mut self) -> bool {
+        let (x, y) = self.cursor_pos;
+        let double_click = self.last_left_press.is_some_and(|(time, (px, py))| {
+            time.elapsed().as_millis() <= DOUBLE_CLICK_MILLIS && (x - px).hypot(y - py) <= DOUBLE_CLICK_DISTANCE
+        });
+        // A third click starts over rather than counting as another double-click
+        self.last_left_press = if double_click { None } else { Some((Instant::now(), self.cursor_pos)) };
+        double_click
+    }
+    
+    /// Move the zoom toward a double-click target, keeping the clicked point under the cursor
+    fn apply_zoom_animation(&mut self, dt: f32) {
+        let Some((target, (sx, sy))) = self.zoom_target else {
+            return;
+        };
+        let zoom = self.rickboard.board.viewport.zoom;
+        let mut next = target + (zoom - target) * (-ZOOM_ANIMATION_RATE * dt).exp();
+        if (next - target).abs() < target * 0.01 {
+            next = target;
+            self.zoom_target = None;
+        }
+        self.rickboard.board.zoom_at(next, sx, sy, self.render_height);
+    }
+    
+    /// Run one of the clear actions (undoable)
+    fn clear_board(&mut self, action: Action) {
+        let what = match action {
+            Action::ClearDrawing => "Drawing",
+            Action::ClearBackground => "Background",
+            _ => "Board",
+        };
+        self.rickboard.clear_board(action);
+        if !self.rickboard.board.undo_stack.is_empty() {
+            self.rickboard.show_toast(format!("{} cleared (Ctrl+Z to undo)", what));
+        }
+        self.save_board_now();
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+    
+    /// Start saving the board on the worker thread (finished in run_saves). If a save is
+    /// still running, the change waits for the next one.
+    fn save_board_now(&mut self) {
+        if self.rickboard.board.start_sync() {
+            self.is_saving = true;
+        } else {
+            self.has_unsaved_changes = true;
+        }
+    }
+    
+    /// Store the current view in a bookmark slot (0-based)
+    fn set_bookmark(&mut self, slot: usize) {
+        match self.rickboard.board.set_bookmark(slot) {
+            Ok(()) => {
+                info!("Bookmark {} set", slot + 1);
+                self.rickboard.show_toast(format!("Bookmark {} set", slot + 1));
+            }
+            Err(e) => self.rickboard.show_error(format!("Could not save bookmarks: {}", e)),
+        }
+    }
+    
+    /// Animate to a preset zoom level around the window center, so the board point there stays put
+    /// (fitting the height also ends up centered vertically, since the board then fits the window)
+    fn zoom_to_preset(&mut self, action: Action) {
+        let zoom = match action {
+            Action::FitHeight => self.render_height as f32 / self.rickboard.board.config.height as f32,
+            Action::Zoom200 => 2.0,
+            Action::Zoom400 => 4.0,
+            _ => 1.0,
+        };
+        self.bookmark_jump = None;
+        self.rickboard.board.viewport.pan_velocity = Point { x: 0.0, y: 0.0 };
+        let center = (self.render_width as f64 / 2.0, self.render_height as f64 / 2.0);
+        self.zoom_target = Some((zoom.clamp(self.rickboard.zoom_limits.0, self.rickboard.zoom_limits.1), center));
+    }
+    
+    /// Start gliding to the view in a bookmark slot (0-based)
+    fn jump_to_bookmark(&mut self, slot: usize) {
+        let Some(mut to) = self.rickboard.board.bookmarks[slot] else {
+            self.rickboard.show_toast(format!("Bookmark {} is empty (Ctrl+{} sets it)", slot + 1, slot + 1));
+            return;
+        };
+        to.zoom = to.zoom.clamp(self.rickboard.zoom_limits.0, self.rickboard.zoom_limits.1);
+        let viewport = &self.rickboard.board.viewport;
+        let from = Bookmark { position: viewport.position, zoom: viewport.zoom };
+        self.zoom_target = None;
+        self.bookmark_jump = Some((from, to, 0.0));
+    }
+    
+    /// Move the view along a bookmark jump: the view center travels the short way around the
+    /// seam while the zoom changes at a steady rate, eased in and out
+    fn apply_bookmark_jump(&mut self, dt: f32) {
+        let Some((from, to, elapsed)) = self.bookmark_jump else {
+            return;
+        };
+        let elapsed = elapsed + dt;
+        let viewport = &mut self.rickboard.board.viewport;
+        if elapsed >= BOOKMARK_JUMP_SECS {
+            viewport.position = to.position;
+            viewport.zoom = to.zoom;
+            self.bookmark_jump = None;
+        } else {
+            let t = elapsed / BOOKMARK_JUMP_SECS;
+            let t = t * t * (3.0 - 2.0 * t);
+            let (half_w, half_h) = (self.render_width as f32 / 2.0, self.render_height as f32 / 2.0);
+            let center = |view: &Bookmark| (view.position.x + half_w / view.zoom, view.position.y + half_h / view.zoom);
+            let ((fx, fy), (tx, ty)) = (center(&from), center(&to));
+            let width = self.rickboard.board.config.width as f32;
+            let dx = (tx - fx + width / 2.0).rem_euclid(width) - width / 2.0;
+            viewport.zoom = from.zoom * (to.zoom / from.zoom).powf(t);
+            viewport.position.x = (fx + dx * t - half_w / viewport.zoom).rem_euclid(width);
+            viewport.position.y = fy + (ty - fy) * t - half_h / viewport.zoom;
+            self.bookmark_jump = Some((from, to, elapsed));
+        }
+        self.rickboard.board.clamp_viewport(self.render_height);
+    }
+    
+    /// Pan the viewport for every held pan key, scaled by frame time and zoom
+    fn apply_held_pan(&mut self, dt: f32) {
+        if self.held_pan_keys.is_empty() {
+            return;
+        }
+        
+        // Speeds up after a moment of holding, so long distances don't take forever
+        self.pan_hold_secs += dt;
+        let ramp = ((self.pan_hold_secs - PAN_ACCEL_DELAY) / PAN_ACCEL_RAMP).clamp(0.0, 1.0);
+        let accel = 1.0 + (PAN_ACCEL_MAX - 1.0) * ramp;
+        
+        // Constant on-screen speed regardless of zoom level; Shift pans faster
+        let fast = if self.modifiers.shift_key() { PAN_FAST_FACTOR } else { 1.0 };
+        let step = self.pan_speed * accel * fast * dt / self.rickboard.board.viewport.zoom;
+        // W and ArrowUp held together still pan at the normal speed
+        let directions: HashSet<Action> = self.held_pan_keys.values().copied().collect();
+        let viewport = &mut self.rickboard.board.viewport;
+        for action in directions {
+            match action {
+                Action::PanUp => viewport.position.y -= step,
+                Action::PanDown => viewport.position.y += step,
+                Action::PanLeft => viewport.position.x -= step,
+                Action::PanRight => viewport.position.x += step,
+                _ => {}
+            }
+        }
+        self.rickboard.board.clamp_viewport(self.render_height);
+    }
+    
+    /// Move the viewport by a single board pixel, for lining things up precisely
+    fn nudge_view(&mut self, direction: Action) {
+        let viewport = &mut self.rickboard.board.viewport;
+        match direction {
+            Action::PanUp => viewport.position.y -= 1.0,
+            Action::PanDown => viewport.position.y += 1.0,
+            Action::PanLeft => viewport.position.x -= 1.0,
+            Action::PanRight => viewport.position.x += 1.0,
+            _ => {}
+        }
+        self.rickboard.board.clamp_viewport(self.render_height);
+    }
+    
+    /// Pick up a finished background save and start an auto-save when one is due. Runs on
+    /// every frame and on timer wake-ups, so saving carries on while the window is inactive.
+    fn run_saves(&mut self) {
+        // Pick up a background save that has finished
+        if let Some(result) = self.rickboard.board.poll_sync() {
+            self.is_saving = false;
+            if let Err(e) = result {
+                self.rickboard.show_save_error(format!("Save error: {}", e));
+                self.has_unsaved_changes = true;
+            } else {
+                self.save_message_until = Some(Instant::now() + std::time::Duration::from_millis(500));
+                self.rickboard.show_toast("Saved".to_string());
+            }
+        }
+
+        // Check for auto-save (every auto_save_interval seconds, only if changes made)
+        let time_since_save = self.last_save.elapsed().as_secs_f32();
+        let auto_save_due = self.auto_save_interval > 0.0 && time_since_save >= self.auto_save_interval;
+        if auto_save_due && !self.is_saving && self.has_unsaved_changes && self.rickboard.replay.is_none() {
+            if self.rickboard.board.start_sync() {
+                self.is_saving = true;
+                self.has_unsaved_changes = false;
+            }
+            // Save posters
+            if let Err(e) = self.rickboard.save_posters() {
+                self.rickboard.show_save_error(format!("Auto-save poster error: {}", e));
+            }
+            self.last_save = Instant::now();
+        }
+    }
+    
+    /// Pin dropped posters that finished decoding, and take in picker previews
+    fn poll_poster_loads(&mut self) {
+        if self.rickboard.poll_poster_loads() {
+            self.has_unsaved_changes = true;
+        }
+        self.rickboard.poll_thumbnails();
+    }
+    
+    /// Focused and visible; otherwise frames aren't composed (see RedrawRequested)
+    fn is_active(&self) -> bool {
+        self.focused && !self.occluded && !self.minimized
+    }
+    
+    /// Keep gliding after a middle-drag flick, slowing down exponentially.
+    /// x wraps around the cylinder; y stops at the board edges unless the board wraps vertically.
+    fn apply_pan_momentum(&mut self, dt: f32) {
+        let board = &mut self.rickboard.board;
+        if self.middle_pan.is_some() || (board.viewport.pan_velocity.x == 0.0 && board.viewport.pan_velocity.y == 0.0) {
+            return;
+        }
+        
+        let width = board.config.width as f32;
+        let viewport = &mut board.viewport;
+        viewport.position.x = (viewport.position.x + viewport.pan_velocity.x * dt).rem_euclid(width);
+        viewport.position.y += viewport.pan_velocity.y * dt;
+        if board.clamp_viewport(self.render_height) {
+            board.viewport.pan_velocity.y = 0.0;
+        }
+        
+        let viewport = &mut board.viewport;
+        let decay = (-PAN_FRICTION * dt).exp();
+        viewport.pan_velocity.x *= decay;
+        viewport.pan_velocity.y *= decay;
+        let speed = viewport.pan_velocity.x.hypot(viewport.pan_velocity.y) * viewport.zoom;
+        if speed < PAN_MIN_SPEED {
+            viewport.pan_velocity = Point { x: 0.0, y: 0.0 };
+        }
+    }
 }
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {        if self.pixels.is_none() {
+            // Boards with a title show it, since file names aren't always telling
+            let title = match self.rickboard.board.metadata.title.as_str() {
+                "" => "RickBoard - Virtual Blackboard/Whiteboard".to_string(),
+                title => format!("{} - RickBoard", title),
+            };
             let window_attrs = Window::default_attributes()
-                .with_title("RickBoard - Virtual Blackboard/Whiteboard")
+                .with_title(title)
                 .with_inner_size(winit::dpi::LogicalSize::new(1024u32, 768u32));
             
-            let window = Rc::new(event_loop.create_window(window_attrs).unwrap());
+            let window = Arc::new(event_loop.create_window(window_attrs).unwrap());
             let window_size = window.inner_size();
             
-            // Leak an Rc clone to create a 'static reference for Pixels
-            let window_clone = Rc::clone(&window);
-            let window_ref: &'static Window = unsafe { &*(Rc::into_raw(window_clone) as *const Window) };
-            let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, window_ref);
+            // The surface takes an owning handle rather than a borrow, which is what lets
+            // Pixels be 'static; the window is freed once both Arcs are dropped
+            let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, Arc::clone(&window));
             let pixels = Pixels::new(window_size.width, window_size.height, surface_texture).unwrap();
             
             self.render_width = window_size.width;
@@ -1544,22 +5135,115 @@ impl ApplicationHandler for App {
         }
     }
 
+    /// A timer we set in about_to_wait ran out: draw the change it was waiting for
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        if matches!(cause, StartCause::ResumeTimeReached { .. }) {
+            // Inactive windows may not get redraws at all, so saves and loads are checked here too
+            self.run_saves();
+            self.poll_poster_loads();
+            if self.is_active() {
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            }
+        }
+    }
+    
+    /// Sleep until the next input, or until the next change that happens on a clock: the
+    /// auto-save bar growing by a pixel (which also catches the auto-save coming due), the
+    /// save message or a toast expiring, an animated poster's next frame, or a frame held back
+    /// by the frame cap. An inactive window only wakes for the auto-save. Either way it checks
+    /// on posters that are still decoding.
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let now = Instant::now();
+        let mut wake = None;
+        if self.is_active() {
+            let expiries = [
+                self.next_frame_at,
+                self.save_message_until,
+                self.rickboard.next_toast_fade(),
+                self.rickboard.next_poster_frame(),
+            ];
+            wake = expiries.into_iter().flatten().filter(|&until| until > now).min();
+        }
+        if self.auto_save_interval > 0.0 {
+            let tick = if self.is_active() {
+                let pixel_secs = (self.auto_save_interval / SAVE_BAR_WIDTH as f32).max(0.05);
+                now + std::time::Duration::from_secs_f32(pixel_secs)
+            } else if self.is_saving {
+                now + std::time::Duration::from_millis(250) // Poll the save in flight
+            } else {
+                (self.last_save + std::time::Duration::from_secs_f32(self.auto_save_interval)).max(now)
+            };
+            wake = Some(wake.map_or(tick, |until: Instant| until.min(tick)));
+        } else if self.is_saving && !self.is_active() {
+            wake = Some(now + std::time::Duration::from_millis(250));
+        }
+        if !self.rickboard.poster_loads.is_empty() || self.rickboard.thumbnail_loads.is_some() {
+            // Check on images decoding in the background
+            let poll = now + std::time::Duration::from_millis(POSTER_LOAD_POLL_MILLIS);
+            wake = Some(wake.map_or(poll, |until: Instant| until.min(poll)));
+        }
+        event_loop.set_control_flow(wake.map_or(ControlFlow::Wait, ControlFlow::WaitUntil));
+    }
+    
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _window_id: WindowId, event: WindowEvent) {
+        // Nothing redraws on its own any more: any input may change what's on screen
+        if !matches!(event, WindowEvent::RedrawRequested) {
+            if let Some(window) = &self.window {
+                window.request_redraw();
+            }
+        }
+        
         match event {
             WindowEvent::CloseRequested => {
-                println!("Closing RickBoard...");
-                let _ = self.rickboard.board.sync();
+                info!("Closing RickBoard...");
+                self.rickboard.stop_replay();
+                self.rickboard.deselect();
+                // Waits for a background save in flight before writing the rest. If that fails,
+                // stay open and say so; closing again with the error up quits anyway
+                if let Err(e) = self.rickboard.board.sync() {
+                    if self.rickboard.error_dialog.is_none() {
+                        self.rickboard.show_save_error(format!("Save error: {} - close again to quit without saving", e));
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                        return;
+                    }
+                    error!("Save error: {}", e);
+                }
                 let _ = self.rickboard.save_posters();
+                if let Err(e) = self.rickboard.save_session() {
+                    error!("Could not save the session: {}", e);
+                }
                 event_loop.exit();
             }
             
+            WindowEvent::Focused(focused) => {
+                self.focused = focused;
+                // Key releases aren't delivered while unfocused - don't keep panning forever
+                if !focused {
+                    self.held_pan_keys.clear();
+                    self.middle_pan = None;
+                    self.space_pan = false;
+                    self.hand_drag = false;
+                    self.update_cursor();
+                }
+            }
+            
+            WindowEvent::Occluded(occluded) => self.occluded = occluded,
+            
             WindowEvent::Resized(new_size) => {
+                self.minimized = new_size.width == 0 || new_size.height == 0;
+                if self.minimized {
+                    return; // Keep the old buffer; there's nothing to draw into
+                }
                 if let Some(pixels) = &mut self.pixels {
                     if let Err(e) = pixels.resize_surface(new_size.width, new_size.height) {
-                        eprintln!("Failed to resize surface: {}", e);
+                        error!("Failed to resize surface: {}", e);
                     }
                     if let Err(e) = pixels.resize_buffer(new_size.width, new_size.height) {
-                        eprintln!("Failed to resize buffer: {}", e);
+                        error!("Failed to resize buffer: {}", e);
                     }
                     self.render_width = new_size.width;
                     self.render_height = new_size.height;
@@ -1568,33 +5252,73 @@ impl ApplicationHandler for App {
             
             WindowEvent::ModifiersChanged(new_modifiers) => {
                 self.modifiers = new_modifiers.state();
+                
+                // Show/hide the grab highlight as Ctrl goes down/up over a poster
+                let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                if self.rickboard.update_hovered_poster(board_x, board_y, self.modifiers.control_key()) {
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                }
             }
             
             WindowEvent::MouseInput { state, button, .. } => {
+                // Hand tool: left-drag pans like the middle button
+                if button == MouseButton::Left && (self.space_pan || self.hand_drag) {
+                    match state {
+                        ElementState::Pressed => {
+                            self.hand_drag = true;
+                            self.start_drag_pan();
+                        }
+                        ElementState::Released if self.hand_drag => {
+                            self.hand_drag = false;
+                            self.end_drag_pan();
+                        }
+                        ElementState::Released => {}
+                    }
+                    return;
+                }
+                
+                // A click (other than a middle-drag pan) ends a replay and does nothing else
+                if self.rickboard.replay.is_some() && state == ElementState::Pressed && button != MouseButton::Middle {
+                    self.rickboard.stop_replay();
+                    info!("Replay stopped");
+                    return;
+                }
+                
                 match button {
                     MouseButton::Left => {
                         match state {
                             ElementState::Pressed => {
+                                // The zoom slider takes over from a double-click zoom still in flight
+                                if self.rickboard.on_legend_slider(self.cursor_pos.0, self.cursor_pos.1, self.render_width, self.render_height) {
+                                    self.zoom_target = None;
+                                    self.bookmark_jump = None;
+                                }
                                 // Check if click is on UI first
                                 if let Ok((on_ui, mode_toggled)) = self.rickboard.handle_ui_click(self.cursor_pos.0, self.cursor_pos.1, self.render_height, self.render_width) {
                                     if mode_toggled {
-                                        self.has_unsaved_changes = true;
+                                        self.save_board_now();
                                     }
                                     if !on_ui {
-                                        // Check if we're placing a poster
-                                        if let Some((image_data, width, height, name)) = self.rickboard.placing_poster.take() {
+                                        let double_click = self.register_left_press();
+                                        if double_click && self.rickboard.placing_poster.is_none() && !self.modifiers.control_key() {
+                                            // Double-click: zoom in on the point (Alt zooms out), chaining onto
+                                            // a zoom that is still animating
+                                            let factor = if self.modifiers.alt_key() { 1.0 / DOUBLE_CLICK_ZOOM } else { DOUBLE_CLICK_ZOOM };
+                                            let from = self.zoom_target.map_or(self.rickboard.board.viewport.zoom, |(zoom, _)| zoom);
+                                            self.bookmark_jump = None;
+                                            self.zoom_target = Some(((from * factor).clamp(self.rickboard.zoom_limits.0, self.rickboard.zoom_limits.1), self.cursor_pos));
+                                        } else if let Some(mut poster) = self.rickboard.placing_poster.take() {
+                                            // Placing a poster
                                             // Convert screen coords to board coords
                                             let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
                                             let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
                                             
-                                            self.rickboard.posters.push(PinnedPoster {
-                                                position: Point { x: board_x, y: board_y },
-                                                image_data,
-                                                width,
-                                                height,
-                                                name,
-                                                scale: 1.0,
-                                            });
+                                            poster.position = Point { x: board_x, y: board_y };
+                                            self.rickboard.posters.push(poster);
+                                            self.rickboard.record(UndoAction::PosterAdd { index: self.rickboard.posters.len() - 1 });
                                             self.has_unsaved_changes = true;
                                         } else if self.modifiers.control_key() {
                                             // Ctrl+Click to select/move poster
@@ -1603,18 +5327,47 @@ impl ApplicationHandler for App {
                                             
                                             if let Some(poster_idx) = self.rickboard.find_poster_at(board_x, board_y) {
                                                 self.rickboard.selected_poster_index = Some(poster_idx);
-                                                // Calculate drag offset
+                                                // Drag offset is measured wrap-aware so grabbing across the seam doesn't jump
                                                 let poster = &self.rickboard.posters[poster_idx];
-                                                self.rickboard.poster_drag_offset = Some(Point {
-                                                    x: board_x - poster.position.x,
-                                                    y: board_y - poster.position.y,
-                                                });
+                                                self.rickboard.poster_drag_offset = self.rickboard.poster_local_point(poster, board_x, board_y);
+                                                self.rickboard.poster_drag_origin = Some(poster.position);
                                             } else {
                                                 self.rickboard.selected_poster_index = None;
                                                 self.rickboard.poster_drag_offset = None;
+                                                self.rickboard.locked_poster_hint(board_x, board_y);
+                                            }
+                                        } else if self.rickboard.drawing_tool.tool == Tool::Fill {
+                                            let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                            let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                            self.rickboard.fill_at(Point { x: board_x, y: board_y });
+                                            self.has_unsaved_changes = true;
+                                        } else if self.rickboard.drawing_tool.tool == Tool::Text {
+                                            let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                            let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                            self.rickboard.start_text(Point { x: board_x, y: board_y });
+                                        } else if self.rickboard.drawing_tool.tool == Tool::Measure {
+                                            let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                            let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                            let point = Point { x: board_x, y: board_y };
+                                            self.rickboard.measure = Some((point, point));
+                                        } else if self.rickboard.drawing_tool.tool == Tool::Pen {
+                                            let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                            let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                            self.rickboard.add_pen_anchor(Point { x: board_x, y: board_y });
+                                        } else if self.rickboard.drawing_tool.tool == Tool::Select {
+                                            let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                            let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                            if self.rickboard.press_selection(Point { x: board_x, y: board_y }) {
+                                                self.has_unsaved_changes = true;
                                             }
                                         } else {
                                             self.mouse_down = true;
+                                            if self.modifiers.shift_key() {
+                                                // Shift-drag: anchor a straight line at the press point
+                                                let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                                let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                                self.rickboard.start_line(Point { x: board_x, y: board_y }, false);
+                                            }
                                         }
                                     }
                                     if let Some(window) = &self.window {
@@ -1624,16 +5377,42 @@ impl ApplicationHandler for App {
                             }
                             ElementState::Released => {
                                 self.mouse_down = false;
+                                self.rickboard.end_legend_drag();
+                                self.rickboard.end_scrollbar_drag();
                                 self.rickboard.stop_drawing();
-                                // Release poster drag
-                                if self.rickboard.selected_poster_index.is_some() {
+                                self.rickboard.drawing_tool.pen_dragging = false;
+                                self.rickboard.end_selection_drag();
+                                // The measurement is only shown while dragging; keep a copy in the console
+                                if let Some((anchor, end)) = self.rickboard.measure.take() {
+                                    let (dx, dy) = self.rickboard.measure_delta(anchor, end);
+                                    info!("Measured {:.1} px ({:.0}, {:.0})", (dx * dx + dy * dy).sqrt(), dx, dy);
+                                    if let Some(window) = &self.window {
+                                        window.request_redraw();
+                                    }
+                                }
+                                // Release poster drag (undoable if it actually moved)
+                                if let Some(index) = self.rickboard.selected_poster_index {
+                                    if let Some(origin) = self.rickboard.poster_drag_origin.take() {
+                                        let position = self.rickboard.posters[index].position;
+                                        if position.x != origin.x || position.y != origin.y {
+                                            self.rickboard.record(UndoAction::PosterMove { index, position: origin });
+                                        }
+                                    }
                                     self.rickboard.selected_poster_index = None;
                                     self.rickboard.poster_drag_offset = None;
+                                    self.rickboard.poster_guides.clear();
                                     self.has_unsaved_changes = true;
                                 }
                             }
                         }
                     }
+                    MouseButton::Middle => {
+                        // Middle-drag pans the view; a flick keeps it gliding after release
+                        match state {
+                            ElementState::Pressed => self.start_drag_pan(),
+                            ElementState::Released => self.end_drag_pan(),
+                        }
+                    }
                     MouseButton::Right => {
                         match state {
                             ElementState::Pressed => {
@@ -1643,14 +5422,25 @@ impl ApplicationHandler for App {
                                     let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
                                     
                                     if let Some(poster_idx) = self.rickboard.find_poster_at(board_x, board_y) {
-                                        self.rickboard.posters.remove(poster_idx);
+                                        let poster = self.rickboard.posters.remove(poster_idx);
+                                        self.rickboard.record(UndoAction::PosterDelete { index: poster_idx, poster });
+                                        // Indices shifted - re-pick whatever is now under the cursor
+                                        self.rickboard.update_hovered_poster(board_x, board_y, true);
                                         self.has_unsaved_changes = true;
                                         if let Some(window) = &self.window {
                                             window.request_redraw();
                                         }
+                                    } else {
+                                        self.rickboard.locked_poster_hint(board_x, board_y);
                                     }
                                 } else {
                                     self.right_mouse_down = true;
+                                    if self.modifiers.shift_key() {
+                                        // Shift+right-drag erases along a straight line
+                                        let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                        let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                        self.rickboard.start_line(Point { x: board_x, y: board_y }, true);
+                                    }
                                 }
                             }
                             ElementState::Released => {
@@ -1665,16 +5455,55 @@ impl ApplicationHandler for App {
             
             WindowEvent::CursorMoved { position, .. } => {
                 self.cursor_pos = (position.x, position.y);
+                self.cursor_in_window = true;
+                
+                // Middle-drag: move the board with the cursor and track the flick velocity
+                if let Some((last_x, last_y)) = self.middle_pan {
+                    let viewport = &mut self.rickboard.board.viewport;
+                    let dx = (last_x - position.x) as f32 / viewport.zoom;
+                    let dy = (last_y - position.y) as f32 / viewport.zoom;
+                    viewport.position.x = (viewport.position.x + dx).rem_euclid(self.rickboard.board.config.width as f32);
+                    viewport.position.y += dy;
+                    
+                    let dt = self.last_pan_move.elapsed().as_secs_f32().max(0.001);
+                    self.last_pan_move = Instant::now();
+                    // Smooth over a few events so a single jittery sample doesn't decide the glide
+                    viewport.pan_velocity.x = viewport.pan_velocity.x * 0.5 + dx / dt * 0.5;
+                    viewport.pan_velocity.y = viewport.pan_velocity.y * 0.5 + dy / dt * 0.5;
+                    // Dragging against an edge shouldn't wind up a glide into it
+                    if self.rickboard.board.clamp_viewport(self.render_height) {
+                        self.rickboard.board.viewport.pan_velocity.y = 0.0;
+                    }
+                    self.middle_pan = Some(self.cursor_pos);
+                    
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                    return; // Don't draw while panning
+                }
+                
+                // Highlight the poster that Ctrl+Click would grab
+                let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                if self.rickboard.update_hovered_poster(board_x, board_y, self.modifiers.control_key()) {
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                }
                 
                 // Move poster if one is selected
                 if let (Some(poster_idx), Some(offset)) = (self.rickboard.selected_poster_index, self.rickboard.poster_drag_offset) {
                     let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
                     let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
                     
+                    let board_width = self.rickboard.board.config.width as f32;
                     if let Some(poster) = self.rickboard.posters.get_mut(poster_idx) {
-                        poster.position.x = board_x - offset.x;
+                        // Keep x canonical in [0, width) - rendering and hit-testing wrap anyway
+                        poster.position.x = (board_x - offset.x).rem_euclid(board_width);
                         poster.position.y = board_y - offset.y;
                     }
+                    // Line up with other posters unless Alt is held
+                    self.rickboard.snap_poster_to_guides(poster_idx, !self.modifiers.alt_key());
                     
                     if let Some(window) = &self.window {
                         window.request_redraw();
@@ -1682,8 +5511,55 @@ impl ApplicationHandler for App {
                     return; // Don't draw on board while dragging poster
                 }
                 
-                // Handle slider dragging
-                if self.mouse_down && position.x >= 20.0 && position.x <= 160.0 && position.y >= 150.0 && position.y <= 165.0 {
+                // Dragging a scrollbar thumb
+                if self.rickboard.drag_scrollbar(position.x, position.y, self.render_width, self.render_height) {
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                    return;
+                }
+                
+                // Dragging the legend by its top bar
+                if self.rickboard.drag_legend(position.x, position.y, self.render_width, self.render_height) {
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                    return;
+                }
+                
+                // Measure tool: follow the cursor with the line's free end
+                if let Some((_, end)) = &mut self.rickboard.measure {
+                    end.x = self.rickboard.board.viewport.position.x + position.x as f32 / self.rickboard.board.viewport.zoom;
+                    end.y = self.rickboard.board.viewport.position.y + position.y as f32 / self.rickboard.board.viewport.zoom;
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                    return;
+                }
+                
+                // Select tool: stretch the rectangle or carry the floating pixels
+                if self.rickboard.selection.as_ref().is_some_and(|s| s.drag.is_some()) {
+                    let board_x = self.rickboard.board.viewport.position.x + position.x as f32 / self.rickboard.board.viewport.zoom;
+                    let board_y = self.rickboard.board.viewport.position.y + position.y as f32 / self.rickboard.board.viewport.zoom;
+                    self.rickboard.drag_selection(Point { x: board_x, y: board_y });
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                    return;
+                }
+                
+                // Pen tool: pull out the handle of the anchor just dropped
+                if self.rickboard.drawing_tool.pen_dragging {
+                    let board_x = self.rickboard.board.viewport.position.x + position.x as f32 / self.rickboard.board.viewport.zoom;
+                    let board_y = self.rickboard.board.viewport.position.y + position.y as f32 / self.rickboard.board.viewport.zoom;
+                    self.rickboard.drag_pen_handle(Point { x: board_x, y: board_y });
+                    return;
+                }
+                
+                // Handle slider dragging (brush size and opacity sliders)
+                if self.mouse_down && self.rickboard.on_legend_slider(position.x, position.y, self.render_width, self.render_height) {
+                    self.zoom_target = None;
+                    self.bookmark_jump = None;
                     let _ = self.rickboard.handle_ui_click(position.x, position.y, self.render_height, self.render_width);
                     if let Some(window) = &self.window {
                         window.request_redraw();
@@ -1706,12 +5582,58 @@ impl ApplicationHandler for App {
                     if let Some(window) = &self.window {
                         window.request_redraw();
                     }
+                } else if self.rickboard.shows_brush_outline() {
+                    // The brush outline follows the cursor
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                }
+            }
+            
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_in_window = false;
+                if let Some(window) = &self.window {
+                    window.request_redraw();
                 }
             }
             
             WindowEvent::MouseWheel { delta, .. } => {
-                if self.modifiers.control_key() {
-                    // Ctrl+Wheel: Scale selected poster
+                if self.rickboard.show_poster_picker {
+                    // The open poster picker takes the wheel to scroll its list
+                    let lines = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(pos) => (pos.y / PICKER_ROW_HEIGHT as f64) as f32,
+                    };
+                    let rows = if lines > 0.0 { -(lines.ceil() as isize) } else { (-lines).ceil() as isize };
+                    self.rickboard.scroll_poster_picker(rows);
+                } else if self.modifiers.control_key() && self.modifiers.shift_key() && !self.modifiers.alt_key() {
+                    // Ctrl+Shift+Wheel: Rotate the poster under the cursor in 5 degree steps
+                    let delta_y = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(pos) => pos.y as f32,
+                    };
+                    
+                    let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                    let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                    
+                    if let Some(poster_idx) = self.rickboard.find_poster_at(board_x, board_y) {
+                        let rotation = self.rickboard.posters[poster_idx].rotation;
+                        self.rickboard.record(UndoAction::PosterRotate { index: poster_idx, rotation });
+                        let poster = &mut self.rickboard.posters[poster_idx];
+                        let step = if delta_y > 0.0 { POSTER_ROTATION_STEP } else { -POSTER_ROTATION_STEP };
+                        poster.rotation = (poster.rotation + step).rem_euclid(std::f32::consts::TAU);
+                        // Snap back to exactly upright so the fast axis-aligned path kicks in again
+                        if poster.rotation < 1e-4 || std::f32::consts::TAU - poster.rotation < 1e-4 {
+                            poster.rotation = 0.0;
+                        }
+                        self.has_unsaved_changes = true;
+                        
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                    }
+                } else if self.modifiers.control_key() {
+                    // Ctrl+Wheel: Scale selected poster (Ctrl+Alt stretches one axis, see below)
                     let delta_y = match delta {
                         MouseScrollDelta::LineDelta(_, y) => y,
                         MouseScrollDelta::PixelDelta(pos) => (pos.y / 20.0) as f32,
@@ -1721,9 +5643,24 @@ impl ApplicationHandler for App {
                     let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
                     
                     if let Some(poster_idx) = self.rickboard.find_poster_at(board_x, board_y) {
+                        let (scale_x, scale_y) = (self.rickboard.posters[poster_idx].scale_x, self.rickboard.posters[poster_idx].scale_y);
+                        self.rickboard.record(UndoAction::PosterScale { index: poster_idx, scale_x, scale_y });
                         if let Some(poster) = self.rickboard.posters.get_mut(poster_idx) {
-                            let scale_factor = if delta_y > 0.0 { 1.1 } else { 0.9 };
-                            poster.scale = (poster.scale * scale_factor).clamp(0.1, 10.0);
+                            let scale_factor: f32 = if delta_y > 0.0 { 1.1 } else { 0.9 };
+                            if self.rickboard.poster_aspect_locked || !self.modifiers.alt_key() {
+                                // Uniform: stop at the limits rather than bend the aspect ratio
+                                let factor = scale_factor
+                                    .min(10.0 / poster.scale_x.max(poster.scale_y))
+                                    .max(0.1 / poster.scale_x.min(poster.scale_y));
+                                poster.scale_x *= factor;
+                                poster.scale_y *= factor;
+                            } else if self.modifiers.shift_key() {
+                                // Ctrl+Alt+Shift+Wheel: stretch height only
+                                poster.scale_y = (poster.scale_y * scale_factor).clamp(0.1, 10.0);
+                            } else {
+                                // Ctrl+Alt+Wheel: stretch width only
+                                poster.scale_x = (poster.scale_x * scale_factor).clamp(0.1, 10.0);
+                            }
                             self.has_unsaved_changes = true;
                             
                             if let Some(window) = &self.window {
@@ -1742,16 +5679,12 @@ impl ApplicationHandler for App {
                         }
                     };
                     
-                    // Calculate board position at cursor before zoom
-                    let cursor_board_x = self.rickboard.board.viewport.position.x + (self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom);
-                    let cursor_board_y = self.rickboard.board.viewport.position.y + (self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom);
-                    
-                    // Apply zoom with limit
-                    self.rickboard.board.viewport.zoom = (self.rickboard.board.viewport.zoom * zoom_factor).clamp(0.1, 1.5);
-                    
-                    // Adjust viewport position to keep cursor at same board position
-                    self.rickboard.board.viewport.position.x = cursor_board_x - (self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom);
-                    self.rickboard.board.viewport.position.y = cursor_board_y - (self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom);
+                    // Zoom around the cursor so the board point under it stays put
+                    // (the wheel takes over from a double-click zoom still in flight)
+                    self.zoom_target = None;
+                    self.bookmark_jump = None;
+                    let zoom = (self.rickboard.board.viewport.zoom * zoom_factor).clamp(self.rickboard.zoom_limits.0, self.rickboard.zoom_limits.1);
+                    self.rickboard.board.zoom_at(zoom, self.cursor_pos.0, self.cursor_pos.1, self.render_height);
                     
                     if let Some(window) = &self.window {
                         window.request_redraw();
@@ -1760,90 +5693,536 @@ impl ApplicationHandler for App {
             }
             
             WindowEvent::KeyboardInput { event, .. } => {
+                // The save error dialog takes key presses until Enter or Escape dismisses it
+                // (so Escape doesn't also quit); releases go on through to stop pans
+                if self.rickboard.error_dialog.is_some() && event.state == ElementState::Pressed {
+                    if matches!(event.logical_key, Key::Named(NamedKey::Enter | NamedKey::Escape)) {
+                        self.rickboard.error_dialog = None;
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                    }
+                    return;
+                }
+                
+                // The focused hex color field swallows all keys (hex digits overlap WASD/C/F...)
+                if self.rickboard.hex_input.is_some() {
+                    if event.state == ElementState::Pressed {
+                        self.rickboard.handle_hex_key(&event);
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                    }
+                    return;
+                }
+                
+                // ...and the go-to prompt (digits, '-' and ',')
+                if self.rickboard.goto_input.is_some() {
+                    if event.state == ElementState::Pressed {
+                        self.rickboard.handle_goto_key(&event);
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                    }
+                    return;
+                }
+                
+                // ...the snapshot name prompt...
+                if self.rickboard.snapshot_input.is_some() {
+                    if event.state == ElementState::Pressed {
+                        self.rickboard.handle_snapshot_input_key(&event);
+                    }
+                    return;
+                }
+                
+                // ...and the snapshot list (arrows, Enter, Escape)
+                if self.rickboard.snapshot_list.is_some() {
+                    if event.state == ElementState::Pressed && self.rickboard.handle_snapshot_list_key(&event) {
+                        self.has_unsaved_changes = true;
+                    }
+                    return;
+                }
+                
+                // ...the picker's rename and delete prompts...
+                if self.rickboard.poster_file_prompt.is_some() {
+                    if event.state == ElementState::Pressed {
+                        self.rickboard.handle_poster_file_key(&event);
+                    }
+                    return;
+                }
+                
+                // The clear confirmation takes the next key press: Y clears, anything else cancels.
+                // Releases go on through so a pan key let go meanwhile still stops
+                if let (Some(clear), ElementState::Pressed) = (self.rickboard.clear_prompt, event.state) {
+                    self.rickboard.clear_prompt = None;
+                    if matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("y")) {
+                        self.clear_board(clear);
+                    } else {
+                        self.rickboard.show_toast("Clear cancelled".to_string());
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                    }
+                    return;
+                }
+                
+                // Same for a label being typed with the text tool
+                if self.rickboard.text_anchor.is_some() {
+                    if event.state == ElementState::Pressed {
+                        if self.rickboard.handle_text_key(&event) {
+                            self.has_unsaved_changes = true;
+                        }
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                    }
+                    return;
+                }
+                
+                // A pen curve in progress takes Enter, Escape and Backspace; other keys (panning
+                // to reach past the view, brush size...) still work while placing anchors
+                if self.rickboard.drawing_tool.tool == Tool::Pen && !self.rickboard.drawing_tool.pen_anchors.is_empty() && event.state == ElementState::Pressed {
+                    if let Some(committed) = self.rickboard.handle_pen_key(&event) {
+                        if committed {
+                            self.has_unsaved_changes = true;
+                        }
+                        return;
+                    }
+                }
+                
+                // A selection takes Enter, Escape, Delete and Backspace
+                if self.rickboard.selection.is_some() && event.state == ElementState::Pressed {
+                    if let Some(changed) = self.rickboard.handle_selection_key(&event) {
+                        if changed {
+                            self.has_unsaved_changes = true;
+                        }
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                        return;
+                    }
+                }
+                
+                // Panning is driven by held-key state (applied every frame in RedrawRequested),
+                // so it starts immediately and doesn't depend on the OS key-repeat rate
+                let PhysicalKey::Code(keycode) = event.physical_key else {
+                    return;
+                };
+                let action = self.key_bindings.action(keycode, self.modifiers.control_key());
+                // Releases always end a pan, even if Ctrl went down meanwhile and turned the key into a chord
+                if event.state == ElementState::Released && self.held_pan_keys.remove(&keycode).is_some() {
+                    return;
+                }
+                // The hand tool is held like a pan key; a drag already under way runs until the button is let go
+                if action == Some(Action::HandTool) {
+                    self.space_pan = event.state == ElementState::Pressed;
+                    self.update_cursor();
+                    return;
+                }
+                // Ctrl+pan key nudges the view by one board pixel per press (and key repeat),
+                // unless that chord is bound to an action of its own
+                if action.is_none() && self.modifiers.control_key() && event.state == ElementState::Pressed {
+                    if let Some(pan @ (Action::PanUp | Action::PanLeft | Action::PanDown | Action::PanRight)) =
+                        self.key_bindings.action(keycode, false)
+                    {
+                        self.bookmark_jump = None;
+                        self.nudge_view(pan);
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                        return;
+                    }
+                }
+                if let Some(pan @ (Action::PanUp | Action::PanLeft | Action::PanDown | Action::PanRight)) = action {
+                    if event.state == ElementState::Pressed {
+                        self.bookmark_jump = None;
+                        if self.held_pan_keys.is_empty() {
+                            self.pan_hold_secs = 0.0;
+                        }
+                        self.held_pan_keys.insert(keycode, pan);
+                        if let Some(window) = &self.window {
+                            window.request_redraw();
+                        }
+                    }
+                    return;
+                }
+                
+                // Digits 1-9 jump to a bookmark and Ctrl+digit stores one, unless the key is bound to an action
+                if let (None, ElementState::Pressed, Some(slot)) = (action, event.state, bookmark_slot(keycode)) {
+                    if self.modifiers.control_key() {
+                        self.set_bookmark(slot);
+                    } else {
+                        self.jump_to_bookmark(slot);
+                    }
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
+                    return;
+                }
+                
                 if event.state == ElementState::Pressed {
-                    if let PhysicalKey::Code(keycode) = event.physical_key {
-                        match keycode {
-                            KeyCode::Escape => event_loop.exit(),
-                            KeyCode::KeyW => {
-                                self.rickboard.board.viewport.position.y -= 50.0;
+                    if let Some(action) = action {
+                        // Anything but looking around ends a replay first, so edits and saves see the real ink
+                        let view_only = matches!(action, Action::Replay | Action::BrushUp | Action::BrushDown
+                            | Action::GoTo | Action::ResetView | Action::FitHeight
+                            | Action::Zoom100 | Action::Zoom200 | Action::Zoom400 | Action::CycleInkBlend | Action::ToggleGrid | Action::ToggleDiagnostics | Action::Exit);
+                        if self.rickboard.replay.is_some() && !view_only {
+                            self.rickboard.stop_replay();
+                            info!("Replay stopped");
+                        }
+                        // Any other edit or tool change puts floating pixels down and ends the selection
+                        if !view_only && !matches!(action, Action::Copy | Action::Cut | Action::Paste | Action::Undo | Action::Quantize)
+                            && self.rickboard.deselect()
+                        {
+                            self.has_unsaved_changes = true;
+                        }
+                        
+                        match action {
+                            Action::Exit => event_loop.exit(),
+                            // +/- change the speed while a replay runs
+                            Action::BrushUp | Action::BrushDown if self.rickboard.replay.is_some() => {
+                                if let Some(replay) = &mut self.rickboard.replay {
+                                    let factor = if action == Action::BrushUp { 2.0 } else { 0.5 };
+                                    replay.speed = (replay.speed * factor).clamp(REPLAY_MIN_SPEED, REPLAY_MAX_SPEED);
+                                    info!("Replay speed: {}x", replay.speed);
+                                }
+                            }
+                            Action::Replay => {
+                                if self.rickboard.replay.is_some() {
+                                    self.rickboard.stop_replay();
+                                    info!("Replay stopped");
+                                } else if self.rickboard.start_replay() {
+                                    info!("Replaying {} strokes at {}x (+/- change speed, Ctrl+R stops)", self.rickboard.board.strokes.len(), REPLAY_DEFAULT_SPEED);
+                                } else {
+                                    info!("Nothing to replay: no strokes logged for this board");
+                                }
+                            }
+                            Action::BrushUp => {
+                                self.rickboard.drawing_tool.brush_size = (self.rickboard.drawing_tool.brush_size + 1).min(100);
+                                info!("Brush size: {}", self.rickboard.drawing_tool.brush_size);
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::BrushDown => {
+                                self.rickboard.drawing_tool.brush_size = (self.rickboard.drawing_tool.brush_size.saturating_sub(1)).max(1);
+                                info!("Brush size: {}", self.rickboard.drawing_tool.brush_size);
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::ToggleTexture => {
+                                // Toggle chalk/marker stroke texture
+                                let texture = &mut self.rickboard.drawing_tool.texture;
+                                texture.enabled = !texture.enabled;
+                                info!("Stroke texture: {} (seed {:#x})", if texture.enabled { "on" } else { "off" }, texture.seed);
+                            }
+                            Action::SaveSnapshot => {
+                                // Open the snapshot name prompt (Enter saves, Escape cancels)
+                                self.rickboard.snapshot_input = Some(String::new());
+                                self.held_pan_keys.clear();
+                            }
+                            Action::OpenSnapshots => {
+                                // List the saved snapshots; nothing changes until one is picked and confirmed
+                                self.rickboard.snapshot_list = Some(self.rickboard.list_snapshots());
+                                self.rickboard.snapshot_selected = None;
+                                self.held_pan_keys.clear();
+                            }
+                            Action::GoTo => {
+                                // Open the go-to prompt (Enter jumps, Escape cancels)
+                                self.rickboard.goto_input = Some(String::new());
+                                self.held_pan_keys.clear();
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::ResetView => {
+                                // Reset view to the origin at 1:1 zoom
+                                self.bookmark_jump = None;
+                                self.zoom_target = None;
+                                self.rickboard.reset_view();
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::FitHeight | Action::Zoom100 | Action::Zoom200 | Action::Zoom400 => {
+                                // Preset zoom levels: fit the board height to the window, 100%, 200% or 400%
+                                self.zoom_to_preset(action);
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::ToggleScratch => {
+                                // Toggle scratch mode (strokes go to a throwaway layer)
+                                let active = !self.rickboard.board.scratch_active;
+                                self.rickboard.board.set_scratch_mode(active);
+                                info!("Scratch mode: {}", if active { "on" } else { "off" });
+                            }
+                            Action::ToggleAspectLock => {
+                                // Toggle the poster aspect lock for Ctrl+Alt+Wheel stretching
+                                self.rickboard.poster_aspect_locked = !self.rickboard.poster_aspect_locked;
+                                info!("Poster aspect ratio: {}", if self.rickboard.poster_aspect_locked { "locked" } else { "free" });
+                            }
+                            Action::CycleInkBlend => {
+                                // Step the ink layer's composite mode: normal, multiply, screen, overlay
+                                let modes = BlendMode::LAYER_MODES;
+                                let next = modes.iter().position(|&mode| mode == self.rickboard.board.ink_blend).map_or(0, |i| (i + 1) % modes.len());
+                                self.rickboard.board.ink_blend = modes[next];
+                                self.rickboard.show_toast(format!("Ink blend: {:?}", modes[next]));
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::ToggleGrid => {
+                                // Toggle the board-space grid overlay
+                                self.rickboard.show_grid = !self.rickboard.show_grid;
+                                info!("Grid: {}", if self.rickboard.show_grid { "on" } else { "off" });
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::ToggleDiagnostics => {
+                                self.rickboard.show_diagnostics = !self.rickboard.show_diagnostics;
+                                if self.rickboard.show_diagnostics {
+                                    info!("Diagnostics: {}", self.rickboard.diagnostics_text());
+                                }
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::BringToFront | Action::SendToBack => {
+                                // Restack the dragged poster (or the one under the cursor)
+                                let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                if let Some(index) = self.rickboard.stacking_target(board_x, board_y) {
+                                    let to = if action == Action::BringToFront {
+                                        self.rickboard.bring_to_front(index)
+                                    } else {
+                                        self.rickboard.send_to_back(index)
+                                    };
+                                    if to != index {
+                                        self.rickboard.record(UndoAction::PosterRestack { from: index, to });
+                                    }
+                                    self.has_unsaved_changes = true;
+                                    if let Some(window) = &self.window {
+                                        window.request_redraw();
+                                    }
+                                }
+                            }
+                            Action::DuplicatePoster => {
+                                // Copy the dragged poster (or the one under the cursor)
+                                let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                if let Some(index) = self.rickboard.stacking_target(board_x, board_y) {
+                                    if let Err(e) = self.rickboard.duplicate_poster(index) {
+                                        self.rickboard.show_error(format!("Error saving posters: {}", e));
+                                    }
+                                    self.has_unsaved_changes = true;
+                                }
+                            }
+                            Action::TogglePosterLock => {
+                                // Lock the dragged poster, or lock/unlock the one under the cursor
+                                let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                let target = self.rickboard.selected_poster_index.or_else(|| self.rickboard.find_any_poster_at(board_x, board_y));
+                                if let Some(index) = target {
+                                    if let Err(e) = self.rickboard.toggle_poster_lock(index) {
+                                        self.rickboard.show_error(format!("Error saving posters: {}", e));
+                                    }
+                                    self.rickboard.update_hovered_poster(board_x, board_y, self.modifiers.control_key());
+                                    self.has_unsaved_changes = true;
+                                }
+                            }
+                            Action::ClearScratch => {
+                                self.rickboard.board.clear_scratch();
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::ToggleBrushShape => {
+                                // Cycle round, square and chisel brushes
+                                let tool = &mut self.rickboard.drawing_tool;
+                                tool.brush_shape = match tool.brush_shape {
+                                    BrushShape::Round => BrushShape::Square,
+                                    BrushShape::Square => BrushShape::Chisel { angle: tool.nib_angle },
+                                    BrushShape::Chisel { .. } => BrushShape::Round,
+                                };
+                                info!("Brush shape: {:?}", tool.brush_shape);
+                                if let Some(window) = &self.window {
+                                    window.request_redraw();
+                                }
+                            }
+                            Action::Export => {
+                                // Ctrl+E: export the whole board (with posters) next to the board file
+                                let path = self.rickboard.board.file_path.with_extension("png");
+                                if let Err(e) = self.rickboard.board.export_full_png(&path, &self.rickboard.posters) {
+                                    self.rickboard.show_error(format!("Export error: {}", e));
+                                }
+                            }
+                            Action::ExportSvg => {
+                                // Ctrl+D: export this session's strokes as SVG next to the board file
+                                let path = self.rickboard.board.file_path.with_extension("svg");
+                                if let Err(e) = self.rickboard.board.export_svg(&path) {
+                                    self.rickboard.show_error(format!("SVG export error: {}", e));
+                                }
+                            }
+                            Action::ToggleEraser => {
+                                // Toggle persistent eraser (left button erases)
+                                let eraser_mode = !self.rickboard.drawing_tool.eraser_mode;
+                                self.rickboard.drawing_tool.eraser_mode = eraser_mode;
+                                info!("Eraser mode: {}", if eraser_mode { "on" } else { "off" });
                                 if let Some(window) = &self.window {
                                     window.request_redraw();
                                 }
                             }
-                            KeyCode::KeyS => {
-                                self.rickboard.board.viewport.position.y += 50.0;
-                                if let Some(window) = &self.window {
-                                    window.request_redraw();
+                            Action::CycleSmoothing => {
+                                // Cycle stroke smoothing strength
+                                let smoothing = &mut self.rickboard.drawing_tool.smoothing;
+                                let next = SMOOTHING_LEVELS.iter().position(|&level| level == *smoothing).map_or(0, |i| (i + 1) % SMOOTHING_LEVELS.len());
+                                *smoothing = SMOOTHING_LEVELS[next];
+                                if *smoothing > 0.0 {
+                                    info!("Stroke smoothing: {:.0}%", *smoothing * 100.0);
+                                } else {
+                                    info!("Stroke smoothing: off");
+                                }
+                            }
+                            Action::ToggleText => {
+                                // Toggle text tool
+                                let tool = &mut self.rickboard.drawing_tool.tool;
+                                *tool = if *tool == Tool::Text { Tool::Brush } else { Tool::Text };
+                                info!("Tool: {:?}", tool);
+                            }
+                            Action::ToggleHighlighter => {
+                                // Toggle highlighter tool
+                                let tool = &mut self.rickboard.drawing_tool.tool;
+                                *tool = if *tool == Tool::Highlighter { Tool::Brush } else { Tool::Highlighter };
+                                info!("Tool: {:?}", tool);
+                            }
+                            Action::ToggleStamp => {
+                                // Toggle the image stamp tool (only once a stamp image has loaded)
+                                let tool = &mut self.rickboard.drawing_tool;
+                                if tool.tool == Tool::Stamp {
+                                    tool.tool = Tool::Brush;
+                                } else if tool.stamp.is_some() {
+                                    tool.tool = Tool::Stamp;
+                                } else {
+                                    self.rickboard.show_toast("No stamp image (set stamp_image in config.toml)".to_string());
                                 }
+                                info!("Tool: {:?}", self.rickboard.drawing_tool.tool);
                             }
-                            KeyCode::KeyA => {
-                                self.rickboard.board.viewport.position.x -= 50.0;
-                                if let Some(window) = &self.window {
-                                    window.request_redraw();
+                            Action::ToggleFill => {
+                                // Toggle bucket fill tool
+                                let tool = &mut self.rickboard.drawing_tool.tool;
+                                *tool = if *tool == Tool::Fill { Tool::Brush } else { Tool::Fill };
+                                info!("Tool: {:?}", tool);
+                            }
+                            Action::ToggleMeasure => {
+                                // Toggle the measure (ruler) tool
+                                let tool = &mut self.rickboard.drawing_tool.tool;
+                                *tool = if *tool == Tool::Measure { Tool::Brush } else { Tool::Measure };
+                                info!("Tool: {:?}", tool);
+                            }
+                            Action::TogglePen => {
+                                // Toggle the Bezier pen tool; leaving it draws the curve built so far
+                                if self.rickboard.drawing_tool.tool == Tool::Pen {
+                                    if self.rickboard.commit_pen_curve() {
+                                        self.has_unsaved_changes = true;
+                                    }
+                                    self.rickboard.drawing_tool.tool = Tool::Brush;
+                                } else {
+                                    self.rickboard.drawing_tool.pen_anchors.clear();
+                                    self.rickboard.drawing_tool.tool = Tool::Pen;
                                 }
+                                info!("Tool: {:?}", self.rickboard.drawing_tool.tool);
                             }
-                            KeyCode::KeyD => {
-                                self.rickboard.board.viewport.position.x += 50.0;
+                            Action::ToggleSelect => {
+                                // Toggle the rectangular select tool
+                                let tool = &mut self.rickboard.drawing_tool.tool;
+                                *tool = if *tool == Tool::Select { Tool::Brush } else { Tool::Select };
+                                info!("Tool: {:?}", tool);
+                            }
+                            Action::Quantize => {
+                                // Quantize drawings (or just the selection) to the marker palette
+                                self.rickboard.quantize_drawing();
+                                if self.rickboard.selection.is_some() {
+                                    info!("Selection quantized to palette");
+                                } else {
+                                    info!("Drawing layer quantized to palette");
+                                }
+                                self.has_unsaved_changes = true;
                                 if let Some(window) = &self.window {
                                     window.request_redraw();
                                 }
                             }
-                            KeyCode::Equal | KeyCode::NumpadAdd => {
-                                self.rickboard.drawing_tool.brush_size = (self.rickboard.drawing_tool.brush_size + 1).min(100);
-                                println!("Brush size: {}", self.rickboard.drawing_tool.brush_size);
+                            Action::Clear | Action::ClearDrawing | Action::ClearBackground if self.rickboard.confirm_clear => {
+                                // Ask first; the answer is the next key press
+                                self.rickboard.clear_prompt = Some(action);
                                 if let Some(window) = &self.window {
                                     window.request_redraw();
                                 }
                             }
-                            KeyCode::Minus | KeyCode::NumpadSubtract => {
-                                self.rickboard.drawing_tool.brush_size = (self.rickboard.drawing_tool.brush_size.saturating_sub(1)).max(1);
-                                println!("Brush size: {}", self.rickboard.drawing_tool.brush_size);
+                            Action::Clear | Action::ClearDrawing | Action::ClearBackground => self.clear_board(action),
+                            Action::Save => {
+                                // The board is written on a worker thread (finished in RedrawRequested)
+                                if self.rickboard.board.start_sync() {
+                                    self.is_saving = true;
+                                    self.has_unsaved_changes = false;
+                                } else {
+                                    self.rickboard.show_toast("Still saving, try again in a moment".to_string());
+                                }
+                                // Save posters
+                                if let Err(e) = self.rickboard.save_posters() {
+                                    self.rickboard.show_save_error(format!("Poster save error: {}", e));
+                                }
+                                self.last_save = Instant::now(); // Reset timer
                                 if let Some(window) = &self.window {
                                     window.request_redraw();
                                 }
                             }
-                            KeyCode::KeyC => {
-                                if let Err(e) = self.rickboard.clear_board() {
-                                    eprintln!("Clear error: {}", e);
-                                }
+                            Action::Paste if self.rickboard.drawing_tool.tool == Tool::Select && self.rickboard.selection_clipboard.is_some() => {
+                                // With the select tool, Ctrl+V floats copied ink at the cursor
+                                let board_x = self.rickboard.board.viewport.position.x + self.cursor_pos.0 as f32 / self.rickboard.board.viewport.zoom;
+                                let board_y = self.rickboard.board.viewport.position.y + self.cursor_pos.1 as f32 / self.rickboard.board.viewport.zoom;
+                                self.rickboard.paste_selection(Point { x: board_x, y: board_y });
                                 self.has_unsaved_changes = true;
                                 if let Some(window) = &self.window {
                                     window.request_redraw();
                                 }
                             }
-                            KeyCode::KeyP => {
-                                self.is_saving = true;
+                            Action::Paste => {
+                                // Ctrl+V pastes a clipboard image as a poster
+                                if let Err(e) = self.rickboard.handle_paste(self.cursor_pos.0, self.cursor_pos.1) {
+                                    self.rickboard.show_error(format!("Error pasting image: {}", e));
+                                }
                                 if let Some(window) = &self.window {
                                     window.request_redraw();
                                 }
-                                if let Err(e) = self.rickboard.board.sync() {
-                                    eprintln!("Save error: {}", e);
-                                } else {
-                                    self.has_unsaved_changes = false;
-                                }
-                                // Save posters
-                                if let Err(e) = self.rickboard.save_posters() {
-                                    eprintln!("Poster save error: {}", e);
-                                }
-                                self.last_save = Instant::now(); // Reset timer
-                                self.save_message_until = Some(Instant::now() + std::time::Duration::from_millis(500));
-                                self.is_saving = false;
+                            }
+                            Action::Copy if self.rickboard.selection.is_some() => {
+                                self.rickboard.copy_selection();
+                            }
+                            // Ctrl+X cuts the selected ink (nothing to cut without a selection)
+                            Action::Cut if self.rickboard.selection.is_some() => {
+                                self.rickboard.cut_selection();
+                                self.has_unsaved_changes = true;
                                 if let Some(window) = &self.window {
                                     window.request_redraw();
                                 }
                             }
-                            KeyCode::KeyZ => {
-                                // Ctrl+Z for undo
-                                if self.modifiers.control_key() {
-                                    if self.rickboard.board.undo() {
-                                        println!("Undo successful");
-                                        self.has_unsaved_changes = true;
-                                        if let Some(window) = &self.window {
-                                            window.request_redraw();
-                                        }
-                                    } else {
-                                        println!("Nothing to undo");
+                            Action::Copy => {
+                                // Ctrl+C copies the poster under the cursor, or else the whole view, as an image
+                                self.rickboard.copy_to_clipboard(self.cursor_pos.0, self.cursor_pos.1, self.render_width, self.render_height);
+                            }
+                            Action::Undo => {
+                                // Ctrl+Z undoes the last drawing or poster edit
+                                if self.rickboard.undo() {
+                                    self.rickboard.show_toast("Undone".to_string());
+                                    self.has_unsaved_changes = true;
+                                    if let Some(window) = &self.window {
+                                        window.request_redraw();
                                     }
+                                } else {
+                                    self.rickboard.show_toast("Nothing to undo".to_string());
                                 }
                             }
                             _ => {}
@@ -1855,13 +6234,34 @@ impl ApplicationHandler for App {
             WindowEvent::DroppedFile(path) => {
                 // Handle dropped image file
                 if let Err(e) = self.rickboard.handle_dropped_file(&path, self.cursor_pos.0, self.cursor_pos.1) {
-                    eprintln!("Error handling dropped file: {}", e);
+                    self.rickboard.show_error(format!("Error handling dropped file: {}", e));
                 }
             }
             
             WindowEvent::RedrawRequested => {
+                // Frame cap: a redraw that comes too soon is put off until the interval is up
+                // (about_to_wait wakes for it), so only frames that change something get paced
+                if let Some(interval) = self.frame_interval {
+                    let due = self.last_frame + interval;
+                    if Instant::now() < due {
+                        self.next_frame_at = Some(due);
+                        return;
+                    }
+                }
+                self.next_frame_at = None;
+                
+                // Time since the previous frame drives steady, frame-rate independent panning
+                // (none after idling, or the first frame of a pan would jump)
+                let dt = if self.animating { self.last_frame.elapsed().as_secs_f32().min(0.1) } else { 0.0 };
+                self.last_frame = Instant::now();
+                self.apply_held_pan(dt);
+                self.apply_pan_momentum(dt);
+                self.apply_zoom_animation(dt);
+                self.apply_bookmark_jump(dt);
+                self.rickboard.advance_replay(dt);
+                
                 // Update legend animation
-                self.rickboard.update_legend_animation();
+                let legend_moving = self.rickboard.update_legend_animation();
                 
                 // Update FPS counter
                 self.frame_count += 1;
@@ -1872,22 +6272,18 @@ impl ApplicationHandler for App {
                     self.last_fps_update = Instant::now();
                 }
                 
-                // Check for auto-save (every 1 minute, only if changes made)
-                let time_since_save = self.last_save.elapsed().as_secs_f32();
-                if time_since_save >= 60.0 && !self.is_saving && self.has_unsaved_changes {
-                    self.is_saving = true;
-                    if let Err(e) = self.rickboard.board.sync() {
-                        eprintln!("Auto-save error: {}", e);
-                    } else {
-                        self.has_unsaved_changes = false;
-                    }
-                    // Save posters
-                    if let Err(e) = self.rickboard.save_posters() {
-                        eprintln!("Auto-save poster error: {}", e);
+                self.run_saves();
+                self.poll_poster_loads();
+                
+                // In the background, put the last frame back up instead of composing a new one
+                if !self.is_active() {
+                    self.animating = false;
+                    if let Some(pixels) = &self.pixels {
+                        if let Err(e) = pixels.render() {
+                            error!("Render error: {}", e);
+                        }
                     }
-                    self.last_save = Instant::now();
-                    self.save_message_until = Some(Instant::now() + std::time::Duration::from_millis(500));
-                    self.is_saving = false;
+                    return;
                 }
                 
                 // Check if save message should still be displayed
@@ -1907,50 +6303,57 @@ impl ApplicationHandler for App {
                     
                     let frame_start = Instant::now();
                     
-                    // Render the board's viewport to the screen
+                    // Render the board's viewport (background, posters, drawing layer)
                     let t0 = Instant::now();
-                    if let Err(e) = self.rickboard.board.render(frame, self.render_width, self.render_height) {
-                        eprintln!("Board render error: {}", e);
+                    if let Err(e) = self.rickboard.render_scene(frame, self.render_width, self.render_height) {
+                        error!("Board render error: {}", e);
                     }
-                    let board_time = t0.elapsed();
-                    
-                    // Render posters on top of board background
-                    let t1 = Instant::now();
-                    self.rickboard.render_posters(frame, self.render_width, self.render_height);
-                    let poster_time = t1.elapsed();
+                    let scene_time = t0.elapsed();
                     
-                    // Render drawing layer on top of posters
-                    let t2 = Instant::now();
-                    self.rickboard.board.render_drawing_layer(frame, self.render_width, self.render_height);
-                    let drawing_time = t2.elapsed();
+                    // Live overlays: throwaway scratch marks go over the ink, still under the UI
+                    self.rickboard.board.render_scratch_layer(frame, self.render_width, self.rickboard.layer_opacity(Layer::Scratch));
+                    self.rickboard.render_stroke_mask(frame, self.render_width, self.render_height);
+                    self.rickboard.render_text_preview(frame, self.render_width, self.render_height);
+                    self.rickboard.render_line_preview(frame, self.render_width, self.render_height);
+                    self.rickboard.render_pen_preview(frame, self.render_width, self.render_height, self.cursor_pos);
+                    // Grid sits over the ink but under the UI
+                    self.rickboard.render_grid(frame, self.render_width, self.render_height);
+                    self.rickboard.render_seam(frame, self.render_width, self.render_height);
+                    self.rickboard.render_measure(frame, self.render_width, self.render_height);
+                    self.rickboard.render_selection(frame, self.render_width, self.render_height);
+                    // Brush outline at the cursor, hidden while the hand tool is up
+                    if self.cursor_in_window && !self.space_pan && !self.hand_drag {
+                        self.rickboard.render_brush_outline(frame, self.render_width, self.render_height, self.cursor_pos);
+                    }
+                    let overlay_time = t0.elapsed() - scene_time;
                     
                     // Render UI overlay on top
                     let t3 = Instant::now();
-                    self.rickboard.render_ui_overlay(frame, self.render_width, self.render_height, self.fps);
+                    self.rickboard.update_minimap(self.render_width, self.render_height);
+                    self.rickboard.render_ui_overlay(frame, self.render_width, self.render_height, self.fps, self.cursor_pos);
                     let ui_time = t3.elapsed();
                     
                     // Render save progress bar
                     let t4 = Instant::now();
-                    let time_until_save = (60.0 - time_since_save).max(0.0);
+                    let time_until_save = (self.auto_save_interval - self.last_save.elapsed().as_secs_f32()).max(0.0);
                     self.rickboard.render_save_progress(frame, self.render_width, time_until_save, show_save_message);
                     let progress_time = t4.elapsed();
                     
                     // Present to screen
                     let t5 = Instant::now();
                     if let Err(e) = pixels.render() {
-                        eprintln!("Render error: {}", e);
+                        error!("Render error: {}", e);
                     }
                     let present_time = t5.elapsed();
                     
                     let total_time = frame_start.elapsed();
                     
                     // Print timing every 60 frames
-                    if self.frame_count % 60 == 0 {
-                        println!("Frame time: {:.2}ms (board: {:.2}ms, posters: {:.2}ms, drawing: {:.2}ms, ui: {:.2}ms, progress: {:.2}ms, present: {:.2}ms)",
+                    if self.frame_count.is_multiple_of(60) {
+                        debug!("Frame time: {:.2}ms (scene: {:.2}ms, overlays: {:.2}ms, ui: {:.2}ms, progress: {:.2}ms, present: {:.2}ms)",
                             total_time.as_secs_f32() * 1000.0,
-                            board_time.as_secs_f32() * 1000.0,
-                            poster_time.as_secs_f32() * 1000.0,
-                            drawing_time.as_secs_f32() * 1000.0,
+                            scene_time.as_secs_f32() * 1000.0,
+                            overlay_time.as_secs_f32() * 1000.0,
                             ui_time.as_secs_f32() * 1000.0,
                             progress_time.as_secs_f32() * 1000.0,
                             present_time.as_secs_f32() * 1000.0
@@ -1958,9 +6361,22 @@ impl ApplicationHandler for App {
                     }
                 }
                 
-                // Request another redraw to keep the display updated
-                if let Some(window) = &self.window {
-                    window.request_redraw();
+                // Only keep rendering while something moves by itself; otherwise wait for input
+                // or the next timed change (see about_to_wait)
+                let velocity = self.rickboard.board.viewport.pan_velocity;
+                let toast_fading = self.rickboard.expire_toasts();
+                self.animating = legend_moving
+                    || toast_fading
+                    || !self.held_pan_keys.is_empty()
+                    || velocity.x != 0.0 || velocity.y != 0.0
+                    || self.zoom_target.is_some()
+                    || self.bookmark_jump.is_some()
+                    || self.rickboard.replay.is_some()
+                    || self.is_saving;
+                if self.animating {
+                    if let Some(window) = &self.window {
+                        window.request_redraw();
+                    }
                 }
             }
             
@@ -1969,14 +6385,928 @@ impl ApplicationHandler for App {
     }
 }
 
+/// Default marker names (matching the marker images in the assets directory) and colors
+const DEFAULT_MARKER_COLORS: [(&str, [u8; 4]); 7] = [
+    ("black", [0, 0, 0, 255]),
+    ("white", [255, 255, 255, 255]),
+    ("red", [255, 0, 0, 255]),
+    ("blue", [30, 144, 255, 255]),      // Dodger blue
+    ("green", [0, 255, 0, 255]),
+    ("yellow", [255, 255, 0, 255]),
+    ("pink", [255, 0, 255, 255]),       // Magenta
+];
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// User preferences loaded from config.toml; every key is optional
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    board_width: u32,
+    board_height: u32,
+    mode: BoardMode,
+    paper: PaperStyle, // Ruling of a new board (plain, ruled, grid or dots)
+    paper_spacing: u16,
+    paper_color: Option<String>, // Hex color of the ruling; unset follows the mode's pen color
+    wrap_vertical: bool,
+    brush_size: u32,
+    nib_angle: f32, // Chisel brush nib angle in degrees, counter-clockwise from horizontal
+    stamp_image: Option<PathBuf>, // Stamp tool image (relative paths are looked up in assets_dir)
+    stamp_scale: f32, // Stamp image size multiplier
+    stamp_spacing: f32, // Distance between stamps along a stroke, in stamp widths
+    shape_assist: bool, // Redraw nearly straight freehand strokes as clean lines on release
+    auto_save_secs: u64,
+    max_undo_steps: usize, // Drawing undo depth (memory is capped separately)
+    max_fps: u32, // Frame-rate cap, 0 = uncapped
+    confirm_clear: bool, // Ask before the Clear key wipes the board
+    scrollbars: bool, // Show scrollbars along the bottom and right edges
+    seam_marker: bool, // Dashed line where the board wraps around
+    pan_step: u32, // Keyboard pan distance in screen pixels per 60th of a second held
+    min_zoom: f32, // Wheel and double-click zoom limits
+    max_zoom: f32,
+    marker_colors: HashMap<String, String>, // Marker name -> hex color override
+    assets_dir: PathBuf,
+    posters_dir: PathBuf,
+    keys: HashMap<String, Vec<String>>, // Action name -> keys, replacing that action's defaults
+    palette: Vec<PaletteEntry>, // Custom markers ([[palette]]), replacing the built-in set
+    legend_position: Option<[f32; 2]>, // Where the legend panel was last dragged to (screen pixels)
+    recent_colors: Vec<String>, // Recently picked pen colors as hex, newest first (written by the app)
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            board_width: 80000,
+            board_height: 1000,
+            mode: BoardMode::Blackboard, // Can be changed via UI button
+            paper: PaperStyle::Plain,
+            paper_spacing: 32,
+            paper_color: None,
+            wrap_vertical: false,
+            brush_size: 2,
+            nib_angle: 45.0,
+            stamp_image: None,
+            stamp_scale: 1.0,
+            stamp_spacing: 1.5,
+            shape_assist: false,
+            auto_save_secs: 60,
+            max_undo_steps: DEFAULT_UNDO_STEPS,
+            max_fps: 0,
+            confirm_clear: true,
+            scrollbars: true,
+            seam_marker: false,
+            pan_step: 20,
+            min_zoom: 0.1,
+            max_zoom: 16.0,
+            marker_colors: HashMap::new(),
+            assets_dir: PathBuf::from("assetts"),
+            posters_dir: PathBuf::from("posters"),
+            keys: HashMap::new(),
+            palette: Vec::new(),
+            legend_position: None,
+            recent_colors: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the config file, falling back to defaults if it is missing or invalid
+    fn load(path: &Path) -> Self {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Config::default(),
+            Err(e) => {
+                error!("Could not read {}: {} - using default settings", path.display(), e);
+                return Config::default();
+            }
+        };
+        
+        match toml::from_str::<Config>(&text).map_err(|e| e.to_string()).and_then(Config::validated) {
+            Ok(config) => {
+                info!("Loaded settings from {}", path.display());
+                config
+            }
+            Err(e) => {
+                error!("Invalid {}: {} - using default settings", path.display(), e.trim_end());
+                Config::default()
+            }
+        }
+    }
+    
+    /// Paper for a new board (existing boards keep the one saved in their header)
+    fn paper(&self) -> Paper {
+        Paper {
+            style: self.paper,
+            spacing: self.paper_spacing,
+            color: self.paper_color.as_deref().and_then(parse_hex_color),
+        }
+    }
+    
+    /// Reject values the rest of the app can't handle
+    fn validated(self) -> Result<Self, String> {
+        for (name, value) in [("board_width", self.board_width), ("board_height", self.board_height)] {
+            if !(1..=MAX_BOARD_DIMENSION).contains(&value) {
+                return Err(format!("{} must be from 1 to {}, got {}", name, MAX_BOARD_DIMENSION, value));
+            }
+        }
+        if !(1..=100).contains(&self.brush_size) {
+            return Err(format!("brush_size must be from 1 to 100, got {}", self.brush_size));
+        }
+        if !(-180.0..=180.0).contains(&self.nib_angle) {
+            return Err(format!("nib_angle must be from -180 to 180, got {}", self.nib_angle));
+        }
+        if !(0.05..=10.0).contains(&self.stamp_scale) {
+            return Err(format!("stamp_scale must be from 0.05 to 10, got {}", self.stamp_scale));
+        }
+        if !(0.1..=20.0).contains(&self.stamp_spacing) {
+            return Err(format!("stamp_spacing must be from 0.1 to 20, got {}", self.stamp_spacing));
+        }
+        if !(4..=1024).contains(&self.paper_spacing) {
+            return Err(format!("paper_spacing must be from 4 to 1024, got {}", self.paper_spacing));
+        }
+        if let Some(hex) = self.paper_color.as_deref().filter(|hex| parse_hex_color(hex).is_none()) {
+            return Err(format!("paper_color is not a hex color: '{}'", hex));
+        }
+        if self.max_undo_steps > MAX_UNDO_STEPS {
+            return Err(format!("max_undo_steps must be from 0 to {}, got {}", MAX_UNDO_STEPS, self.max_undo_steps));
+        }
+        if self.max_fps > 1000 {
+            return Err(format!("max_fps must be from 0 (uncapped) to 1000, got {}", self.max_fps));
+        }
+        if !(1..=500).contains(&self.pan_step) {
+            return Err(format!("pan_step must be from 1 to 500, got {}", self.pan_step));
+        }
+        for (name, value) in [("min_zoom", self.min_zoom), ("max_zoom", self.max_zoom)] {
+            if !ZOOM_RANGE.contains(&value) {
+                return Err(format!("{} must be from {} to {}, got {}", name, ZOOM_RANGE.start(), ZOOM_RANGE.end(), value));
+            }
+        }
+        if self.min_zoom > self.max_zoom {
+            return Err(format!("min_zoom ({}) is above max_zoom ({})", self.min_zoom, self.max_zoom));
+        }
+        for (i, entry) in self.palette.iter().enumerate() {
+            if entry.name.is_empty() {
+                return Err(format!("palette entry {} has no name", i + 1));
+            }
+            if self.palette[..i].iter().any(|other| other.name == entry.name) {
+                return Err(format!("palette has two markers named '{}'", entry.name));
+            }
+            if parse_hex_color(&entry.color).is_none() {
+                return Err(format!("palette color for '{}' is not a hex color: '{}'", entry.name, entry.color));
+            }
+        }
+        for (name, hex) in &self.marker_colors {
+            let known = if self.palette.is_empty() {
+                DEFAULT_MARKER_COLORS.iter().any(|(marker, _)| marker == name)
+            } else {
+                self.palette.iter().any(|entry| &entry.name == name)
+            };
+            if !known {
+                return Err(format!("unknown marker '{}' in marker_colors", name));
+            }
+            if parse_hex_color(hex).is_none() {
+                return Err(format!("marker_colors.{} is not a hex color: '{}'", name, hex));
+            }
+        }
+        KeyBindings::from_config(&self.keys)?;
+        if self.legend_position.is_some_and(|position| position.iter().any(|v| !v.is_finite())) {
+            return Err("legend_position must be two numbers".to_string());
+        }
+        if let Some(hex) = self.recent_colors.iter().find(|hex| parse_hex_color(hex).is_none()) {
+            return Err(format!("recent_colors entry is not a hex color: '{}'", hex));
+        }
+        Ok(self)
+    }
+    
+    /// Apply RICKBOARD_UNDO_STEPS (if valid) over the configured undo depth
+    fn apply_env_overrides(&mut self) {
+        if let Some(steps) = std::env::var("RICKBOARD_UNDO_STEPS").ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|&steps| steps <= MAX_UNDO_STEPS)
+        {
+            self.max_undo_steps = steps;
+        }
+    }
+    
+    /// Apply RICKBOARD_ASSETS / RICKBOARD_POSTERS and make both directories absolute
+    fn resolve_paths(&mut self) {
+        if let Some(dir) = std::env::var_os("RICKBOARD_ASSETS") {
+            self.assets_dir = PathBuf::from(dir);
+        }
+        if let Some(dir) = std::env::var_os("RICKBOARD_POSTERS") {
+            self.posters_dir = PathBuf::from(dir);
+        }
+        self.assets_dir = resolve_dir(&self.assets_dir);
+        self.posters_dir = resolve_dir(&self.posters_dir);
+    }
+    
+    /// Configured color for a marker, if overridden
+    fn marker_color(&self, name: &str) -> Option<[u8; 4]> {
+        self.marker_colors.get(name).and_then(|hex| parse_hex_color(hex))
+    }
+    
+    /// Markers to load as (name, color, open image, closed image): the [[palette]] entries,
+    /// or the built-in set. Images default to <name>_marker_open.png / _closed.png in assets_dir.
+    fn palette_markers(&self) -> Vec<(String, [u8; 4], PathBuf, PathBuf)> {
+        let default_image = |name: &str, state: &str| self.assets_dir.join(format!("{}_marker_{}.png", name, state));
+        if self.palette.is_empty() {
+            return DEFAULT_MARKER_COLORS.iter()
+                .map(|&(name, default)| (
+                    name.to_string(),
+                    self.marker_color(name).unwrap_or(default),
+                    default_image(name, "open"),
+                    default_image(name, "closed"),
+                ))
+                .collect();
+        }
+        self.palette.iter()
+            .map(|entry| (
+                entry.name.clone(),
+                self.marker_color(&entry.name).or_else(|| parse_hex_color(&entry.color)).unwrap_or([0, 0, 0, 255]),
+                entry.open_image.as_ref().map_or_else(|| default_image(&entry.name, "open"), |path| self.assets_dir.join(path)),
+                entry.closed_image.as_ref().map_or_else(|| default_image(&entry.name, "closed"), |path| self.assets_dir.join(path)),
+            ))
+            .collect()
+    }
+}
+
+/// One marker of a custom palette (a [[palette]] table in config.toml)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PaletteEntry {
+    name: String,
+    color: String, // Hex, as in marker_colors
+    #[serde(default)]
+    open_image: Option<PathBuf>, // Relative to assets_dir; a plain swatch is drawn if missing
+    #[serde(default)]
+    closed_image: Option<PathBuf>,
+}
+
+/// Remember the legend position in config.toml
+fn save_legend_position(path: &Path, position: Point) -> io::Result<()> {
+    save_config_value(path, "legend_position", &format!("[{}, {}]", position.x.round(), position.y.round()))
+}
+
+/// Set one top-level key in config.toml: rewrites only that key's line (kept as the first
+/// line, where top-level keys are always valid) and leaves the rest untouched
+fn save_config_value(path: &Path, key: &str, value: &str) -> io::Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut updated = format!("{} = {}\n", key, value);
+    let is_key = |line: &str| line.trim_start().strip_prefix(key).is_some_and(|rest| rest.trim_start().starts_with('='));
+    for line in text.lines().filter(|line| !is_key(line)) {
+        updated.push_str(line);
+        updated.push('\n');
+    }
+    fs::write(path, updated)
+}
+
+/// Make a relative directory absolute: against the working directory if it exists there,
+/// otherwise next to the executable (so running from another directory still finds the assets)
+fn resolve_dir(dir: &Path) -> PathBuf {
+    if dir.is_absolute() {
+        return dir.to_path_buf();
+    }
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let in_cwd = cwd.join(dir);
+    if in_cwd.is_dir() {
+        return in_cwd;
+    }
+    std::env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(|parent| parent.join(dir)))
+        .filter(|beside_exe| beside_exe.is_dir())
+        .unwrap_or(in_cwd)
+}
+
+/// Something a key can be bound to (see KeyBindings)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Action {
+    PanUp,
+    PanLeft,
+    PanDown,
+    PanRight,
+    BrushUp,
+    BrushDown,
+    ToggleTexture,
+    GoTo,
+    ResetView,
+    FitHeight,
+    Zoom100,
+    Zoom200,
+    Zoom400,
+    ToggleScratch,
+    ToggleAspectLock,
+    ToggleGrid,
+    CycleInkBlend,
+    ToggleDiagnostics,
+    HandTool,
+    BringToFront,
+    SendToBack,
+    DuplicatePoster,
+    TogglePosterLock,
+    ClearScratch,
+    ToggleBrushShape,
+    Export,
+    ExportSvg,
+    ToggleEraser,
+    CycleSmoothing,
+    ToggleText,
+    ToggleHighlighter,
+    ToggleStamp,
+    ToggleFill,
+    ToggleMeasure,
+    TogglePen,
+    ToggleSelect,
+    Quantize,
+    Clear,
+    ClearDrawing,
+    ClearBackground,
+    Save,
+    SaveSnapshot,
+    OpenSnapshots,
+    Paste,
+    Copy,
+    Cut,
+    Undo,
+    Replay,
+    Exit,
+}
+
+/// Every action with its config.toml name and default keys ("Ctrl+" prefix for chords)
+const DEFAULT_KEY_BINDINGS: [(Action, &str, &[&str]); 49] = [
+    (Action::PanUp, "pan_up", &["W", "ArrowUp"]),
+    (Action::PanLeft, "pan_left", &["A", "ArrowLeft"]),
+    (Action::PanDown, "pan_down", &["S", "ArrowDown"]),
+    (Action::PanRight, "pan_right", &["D", "ArrowRight"]),
+    (Action::BrushUp, "brush_up", &["Equal", "NumpadAdd"]),
+    (Action::BrushDown, "brush_down", &["Minus", "NumpadSubtract"]),
+    (Action::ToggleTexture, "toggle_texture", &["K"]),
+    (Action::GoTo, "go_to", &["G"]),
+    (Action::ResetView, "reset_view", &["Home", "0"]),
+    (Action::FitHeight, "fit_height", &["End", "F4"]),
+    (Action::Zoom100, "zoom_100", &["F5"]),
+    (Action::Zoom200, "zoom_200", &["F6"]),
+    (Action::Zoom400, "zoom_400", &["F7"]),
+    (Action::ToggleScratch, "toggle_scratch", &["X"]),
+    (Action::ToggleAspectLock, "toggle_aspect_lock", &["L"]),
+    (Action::ToggleGrid, "toggle_grid", &["Ctrl+G"]),
+    (Action::CycleInkBlend, "cycle_ink_blend", &["Ctrl+B"]),
+    (Action::ToggleDiagnostics, "toggle_diagnostics", &["F3"]),
+    (Action::HandTool, "hand_tool", &["Space"]),
+    (Action::BringToFront, "bring_to_front", &["BracketRight"]),
+    (Action::SendToBack, "send_to_back", &["BracketLeft"]),
+    (Action::DuplicatePoster, "duplicate_poster", &["Ctrl+J"]),
+    (Action::TogglePosterLock, "toggle_poster_lock", &["Ctrl+L"]),
+    (Action::ClearScratch, "clear_scratch", &["Delete"]),
+    (Action::ToggleBrushShape, "toggle_brush_shape", &["B"]),
+    (Action::Export, "export", &["Ctrl+E"]),
+    (Action::ExportSvg, "export_svg", &["Ctrl+D"]),
+    (Action::ToggleEraser, "toggle_eraser", &["E"]),
+    (Action::CycleSmoothing, "cycle_smoothing", &["M"]),
+    (Action::ToggleText, "toggle_text", &["T"]),
+    (Action::ToggleHighlighter, "toggle_highlighter", &["H"]),
+    (Action::ToggleStamp, "toggle_stamp", &["I"]),
+    (Action::ToggleFill, "toggle_fill", &["F"]),
+    (Action::ToggleMeasure, "toggle_measure", &["R"]),
+    (Action::TogglePen, "toggle_pen", &["V"]),
+    (Action::ToggleSelect, "toggle_select", &["N"]),
+    (Action::Quantize, "quantize", &["Q"]),
+    (Action::Clear, "clear", &["C"]),
+    (Action::ClearDrawing, "clear_drawing", &["Ctrl+Delete"]),
+    (Action::ClearBackground, "clear_background", &["Ctrl+Backspace"]),
+    (Action::Save, "save", &["P"]),
+    (Action::SaveSnapshot, "save_snapshot", &["Ctrl+S"]),
+    (Action::OpenSnapshots, "open_snapshots", &["Ctrl+O"]),
+    (Action::Paste, "paste", &["Ctrl+V"]),
+    (Action::Copy, "copy", &["Ctrl+C"]),
+    (Action::Cut, "cut", &["Ctrl+X"]),
+    (Action::Undo, "undo", &["Ctrl+Z"]),
+    (Action::Replay, "replay", &["Ctrl+R"]),
+    (Action::Exit, "exit", &["Escape"]),
+];
+
+/// Bookmark slot (0-based) for a digit key 1-9 on the main row or the numpad
+fn bookmark_slot(key: KeyCode) -> Option<usize> {
+    [
+        (KeyCode::Digit1, KeyCode::Numpad1), (KeyCode::Digit2, KeyCode::Numpad2), (KeyCode::Digit3, KeyCode::Numpad3),
+        (KeyCode::Digit4, KeyCode::Numpad4), (KeyCode::Digit5, KeyCode::Numpad5), (KeyCode::Digit6, KeyCode::Numpad6),
+        (KeyCode::Digit7, KeyCode::Numpad7), (KeyCode::Digit8, KeyCode::Numpad8), (KeyCode::Digit9, KeyCode::Numpad9),
+    ]
+    .iter()
+    .position(|&(digit, numpad)| key == digit || key == numpad)
+}
+
+/// Key names accepted in config.toml besides letters, digits and F1-F12
+const NAMED_KEYS: [(&str, KeyCode); 35] = [
+    ("Escape", KeyCode::Escape),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Space", KeyCode::Space),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("ArrowUp", KeyCode::ArrowUp),
+    ("ArrowDown", KeyCode::ArrowDown),
+    ("ArrowLeft", KeyCode::ArrowLeft),
+    ("ArrowRight", KeyCode::ArrowRight),
+    ("Equal", KeyCode::Equal),
+    ("Minus", KeyCode::Minus),
+    ("BracketLeft", KeyCode::BracketLeft),
+    ("BracketRight", KeyCode::BracketRight),
+    ("Backslash", KeyCode::Backslash),
+    ("Semicolon", KeyCode::Semicolon),
+    ("Quote", KeyCode::Quote),
+    ("Backquote", KeyCode::Backquote),
+    ("Comma", KeyCode::Comma),
+    ("Period", KeyCode::Period),
+    ("Slash", KeyCode::Slash),
+    ("NumpadAdd", KeyCode::NumpadAdd),
+    ("NumpadSubtract", KeyCode::NumpadSubtract),
+    ("NumpadMultiply", KeyCode::NumpadMultiply),
+    ("NumpadDivide", KeyCode::NumpadDivide),
+    ("NumpadEnter", KeyCode::NumpadEnter),
+    ("Numpad0", KeyCode::Numpad0),
+    ("Numpad1", KeyCode::Numpad1),
+    ("Numpad2", KeyCode::Numpad2),
+    ("Numpad3", KeyCode::Numpad3),
+];
+
+/// Parse a key name from config.toml: "W", "0", "F5", "Home", "NumpadAdd"... (case-insensitive)
+fn parse_key_code(name: &str) -> Option<KeyCode> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF, KeyCode::KeyG,
+        KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL, KeyCode::KeyM, KeyCode::KeyN,
+        KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR, KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU,
+        KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX, KeyCode::KeyY, KeyCode::KeyZ,
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+        KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    ];
+    const FUNCTION_KEYS: [KeyCode; 12] = [
+        KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+        KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    ];
+    
+    // winit's own names ("KeyW", "Digit0") work too
+    let upper = name.to_ascii_uppercase();
+    let short = upper.strip_prefix("KEY").or_else(|| upper.strip_prefix("DIGIT")).unwrap_or(&upper);
+    let mut chars = short.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_uppercase() {
+            return Some(LETTERS[(c as u8 - b'A') as usize]);
+        }
+        if c.is_ascii_digit() {
+            return Some(DIGITS[(c as u8 - b'0') as usize]);
+        }
+    }
+    if let Some(n) = upper.strip_prefix('F').and_then(|n| n.parse::<usize>().ok()) {
+        return FUNCTION_KEYS.get(n.wrapping_sub(1)).copied();
+    }
+    NAMED_KEYS.iter().find(|(key_name, _)| key_name.eq_ignore_ascii_case(name)).map(|&(_, code)| code)
+}
+
+/// Key -> action lookup built from the defaults plus the `[keys]` table of config.toml
+struct KeyBindings {
+    map: HashMap<(KeyCode, bool), Action>, // (key, ctrl held) -> action
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::from_config(&HashMap::new()).expect("default key bindings are valid")
+    }
+}
+
+impl KeyBindings {
+    /// Apply `[keys]` overrides (action name -> list of keys, empty to unbind) on top of the
+    /// defaults. Fails on unknown actions or keys, or if two actions end up on the same key.
+    fn from_config(overrides: &HashMap<String, Vec<String>>) -> Result<Self, String> {
+        for name in overrides.keys() {
+            if !DEFAULT_KEY_BINDINGS.iter().any(|(_, action_name, _)| action_name == name) {
+                return Err(format!("unknown action '{}' in keys", name));
+            }
+        }
+        
+        let mut map = HashMap::new();
+        for (action, name, defaults) in DEFAULT_KEY_BINDINGS {
+            let keys: Vec<&str> = match overrides.get(name) {
+                Some(keys) => keys.iter().map(String::as_str).collect(),
+                None => defaults.to_vec(),
+            };
+            for key in keys {
+                let (ctrl, key_name) = match key.get(..5) {
+                    Some(prefix) if prefix.eq_ignore_ascii_case("ctrl+") => (true, &key[5..]),
+                    _ => (false, key),
+                };
+                let code = parse_key_code(key_name).ok_or_else(|| format!("unknown key '{}' for {}", key, name))?;
+                if let Some(other) = map.insert((code, ctrl), action) {
+                    let other_name = DEFAULT_KEY_BINDINGS.iter().find(|(a, _, _)| *a == other).map_or("?", |(_, n, _)| *n);
+                    return Err(format!("key '{}' is bound to both {} and {}", key, other_name, name));
+                }
+            }
+        }
+        Ok(KeyBindings { map })
+    }
+    
+    /// Action for a key press. Plain bindings also fire with Ctrl held unless a Ctrl chord
+    /// claims the key (so Ctrl+E exports while E toggles the eraser).
+    fn action(&self, key: KeyCode, ctrl: bool) -> Option<Action> {
+        let chord = if ctrl { self.map.get(&(key, true)) } else { None };
+        chord.or_else(|| self.map.get(&(key, false))).copied()
+    }
+}
+
+/// Command-line options; any given here override config.toml (an existing board file keeps its own size and mode)
+struct CliArgs {
+    width: Option<u32>,
+    height: Option<u32>,
+    mode: Option<BoardMode>,
+    wrap_vertical: bool,
+    board_path: PathBuf,
+    full_sync: bool,
+    render_to: Option<PathBuf>, // Headless mode: render one view to this PNG and exit
+    replay_frames: Option<PathBuf>, // Headless mode: write one PNG per replayed stroke into this directory
+    view: Point, // --view x,y for --render-to (board pixels, top-left corner)
+    zoom: f32,
+    size: (u32, u32),
+    title: Option<String>, // --title / --author: stored in the board header
+    author: Option<String>,
+    log_level: Option<log::LevelFilter>, // --verbose / --quiet; None = RUST_LOG or info
+    log_file: Option<PathBuf>, // --log-file: also append log messages here
+    undo_steps: Option<usize>, // --undo-steps: overrides max_undo_steps from config.toml
+}
+
+/// Deepest undo history accepted from config.toml, RICKBOARD_UNDO_STEPS or --undo-steps
+const MAX_UNDO_STEPS: usize = 1000;
+
+/// Largest --size accepted by --render-to, per side
+const MAX_RENDER_SIZE: u32 = 16384;
+
+const USAGE: &str = "Usage: rickboard [OPTIONS] [BOARD_FILE]
+
+Arguments:
+  [BOARD_FILE]              Board file to open or create (default: rickboard.data)
+
+Options:
+  --width <PIXELS>          Width of a new board, 1-100000 (default: 80000)
+  --height <PIXELS>         Height of a new board, 1-100000 (default: 1000)
+  --mode <MODE>             blackboard, whiteboard, greenboard, corkboard, slate or #RRGGBB
+                            for a new board (default: blackboard)
+  --wrap-vertical           Make a new board wrap top-to-bottom as well (toroidal)
+  --undo-steps <N>          Drawing steps Ctrl+Z can take back, 0-1000 (default: 3)
+  --full-sync               Rewrite the whole board on every save instead of only changed tiles
+  --title <TEXT>            Give the board a title (shown in the window title and status bar)
+  --author <TEXT>           Record the board's author
+  --render-to <PNG>         Render one view of an existing board to a PNG and exit (no window)
+  --replay-frames <DIR>     Replay the board's stroke log into numbered PNGs in DIR and exit
+  --view <X,Y>              Top-left board pixel of the rendered view(s) (default: 0,0)
+  --zoom <FACTOR>           Zoom of the rendered view(s), 0.01-16 (default: 1)
+  --size <WxH>              Size of the rendered image(s) in pixels (default: 1024x768)
+  -v, --verbose             Also log debug messages (frame timings and the like)
+  -q, --quiet               Only log warnings and errors
+  --log-file <FILE>         Append log messages to FILE as well, e.g. for a bug report
+  -h, --help                Print this help
+
+Defaults can be changed in config.toml. Size, mode and wrapping only apply when the
+board file is created; existing boards keep theirs. Without --verbose or --quiet, the
+RUST_LOG environment variable can set the log level (error, warn, info, debug or trace).";
+
+/// Parse command-line arguments (without the program name). Err carries the message to print.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<CliArgs, String> {
+    let mut cli = CliArgs {
+        width: None,
+        height: None,
+        mode: None,
+        wrap_vertical: false,
+        board_path: PathBuf::from("rickboard.data"),
+        full_sync: false,
+        render_to: None,
+        replay_frames: None,
+        view: Point { x: 0.0, y: 0.0 },
+        zoom: 1.0,
+        size: (1024, 768),
+        title: None,
+        author: None,
+        log_level: None,
+        log_file: None,
+        undo_steps: None,
+    };
+    let mut board_path = None;
+    
+    let parse_dimension = |name: &str, value: Option<String>| -> Result<u32, String> {
+        let value = value.ok_or_else(|| format!("{} needs a value", name))?;
+        match value.parse::<u32>() {
+            Ok(n) if (1..=MAX_BOARD_DIMENSION).contains(&n) => Ok(n),
+            _ => Err(format!("{} must be a number from 1 to {}, got '{}'", name, MAX_BOARD_DIMENSION, value)),
+        }
+    };
+    
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // Accept both "--width 100" and "--width=100"
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        
+        match flag.as_str() {
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            "--full-sync" => cli.full_sync = true,
+            "--wrap-vertical" => cli.wrap_vertical = true,
+            "-v" | "--verbose" => cli.log_level = Some(log::LevelFilter::Debug),
+            "-q" | "--quiet" => cli.log_level = Some(log::LevelFilter::Warn),
+            "--log-file" => cli.log_file = Some(PathBuf::from(inline_value.or_else(|| args.next()).ok_or("--log-file needs a value")?)),
+            "--width" => cli.width = Some(parse_dimension("--width", inline_value.or_else(|| args.next()))?),
+            "--height" => cli.height = Some(parse_dimension("--height", inline_value.or_else(|| args.next()))?),
+            "--title" => cli.title = Some(inline_value.or_else(|| args.next()).ok_or("--title needs a value")?),
+            "--author" => cli.author = Some(inline_value.or_else(|| args.next()).ok_or("--author needs a value")?),
+            "--render-to" => {
+                let value = inline_value.or_else(|| args.next()).ok_or("--render-to needs a value")?;
+                cli.render_to = Some(PathBuf::from(value));
+            }
+            "--replay-frames" => {
+                let value = inline_value.or_else(|| args.next()).ok_or("--replay-frames needs a value")?;
+                cli.replay_frames = Some(PathBuf::from(value));
+            }
+            "--view" => {
+                let value = inline_value.or_else(|| args.next()).unwrap_or_default();
+                let parsed = value.split_once(',').and_then(|(x, y)| Some((x.trim().parse::<f32>().ok()?, y.trim().parse::<f32>().ok()?)));
+                match parsed {
+                    Some((x, y)) if x.is_finite() && y.is_finite() => cli.view = Point { x, y },
+                    _ => return Err(format!("--view must be two numbers like '1200,300', got '{}'", value)),
+                }
+            }
+            "--zoom" => {
+                let value = inline_value.or_else(|| args.next()).unwrap_or_default();
+                match value.parse::<f32>() {
+                    Ok(zoom) if ZOOM_RANGE.contains(&zoom) => cli.zoom = zoom,
+                    _ => return Err(format!("--zoom must be a number from 0.01 to 16, got '{}'", value)),
+                }
+            }
+            "--size" => {
+                let value = inline_value.or_else(|| args.next()).unwrap_or_default();
+                let parsed = value.split_once('x').and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)));
+                match parsed {
+                    Some((w, h)) if (1..=MAX_RENDER_SIZE).contains(&w) && (1..=MAX_RENDER_SIZE).contains(&h) => cli.size = (w, h),
+                    _ => return Err(format!("--size must be WIDTHxHEIGHT with sides from 1 to {}, got '{}'", MAX_RENDER_SIZE, value)),
+                }
+            }
+            "--undo-steps" => {
+                let value = inline_value.or_else(|| args.next()).unwrap_or_default();
+                match value.parse::<usize>() {
+                    Ok(steps) if steps <= MAX_UNDO_STEPS => cli.undo_steps = Some(steps),
+                    _ => return Err(format!("--undo-steps must be a number from 0 to {}, got '{}'", MAX_UNDO_STEPS, value)),
+                }
+            }
+            "--mode" => {
+                let value = inline_value.or_else(|| args.next()).unwrap_or_default();
+                cli.mode = Some(BoardMode::parse(&value).ok_or_else(|| format!(
+                    "--mode must be blackboard, whiteboard, greenboard, corkboard, slate or a #RRGGBB color, got '{}'", value))?);
+            }
+            _ if flag.starts_with('-') => return Err(format!("Unknown option '{}'", flag)),
+            _ if board_path.is_some() => return Err(format!("Unexpected extra argument '{}'", arg)),
+            _ => board_path = Some(PathBuf::from(arg)),
+        }
+    }
+    
+    if let Some(path) = board_path {
+        cli.board_path = path;
+    }
+    Ok(cli)
+}
+
+/// --render-to: compose one view of the board without a window and write it as an RGB PNG
+fn render_to_png(rickboard: &mut RickBoard, path: &Path, view: Point, zoom: f32, size: (u32, u32)) -> io::Result<()> {
+    set_headless_view(rickboard, view, zoom);
+    write_view_png(rickboard, path, size)?;
+    info!("Rendered {}x{} view at ({}, {}) x{} to {}", size.0, size.1, view.x, view.y, zoom, path.display());
+    Ok(())
+}
+
+/// --replay-frames: replay the stroke log without a window, writing the view after every
+/// stroke as frame_00000.png (empty), frame_00001.png, ... for encoding into a video
+fn render_replay_frames(rickboard: &mut RickBoard, dir: &Path, view: Point, zoom: f32, size: (u32, u32)) -> io::Result<()> {
+    if !rickboard.start_replay() {
+        return Err(io::Error::other("no strokes logged for this board"));
+    }
+    fs::create_dir_all(dir)?;
+    set_headless_view(rickboard, view, zoom);
+    
+    let mut frame_index = 0;
+    loop {
+        write_view_png(rickboard, &dir.join(format!("frame_{:05}.png", frame_index)), size)?;
+        frame_index += 1;
+        if !rickboard.replay_next_stroke() {
+            break;
+        }
+    }
+    rickboard.stop_replay();
+    info!("Wrote {} replay frames to {}", frame_index, dir.display());
+    Ok(())
+}
+
+/// Point the viewport for headless rendering
+fn set_headless_view(rickboard: &mut RickBoard, view: Point, zoom: f32) {
+    let viewport = &mut rickboard.board.viewport;
+    viewport.position = Point { x: view.x.rem_euclid(rickboard.board.config.width as f32), y: view.y };
+    viewport.zoom = zoom;
+}
+
+/// Compose the current view at `size` and write it as an RGB PNG
+fn write_view_png(rickboard: &mut RickBoard, path: &Path, size: (u32, u32)) -> io::Result<()> {
+    let (width, height) = size;
+    let mut frame = vec![0u8; width as usize * height as usize * 4];
+    rickboard.render_scene(&mut frame, width, height)?;
+    
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let rgb: Vec<u8> = frame.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&rgb))
+        .map_err(io::Error::other)
+}
+
+/// Offer to redraw the strokes a crash left in the journal (asked on the console; without
+/// one, e.g. when started from a desktop launcher, they're recovered without asking).
+/// Either way the journal is gone afterwards.
+fn recover_journal(board: &mut Board) {
+    use std::io::IsTerminal;
+    
+    println!("{} wasn't closed cleanly and has unsaved changes in {}", board.file_path.display(), board.journal_path().display());
+    let recover = if io::stdin().is_terminal() {
+        print!("Recover them? [Y/n] ");
+        let _ = io::stdout().flush();
+        let mut answer = String::new();
+        let _ = io::stdin().read_line(&mut answer);
+        !answer.trim().eq_ignore_ascii_case("n")
+    } else {
+        true
+    };
+    
+    if !recover {
+        match board.trim_journal(u64::MAX) {
+            Ok(()) => info!("Discarded the journal"),
+            Err(e) => error!("Journal error: {}", e),
+        }
+        return;
+    }
+    match board.recover_journal() {
+        Ok(count) => {
+            info!("Recovered {} strokes", count);
+            // Save right away; that also empties the journal
+            if let Err(e) = board.sync() {
+                error!("Save error: {}", e);
+            }
+        }
+        Err(e) => error!("Journal recovery error: {} (the journal is kept)", e),
+    }
+}
+
+/// Log backend: messages go to the console (info to stdout, warnings and errors to stderr)
+/// and, with --log-file, to that file with a timestamp and level
+struct Logger {
+    level: log::LevelFilter,
+    file: Option<std::sync::Mutex<File>>,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // Other crates (wgpu, winit) only get through with warnings and errors
+        metadata.level() <= self.level
+            && (metadata.target().starts_with(module_path!()) || metadata.level() <= log::Level::Warn)
+    }
+    
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            log::Level::Error => eprintln!("{}", record.args()),
+            log::Level::Warn => eprintln!("Warning: {}", record.args()),
+            log::Level::Info => println!("{}", record.args()),
+            level => println!("[{}] {}", level.as_str().to_lowercase(), record.args()),
+        }
+        if let Some(file) = &self.file {
+            let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64());
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "{:.3} {:<5} {}", secs, record.level(), record.args());
+            }
+        }
+    }
+    
+    fn flush(&self) {}
+}
+
+/// Install the logger: --verbose/--quiet win over RUST_LOG, which wins over the default (info)
+fn init_logging(cli: &CliArgs) {
+    let level = cli.log_level
+        .or_else(|| std::env::var("RUST_LOG").ok().and_then(|value| value.trim().parse().ok()))
+        .unwrap_or(log::LevelFilter::Info);
+    let file = cli.log_file.as_ref().and_then(|path| {
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => Some(std::sync::Mutex::new(file)),
+            Err(e) => {
+                eprintln!("Warning: can't open log file {}: {}", path.display(), e);
+                None
+            }
+        }
+    });
+    if log::set_boxed_logger(Box::new(Logger { level, file })).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
 fn main() {
-    // Default to Blackboard mode (can be changed via UI button)
-    let mode = BoardMode::Blackboard;
+    let cli = match parse_args(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(message) if message == USAGE => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            std::process::exit(2);
+        }
+    };
+    init_logging(&cli);
     
-    let board_path = Path::new("rickboard.data");
+    // Stroke texture seed can be pinned for reproducible grain
+    let texture_seed = std::env::var("RICKBOARD_TEXTURE_SEED").ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_TEXTURE_SEED);
     
-    match RickBoard::new(80000, 1000, mode, board_path).and_then(|rb| rb.init_with_posters()) {
-        Ok(rickboard) => {
+    // On-disk format for future saves: "rle" compresses the (mostly uniform) background
+    let storage_format = match std::env::var("RICKBOARD_STORAGE").ok().as_deref() {
+        Some("rle") => Some(StorageFormat::Rle),
+        Some("raw") => Some(StorageFormat::Raw),
+        _ => None,
+    };
+    
+    // Command-line options take precedence over config.toml
+    let mut config = Config::load(Path::new(CONFIG_FILE));
+    config.board_width = cli.width.unwrap_or(config.board_width);
+    config.board_height = cli.height.unwrap_or(config.board_height);
+    config.mode = cli.mode.unwrap_or(config.mode);
+    config.wrap_vertical |= cli.wrap_vertical;
+    config.apply_env_overrides();
+    // Undo depth (each step holds copies of the 256x256 tiles it changed, 256KB each)
+    config.max_undo_steps = cli.undo_steps.unwrap_or(config.max_undo_steps);
+    config.resolve_paths();
+    let undo_steps = config.max_undo_steps;
+    let auto_save_interval = config.auto_save_secs as f32;
+    let frame_interval = (config.max_fps > 0).then(|| std::time::Duration::from_secs_f64(1.0 / config.max_fps as f64));
+    let pan_speed = config.pan_step as f32 * PAN_STEPS_PER_SEC;
+    // Already validated by Config::load
+    let key_bindings = KeyBindings::from_config(&config.keys).unwrap_or_default();
+    
+    // Headless rendering only reads an existing board; don't create one by accident
+    let headless = cli.render_to.is_some() || cli.replay_frames.is_some();
+    if headless && !cli.board_path.exists() {
+        error!("Error: board file {} not found", cli.board_path.display());
+        std::process::exit(1);
+    }
+    
+    match RickBoard::new(config, &cli.board_path, texture_seed).and_then(|rb| rb.init_with_posters()) {
+        Ok(mut rickboard) if cli.render_to.is_some() => {
+            let path = cli.render_to.as_deref().unwrap();
+            if let Err(e) = render_to_png(&mut rickboard, path, cli.view, cli.zoom, cli.size) {
+                error!("Error rendering {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        Ok(mut rickboard) if cli.replay_frames.is_some() => {
+            let dir = cli.replay_frames.as_deref().unwrap();
+            if let Err(e) = render_replay_frames(&mut rickboard, dir, cli.view, cli.zoom, cli.size) {
+                error!("Error replaying into {}: {}", dir.display(), e);
+                std::process::exit(1);
+            }
+        }
+        Ok(mut rickboard) => {
+            if rickboard.board.journal_len > 0 {
+                recover_journal(&mut rickboard.board);
+            }
+            rickboard.board.set_max_undo_steps(undo_steps);
+            if let Some(format) = storage_format {
+                rickboard.board.set_storage_format(format);
+            }
+            rickboard.board.full_sync = cli.full_sync;
+            if cli.title.is_some() || cli.author.is_some() {
+                // Saved right away, so the new title sticks even if nothing gets drawn
+                rickboard.board.set_metadata(cli.title, cli.author);
+                if let Err(e) = rickboard.board.sync() {
+                    error!("Save error: {}", e);
+                }
+            }
+            
             let event_loop = EventLoop::new().unwrap();
             event_loop.set_control_flow(ControlFlow::Wait);
             
@@ -1987,22 +7317,47 @@ fn main() {
                 mouse_down: false,
                 right_mouse_down: false,
                 cursor_pos: (0.0, 0.0),
+                cursor_in_window: false,
                 render_width: 1024,
                 render_height: 768,
                 frame_count: 0,
                 last_fps_update: Instant::now(),
                 fps: 0.0,
                 last_save: Instant::now(),
+                auto_save_interval,
+                key_bindings,
                 is_saving: false,
                 has_unsaved_changes: false,
                 modifiers: ModifiersState::empty(),
                 save_message_until: None,
+                held_pan_keys: HashMap::new(),
+                pan_speed,
+                pan_hold_secs: 0.0,
+                last_frame: Instant::now(),
+                frame_interval,
+                next_frame_at: None,
+                animating: false,
+                focused: true,
+                occluded: false,
+                minimized: false,
+                middle_pan: None,
+                space_pan: false,
+                hand_drag: false,
+                last_pan_move: Instant::now(),
+                last_left_press: None,
+                zoom_target: None,
+                bookmark_jump: None,
             };
             
             event_loop.run_app(&mut app).unwrap();
+            
+            // Exiting with Escape skips the final save, but a background save must not be cut off
+            if let Err(e) = app.rickboard.board.finish_sync() {
+                error!("Save error: {}", e);
+            }
         }
         Err(e) => {
-            eprintln!("Error creating board: {}", e);
+            error!("Error creating board: {}", e);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,102 @@
+//! The 4-wide blend must give exactly what four scalar blends give, the ink blend modes
+//! must do what their names say, and stroke masks must layer over the ink underneath
+
+use std::collections::HashMap;
+
+mod common;
+
+use common::scratch_dir;
+use rickboard::{blend_pixel, blend_pixels4, BlendMode, Board, BoardMode, Paper};
+
+#[test]
+fn four_wide_blend_matches_scalar() {
+    let mut seed = 12345u64;
+    let mut random = || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as u8
+    };
+    for _ in 0..200_000 {
+        let mut src = [[0u8; 4]; 4];
+        for px in src.iter_mut() {
+            for channel in px.iter_mut() {
+                *channel = random();
+            }
+            // Plenty of fully transparent and fully opaque pixels, which take the shortcuts
+            match random() {
+                0..=59 => px[3] = 0,
+                60..=119 => px[3] = 255,
+                _ => {}
+            }
+        }
+        let mut wide = [0u8; 16];
+        for channel in wide.iter_mut() {
+            *channel = random();
+        }
+        let mut scalar = wide;
+        blend_pixels4(&mut wide, &src);
+        for (k, &px) in src.iter().enumerate() {
+            blend_pixel(&mut scalar[k * 4..k * 4 + 4], px);
+        }
+        assert_eq!(wide, scalar, "src {:?}", src);
+    }
+}
+
+/// `src` blended onto one mid-grey and one near-white destination pixel
+fn blend_both(mode: BlendMode, src: [u8; 4]) -> ([u8; 4], [u8; 4]) {
+    let (mut dark, mut light) = ([60, 60, 60, 255], [220, 220, 220, 255]);
+    mode.blend(&mut dark, src);
+    mode.blend(&mut light, src);
+    (dark, light)
+}
+
+#[test]
+fn ink_blend_modes() {
+    let grey = [128, 128, 128, 255];
+    
+    // Normal is plain src-over: an opaque pixel is a copy
+    assert_eq!(blend_both(BlendMode::Normal, grey), (grey, grey));
+    // Multiply only darkens, screen only lightens
+    let (dark, light) = blend_both(BlendMode::Multiply, grey);
+    assert!(dark[0] < 60 && light[0] < 220);
+    let (dark, light) = blend_both(BlendMode::Screen, grey);
+    assert!(dark[0] > 60 && light[0] > 220);
+    // Overlay: mid-grey ink is neutral, lighter ink lightens and darker ink darkens
+    let (dark, light) = blend_both(BlendMode::Overlay, grey);
+    assert!(dark[0].abs_diff(60) <= 1 && light[0].abs_diff(220) <= 1);
+    let (dark, light) = blend_both(BlendMode::Overlay, [200, 200, 200, 255]);
+    assert!(dark[0] > 60 && light[0] > 220);
+    let (dark, light) = blend_both(BlendMode::Overlay, [50, 50, 50, 255]);
+    assert!(dark[0] < 60 && light[0] < 220);
+    
+    // Transparent ink leaves the destination alone, and half-transparent ink lands halfway
+    for mode in BlendMode::LAYER_MODES {
+        assert_eq!(blend_both(mode, [255, 0, 0, 0]), ([60, 60, 60, 255], [220, 220, 220, 255]), "{:?}", mode);
+        let (full, _) = blend_both(mode, [255, 255, 255, 255]);
+        let (half, _) = blend_both(mode, [255, 255, 255, 128]);
+        assert!(half[0].abs_diff(((full[0] as u32 + 60) / 2) as u8) <= 1, "{:?}: {} vs {}", mode, half[0], full[0]);
+    }
+}
+
+#[test]
+fn chalk_grain_gaps_keep_the_ink_underneath() {
+    let path = scratch_dir("chalk-over-ink").join("board.data");
+    let mut board = Board::new(300, 200, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    let red = [255, 0, 0, 255];
+    for x in 0..8 {
+        board.draw_pixel(x, 10, red);
+    }
+    
+    // A textured white stroke as draw_brush collects it: skipped grains, light grain, solid grain
+    let width = board.config.width as usize;
+    let grain = [0u8, 0, 60, 128, 200, 255, 0, 255];
+    let mask: HashMap<usize, u8> = grain.iter().enumerate().map(|(x, &alpha)| (10 * width + x, alpha)).collect();
+    board.save_undo_state();
+    board.blend_stroke(&mask, [255, 255, 255, 255]);
+    
+    for (x, &alpha) in grain.iter().enumerate() {
+        let pixel = board.drawing_layer.get(x, 10);
+        assert_eq!(pixel[3], 255, "pixel {} lost its ink", x);
+        assert!(pixel[1].abs_diff(alpha) <= 1, "pixel {}: {:?} under grain alpha {}", x, pixel, alpha);
+        assert_eq!(pixel[0], 255);
+    }
+}
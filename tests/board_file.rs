@@ -0,0 +1,363 @@
+//! Board file round trips: pixels, header fields and the fallbacks for damaged or older files
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+
+use common::scratch_dir;
+use rickboard::{sidecar_path, BlendMode, Board, BoardMode, Paper, PaperStyle, TiledLayer};
+
+/// Open a board, with arguments that only matter if the file has to be created
+fn open(path: &Path) -> Board {
+    Board::new(40, 30, BoardMode::Whiteboard, Paper::PLAIN, false, path).unwrap()
+}
+
+/// Whole layer as raw RGBA
+fn pixels(layer: &TiledLayer) -> Vec<u8> {
+    let mut out = Vec::new();
+    layer.write_raw(&mut out).unwrap();
+    out
+}
+
+/// A 9-byte header (mode byte, then width and height LE) as written before format versions
+fn plain_header(mode: u8, width: u32, height: u32) -> Vec<u8> {
+    let mut header = vec![mode];
+    header.extend_from_slice(&width.to_le_bytes());
+    header.extend_from_slice(&height.to_le_bytes());
+    header
+}
+
+#[test]
+fn drawing_survives_sync_and_reopen() {
+    let path = scratch_dir("round-trip").join("board.data");
+    let mut board = Board::new(600, 300, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    board.draw_pixel(10, 20, [255, 0, 0, 255]);
+    board.draw_pixel(599, 299, [1, 2, 3, 255]);
+    board.draw_pixel(-1, 5, [0, 255, 0, 255]); // Wraps to the last column
+    board.draw_pixel(300, 400, [0, 0, 255, 255]); // Off the board, ignored
+    board.sync().unwrap();
+    let (cache, drawing) = (pixels(&board.cache), pixels(&board.drawing_layer));
+    drop(board);
+    
+    let board = open(&path);
+    assert_eq!((board.config.width, board.config.height), (600, 300));
+    assert_eq!(board.drawing_layer.get(10, 20), [255, 0, 0, 255]);
+    assert_eq!(board.drawing_layer.get(599, 299), [1, 2, 3, 255]);
+    assert_eq!(board.drawing_layer.get(599, 5), [0, 255, 0, 255]);
+    assert!(board.has_drawings);
+    assert!(pixels(&board.cache) == cache);
+    assert!(pixels(&board.drawing_layer) == drawing);
+    assert!(pixels(&board.cache).chunks_exact(4).all(|p| p == BoardMode::Blackboard.background_color()));
+}
+
+#[test]
+fn header_keeps_mode_size_wrap_and_paper() {
+    let dir = scratch_dir("header");
+    let paper = Paper { style: PaperStyle::Grid, spacing: 16, color: Some([10, 20, 30, 40]) };
+    let modes = [BoardMode::Blackboard, BoardMode::Whiteboard, BoardMode::Greenboard, BoardMode::Corkboard, BoardMode::Slate, BoardMode::Custom([90, 30, 120, 255])];
+    for (i, mode) in modes.into_iter().enumerate() {
+        let path = dir.join(format!("board{}.data", i));
+        let wrap = i % 2 == 0;
+        let board = Board::new(300 + i as u32, 100, mode, paper, wrap, &path).unwrap();
+        drop(board);
+        
+        let board = open(&path);
+        assert_eq!(board.config.mode, mode);
+        assert_eq!((board.config.width, board.config.height), (300 + i as u32, 100));
+        assert_eq!(board.config.wrap_vertical, wrap);
+        assert_eq!(board.config.paper, paper);
+        assert_eq!(board.cache.get(7, 7), mode.background_color());
+    }
+}
+
+#[test]
+fn metadata_round_trips() {
+    let path = scratch_dir("metadata").join("board.data");
+    let mut board = open(&path);
+    board.set_metadata(Some("Lecture 4".to_string()), Some("Ada".to_string()));
+    board.sync().unwrap();
+    drop(board);
+    
+    let board = open(&path);
+    assert_eq!(board.metadata.title, "Lecture 4");
+    assert_eq!(board.metadata.author, "Ada");
+}
+
+#[test]
+fn invalid_dimensions_create_a_new_board() {
+    let path = scratch_dir("invalid-dims").join("board.data");
+    let mut data = plain_header(0, 0, 100);
+    data.extend_from_slice(&[7u8; 64]);
+    fs::write(&path, data).unwrap();
+    
+    let board = Board::new(200, 50, BoardMode::Greenboard, Paper::PLAIN, false, &path).unwrap();
+    assert_eq!((board.config.width, board.config.height), (200, 50));
+    assert_eq!(board.config.mode, BoardMode::Greenboard);
+    assert_eq!(board.cache.get(199, 49), BoardMode::Greenboard.background_color());
+    drop(board);
+    
+    // ...and the file was rewritten with a good header
+    let board = open(&path);
+    assert_eq!((board.config.width, board.config.height), (200, 50));
+}
+
+#[test]
+fn file_too_short_for_a_header_is_replaced() {
+    // The "old format detected" branch: a file with no room for a header
+    let path = scratch_dir("old-format").join("board.data");
+    fs::write(&path, [1, 2, 3, 4, 5]).unwrap();
+    
+    let board = Board::new(120, 40, BoardMode::Slate, Paper::PLAIN, false, &path).unwrap();
+    assert_eq!((board.config.width, board.config.height), (120, 40));
+    assert_eq!(board.config.mode, BoardMode::Slate);
+    drop(board);
+    
+    let board = open(&path);
+    assert_eq!((board.config.width, board.config.height), (120, 40));
+    assert_eq!(board.cache.get(0, 0), BoardMode::Slate.background_color());
+}
+
+#[test]
+fn boards_keep_their_own_sidecars() {
+    let dir = scratch_dir("sidecars");
+    let (a, b) = (dir.join("a.data"), dir.join("b.data"));
+    let mut board_a = Board::new(600, 200, BoardMode::Blackboard, Paper::PLAIN, false, &a).unwrap();
+    board_a.draw_pixel(5, 5, [255, 0, 0, 255]);
+    board_a.sync().unwrap();
+    drop(board_a);
+    
+    // Board B is in the same folder but starts without A's ink
+    let board_b = Board::new(600, 200, BoardMode::Blackboard, Paper::PLAIN, false, &b).unwrap();
+    assert!(!board_b.has_drawings);
+    assert_eq!(board_b.drawing_layer.get(5, 5), [0, 0, 0, 0]);
+    drop(board_b);
+    
+    let board_a = Board::new(600, 200, BoardMode::Blackboard, Paper::PLAIN, false, &a).unwrap();
+    assert_eq!(board_a.drawing_layer.get(5, 5), [255, 0, 0, 255]);
+}
+
+#[test]
+fn boards_with_the_same_stem_keep_their_own_sidecars() {
+    let dir = scratch_dir("same-stem");
+    let paths = [dir.join("a.data"), dir.join("a.bak"), dir.join("a")];
+    for (i, path) in paths.iter().enumerate() {
+        let mut board = Board::new(600, 200, BoardMode::Blackboard, Paper::PLAIN, false, path).unwrap();
+        board.draw_pixel(i as i32, 0, [255, 0, 0, 255]);
+        board.sync().unwrap();
+    }
+    
+    // Each board sees only its own pixel
+    for (i, path) in paths.iter().enumerate() {
+        let board = Board::new(600, 200, BoardMode::Blackboard, Paper::PLAIN, false, path).unwrap();
+        for x in 0..paths.len() {
+            let expected = if x == i { [255, 0, 0, 255] } else { [0, 0, 0, 0] };
+            assert_eq!(board.drawing_layer.get(x, 0), expected, "{} at x={}", path.display(), x);
+        }
+    }
+}
+
+#[test]
+fn sidecars_named_after_the_stem_are_adopted() {
+    let path = scratch_dir("stem-sidecars").join("notes.data");
+    let mut board = Board::new(600, 200, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    board.draw_pixel(7, 7, [0, 255, 0, 255]);
+    board.sync().unwrap();
+    drop(board);
+    
+    // How the sidecar used to be named: the board's extension replaced
+    let old = path.with_extension("drawing");
+    fs::rename(sidecar_path(&path, "drawing"), &old).unwrap();
+    let board = Board::new(600, 200, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    assert_eq!(board.drawing_layer.get(7, 7), [0, 255, 0, 255]);
+    assert!(!old.exists());
+}
+
+#[test]
+fn saving_rewrites_only_touched_bytes() {
+    let path = scratch_dir("dirty-tiles").join("board.data");
+    let drawing_path = sidecar_path(&path, "drawing");
+    let (width, height) = (5000usize, 1000usize);
+    let mut board = Board::new(width as u32, height as u32, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    board.draw_pixel(1, 1, [9, 9, 9, 255]);
+    board.sync().unwrap();
+    
+    // Scribble where the next save has nothing to write; rewriting whole files would undo it
+    let far = (500 * width + 2500) * 4;
+    let mut board_file = fs::read(&path).unwrap();
+    let mut drawing_file = fs::read(&drawing_path).unwrap();
+    board_file[far] ^= 0xFF;
+    drawing_file[far] ^= 0xFF;
+    fs::write(&path, &board_file).unwrap();
+    fs::write(&drawing_path, &drawing_file).unwrap();
+    
+    board.draw_pixel(10, 20, [255, 0, 0, 255]);
+    board.draw_pixel(4999, 999, [1, 2, 3, 255]);
+    board.sync().unwrap();
+    
+    assert!(fs::read(&path).unwrap() == board_file);
+    let drawing = fs::read(&drawing_path).unwrap();
+    let touched = [(20 * width + 10) * 4, (999 * width + 4999) * 4];
+    for (i, (new, old)) in drawing.iter().zip(&drawing_file).enumerate() {
+        if new != old {
+            assert!(touched.iter().any(|&start| (start..start + 4).contains(&i)), "byte {} changed", i);
+        }
+    }
+    assert_eq!(drawing[touched[0]..touched[0] + 4], [255, 0, 0, 255]);
+    assert_eq!(drawing[touched[1]..touched[1] + 4], [1, 2, 3, 255]);
+}
+
+#[test]
+fn highlighter_modes_are_patched_per_tile() {
+    let path = scratch_dir("blend-tiles").join("board.data");
+    let blend_path = sidecar_path(&path, "blend");
+    let (width, height) = (5000usize, 1000usize);
+    let mut board = Board::new(width as u32, height as u32, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    board.draw_pixel(1, 1, [255, 255, 0, 128]);
+    board.set_blend_mode(1, 1, BlendMode::Multiply);
+    board.sync().unwrap();
+    
+    // As with the drawing layer, only the dirty tile's modes may be written back
+    let far = 500 * width + 2500;
+    let mut blend_file = fs::read(&blend_path).unwrap();
+    assert_eq!(blend_file.len(), width * height);
+    assert_eq!(blend_file[width + 1], BlendMode::Multiply as u8);
+    blend_file[far] = BlendMode::Multiply as u8;
+    fs::write(&blend_path, &blend_file).unwrap();
+    
+    board.set_blend_mode(4999, 999, BlendMode::Multiply);
+    board.sync().unwrap();
+    let touched = 999 * width + 4999;
+    blend_file[touched] = BlendMode::Multiply as u8;
+    assert!(fs::read(&blend_path).unwrap() == blend_file);
+    drop(board);
+    
+    let mut board = Board::new(width as u32, height as u32, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    assert_eq!(board.drawing_layer.blend_mode(1, 1), BlendMode::Multiply);
+    assert_eq!(board.drawing_layer.blend_mode(4999, 999), BlendMode::Multiply);
+    assert_eq!(board.drawing_layer.blend_mode(2, 1), BlendMode::Normal);
+    
+    // Clearing the ink drops every blend tile, and the sidecar with them
+    board.clear_drawing();
+    board.sync().unwrap();
+    assert!(!board.drawing_layer.has_blend_modes());
+    assert!(!blend_path.exists());
+}
+
+#[test]
+fn sidecars_of_another_size_are_ignored() {
+    let dir = scratch_dir("sidecar-size");
+    let (a, b) = (dir.join("a.data"), dir.join("b.data"));
+    let mut board_a = Board::new(600, 200, BoardMode::Blackboard, Paper::PLAIN, false, &a).unwrap();
+    board_a.draw_pixel(5, 5, [255, 0, 0, 255]);
+    board_a.sync().unwrap();
+    drop(board_a);
+    drop(Board::new(400, 100, BoardMode::Blackboard, Paper::PLAIN, false, &b).unwrap());
+    
+    // Board A's drawing copied over board B's: it doesn't fit, so B must not show A's ink
+    fs::copy(sidecar_path(&a, "drawing"), sidecar_path(&b, "drawing")).unwrap();
+    let board_b = Board::new(400, 100, BoardMode::Blackboard, Paper::PLAIN, false, &b).unwrap();
+    assert!(!board_b.has_drawings);
+    assert_eq!(board_b.drawing_layer.get(5, 5), [0, 0, 0, 0]);
+}
+
+#[test]
+fn older_format_versions_load_and_newer_ones_are_refused() {
+    let path = scratch_dir("versions").join("board.data");
+    let (width, height) = (300u32, 100u32);
+    let black = BoardMode::Blackboard.background_color();
+    let mut body: Vec<u8> = black.iter().copied().cycle().take((width * height * 4) as usize).collect();
+    body[5 * 4..5 * 4 + 4].copy_from_slice(&[1, 2, 3, 255]);
+    
+    // Version 0 (plain header) and version 1 (version byte = storage format) baked ink into
+    // the background; loading moves it into the drawing layer
+    let v1 = [plain_header(0x80, width, height), vec![0]].concat();
+    for header in [plain_header(0, width, height), v1] {
+        fs::write(&path, [header, body.clone()].concat()).unwrap();
+        let mut board = open(&path);
+        assert_eq!((board.config.width, board.config.height), (width, height));
+        assert_eq!(board.config.mode, BoardMode::Blackboard);
+        assert_eq!(board.drawing_layer.get(5, 0), [1, 2, 3, 255]);
+        assert_eq!(board.cache.get(5, 0), black);
+        board.sync().unwrap();
+        drop(board);
+        
+        // Saved in the current format, which keeps the ink where it is
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data[9], 3);
+        let board = open(&path);
+        assert_eq!(board.drawing_layer.get(5, 0), [1, 2, 3, 255]);
+        assert_eq!(board.cache.get(5, 0), black);
+    }
+    
+    // Version 2: version byte, storage format byte, no background color
+    let v2 = [plain_header(0x80 | 1, width, height), vec![2, 0]].concat();
+    let white = BoardMode::Whiteboard.background_color();
+    let white_body: Vec<u8> = white.iter().copied().cycle().take((width * height * 4) as usize).collect();
+    fs::write(&path, [v2, white_body].concat()).unwrap();
+    let board = open(&path);
+    assert_eq!(board.config.mode, BoardMode::Whiteboard);
+    assert_eq!(board.cache.get(299, 99), white);
+    drop(board);
+    
+    // A version from a newer build is refused rather than misread
+    let mut data = fs::read(&path).unwrap();
+    data[9] = 4;
+    fs::write(&path, &data).unwrap();
+    assert!(Board::new(40, 30, BoardMode::Whiteboard, Paper::PLAIN, false, &path).is_err());
+}
+
+#[test]
+fn mode_switches_and_clears_wait_for_the_next_save() {
+    let path = scratch_dir("mode-switch").join("board.data");
+    let mut board = Board::new(300, 100, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    board.cache.set(3, 3, [1, 2, 3, 255]);
+    board.sync().unwrap();
+    
+    // Neither writes anything by itself (the app saves on its worker thread)
+    board.toggle_mode();
+    board.clear_background();
+    let saved = open(&path);
+    assert_eq!(saved.config.mode, BoardMode::Blackboard);
+    assert_eq!(saved.cache.get(3, 3), [1, 2, 3, 255]);
+    drop(saved);
+    
+    board.sync().unwrap();
+    let saved = open(&path);
+    assert_eq!(saved.config.mode, BoardMode::Blackboard.next());
+    assert_eq!(saved.cache.get(3, 3), BoardMode::Blackboard.next().background_color());
+}
+
+#[test]
+fn mode_switches_and_background_clears_are_recovered_from_the_journal() {
+    let path = scratch_dir("journal-background").join("board.data");
+    let mut board = Board::new(300, 100, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    board.cache.set(3, 3, [1, 2, 3, 255]);
+    board.sync().unwrap();
+    
+    // A clear that's undone again must leave the saved background alone
+    board.clear_background();
+    assert!(board.undo());
+    drop(board); // Crash before the next save
+    let mut board = open(&path);
+    assert!(board.journal_len > 0);
+    board.recover_journal().unwrap();
+    assert_eq!(board.cache.get(3, 3), [1, 2, 3, 255]);
+    board.sync().unwrap();
+    
+    board.clear_background();
+    board.toggle_mode();
+    drop(board);
+    let mut board = open(&path);
+    assert_eq!(board.config.mode, BoardMode::Blackboard);
+    board.recover_journal().unwrap();
+    let mode = BoardMode::Blackboard.next();
+    assert_eq!(board.config.mode, mode);
+    assert_eq!(board.cache.get(3, 3), mode.background_color());
+    
+    // Recovery is saved like any other change
+    board.sync().unwrap();
+    drop(board);
+    let board = open(&path);
+    assert_eq!((board.config.mode, board.cache.get(3, 3)), (mode, mode.background_color()));
+}
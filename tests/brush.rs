@@ -0,0 +1,45 @@
+//! Brush footprints: the chisel nib is long one way and thin the other, so the direction of
+//! motion decides how wide its stroke is
+
+use rickboard::BrushShape;
+
+/// Width of the band a stamp of this shape leaves when dragged 20 steps of (dx, dy)
+fn swept_width(shape: BrushShape, radius: i32, step: (i32, i32)) -> usize {
+    let mut covered = std::collections::HashSet::new();
+    for i in 0..20 {
+        let (cx, cy) = (step.0 * i, step.1 * i);
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if shape.contains(dx, dy, radius) {
+                    covered.insert((cx + dx, cy + dy));
+                }
+            }
+        }
+    }
+    // Cross-section of the band halfway along, perpendicular to the motion
+    let (mx, my) = (step.0 * 10, step.1 * 10);
+    covered.iter().filter(|&&(x, y)| if step.0 != 0 { x == mx } else { y == my }).count()
+}
+
+#[test]
+fn chisel_width_follows_the_direction_of_motion() {
+    let radius = 10;
+    // A horizontal nib draws thin horizontal strokes and full-width vertical ones
+    let flat = BrushShape::Chisel { angle: 0.0 };
+    let across = swept_width(flat, radius, (0, 1));
+    let along = swept_width(flat, radius, (1, 0));
+    assert_eq!(across, 2 * radius as usize + 1);
+    assert!(along <= 3, "{} pixels thick", along);
+    
+    // Turned upright, it's the other way round
+    let upright = BrushShape::Chisel { angle: 90.0 };
+    assert_eq!(swept_width(upright, radius, (1, 0)), 2 * radius as usize + 1);
+    assert!(swept_width(upright, radius, (0, 1)) <= 3);
+}
+
+#[test]
+fn round_and_square_are_the_same_every_way() {
+    for shape in [BrushShape::Round, BrushShape::Square] {
+        assert_eq!(swept_width(shape, 6, (1, 0)), swept_width(shape, 6, (0, 1)));
+    }
+}
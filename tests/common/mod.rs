@@ -0,0 +1,12 @@
+//! Fixtures shared by the integration tests
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Fresh, empty directory for one test
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rickboard-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,56 @@
+//! Golden image of the built-in 5x7 font, so a changed glyph shows up in review.
+//! After an intended change, regenerate with `RICKBOARD_BLESS=1 cargo test --test glyphs`.
+
+use std::fs;
+use std::path::Path;
+
+use rickboard::glyph;
+
+const PER_ROW: usize = 16;
+
+/// Printable ASCII, 16 glyphs a row, '#' for ink and '.' for paper
+fn glyph_table() -> String {
+    let chars: Vec<char> = (' '..='~').collect();
+    let mut out = String::new();
+    for row in chars.chunks(PER_ROW) {
+        for line in 0..7 {
+            let cells: Vec<String> = row.iter().map(|&ch| {
+                let bits = glyph(ch)[line];
+                (0..5).map(|col| if bits & (0b10000 >> col) != 0 { '#' } else { '.' }).collect()
+            }).collect();
+            out.push_str(&cells.join(" "));
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[test]
+fn glyph_table_matches_golden() {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/glyphs.txt");
+    let table = glyph_table();
+    if std::env::var_os("RICKBOARD_BLESS").is_some() {
+        fs::write(&golden, &table).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap();
+    for (i, (got, want)) in table.lines().zip(expected.lines()).enumerate() {
+        assert_eq!(got, want, "line {} of {}", i + 1, golden.display());
+    }
+    assert_eq!(table.lines().count(), expected.lines().count());
+}
+
+#[test]
+fn glyphs_are_distinct_and_fit_the_cell() {
+    let chars: Vec<char> = (' '..='~').collect();
+    for (i, &a) in chars.iter().enumerate() {
+        assert_eq!(glyph(a).len(), 7, "{:?}", a);
+        assert!(glyph(a).iter().all(|&bits| bits < 0b100000), "{:?}", a);
+        for &b in &chars[i + 1..] {
+            assert_ne!(glyph(a), glyph(b), "{:?} and {:?} look the same", a, b);
+        }
+    }
+    // Anything outside the table is drawn as a box
+    assert_eq!(glyph('\u{e9}'), glyph('\u{2603}'));
+}
@@ -0,0 +1,91 @@
+//! Viewport rendering: the scrolled cache must match a full redraw at any position and zoom
+
+mod common;
+
+use common::scratch_dir;
+use rickboard::{Board, BoardMode, Paper, Point};
+
+/// Background and ink together, as the window draws them
+fn render_scene(board: &mut Board, frame: &mut [u8], width: u32, height: u32) {
+    board.render(frame, width, height).unwrap();
+    board.render_drawing_layer(frame, width, height, 255);
+}
+
+#[test]
+fn panning_matches_a_full_render() {
+    let path = scratch_dir("pan").join("board.data");
+    let mut board = Board::new(600, 300, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    for y in 0..300 {
+        for x in 0..600 {
+            board.cache.set(x, y, [(x * 7 % 256) as u8, (y * 13 % 256) as u8, ((x ^ y) % 256) as u8, 255]);
+        }
+    }
+    let (width, height) = (200u32, 120u32);
+    let mut frame = vec![0u8; (width * height * 4) as usize];
+    let mut full = frame.clone();
+    for zoom in [1.0f32, 2.0, 3.0] {
+        board.viewport.zoom = zoom;
+        board.viewport.position = Point { x: 100.0, y: 50.0 };
+        board.viewport_dirty = true;
+        board.render(&mut frame, width, height).unwrap();
+        // Small steps, steps past the seam and jumps bigger than the screen
+        for (x, y) in [(110.5, 50.0), (90.0, 40.2), (95.0, 70.0), (5.0, 60.0), (-20.0, -10.0), (-20.0, 200.0), (-10.0, 250.0), (300.0, 250.0)] {
+            board.viewport.position = Point { x, y };
+            board.render(&mut frame, width, height).unwrap();
+            board.viewport_dirty = true;
+            board.render(&mut full, width, height).unwrap();
+            assert!(frame == full, "zoom {} at ({}, {})", zoom, x, y);
+        }
+    }
+}
+
+#[test]
+fn high_zoom_magnifies_the_right_pixels() {
+    let path = scratch_dir("zoom").join("board.data");
+    let mut board = Board::new(400, 300, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    let color = |x: i32, y: i32| [(x * 10 % 256) as u8, (y * 10 % 256) as u8, 77, 255];
+    for y in 100..120 {
+        for x in 100..120 {
+            board.draw_pixel(x, y, color(x, y));
+        }
+    }
+    board.viewport.position = Point { x: 100.0, y: 100.0 };
+    board.viewport.zoom = 8.0;
+    let (width, height) = (160u32, 120u32);
+    let mut frame = vec![0u8; (width * height * 4) as usize];
+    render_scene(&mut board, &mut frame, width, height);
+    for sy in 0..height {
+        for sx in 0..width {
+            let offset = ((sy * width + sx) * 4) as usize;
+            let expected = color(100 + sx as i32 / 8, 100 + sy as i32 / 8);
+            assert_eq!(frame[offset..offset + 3], expected[..3], "at ({}, {})", sx, sy);
+        }
+    }
+    
+    // Half a board pixel across is 4 screen pixels, a quarter down is 2
+    board.viewport.position = Point { x: 100.5, y: 100.25 };
+    render_scene(&mut board, &mut frame, width, height);
+    for sy in 0..height {
+        for sx in 0..width {
+            let (bx, by) = (100 + (sx as i32 + 4) / 8, 100 + (sy as i32 + 2) / 8);
+            let offset = ((sy * width + sx) * 4) as usize;
+            if bx < 120 && by < 120 {
+                assert_eq!(frame[offset..offset + 3], color(bx, by)[..3], "at ({}, {})", sx, sy);
+            }
+        }
+    }
+    
+    // Exports composite the same way the window does
+    assert!(board.composite_viewport(width, height, &[]) == frame);
+    
+    // Scrolling at a zoom that isn't a whole number still matches a full render
+    board.viewport.zoom = 1.1f32.powi(7);
+    board.viewport.position = Point { x: 90.3, y: 95.7 };
+    render_scene(&mut board, &mut frame, width, height);
+    board.viewport.position = Point { x: 92.9, y: 94.1 };
+    render_scene(&mut board, &mut frame, width, height);
+    let scrolled = frame.clone();
+    board.viewport_dirty = true;
+    render_scene(&mut board, &mut frame, width, height);
+    assert!(scrolled == frame);
+}
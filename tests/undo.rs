@@ -0,0 +1,168 @@
+//! Patch undo: each step only keeps the tiles it changed, so undoing must walk back through
+//! exactly the states the board went through
+
+mod common;
+
+use common::scratch_dir;
+use rickboard::{BlendMode, Board, BoardMode, Paper, MAX_FILL_PIXELS};
+
+/// Full snapshot to compare against: ink, blend modes, background and stroke count
+fn state(board: &Board) -> (Vec<u8>, Vec<u8>, Vec<u8>, bool, usize) {
+    let (mut ink, mut blend, mut background) = (Vec::new(), Vec::new(), Vec::new());
+    board.drawing_layer.write_raw(&mut ink).unwrap();
+    board.drawing_layer.write_blend_raw(&mut blend).unwrap();
+    board.cache.write_raw(&mut background).unwrap();
+    (ink, blend, background, board.has_drawings, board.strokes.len())
+}
+
+/// A thick diagonal stroke as one undo step, tagged with a blend mode
+fn stroke(board: &mut Board, x: i32, y: i32, color: [u8; 4], mode: BlendMode) {
+    board.save_undo_state();
+    for step in 0..300 {
+        for dy in 0..6 {
+            for dx in 0..6 {
+                let (px, py) = (x + step + dx, y + step * 2 / 3 + dy);
+                board.draw_pixel(px, py, color);
+                if mode != BlendMode::Normal {
+                    board.set_blend_mode(px, py, mode);
+                }
+            }
+        }
+    }
+}
+
+fn undo_walks_back(width: u32, height: u32, name: &str) {
+    let path = scratch_dir(name).join("board.data");
+    let mut board = Board::new(width, height, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    board.set_max_undo_steps(100);
+    board.cache.set(5, 5, [1, 2, 3, 255]); // Something on the background for clear_background to wipe
+    let steps_before = board.undo_stack.len();
+    let mut states = vec![state(&board)];
+    
+    stroke(&mut board, 10, 10, [255, 0, 0, 255], BlendMode::Normal);
+    states.push(state(&board));
+    stroke(&mut board, width as i32 - 50, 100, [0, 0, 255, 255], BlendMode::Normal); // Across the seam
+    states.push(state(&board));
+    stroke(&mut board, 100, 50, [255, 255, 0, 128], BlendMode::Multiply);
+    states.push(state(&board));
+    stroke(&mut board, 120, 60, [0, 255, 255, 100], BlendMode::Normal);
+    states.push(state(&board));
+    stroke(&mut board, 130, 40, [0, 0, 0, 0], BlendMode::Normal); // Eraser
+    states.push(state(&board));
+    assert!(board.flood_fill(500, 500, [0, 200, 0, 255]).is_some());
+    states.push(state(&board));
+    board.save_undo_state();
+    board.quantize_to_palette(&[[255, 0, 0, 255], [0, 0, 255, 255]], None);
+    states.push(state(&board));
+    board.clear_drawing();
+    states.push(state(&board));
+    stroke(&mut board, 300, 300, [255, 255, 0, 128], BlendMode::Multiply);
+    states.push(state(&board));
+    board.clear_background();
+    states.push(state(&board));
+    stroke(&mut board, 310, 310, [255, 0, 255, 255], BlendMode::Normal);
+    states.push(state(&board));
+    board.clear();
+    
+    while let Some(expected) = states.pop() {
+        assert!(board.undo());
+        let got = state(&board);
+        assert!(got.0 == expected.0, "ink differs {} steps in", states.len());
+        assert!(got.1 == expected.1, "blend modes differ {} steps in", states.len());
+        assert!(got.2 == expected.2, "background differs {} steps in", states.len());
+        assert_eq!((got.3, got.4), (expected.3, expected.4));
+    }
+    assert_eq!(board.undo_stack.len(), steps_before);
+    
+    // Saving after the undos writes the restored board
+    board.sync().unwrap();
+}
+
+#[test]
+fn undo_on_a_sparse_board() {
+    undo_walks_back(3000, 2000, "undo-sparse");
+}
+
+#[test]
+fn undo_on_a_dense_board() {
+    undo_walks_back(800, 600, "undo-dense");
+}
+
+#[test]
+fn undo_steps_only_keep_touched_tiles() {
+    let path = scratch_dir("undo-budget").join("board.data");
+    let mut board = Board::new(3000, 2000, BoardMode::Whiteboard, Paper::PLAIN, false, &path).unwrap();
+    board.save_undo_state();
+    board.draw_pixel(10, 10, [255, 0, 0, 255]);
+    assert!(board.undo_bytes() < 3000 * 2000 * 4 / 100);
+    stroke(&mut board, 10, 10, [0, 0, 255, 255], BlendMode::Normal);
+    assert!(board.undo_bytes() < 3000 * 2000 * 4 / 10);
+}
+
+#[test]
+fn rewinding_keeps_the_step_open() {
+    let path = scratch_dir("undo-rewind").join("board.data");
+    let mut board = Board::new(800, 600, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    let before = state(&board);
+    let steps_before = board.undo_stack.len();
+    
+    // A highlighter stroke taken back and redrawn as plain ink is still one step
+    stroke(&mut board, 10, 10, [255, 255, 0, 128], BlendMode::Multiply);
+    assert!(board.rewind_undo_step());
+    assert!(state(&board) == before);
+    for x in 0..200 {
+        board.draw_pixel(x, 50, [255, 0, 0, 255]);
+    }
+    assert_eq!(board.undo_stack.len(), steps_before + 1);
+    
+    assert!(board.undo());
+    assert!(state(&board) == before);
+}
+
+#[test]
+fn quantizing_a_selection_only_keeps_its_tiles() {
+    let path = scratch_dir("undo-quantize-region").join("board.data");
+    let mut board = Board::new(3000, 2000, BoardMode::Whiteboard, Paper::PLAIN, false, &path).unwrap();
+    for y in (0..2000).step_by(256) {
+        for x in (0..3000).step_by(256) {
+            board.draw_pixel(x, y, [10, 20, 30, 100]);
+        }
+    }
+    board.draw_pixel(5, 15, [250, 10, 10, 200]);
+    let before = state(&board);
+    
+    // A selection across the seam touches the last and first tile of the top row only
+    board.save_undo_state();
+    board.quantize_to_palette(&[[255, 0, 0, 255]], Some((2990, 10, 20, 20)));
+    assert_eq!(board.undo_bytes(), 2 * 256 * 256 * 4);
+    assert_eq!(board.drawing_layer.get(5, 15), [255, 0, 0, 255]);
+    assert_eq!(board.drawing_layer.get(0, 0), [10, 20, 30, 100]); // Outside the selection
+    
+    assert!(board.undo());
+    assert!(state(&board) == before);
+}
+
+#[test]
+fn filling_a_blank_board_past_the_pixel_cap() {
+    let path = scratch_dir("fill-blank").join("board.data");
+    let (width, height) = (20000, 1000);
+    let mut board = Board::new(width, height, BoardMode::Blackboard, Paper::PLAIN, false, &path).unwrap();
+    assert!(width as usize * height as usize > MAX_FILL_PIXELS);
+    
+    // Two walls fence off a strip; everything else is one region, joined across the seam
+    for y in 0..height as i32 {
+        board.draw_pixel(100, y, [255, 255, 255, 255]);
+        board.draw_pixel(200, y, [255, 255, 255, 255]);
+    }
+    let before = state(&board);
+    let green = [0, 200, 0, 255];
+    let filled = board.flood_fill(5000, 500, green);
+    assert_eq!(filled, Some((width as usize - 101) * height as usize));
+    for (x, y) in [(0, 0), (99, 999), (201, 0), (5000, 500), (19999, 999)] {
+        assert_eq!(board.drawing_layer.get(x, y), green, "({}, {})", x, y);
+    }
+    assert_eq!(board.drawing_layer.get(150, 500), [0, 0, 0, 0]);
+    
+    assert!(board.undo());
+    assert!(state(&board) == before);
+}